@@ -0,0 +1,135 @@
+//! Benchmarks for decompressing and parsing FsEvent files, using the bundled test data
+//!
+//! Run with `cargo bench`. To check a change for performance regressions, save a baseline before
+//! it with `cargo bench --bench parser -- --save-baseline before`, then compare with
+//! `cargo bench --bench parser -- --baseline before`. Criterion reports each benchmark that
+//! regressed by more than the noise threshold.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use macos_fseventsd::{
+    flags::FsEventFlags,
+    fsevents::FsEventsPageHeader,
+    parser::{
+        decompress_reader, parse_bytes, parse_fsevents, parse_fsevents_pages, parse_fsevents_ref,
+    },
+};
+use std::{fs, path::PathBuf, time::Duration};
+
+/// Compressed FsEvent files to benchmark, one for each DLS version
+const FILES: [&str; 3] = [
+    "DLS1/0000000000027d7a",
+    "DLS2/000000000002469c",
+    "DLS3/0000000000030012",
+];
+
+/// Changes smaller than this fraction of the baseline are treated as noise
+const NOISE_THRESHOLD: f64 = 0.05;
+
+/// Read a file from the test data
+fn test_data(name: &str) -> Vec<u8> {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data");
+    test_location.push(name);
+    fs::read(test_location).unwrap()
+}
+
+/// Get the DLS version of a test file (ex: `DLS2`)
+fn version(name: &str) -> &str {
+    name.split('/').next().unwrap_or(name)
+}
+
+fn bench_decompress(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decompress");
+    for name in FILES {
+        let data = test_data(name);
+        let decompressed = decompress_reader(data.as_slice()).unwrap();
+        group.throughput(Throughput::Bytes(decompressed.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(version(name)),
+            &data,
+            |b, data| b.iter(|| decompress_reader(black_box(data.as_slice())).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn bench_pages(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pages");
+    for name in FILES {
+        let data = decompress_reader(test_data(name).as_slice()).unwrap();
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("header", version(name)),
+            &data,
+            |b, data| b.iter(|| FsEventsPageHeader::parse(black_box(data)).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("pages", version(name)),
+            &data,
+            |b, data| b.iter(|| parse_fsevents_pages(black_box(data)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn bench_records(c: &mut Criterion) {
+    let mut group = c.benchmark_group("records");
+    for name in FILES {
+        let compressed = test_data(name);
+        let data = decompress_reader(compressed.as_slice()).unwrap();
+        let records = parse_fsevents(&data).unwrap().len();
+        group.throughput(Throughput::Elements(records as u64));
+        group.bench_with_input(
+            BenchmarkId::new("decompressed", version(name)),
+            &data,
+            |b, data| b.iter(|| parse_fsevents(black_box(data)).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("borrowed", version(name)),
+            &data,
+            |b, data| b.iter(|| parse_fsevents_ref(black_box(data)).unwrap()),
+        );
+        // Decompressing and parsing one page at a time, like the parse functions
+        group.bench_with_input(
+            BenchmarkId::new("compressed", version(name)),
+            &compressed,
+            |b, data| b.iter(|| parse_bytes(black_box(data)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn bench_flags(c: &mut Criterion) {
+    let records = parse_bytes(&test_data(FILES[1])).unwrap();
+    let raw_flags: Vec<u32> = records.iter().map(|record| record.raw_flags).collect();
+    let names: Vec<&str> = records.iter().map(|record| record.flags.as_str()).collect();
+
+    let mut group = c.benchmark_group("flags");
+    group.throughput(Throughput::Elements(records.len() as u64));
+    group.bench_function("names", |b| {
+        b.iter(|| {
+            black_box(&raw_flags)
+                .iter()
+                .map(|flags| FsEventFlags::from_bits_retain(*flags).to_string())
+                .collect::<Vec<String>>()
+        })
+    });
+    group.bench_function("from_str", |b| {
+        b.iter(|| {
+            black_box(&names)
+                .iter()
+                .map(|flags| flags.parse::<FsEventFlags>().unwrap())
+                .collect::<Vec<FsEventFlags>>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default()
+        .noise_threshold(NOISE_THRESHOLD)
+        .warm_up_time(Duration::from_secs(1));
+    targets = bench_decompress, bench_pages, bench_records, bench_flags
+}
+criterion_main!(benches);
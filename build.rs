@@ -0,0 +1,58 @@
+//! Generate the C header for the ffi feature and set up linking for the nodejs feature
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    generate_header();
+    // Link flags for loading the addon into Node.js
+    #[cfg(feature = "nodejs")]
+    napi_build::setup();
+}
+
+#[cfg(feature = "ffi")]
+/// Write the declarations in src/ffi.rs to macos_fseventsd.h in OUT_DIR
+/// Set MACOS_FSEVENTSD_HEADER_DIR to also copy the header to a directory (ex: `include`)
+fn generate_header() {
+    use std::{env::var, path::Path};
+
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-env-changed=MACOS_FSEVENTSD_HEADER_DIR");
+    let (Ok(crate_dir), Ok(out_dir)) = (var("CARGO_MANIFEST_DIR"), var("OUT_DIR")) else {
+        println!(
+            "cargo:warning=Cargo did not set CARGO_MANIFEST_DIR or OUT_DIR, skipping the C header"
+        );
+        return;
+    };
+    let header = match cbindgen::Builder::new()
+        .with_src(Path::new(&crate_dir).join("src/ffi.rs"))
+        .with_language(cbindgen::Language::C)
+        .with_include_guard("MACOS_FSEVENTSD_H")
+        .with_header("/* Generated by cbindgen from src/ffi.rs, do not edit */")
+        .with_documentation(true)
+        .generate()
+    {
+        Ok(result) => result,
+        Err(err) => {
+            println!("cargo:warning=Failed to generate the C header: {}", err);
+            return;
+        }
+    };
+
+    let mut directories = vec![out_dir];
+    if let Ok(directory) = var("MACOS_FSEVENTSD_HEADER_DIR") {
+        directories.push(Path::new(&crate_dir).join(directory).display().to_string());
+    }
+    for directory in directories {
+        let path = Path::new(&directory).join("macos_fseventsd.h");
+        if let Err(err) = std::fs::create_dir_all(&directory) {
+            println!("cargo:warning=Failed to create {}: {}", directory, err);
+            continue;
+        }
+        // write_to_file panics if the file cannot be written
+        let mut data = Vec::new();
+        header.write(&mut data);
+        if let Err(err) = std::fs::write(&path, data) {
+            println!("cargo:warning=Failed to write {}: {}", path.display(), err);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#ifndef MACOS_FSEVENTSD_H
+#define MACOS_FSEVENTSD_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Parse a FsEvent file and return its records as a JSON array
+ * Returns null on failure, see `fsevents_last_error`
+ *
+ * # Safety
+ * `path` must be a valid null terminated string
+ */
+char *fsevents_parse_file(const char *path);
+
+/**
+ * Parse every FsEvent file in a directory and return the records as a JSON array
+ * Returns null on failure, see `fsevents_last_error`
+ *
+ * # Safety
+ * `path` must be a valid null terminated string
+ */
+char *fsevents_parse_directory(const char *path);
+
+/**
+ * Release a buffer returned by a parse function. Null is ignored
+ *
+ * # Safety
+ * `buffer` must be returned by a parse function and not already released
+ */
+void fsevents_free_buffer(char *buffer);
+
+/**
+ * Get the reason the last parse function failed on this thread. Empty if none failed
+ * The string is owned by the library and is valid until the next parse function call
+ */
+const char *fsevents_last_error(void);
+
+#endif  /* MACOS_FSEVENTSD_H */
@@ -0,0 +1,1063 @@
+//! Find and parse FsEvent files inside APFS disk images
+//!
+//! A minimal read only APFS reader, so FsEvent files can be parsed from a raw or EWF image without
+//! mounting it. The image can be an APFS container or a GPT disk with APFS partitions. The latest
+//! checkpoint of each container is used to find its volumes, then the `.fseventsd` directory in the
+//! root of each volume is parsed. Encrypted and sealed volumes are skipped and snapshots are not read.
+
+use crate::{
+    enrich::{estimated_times, time_bounds},
+    error::FsEventsError,
+    fsevents::FsEvents,
+    image::open_image,
+    options::ParserOptions,
+    parser::{parse_fseventsd_uuid, parse_reader_pages},
+    report::ParseReport,
+};
+use log::{debug, error, warn};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+const NX_MAGIC: &[u8; 4] = b"NXSB";
+const APFS_MAGIC: &[u8; 4] = b"APSB";
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+// Offset to the magic value in a container or volume superblock
+const MAGIC_OFFSET: u64 = 32;
+const MAX_FILE_SYSTEMS: usize = 100;
+const MAX_PARTITIONS: usize = 256;
+
+const NODE_HEADER_SIZE: usize = 56;
+const BTREE_INFO_SIZE: usize = 40;
+const NODE_ROOT: u16 = 0x1;
+const NODE_LEAF: u16 = 0x2;
+const NODE_FIXED_SIZE: u16 = 0x4;
+// Object map keys are an object ID and transaction ID, values are flags, size, and an address
+const OMAP_KEY_SIZE: usize = 16;
+const OMAP_VALUE_SIZE: usize = 16;
+// How deep a B-tree is followed, so a corrupt tree cannot loop forever
+const MAX_TREE_DEPTH: usize = 16;
+// fseventsd-uuid only contains a UUID, a corrupt inode size is not allocated
+const MAX_UUID_FILE_SIZE: u64 = 4096;
+
+const TYPE_NX_SUPERBLOCK: u32 = 0x1;
+const TYPE_INODE: u8 = 3;
+const TYPE_FILE_EXTENT: u8 = 8;
+const TYPE_DIRECTORY_RECORD: u8 = 9;
+const OBJECT_ID_MASK: u64 = 0x0fffffffffffffff;
+const OBJECT_TYPE_SHIFT: u64 = 60;
+const ROOT_DIRECTORY_ID: u64 = 2;
+const DIRECTORY_TYPE: u16 = 4;
+const FILE_TYPE: u16 = 8;
+const INODE_XFIELDS_OFFSET: usize = 92;
+const INODE_DSTREAM: u8 = 8;
+const EXTENT_LENGTH_MASK: u64 = 0x00ffffffffffffff;
+
+const FS_UNENCRYPTED: u64 = 0x1;
+const INCOMPAT_CASE_INSENSITIVE: u64 = 0x1;
+const INCOMPAT_NORMALIZATION_INSENSITIVE: u64 = 0x8;
+const INCOMPAT_SEALED: u64 = 0x20;
+
+/// Parse the FsEvent files in every APFS volume in a raw or EWF image
+/// Records have a source file of `<image>:<volume name>/.fseventsd/<file>` and are tagged with the volume name
+pub fn parse_apfs_image(
+    path: &str,
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_apfs_image_with_report(path, options).map(|(data, _)| data)
+}
+
+/// Parse the FsEvent files in every APFS volume in a raw or EWF image, and report any files or
+/// volumes that were skipped
+pub fn parse_apfs_image_with_report(
+    path: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let mut reader = open_image(path)?;
+    parse_apfs_reader_with_report(&mut reader, path, options)
+}
+
+/// Parse the FsEvent files in every APFS volume from any seekable image reader
+pub fn parse_apfs_reader<R: Read + Seek>(
+    reader: &mut R,
+    name: &str,
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_apfs_reader_with_report(reader, name, options).map(|(data, _)| data)
+}
+
+/// Parse the FsEvent files in every APFS volume from any seekable image reader, and report any
+/// files or volumes that were skipped
+pub fn parse_apfs_reader_with_report<R: Read + Seek>(
+    reader: &mut R,
+    name: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let containers = find_containers(reader)?;
+    if containers.is_empty() {
+        return Err(FsEventsError::InvalidImage(format!(
+            "No APFS container found in {}",
+            name
+        )));
+    }
+
+    let mut report = ParseReport::default();
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    for offset in containers {
+        let mut container = Container::open(reader, offset)?;
+        for oid in container.volumes.clone() {
+            if options.is_cancelled() {
+                break;
+            }
+            let results = container.volume(oid).and_then(|volume| match volume {
+                Some(volume) => container.parse_volume(&volume, name, options, &mut report),
+                None => Ok(Vec::new()),
+            });
+            match results {
+                Ok(mut data) => fsevents_data.append(&mut data),
+                Err(err) if options.lenient => {
+                    error!(
+                        "Failed to parse APFS volume {} in {}, err: {}",
+                        oid, name, err
+                    );
+                    report.skip_directory(&format!("{}:volume {}", name, oid), err.to_string());
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    Ok((fsevents_data, report))
+}
+
+// File system record key and value
+type Record = (Vec<u8>, Vec<u8>);
+
+struct Container<'a, R> {
+    reader: &'a mut R,
+    offset: u64,       // Offset to the container in the image
+    block_size: u64,   // Size of each block in the container
+    xid: u64,          // Transaction ID of the latest checkpoint
+    omap_tree: u64,    // Block of the container object map B-tree
+    volumes: Vec<u64>, // Object IDs of the volume superblocks
+}
+
+struct Volume {
+    name: String,   // Volume name
+    omap_tree: u64, // Block of the volume object map B-tree
+    root_tree: u64, // Object ID of the file system B-tree
+    hashed: bool,   // Directory records have hashed names
+}
+
+struct DirectoryEntry {
+    name: String, // Name of the file or directory
+    id: u64,      // Inode ID
+    kind: u16,    // Directory entry type
+}
+
+struct Inode {
+    private_id: u64, // ID used by the file extents
+    size: u64,       // Size of the file data
+    modified: i64,   // Modified time in seconds since the Unix epoch
+}
+
+impl<'a, R: Read + Seek> Container<'a, R> {
+    /// Open the container at an offset in the image using its latest checkpoint
+    fn open(reader: &'a mut R, offset: u64) -> Result<Container<'a, R>, FsEventsError> {
+        let mut header = [0; 40];
+        read_at(reader, offset, &mut header)?;
+        let block_size = read_u32(&header, 36) as u64;
+        if &header[32..36] != NX_MAGIC
+            || !block_size.is_power_of_two()
+            || !(4096..=65536).contains(&block_size)
+        {
+            return Err(FsEventsError::InvalidImage(format!(
+                "Invalid APFS container at {:#x}",
+                offset
+            )));
+        }
+
+        let mut container = Container {
+            reader,
+            offset,
+            block_size,
+            xid: 0,
+            omap_tree: 0,
+            volumes: Vec::new(),
+        };
+        let superblock = container.latest_superblock()?;
+        container.xid = read_u64(&superblock, 16);
+        let omap = container.read_block(read_u64(&superblock, 160))?;
+        container.omap_tree = read_u64(&omap, 48);
+
+        let count = (read_u32(&superblock, 180) as usize).min(MAX_FILE_SYSTEMS);
+        container.volumes = (0..count)
+            .map(|index| read_u64(&superblock, 184 + index * 8))
+            .filter(|oid| *oid != 0)
+            .collect();
+        debug!(
+            "APFS container at {:#x} has {} volumes at transaction {}",
+            offset,
+            container.volumes.len(),
+            container.xid
+        );
+        Ok(container)
+    }
+
+    /// Get the newest valid superblock in the checkpoint area, block 0 may be out of date
+    fn latest_superblock(&mut self) -> Result<Vec<u8>, FsEventsError> {
+        let mut superblock = self.read_block(0)?;
+        let descriptor_blocks = read_u32(&superblock, 104);
+        let descriptor_base = read_u64(&superblock, 112);
+        // The high bit is set if the checkpoint area is not contiguous, block 0 is used instead
+        if descriptor_blocks & 0x80000000 != 0 {
+            return Ok(superblock);
+        }
+
+        let descriptor_end = descriptor_base
+            .checked_add(descriptor_blocks as u64)
+            .ok_or_else(|| {
+                FsEventsError::InvalidImage(format!(
+                    "Invalid APFS checkpoint area at block {}",
+                    descriptor_base
+                ))
+            })?;
+        for block in descriptor_base..descriptor_end {
+            let data = match self.read_block(block) {
+                Ok(result) => result,
+                Err(_) => break,
+            };
+            if &data[32..36] == NX_MAGIC
+                && read_u32(&data, 24) & 0xffff == TYPE_NX_SUPERBLOCK
+                && is_valid_checksum(&data)
+                && read_u64(&data, 16) > read_u64(&superblock, 16)
+            {
+                superblock = data;
+            }
+        }
+        Ok(superblock)
+    }
+
+    /// Get a volume from its superblock, encrypted and sealed volumes are skipped
+    fn volume(&mut self, oid: u64) -> Result<Option<Volume>, FsEventsError> {
+        let block = self.omap_lookup(self.omap_tree, oid)?;
+        let superblock = self.read_block(block)?;
+        if &superblock[32..36] != APFS_MAGIC {
+            return Err(FsEventsError::InvalidImage(format!(
+                "Invalid APFS volume superblock at block {}",
+                block
+            )));
+        }
+        let name = null_terminated(superblock.get(704..960).unwrap_or_default());
+        let incompatible = read_u64(&superblock, 56);
+        if read_u64(&superblock, 264) & FS_UNENCRYPTED == 0 {
+            warn!("Skipping encrypted APFS volume {}", name);
+            return Ok(None);
+        }
+        if incompatible & INCOMPAT_SEALED != 0 {
+            debug!("Skipping sealed APFS volume {}", name);
+            return Ok(None);
+        }
+
+        let omap = self.read_block(read_u64(&superblock, 128))?;
+        Ok(Some(Volume {
+            name,
+            omap_tree: read_u64(&omap, 48),
+            root_tree: read_u64(&superblock, 136),
+            hashed: incompatible & (INCOMPAT_CASE_INSENSITIVE | INCOMPAT_NORMALIZATION_INSENSITIVE)
+                != 0,
+        }))
+    }
+
+    /// Parse the FsEvent files in the .fseventsd directory of a volume
+    fn parse_volume(
+        &mut self,
+        volume: &Volume,
+        name: &str,
+        options: &ParserOptions,
+        report: &mut ParseReport,
+    ) -> Result<Vec<FsEvents>, FsEventsError> {
+        let root = self.list_directory(volume, ROOT_DIRECTORY_ID)?;
+        let directory = match root
+            .iter()
+            .find(|entry| entry.name == ".fseventsd" && entry.kind == DIRECTORY_TYPE)
+        {
+            Some(entry) => entry.id,
+            None => {
+                debug!("APFS volume {} has no .fseventsd directory", volume.name);
+                return Ok(Vec::new());
+            }
+        };
+
+        let directory_path = format!("{}:{}/.fseventsd", name, volume.name);
+        let mut entries = self.list_directory(volume, directory)?;
+        entries.retain(|entry| entry.kind == FILE_TYPE);
+        entries.sort_by(|first, second| first.name.cmp(&second.name));
+        options.notify(|observer| observer.files_discovered(&directory_path, entries.len()));
+
+        let mut fsevents_data: Vec<FsEvents> = Vec::new();
+        let mut volume_uuid = String::new();
+        let mut times = Vec::new();
+        for entry in entries {
+            if options.is_cancelled() {
+                break;
+            }
+            let file = format!("{}/{}", directory_path, entry.name);
+            if entry.name == "fseventsd-uuid" {
+                if options.attach_uuid {
+                    match self.read_uuid(volume, entry.id, &file) {
+                        Ok(result) => volume_uuid = result,
+                        Err(err) if options.lenient => {
+                            warn!("Failed to get fseventsd UUID for {}, err: {}", file, err)
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+                continue;
+            }
+
+            match self.parse_file(volume, entry.id, &file, options, report) {
+                Ok((mut data, modified)) => {
+                    report.files_parsed += 1;
+                    times.push((file, modified));
+                    fsevents_data.append(&mut data);
+                }
+                // Large files are always skipped, even if not lenient
+                Err(err @ FsEventsError::FileTooLarge(..)) => {
+                    warn!("Skipping FsEvent file {}, err: {}", file, err);
+                    report.skip_file(&file, err.to_string());
+                }
+                Err(err) if options.lenient => {
+                    error!("Failed to parse FsEvent file {}, err: {}", file, err);
+                    report.skip_file(&file, err.to_string());
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        if options.estimate_times {
+            estimated_times(&mut fsevents_data, times.clone());
+        }
+        let bounds = time_bounds(times);
+        for fsevent in fsevents_data.iter_mut() {
+            fsevent.source_tag = volume.name.clone();
+            if options.attach_uuid {
+                fsevent.volume_uuid = volume_uuid.clone();
+            }
+            if options.approx_times {
+                if let Some((earliest, latest)) = bounds.get(&fsevent.source_file) {
+                    fsevent.approx_earliest_time = *earliest;
+                    fsevent.approx_latest_time = *latest;
+                }
+            }
+        }
+        Ok(fsevents_data)
+    }
+
+    /// Parse a FsEvent file, returns the matching records and the file modified time
+    /// Truncated data and skipped or resynced pages are added to the report
+    fn parse_file(
+        &mut self,
+        volume: &Volume,
+        id: u64,
+        file: &str,
+        options: &ParserOptions,
+        report: &mut ParseReport,
+    ) -> Result<(Vec<FsEvents>, i64), FsEventsError> {
+        let inode = self.inode(volume, id)?;
+        options.notify(|observer| observer.file_started(file));
+        let data = self.read_data(volume, &inode, file, options.max_file_size)?;
+        let fsevents_data = parse_reader_pages(Cursor::new(data), file, options, report)?;
+        options.notify(|observer| observer.file_finished(file, fsevents_data.len()));
+        Ok((fsevents_data, inode.modified))
+    }
+
+    /// Read the volume UUID from the fseventsd-uuid file
+    fn read_uuid(&mut self, volume: &Volume, id: u64, file: &str) -> Result<String, FsEventsError> {
+        let inode = self.inode(volume, id)?;
+        let data = self.read_data(volume, &inode, file, MAX_UUID_FILE_SIZE)?;
+        parse_fseventsd_uuid(&data)
+    }
+
+    /// List the files and directories in a directory
+    fn list_directory(
+        &mut self,
+        volume: &Volume,
+        id: u64,
+    ) -> Result<Vec<DirectoryEntry>, FsEventsError> {
+        let records = self.records(volume, id, TYPE_DIRECTORY_RECORD)?;
+        Ok(records
+            .iter()
+            .map(|(key, value)| DirectoryEntry {
+                name: directory_name(key, volume.hashed),
+                id: read_u64(value, 0),
+                kind: read_u16(value, 16) & 0xf,
+            })
+            .collect())
+    }
+
+    /// Get the inode for a file
+    fn inode(&mut self, volume: &Volume, id: u64) -> Result<Inode, FsEventsError> {
+        let inode = match self.records(volume, id, TYPE_INODE)?.into_iter().next() {
+            Some((_, value)) => value,
+            None => {
+                return Err(FsEventsError::InvalidImage(format!(
+                    "Missing inode {} in APFS volume {}",
+                    id, volume.name
+                )))
+            }
+        };
+        Ok(Inode {
+            private_id: read_u64(&inode, 8),
+            size: inode_size(&inode),
+            modified: (read_u64(&inode, 24) / 1_000_000_000) as i64,
+        })
+    }
+
+    /// Read the data for a file from its extents, sparse extents are left as zeros
+    /// Files that are not smaller than the max size are not read
+    fn read_data(
+        &mut self,
+        volume: &Volume,
+        inode: &Inode,
+        file: &str,
+        max_size: u64,
+    ) -> Result<Vec<u8>, FsEventsError> {
+        if inode.size >= max_size {
+            return Err(FsEventsError::FileTooLarge(file.to_string(), max_size));
+        }
+        let mut data = vec![0; inode.size as usize];
+        for (key, value) in self.records(volume, inode.private_id, TYPE_FILE_EXTENT)? {
+            let logical = read_u64(&key, 8);
+            let length = read_u64(&value, 0) & EXTENT_LENGTH_MASK;
+            let physical = read_u64(&value, 8);
+            if logical >= inode.size || physical == 0 {
+                continue;
+            }
+            let start = logical as usize;
+            let end = start + length.min(inode.size - logical) as usize;
+            let offset = self.block_offset(physical)?;
+            read_at(self.reader, offset, &mut data[start..end])?;
+        }
+        Ok(data)
+    }
+
+    /// Get the file system records with an object ID and record type
+    fn records(
+        &mut self,
+        volume: &Volume,
+        id: u64,
+        kind: u8,
+    ) -> Result<Vec<Record>, FsEventsError> {
+        let root = self.omap_lookup(volume.omap_tree, volume.root_tree)?;
+        let mut records = Vec::new();
+        self.find_records(volume, root, (id, kind), 0, &mut records)?;
+        Ok(records)
+    }
+
+    /// Walk the file system B-tree, only following children that can contain the target key
+    fn find_records(
+        &mut self,
+        volume: &Volume,
+        block: u64,
+        target: (u64, u8),
+        depth: usize,
+        records: &mut Vec<Record>,
+    ) -> Result<(), FsEventsError> {
+        if depth >= MAX_TREE_DEPTH {
+            return Err(FsEventsError::InvalidImage(format!(
+                "APFS volume {} file system tree is too deep",
+                volume.name
+            )));
+        }
+        let node = Node::parse(self.read_block(block)?)?;
+        let mut children = Vec::new();
+        for index in 0..node.count {
+            let (key, value) = match node.entry(index) {
+                Some(result) => result,
+                None => break,
+            };
+            let key_type = record_key(key);
+            if key_type > target {
+                break;
+            }
+            if node.is_leaf() {
+                if key_type == target {
+                    records.push((key.to_vec(), value.to_vec()));
+                }
+                continue;
+            }
+
+            let next = node.entry(index + 1).map(|(next, _)| record_key(next));
+            if next.is_none_or(|next| next >= target) {
+                children.push(read_u64(value, 0));
+            }
+        }
+
+        for child in children {
+            let block = self.omap_lookup(volume.omap_tree, child)?;
+            self.find_records(volume, block, target, depth + 1, records)?;
+        }
+        Ok(())
+    }
+
+    /// Get the block of a virtual object from an object map B-tree
+    fn omap_lookup(&mut self, tree: u64, oid: u64) -> Result<u64, FsEventsError> {
+        let mut block = tree;
+        for _ in 0..MAX_TREE_DEPTH {
+            let node = Node::parse(self.read_block(block)?)?;
+            // Use the last entry at or before the object ID and checkpoint transaction
+            let mut found = None;
+            for index in 0..node.count {
+                let (key, value) = match node.entry(index) {
+                    Some(result) => result,
+                    None => break,
+                };
+                let key = (read_u64(key, 0), read_u64(key, 8));
+                if key > (oid, self.xid) {
+                    break;
+                }
+                found = Some((key.0, value));
+            }
+
+            match found {
+                Some((key_oid, value)) if node.is_leaf() && key_oid == oid => {
+                    return Ok(read_u64(value, 8));
+                }
+                Some((_, value)) if !node.is_leaf() => block = read_u64(value, 0),
+                _ => {
+                    return Err(FsEventsError::InvalidImage(format!(
+                        "APFS object {} not found in object map",
+                        oid
+                    )))
+                }
+            }
+        }
+        Err(FsEventsError::InvalidImage(format!(
+            "APFS object map at block {} is too deep",
+            tree
+        )))
+    }
+
+    /// Read a block from the container
+    fn read_block(&mut self, block: u64) -> Result<Vec<u8>, FsEventsError> {
+        let offset = self.block_offset(block)?;
+        let mut data = vec![0; self.block_size as usize];
+        read_at(self.reader, offset, &mut data)?;
+        Ok(data)
+    }
+
+    /// Get the offset to a block in the image
+    fn block_offset(&self, block: u64) -> Result<u64, FsEventsError> {
+        block
+            .checked_mul(self.block_size)
+            .and_then(|offset| offset.checked_add(self.offset))
+            .ok_or_else(|| FsEventsError::InvalidImage(format!("Invalid APFS block {}", block)))
+    }
+}
+
+struct Node {
+    data: Vec<u8>,      // Node block
+    flags: u16,         // Root, leaf, and fixed size flags
+    count: usize,       // Number of entries
+    table_start: usize, // Offset to the table of contents
+    key_start: usize,   // Offset to the key area
+    value_end: usize,   // Offset to the end of the value area
+}
+
+impl Node {
+    /// Parse the header of a B-tree node
+    fn parse(data: Vec<u8>) -> Result<Node, FsEventsError> {
+        let flags = read_u16(&data, 32);
+        let count = read_u32(&data, 36) as usize;
+        let table_start = NODE_HEADER_SIZE + read_u16(&data, 40) as usize;
+        let table_size = read_u16(&data, 42) as usize;
+        let key_start = table_start + table_size;
+        // Root nodes end with the B-tree info
+        let value_end = if flags & NODE_ROOT != 0 {
+            data.len() - BTREE_INFO_SIZE
+        } else {
+            data.len()
+        };
+        let entry_size = if flags & NODE_FIXED_SIZE != 0 { 4 } else { 8 };
+        if key_start > value_end || count * entry_size > table_size {
+            return Err(FsEventsError::InvalidImage(String::from(
+                "Invalid APFS B-tree node",
+            )));
+        }
+
+        Ok(Node {
+            data,
+            flags,
+            count,
+            table_start,
+            key_start,
+            value_end,
+        })
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.flags & NODE_LEAF != 0
+    }
+
+    /// Get the key and value of an entry
+    fn entry(&self, index: usize) -> Option<(&[u8], &[u8])> {
+        if index >= self.count {
+            return None;
+        }
+        let (key_offset, key_size, value_offset, value_size) = if self.flags & NODE_FIXED_SIZE != 0
+        {
+            // Fixed size nodes are only used for object maps, index nodes point to child blocks
+            let entry = self.table_start + index * 4;
+            let value_size = if self.is_leaf() { OMAP_VALUE_SIZE } else { 8 };
+            (
+                read_u16(&self.data, entry),
+                OMAP_KEY_SIZE,
+                read_u16(&self.data, entry + 2),
+                value_size,
+            )
+        } else {
+            let entry = self.table_start + index * 8;
+            (
+                read_u16(&self.data, entry),
+                read_u16(&self.data, entry + 2) as usize,
+                read_u16(&self.data, entry + 4),
+                read_u16(&self.data, entry + 6) as usize,
+            )
+        };
+
+        let key_start = self.key_start + key_offset as usize;
+        let key = self.data.get(key_start..key_start + key_size)?;
+        // Value offsets count back from the end of the value area
+        let value_start = self.value_end.checked_sub(value_offset as usize)?;
+        let value = self.data.get(value_start..value_start + value_size)?;
+        Some((key, value))
+    }
+}
+
+/// Find the offsets to APFS containers, at the start of the image or in GPT partitions
+fn find_containers<R: Read + Seek>(reader: &mut R) -> Result<Vec<u64>, FsEventsError> {
+    if has_magic(reader, 0) {
+        return Ok(vec![0]);
+    }
+
+    let mut containers = Vec::new();
+    for sector_size in [512, 4096] {
+        let mut header = [0; 92];
+        if read_at(reader, sector_size, &mut header).is_err() || &header[..8] != GPT_SIGNATURE {
+            continue;
+        }
+        let entries_start = read_u64(&header, 72).saturating_mul(sector_size);
+        let count = (read_u32(&header, 80) as usize).min(MAX_PARTITIONS);
+        let entry_size = read_u32(&header, 84) as u64;
+
+        for index in 0..count as u64 {
+            let mut entry = [0; 48];
+            let entry_offset = index
+                .checked_mul(entry_size)
+                .and_then(|offset| offset.checked_add(entries_start))
+                .ok_or_else(|| {
+                    FsEventsError::InvalidImage(format!("Invalid GPT partition entry {}", index))
+                })?;
+            read_at(reader, entry_offset, &mut entry)?;
+            let start = read_u64(&entry, 32).saturating_mul(sector_size);
+            if start != 0 && has_magic(reader, start) {
+                containers.push(start);
+            }
+        }
+        break;
+    }
+    Ok(containers)
+}
+
+/// Check for a container superblock at an offset
+fn has_magic<R: Read + Seek>(reader: &mut R, offset: u64) -> bool {
+    let mut magic = [0; 4];
+    offset
+        .checked_add(MAGIC_OFFSET)
+        .is_some_and(|offset| read_at(reader, offset, &mut magic).is_ok())
+        && &magic == NX_MAGIC
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, data: &mut [u8]) -> std::io::Result<()> {
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(data)
+}
+
+/// Get the object ID and record type from a file system key
+fn record_key(key: &[u8]) -> (u64, u8) {
+    let header = read_u64(key, 0);
+    (header & OBJECT_ID_MASK, (header >> OBJECT_TYPE_SHIFT) as u8)
+}
+
+/// Get the name from a directory record key
+fn directory_name(key: &[u8], hashed: bool) -> String {
+    let name = if hashed {
+        // Length is the low 10 bits, followed by a hash of the name
+        let size = (read_u32(key, 8) & 0x3ff) as usize;
+        key.get(12..12 + size)
+    } else {
+        let size = read_u16(key, 8) as usize;
+        key.get(10..10 + size)
+    };
+    null_terminated(name.unwrap_or_default())
+}
+
+/// Get the file size from the data stream extended field of an inode
+fn inode_size(inode: &[u8]) -> u64 {
+    let count = read_u16(inode, INODE_XFIELDS_OFFSET) as usize;
+    let mut data_offset = INODE_XFIELDS_OFFSET + 4 + count * 4;
+    for index in 0..count {
+        let field = INODE_XFIELDS_OFFSET + 4 + index * 4;
+        if inode.get(field) == Some(&INODE_DSTREAM) {
+            return read_u64(inode, data_offset);
+        }
+        // Extended field data is aligned to 8 bytes
+        data_offset += (read_u16(inode, field + 2) as usize).div_ceil(8) * 8;
+    }
+    0
+}
+
+/// Check the Fletcher 64 checksum at the start of an object
+fn is_valid_checksum(object: &[u8]) -> bool {
+    read_u64(object, 0) == fletcher64(&object[8..])
+}
+
+fn fletcher64(data: &[u8]) -> u64 {
+    let modulus = u32::MAX as u64;
+    let mut first = 0;
+    let mut second = 0;
+    for word in data.chunks_exact(4) {
+        first = (first + read_u32(word, 0) as u64) % modulus;
+        second = (second + first) % modulus;
+    }
+    let low = modulus - ((first + second) % modulus);
+    let high = modulus - ((first + low) % modulus);
+    (high << 32) | low
+}
+
+fn null_terminated(data: &[u8]) -> String {
+    let end = data
+        .iter()
+        .position(|value| *value == 0)
+        .unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).to_string()
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .unwrap_or_default()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .unwrap_or_default()
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    data.get(offset..offset + 8)
+        .map(|bytes| {
+            let mut value = [0; 8];
+            value.copy_from_slice(bytes);
+            u64::from_le_bytes(value)
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        directory_name, fletcher64, inode_size, is_valid_checksum, parse_apfs_image,
+        parse_apfs_reader, parse_apfs_reader_with_report,
+    };
+    use crate::{error::FsEventsError, options::ParserOptions};
+    use std::{fs, io::Cursor, path::PathBuf};
+    use tempfile::tempdir;
+
+    const BLOCK_SIZE: usize = 4096;
+    const UUID: &str = "5B3A1C2E-8F4D-4E6A-9C1B-2D3E4F5A6B7C";
+
+    fn put(block: &mut [u8], offset: usize, value: &[u8]) {
+        block[offset..offset + value.len()].copy_from_slice(value);
+    }
+
+    /// Build a B-tree node, entries must be sorted by key
+    fn node(root: bool, leaf: bool, fixed: bool, entries: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+        let mut block = vec![0; BLOCK_SIZE];
+        let flags = root as u16 | (leaf as u16) << 1 | (fixed as u16) << 2;
+        let entry_size = if fixed { 4 } else { 8 };
+        let table_size = entries.len() * entry_size;
+        put(&mut block, 32, &flags.to_le_bytes());
+        put(&mut block, 36, &(entries.len() as u32).to_le_bytes());
+        put(&mut block, 42, &(table_size as u16).to_le_bytes());
+
+        let key_start = 56 + table_size;
+        let value_end = if root { BLOCK_SIZE - 40 } else { BLOCK_SIZE };
+        let mut key_offset = 0;
+        let mut value_offset = 0;
+        for (index, (key, value)) in entries.iter().enumerate() {
+            value_offset += value.len();
+            put(&mut block, key_start + key_offset, key);
+            put(&mut block, value_end - value_offset, value);
+
+            let entry = 56 + index * entry_size;
+            put(&mut block, entry, &(key_offset as u16).to_le_bytes());
+            if fixed {
+                put(&mut block, entry + 2, &(value_offset as u16).to_le_bytes());
+            } else {
+                put(&mut block, entry + 2, &(key.len() as u16).to_le_bytes());
+                put(&mut block, entry + 4, &(value_offset as u16).to_le_bytes());
+                put(&mut block, entry + 6, &(value.len() as u16).to_le_bytes());
+            }
+            key_offset += key.len();
+        }
+        block
+    }
+
+    fn omap_entry(oid: u64, block: u64) -> (Vec<u8>, Vec<u8>) {
+        let mut key = oid.to_le_bytes().to_vec();
+        key.extend_from_slice(&1u64.to_le_bytes());
+        let mut value = vec![0; 8];
+        value.extend_from_slice(&block.to_le_bytes());
+        (key, value)
+    }
+
+    fn omap(tree: u64) -> Vec<u8> {
+        let mut block = vec![0; BLOCK_SIZE];
+        put(&mut block, 48, &tree.to_le_bytes());
+        block
+    }
+
+    fn key(id: u64, kind: u64) -> Vec<u8> {
+        (id | kind << 60).to_le_bytes().to_vec()
+    }
+
+    fn directory_record(parent: u64, name: &str, id: u64, kind: u16) -> (Vec<u8>, Vec<u8>) {
+        let mut record_key = key(parent, 9);
+        record_key.extend_from_slice(&(name.len() as u32 + 1).to_le_bytes());
+        record_key.extend_from_slice(name.as_bytes());
+        record_key.push(0);
+
+        let mut value = id.to_le_bytes().to_vec();
+        value.extend_from_slice(&[0; 8]);
+        value.extend_from_slice(&kind.to_le_bytes());
+        (record_key, value)
+    }
+
+    fn inode(id: u64, size: u64, modified: u64) -> (Vec<u8>, Vec<u8>) {
+        let mut value = vec![0; 92];
+        put(&mut value, 8, &id.to_le_bytes());
+        put(&mut value, 24, &(modified * 1_000_000_000).to_le_bytes());
+        // One data stream extended field
+        value.extend_from_slice(&[1, 0, 40, 0, 8, 0, 40, 0]);
+        value.extend_from_slice(&size.to_le_bytes());
+        value.extend_from_slice(&[0; 32]);
+        (key(id, 3), value)
+    }
+
+    fn extent(id: u64, block: u64) -> (Vec<u8>, Vec<u8>) {
+        let mut record_key = key(id, 8);
+        record_key.extend_from_slice(&0u64.to_le_bytes());
+        let mut value = (BLOCK_SIZE as u64).to_le_bytes().to_vec();
+        value.extend_from_slice(&block.to_le_bytes());
+        value.extend_from_slice(&[0; 8]);
+        (record_key, value)
+    }
+
+    fn container_superblock(xid: u64, omap: u64) -> Vec<u8> {
+        let mut block = vec![0; BLOCK_SIZE];
+        put(&mut block, 16, &xid.to_le_bytes());
+        put(&mut block, 24, &1u32.to_le_bytes());
+        put(&mut block, 32, b"NXSB");
+        put(&mut block, 36, &(BLOCK_SIZE as u32).to_le_bytes());
+        put(&mut block, 104, &1u32.to_le_bytes());
+        put(&mut block, 112, &1u64.to_le_bytes());
+        put(&mut block, 160, &omap.to_le_bytes());
+        put(&mut block, 180, &1u32.to_le_bytes());
+        put(&mut block, 184, &1026u64.to_le_bytes());
+        let checksum = fletcher64(&block[8..]);
+        put(&mut block, 0, &checksum.to_le_bytes());
+        block
+    }
+
+    /// Build an APFS container with a Data volume containing a .fseventsd directory
+    fn test_container() -> Vec<u8> {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let fsevents = fs::read(test_location.join("0000000000030012")).unwrap();
+        let uuid = fs::read(test_location.join("fseventsd-uuid")).unwrap();
+
+        let mut volume = vec![0; BLOCK_SIZE];
+        put(&mut volume, 32, b"APSB");
+        put(&mut volume, 56, &0x8u64.to_le_bytes());
+        put(&mut volume, 128, &5u64.to_le_bytes());
+        put(&mut volume, 136, &1028u64.to_le_bytes());
+        put(&mut volume, 264, &1u64.to_le_bytes());
+        put(&mut volume, 704, b"Data");
+
+        let first_leaf = vec![
+            directory_record(2, ".fseventsd", 16, 4),
+            directory_record(16, "0000000000030012", 17, 8),
+            directory_record(16, "fseventsd-uuid", 18, 8),
+        ];
+        let second_leaf = vec![
+            inode(17, fsevents.len() as u64, 2000),
+            extent(17, 10),
+            inode(18, uuid.len() as u64, 1000),
+            extent(18, 11),
+        ];
+        let root = vec![
+            (key(2, 9), 1029u64.to_le_bytes().to_vec()),
+            (key(17, 3), 1030u64.to_le_bytes().to_vec()),
+        ];
+
+        let blocks = [
+            // Out of date superblock, the checkpoint superblock is newer
+            container_superblock(1, 0),
+            container_superblock(2, 2),
+            omap(3),
+            node(true, true, true, &[omap_entry(1026, 4)]),
+            volume,
+            omap(6),
+            node(
+                true,
+                true,
+                true,
+                &[
+                    omap_entry(1028, 7),
+                    omap_entry(1029, 8),
+                    omap_entry(1030, 9),
+                ],
+            ),
+            node(true, false, false, &root),
+            node(false, true, false, &first_leaf),
+            node(false, true, false, &second_leaf),
+            fsevents,
+            uuid,
+        ];
+        let mut container = Vec::new();
+        for block in blocks {
+            let start = container.len();
+            container.extend_from_slice(&block);
+            container.resize(start + BLOCK_SIZE, 0);
+        }
+        container
+    }
+
+    #[test]
+    fn test_parse_apfs_reader() {
+        let options = ParserOptions::new().attach_uuid(true).approx_times(true);
+        let results =
+            parse_apfs_reader(&mut Cursor::new(test_container()), "image", &options).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].path == "/Users/bob/Downloads/invoice.pdf");
+        assert!(results[0].source_file == "image:Data/.fseventsd/0000000000030012");
+        assert!(results[0].source_tag == "Data");
+        assert!(results[0].volume_uuid == UUID);
+        assert!(results[0].approx_latest_time == 2000);
+    }
+
+    #[test]
+    fn test_parse_apfs_reader_max_size() {
+        let options = ParserOptions::new().lenient(false).max_file_size(100);
+        let (results, report) =
+            parse_apfs_reader_with_report(&mut Cursor::new(test_container()), "image", &options)
+                .unwrap();
+        assert!(results.is_empty());
+        assert!(report.files_parsed == 0);
+        assert!(report.skipped_files.len() == 1);
+        assert!(report.skipped_files[0].path == "image:Data/.fseventsd/0000000000030012");
+        assert!(report.skipped_files[0].reason.contains("100 bytes"));
+
+        let (results, report) = parse_apfs_reader_with_report(
+            &mut Cursor::new(test_container()),
+            "image",
+            &ParserOptions::new(),
+        )
+        .unwrap();
+        assert!(results.len() == 5);
+        assert!(report.files_parsed == 1);
+        assert!(report.is_complete());
+    }
+
+    #[test]
+    fn test_parse_apfs_image_gpt() {
+        // Protective MBR, GPT header, one partition entry, then the container at sector 40
+        let mut image = vec![0; 40 * 512];
+        image[512..520].copy_from_slice(b"EFI PART");
+        image[512 + 72..512 + 80].copy_from_slice(&2u64.to_le_bytes());
+        image[512 + 80..512 + 84].copy_from_slice(&1u32.to_le_bytes());
+        image[512 + 84..512 + 88].copy_from_slice(&128u32.to_le_bytes());
+        image[1024 + 32..1024 + 40].copy_from_slice(&40u64.to_le_bytes());
+        image.extend_from_slice(&test_container());
+
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("apfs_gpt.dd");
+        fs::write(&test_location, image).unwrap();
+
+        let results = parse_apfs_image(
+            &test_location.display().to_string(),
+            &ParserOptions::new().path_prefix("/Users"),
+        )
+        .unwrap();
+        assert!(results.len() == 3);
+    }
+
+    #[test]
+    fn test_parse_apfs_invalid_offsets() {
+        // Checkpoint area that ends past the largest block
+        let mut container = test_container();
+        let mut superblock = container_superblock(1, 0);
+        put(&mut superblock, 112, &u64::MAX.to_le_bytes());
+        let checksum = fletcher64(&superblock[8..]);
+        put(&mut superblock, 0, &checksum.to_le_bytes());
+        put(&mut container, 0, &superblock);
+        let results =
+            parse_apfs_reader(&mut Cursor::new(container), "image", &ParserOptions::new());
+        assert!(matches!(results, Err(FsEventsError::InvalidImage(_))));
+
+        // GPT partition that starts at the end of the address space
+        let mut image = vec![0; 40 * 512];
+        image[512..520].copy_from_slice(b"EFI PART");
+        image[512 + 72..512 + 80].copy_from_slice(&2u64.to_le_bytes());
+        image[512 + 80..512 + 84].copy_from_slice(&1u32.to_le_bytes());
+        image[512 + 84..512 + 88].copy_from_slice(&128u32.to_le_bytes());
+        image[1024 + 32..1024 + 40].copy_from_slice(&(u64::MAX / 512).to_le_bytes());
+        let results = parse_apfs_reader(&mut Cursor::new(image), "image", &ParserOptions::new());
+        assert!(results.is_err());
+    }
+
+    #[test]
+    fn test_parse_apfs_no_container() {
+        let results = parse_apfs_reader(
+            &mut Cursor::new(vec![0; 8192]),
+            "image",
+            &ParserOptions::new(),
+        );
+        assert!(results.is_err());
+    }
+
+    #[test]
+    fn test_directory_name() {
+        let (hashed, _) = directory_record(2, ".fseventsd", 16, 4);
+        assert!(directory_name(&hashed, true) == ".fseventsd");
+
+        let mut unhashed = 2u64.to_le_bytes().to_vec();
+        unhashed.extend_from_slice(&5u16.to_le_bytes());
+        unhashed.extend_from_slice(b"Data\0");
+        assert!(directory_name(&unhashed, false) == "Data");
+    }
+
+    #[test]
+    fn test_inode_size() {
+        let (_, value) = inode(17, 202, 0);
+        assert!(inode_size(&value) == 202);
+        assert!(inode_size(&[0; 10]) == 0);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let block = container_superblock(2, 2);
+        assert!(is_valid_checksum(&block));
+        assert!(!is_valid_checksum(&[0; 64]));
+    }
+}
@@ -0,0 +1,134 @@
+//! Convert parsed records to Apache Arrow RecordBatches
+//!
+//! Each record becomes a row with its path, flags (raw bits and a list of flag names), node ID,
+//! event ID, extra ID, source file, offset, and page. The batches can be handed directly to Arrow
+//! based tools (polars, DataFusion, DuckDB) without a JSON or CSV round trip.
+
+use crate::{error::FsEventsError, fsevents::FsEvents};
+use arrow_array::{
+    builder::{ListBuilder, StringBuilder, UInt32Builder, UInt64Builder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use std::sync::Arc;
+
+/// Schema of the RecordBatches created from records
+pub fn fsevents_schema() -> SchemaRef {
+    let flag_name = Field::new("item", DataType::Utf8, true);
+    Arc::new(Schema::new(vec![
+        Field::new("path", DataType::Utf8, false),
+        Field::new("flags", DataType::List(Arc::new(flag_name)), false),
+        Field::new("raw_flags", DataType::UInt32, false),
+        Field::new("node", DataType::UInt64, false),
+        Field::new("event_id", DataType::UInt64, false),
+        Field::new("extra_id", DataType::UInt32, false),
+        Field::new("source_file", DataType::Utf8, false),
+        Field::new("offset", DataType::UInt64, false),
+        Field::new("page", DataType::UInt32, false),
+    ]))
+}
+
+/// Convert records to a single RecordBatch
+pub fn to_record_batch(fsevents: &[FsEvents]) -> Result<RecordBatch, FsEventsError> {
+    let mut paths = StringBuilder::new();
+    let mut flags = ListBuilder::new(StringBuilder::new());
+    let mut raw_flags = UInt32Builder::with_capacity(fsevents.len());
+    let mut nodes = UInt64Builder::with_capacity(fsevents.len());
+    let mut event_ids = UInt64Builder::with_capacity(fsevents.len());
+    let mut extra_ids = UInt32Builder::with_capacity(fsevents.len());
+    let mut source_files = StringBuilder::new();
+    let mut offsets = UInt64Builder::with_capacity(fsevents.len());
+    let mut pages = UInt32Builder::with_capacity(fsevents.len());
+
+    for fsevent in fsevents {
+        paths.append_value(&fsevent.path);
+        for name in fsevent.event_flags().names() {
+            flags.values().append_value(name);
+        }
+        flags.append(true);
+        raw_flags.append_value(fsevent.raw_flags);
+        nodes.append_value(fsevent.node);
+        event_ids.append_value(fsevent.event_id);
+        extra_ids.append_value(fsevent.extra_id);
+        source_files.append_value(&fsevent.source_file);
+        offsets.append_value(fsevent.offset);
+        pages.append_value(fsevent.page);
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(paths.finish()),
+        Arc::new(flags.finish()),
+        Arc::new(raw_flags.finish()),
+        Arc::new(nodes.finish()),
+        Arc::new(event_ids.finish()),
+        Arc::new(extra_ids.finish()),
+        Arc::new(source_files.finish()),
+        Arc::new(offsets.finish()),
+        Arc::new(pages.finish()),
+    ];
+    RecordBatch::try_new(fsevents_schema(), columns)
+        .map_err(|err| FsEventsError::Output(err.to_string()))
+}
+
+/// Convert records to RecordBatches of at most `batch_size` rows
+pub fn to_record_batches(
+    fsevents: &[FsEvents],
+    batch_size: usize,
+) -> Result<Vec<RecordBatch>, FsEventsError> {
+    fsevents
+        .chunks(batch_size.max(1))
+        .map(to_record_batch)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fsevents_schema, to_record_batch, to_record_batches};
+    use crate::{
+        options::ParserOptions,
+        parser::{parse_bytes, parse_directory_with_options},
+    };
+    use arrow_array::{cast::AsArray, types::UInt64Type, Array};
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn test_to_record_batch() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let fsevents = parse_bytes(&fs::read(test_location).unwrap()).unwrap();
+
+        let batch = to_record_batch(&fsevents).unwrap();
+        assert!(batch.num_rows() == 5);
+        assert!(batch.schema() == fsevents_schema());
+
+        let paths = batch.column_by_name("path").unwrap().as_string::<i32>();
+        assert!(paths.value(4) == "/Users/bob/.ssh/authorized_keys");
+        let event_ids = batch
+            .column_by_name("event_id")
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        assert!(event_ids.value(4) == fsevents[4].event_id);
+
+        let flags = batch.column_by_name("flags").unwrap().as_list::<i32>();
+        let names = flags.value(0);
+        let names = names.as_string::<i32>();
+        assert!(names.len() == 2);
+        assert!(names.value(0) == "Created" && names.value(1) == "IsFile");
+    }
+
+    #[test]
+    fn test_to_record_batches() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let fsevents = parse_directory_with_options(
+            &test_location.display().to_string(),
+            &ParserOptions::new(),
+        )
+        .unwrap();
+
+        let batches = to_record_batches(&fsevents, 2000).unwrap();
+        assert!(batches.len() == 3);
+        assert!(batches[2].num_rows() == 1736);
+        assert!(to_record_batches(&[], 2000).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+//! Parse macOS FsEvent data without blocking an async runtime
+//!
+//! Files are read with `tokio::fs` and decompressed/parsed on the blocking thread pool.
+//! A semaphore limits how many files are read at the same time.
+
+use crate::{
+    error::FsEventsError,
+    fsevents::FsEvents,
+    options::ParserOptions,
+    parser::{enrich_directory, parse_reader_pages, CURRENT_PATH, OLD_PATH},
+    report::ParseReport,
+    size::check_file_size,
+};
+use log::{error, warn};
+use std::{
+    io::{Cursor, Error},
+    sync::Arc,
+};
+use tokio::{fs, sync::Semaphore, task::spawn_blocking};
+
+/// Parse FsEvents files at the default path (and legacy path if enabled in the options)
+/// At most `max_concurrent` files are read at the same time
+pub async fn parse_fseventsd_data_async(
+    options: &ParserOptions,
+    max_concurrent: usize,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    let mut directories = vec![CURRENT_PATH];
+    if options.include_legacy {
+        directories.push(OLD_PATH);
+    }
+
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    for directory in directories {
+        if options.is_cancelled() {
+            break;
+        }
+        let results = parse_directory_async(directory, options, max_concurrent).await;
+        match results {
+            Ok(mut data) => fsevents_data.append(&mut data),
+            Err(err) if options.lenient => {
+                error!(
+                    "Failed to parse FsEvent directory {}, err: {}",
+                    directory, err
+                )
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(fsevents_data)
+}
+
+/// Parse all FsEvents files in a directory using the provided options
+/// At most `max_concurrent` files are read at the same time, records keep the file order
+pub async fn parse_directory_async(
+    directory: &str,
+    options: &ParserOptions,
+    max_concurrent: usize,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_directory_async_with_report(directory, options, max_concurrent)
+        .await
+        .map(|(data, _)| data)
+}
+
+/// Parse all FsEvents files in a directory, and report any files that were skipped
+/// At most `max_concurrent` files are read at the same time, records keep the file order
+pub async fn parse_directory_async_with_report(
+    directory: &str,
+    options: &ParserOptions,
+    max_concurrent: usize,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let files = fseventsd_async(directory, options.skip_uuid).await?;
+    options.notify(|observer| observer.files_discovered(directory, files.len()));
+    let permits = Arc::new(Semaphore::new(max_concurrent.max(1)));
+
+    let mut tasks = Vec::new();
+    for file in &files {
+        let file = file.clone();
+        let permits = permits.clone();
+        let options = options.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = permits.acquire_owned().await.ok();
+            parse_file_report(&file, &options).await
+        }));
+    }
+
+    let mut report = ParseReport::default();
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    for (file, task) in files.iter().zip(tasks) {
+        let results = task
+            .await
+            .unwrap_or_else(|err| Err(FsEventsError::Io(Error::other(err))));
+        match results {
+            Ok((mut data, mut file_report)) => {
+                report.append(&mut file_report);
+                fsevents_data.append(&mut data);
+            }
+            // Large files are always skipped, even if not lenient
+            Err(err @ FsEventsError::FileTooLarge(..)) => {
+                warn!("Skipping FsEvent file {}, err: {}", file, err);
+                report.skip_file(file, err.to_string());
+            }
+            Err(err) if options.lenient => {
+                error!("Failed to parse FsEvent file {}, err: {}", file, err);
+                report.skip_file(file, err.to_string());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let directory = directory.to_string();
+    let options = options.clone();
+    spawn_blocking(move || {
+        enrich_directory(&directory, &mut fsevents_data, &options).map(|_| fsevents_data)
+    })
+    .await
+    .map_err(|err| FsEventsError::Io(Error::other(err)))?
+    .map(|data| (data, report))
+}
+
+/// Read a FsEvents file and parse it on the blocking thread pool
+pub async fn parse_file_async(
+    file: &str,
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_file_report(file, options).await.map(|(data, _)| data)
+}
+
+/// Read a FsEvents file and parse it on the blocking thread pool, and report what could not be
+/// parsed
+async fn parse_file_report(
+    file: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    if options.is_cancelled() {
+        return Ok((Vec::new(), ParseReport::default()));
+    }
+    options.notify(|observer| observer.file_started(file));
+    let results = read_and_parse_file(file, options).await;
+    let records = results
+        .as_ref()
+        .map(|(data, _)| data.len())
+        .unwrap_or_default();
+    options.notify(|observer| observer.file_finished(file, records));
+    results
+}
+
+/// Read a FsEvents file and parse it on the blocking thread pool
+async fn read_and_parse_file(
+    file: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    check_file_size(file, &fs::metadata(file).await?, options.max_file_size)?;
+
+    let buffer = fs::read(file).await?;
+    let file = file.to_string();
+    let options = options.clone();
+    spawn_blocking(move || {
+        let mut report = ParseReport {
+            files_parsed: 1,
+            ..Default::default()
+        };
+        let data = parse_reader_pages(Cursor::new(buffer), &file, &options, &mut report)?;
+        Ok((data, report))
+    })
+    .await
+    .map_err(|err| FsEventsError::Io(Error::other(err)))?
+}
+
+/// Get list of files in a directory, optionally skipping the fseventsd-uuid file
+pub async fn fseventsd_async(
+    directory: &str,
+    skip_uuid: bool,
+) -> Result<Vec<String>, FsEventsError> {
+    if fs::metadata(directory).await.is_err() {
+        return Err(FsEventsError::NotADirectory(directory.to_string()));
+    }
+    let mut entries = fs::read_dir(directory).await?;
+    let mut files: Vec<String> = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if skip_uuid && entry.file_name() == "fseventsd-uuid" {
+            continue;
+        }
+        files.push(entry.path().display().to_string());
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        fseventsd_async, parse_directory_async, parse_directory_async_with_report, parse_file_async,
+    };
+    use crate::{
+        error::FsEventsError,
+        options::ParserOptions,
+        parser::{decompress, fseventsd, parse_directory_with_options},
+        test_util::test_data,
+    };
+    use std::{fs, path::PathBuf};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_parse_directory_async() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let directory = test_location.display().to_string();
+        let options = ParserOptions::new();
+
+        let results = parse_directory_async(&directory, &options, 1)
+            .await
+            .unwrap();
+        let expected = parse_directory_with_options(&directory, &options).unwrap();
+        assert!(results.len() == expected.len());
+        assert!(results
+            .iter()
+            .zip(expected.iter())
+            .all(|(first, second)| first.event_id == second.event_id
+                && first.source_file == second.source_file));
+    }
+
+    #[tokio::test]
+    async fn test_parse_file_async() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let file = test_location.display().to_string();
+
+        let results = parse_file_async(&file, &ParserOptions::new())
+            .await
+            .unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].source_file == file);
+
+        let options = ParserOptions::new().max_file_size(10);
+        let results = parse_file_async(&file, &options).await;
+        assert!(matches!(results, Err(FsEventsError::FileTooLarge(_, 10))));
+    }
+
+    #[tokio::test]
+    async fn test_parse_directory_async_with_report() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let directory = test_location.display().to_string();
+
+        let options = ParserOptions::new().lenient(false).max_file_size(20000);
+        let (results, report) = parse_directory_async_with_report(&directory, &options, 2)
+            .await
+            .unwrap();
+        assert!(results.len() == 736);
+        assert!(report.files_parsed == 1);
+        assert!(report.skipped_files.len() == 1);
+        assert!(report.skipped_files[0].path.ends_with("000000000002469c"));
+        assert!(report.skipped_files[0].reason.contains("20000 bytes"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_directory_async_skip_corrupt_pages() {
+        let temp = tempdir().unwrap();
+        let mut data = decompress(&test_data("Malformed/malformed").display().to_string()).unwrap();
+        data.append(
+            &mut decompress(&test_data("DLS3/0000000000030012").display().to_string()).unwrap(),
+        );
+        fs::write(temp.path().join("0000000000030012"), &data).unwrap();
+        let directory = temp.path().display().to_string();
+
+        let options = ParserOptions::new().skip_corrupt_pages(true);
+        let (results, report) = parse_directory_async_with_report(&directory, &options, 1)
+            .await
+            .unwrap();
+        assert!(results.len() == 5);
+        assert!(report.files_parsed == 1);
+        assert!(report.skipped_pages.len() == 1);
+        assert!(report.skipped_pages[0].path.ends_with("0000000000030012"));
+    }
+
+    #[tokio::test]
+    async fn test_fseventsd_async() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let directory = test_location.display().to_string();
+        let mut files = fseventsd_async(&directory, true).await.unwrap();
+        let mut expected = fseventsd(&directory).unwrap();
+        files.sort();
+        expected.sort();
+        assert!(files == expected);
+
+        let results = fseventsd_async("/this/path/does/not/exist", true).await;
+        assert!(matches!(results, Err(FsEventsError::NotADirectory(_))));
+    }
+}
@@ -0,0 +1,319 @@
+//! Suppress records that match a baseline of normal activity
+//!
+//! A baseline is made from the records of a known clean system, and then used to hide the same
+//! activity on a suspect system so only the anomalous records are left. Paths are normalized so
+//! the baseline applies to other systems and users:
+//! - User names are replaced with `*` (ex: `/Users/bob/Library` is `/Users/*/Library`), except
+//!   `/Users/Shared`
+//! - The random directories under `/private/var/folders` are replaced with `*`
+//! - Words containing a digit (ex: version numbers, UUIDs, and temporary file names) are replaced
+//!   with `*` (ex: `com.apple.Safari.A1B2` is `com.apple.Safari.*`)
+//!
+//! A baseline file has one entry per line, blank lines and lines starting with `#` are skipped.
+//! Entries are the comma separated flags (`-` for none) and the path, separated by a space
+//! (ex: `Created,IsFile /Users/*/Library/Caches/com.apple.Safari/Cache.db`). Entries can be added
+//! by hand to allowlist activity: an entry without flags matches any flags, and a path ending in
+//! `/**` matches everything under the directory (ex: `/Users/*/Library/Caches/**`).
+//! `*` is not a wildcard, it only stands for the parts normalization replaces, so hand written
+//! paths must be normalized (ex: `/Users/*/Library/Caches/*.db` does not match `Cache.db`).
+//!
+//! Matching is case insensitive, like most macOS volumes.
+
+use crate::{error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents};
+use std::collections::{HashMap, HashSet};
+
+/// Paths and flags of normal activity
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    entries: HashMap<String, HashSet<Option<FsEventFlags>>>, // Flags for each normalized path, None is any
+}
+
+impl Baseline {
+    /// Make a baseline of the paths and flags in the records
+    pub fn from_records(fsevents: &[FsEvents]) -> Baseline {
+        let mut baseline = Baseline::default();
+        for fsevent in fsevents {
+            baseline.add(&fsevent.path, Some(fsevent.event_flags()));
+        }
+        baseline
+    }
+
+    /// Read the entries of a newline delimited baseline
+    pub fn parse(text: &str) -> Result<Baseline, FsEventsError> {
+        let mut baseline = Baseline::default();
+        for line in text.lines() {
+            let entry = line.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            if entry.starts_with('/') {
+                baseline.add(entry, None);
+                continue;
+            }
+            let (flags, path) = entry
+                .split_once(char::is_whitespace)
+                .map(|(flags, path)| (flags, path.trim_start()))
+                .filter(|(_, path)| path.starts_with('/'))
+                .ok_or_else(|| FsEventsError::InvalidBaseline(format!("no path in: {}", entry)))?;
+            let flags = if flags == "-" {
+                FsEventFlags::empty()
+            } else {
+                flags.parse()?
+            };
+            baseline.add(path, Some(flags));
+        }
+        Ok(baseline)
+    }
+
+    #[cfg(feature = "filesystem")]
+    /// Read a baseline file
+    pub fn from_file(path: &str) -> Result<Baseline, FsEventsError> {
+        Baseline::parse(&std::fs::read_to_string(path)?)
+    }
+
+    #[cfg(feature = "filesystem")]
+    /// Save the baseline to a file
+    pub fn save(&self, path: &str) -> Result<(), FsEventsError> {
+        std::fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    /// Get the baseline file text, with the entries sorted by path
+    pub fn to_text(&self) -> String {
+        let mut lines: Vec<(&str, String)> = Vec::new();
+        for (path, flags) in &self.entries {
+            for flag in flags {
+                let flag = match flag {
+                    Some(flag) if flag.is_empty() => String::from("-"),
+                    Some(flag) => flag.to_string(),
+                    None => String::new(),
+                };
+                lines.push((path, flag));
+            }
+        }
+        lines.sort_unstable();
+
+        let mut text = String::from("# FsEvents baseline: <flags> <path>, paths are normalized\n");
+        for (path, flags) in lines {
+            if flags.is_empty() {
+                text.push_str(path);
+            } else {
+                text.push_str(&format!("{} {}", flags, path));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Get the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.values().map(HashSet::len).sum()
+    }
+
+    /// Check if the baseline has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Check if a record's path and flags are in the baseline
+    pub fn matches(&self, fsevent: &FsEvents) -> bool {
+        let path = normalize_path(&fsevent.path).to_lowercase();
+        let flags = fsevent.event_flags();
+        let matches_flags = |key: &str| {
+            self.entries
+                .get(key)
+                .is_some_and(|entry| entry.contains(&None) || entry.contains(&Some(flags)))
+        };
+        if matches_flags(&path) {
+            return true;
+        }
+        // Directory entries match everything under them
+        let mut directory = path.as_str();
+        while let Some(index) = directory.rfind('/') {
+            directory = &directory[..index];
+            if matches_flags(&format!("{}/**", directory)) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Remove the records that are in the baseline, and return how many were removed
+    pub fn suppress(&self, fsevents: &mut Vec<FsEvents>) -> usize {
+        let count = fsevents.len();
+        fsevents.retain(|fsevent| !self.matches(fsevent));
+        count - fsevents.len()
+    }
+
+    /// Add an entry for a path
+    fn add(&mut self, path: &str, flags: Option<FsEventFlags>) {
+        self.entries
+            .entry(normalize_path(path).to_lowercase())
+            .or_default()
+            .insert(flags);
+    }
+}
+
+/// Normalize a path so the same activity matches on other systems and for other users
+/// (ex: `/Users/bob/Library/Caches/com.apple.Safari/fsCachedData/5B3A1C2E-8F4D` is
+/// `/Users/*/Library/Caches/com.apple.Safari/fsCachedData/*-*`)
+pub fn normalize_path(path: &str) -> String {
+    let temporary = path.starts_with("/private/var/folders/");
+    path.split('/')
+        .enumerate()
+        .map(|(index, component)| match index {
+            2 if path.starts_with("/Users/")
+                && !component.is_empty()
+                && !component.eq_ignore_ascii_case("Shared") =>
+            {
+                String::from("*")
+            }
+            4 | 5 if temporary && !component.is_empty() => String::from("*"),
+            _ => normalize_component(component),
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Replace the words of a path component that contain a digit with `*`
+fn normalize_component(component: &str) -> String {
+    let mut normalized = String::with_capacity(component.len());
+    let mut word = String::new();
+    for character in component.chars() {
+        if character.is_alphanumeric() {
+            word.push(character);
+        } else {
+            end_word(&mut word, &mut normalized);
+            normalized.push(character);
+        }
+    }
+    end_word(&mut word, &mut normalized);
+    normalized
+}
+
+/// Add a word to a normalized path component, replacing it with `*` if it contains a digit
+fn end_word(word: &mut String, normalized: &mut String) {
+    if word.chars().any(|character| character.is_ascii_digit()) {
+        normalized.push('*');
+    } else {
+        normalized.push_str(word);
+    }
+    word.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_path, Baseline};
+    use crate::{error::FsEventsError, fsevents::FsEvents};
+
+    fn record(path: &str, flags: u32) -> FsEvents {
+        FsEvents {
+            path: path.to_string(),
+            raw_flags: flags,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert!(
+            normalize_path("/Users/bob/Library/Caches/com.apple.Safari/5B3A1C2E-8F4D")
+                == "/Users/*/Library/Caches/com.apple.Safari/*-*"
+        );
+        assert!(normalize_path("/Users/Shared/file.txt") == "/Users/Shared/file.txt");
+        assert!(
+            normalize_path("/private/var/folders/zz/zyxvpxvq6csfxvn_n00000/T/log.txt")
+                == "/private/var/folders/*/*/T/log.txt"
+        );
+        assert!(normalize_path("/Applications/App 2.app") == "/Applications/App *.app");
+        assert!(normalize_path("/Users") == "/Users");
+        assert!(normalize_path("").is_empty());
+    }
+
+    #[test]
+    fn test_baseline_from_records() {
+        let clean = vec![
+            record(
+                "/Users/alice/Library/Caches/com.apple.Safari/Cache.db-wal",
+                0x800001,
+            ),
+            record("/private/var/log/system.log.1", 0x800010),
+        ];
+        let baseline = Baseline::from_records(&clean);
+        assert!(baseline.len() == 2);
+
+        let mut suspect = vec![
+            record(
+                "/Users/bob/Library/Caches/com.apple.Safari/Cache.db-wal",
+                0x800001,
+            ),
+            // Same path, different flags
+            record(
+                "/Users/bob/Library/Caches/com.apple.Safari/Cache.db-wal",
+                0x800200,
+            ),
+            record("/private/var/log/SYSTEM.log.2", 0x800010),
+            record("/Users/bob/Library/LaunchAgents/com.evil.plist", 0x800001),
+        ];
+        assert!(baseline.suppress(&mut suspect) == 2);
+        assert!(suspect.len() == 2);
+        assert!(suspect[0].raw_flags == 0x800200);
+        assert!(suspect[1].path.ends_with("com.evil.plist"));
+    }
+
+    #[test]
+    fn test_baseline_text() {
+        let baseline = Baseline::parse(
+            "# Allowlist\n\
+            \n\
+            /Users/*/Library/Caches/**\n\
+            Created,IsFile /private/tmp/build 12.log\n\
+            - /private/var/db/empty\n",
+        )
+        .unwrap();
+        assert!(baseline.len() == 3);
+        assert!(baseline.matches(&record("/Users/bob/Library/Caches/a/b/c.db", 0x02)));
+        assert!(!baseline.matches(&record("/Users/bob/Library/Cookies.db", 0x02)));
+        assert!(baseline.matches(&record("/private/tmp/build 99.log", 0x800001)));
+        assert!(!baseline.matches(&record("/private/tmp/build 99.log", 0x800002)));
+        assert!(baseline.matches(&record("/private/var/db/empty", 0)));
+
+        // Saved baselines can be read back
+        let text = baseline.to_text();
+        assert!(text.contains("\nCreated,IsFile /private/tmp/build *.log\n"));
+        assert!(text.contains("\n/users/*/library/caches/**\n"));
+        let saved = Baseline::parse(&text).unwrap();
+        assert!(saved.len() == 3);
+        assert!(saved.matches(&record("/private/var/db/empty", 0)));
+    }
+
+    #[test]
+    fn test_baseline_placeholder() {
+        let baseline = Baseline::parse(
+            "Created,IsFile /Users/*/Library/Caches/com.apple.Safari/Cache.db
+            Created,IsFile /Users/*/Library/Caches/*.db
+",
+        )
+        .unwrap();
+        assert!(baseline.matches(&record(
+            "/Users/bob/Library/Caches/com.apple.Safari/Cache.db",
+            0x800001
+        )));
+        // `*` only matches the words normalization replaces
+        assert!(!baseline.matches(&record("/Users/bob/Library/Caches/Cookies.db", 0x800001)));
+        assert!(baseline.matches(&record("/Users/bob/Library/Caches/2.db", 0x800001)));
+    }
+
+    #[test]
+    fn test_baseline_invalid() {
+        assert!(matches!(
+            Baseline::parse("Created"),
+            Err(FsEventsError::InvalidBaseline(_))
+        ));
+        assert!(matches!(
+            Baseline::parse("Made /tmp/a"),
+            Err(FsEventsError::InvalidFlag(_))
+        ));
+        assert!(Baseline::parse("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+//! osquery extension with a `fsevents_records` table
+//!
+//! Load it with `osqueryi --extension <path to fsevents_osquery>`, or add it to the osquery
+//! extensions autoload file (osquery expects autoloaded extensions to end with `.ext`).
+
+use std::{env, process::exit};
+
+#[cfg(unix)]
+fn main() {
+    let mut socket = None;
+    let mut timeout = 3;
+    let mut interval = 3;
+
+    // osquery passes --socket, --timeout, --interval, and --verbose
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--socket" => socket = args.next(),
+            "--timeout" => timeout = number_value(args.next(), &arg),
+            "--interval" => interval = number_value(args.next(), &arg),
+            _ => {}
+        }
+    }
+    let socket = match socket {
+        Some(result) => result,
+        None => {
+            println!("Missing --socket, fsevents_osquery is started by osquery");
+            exit(1);
+        }
+    };
+
+    let extension = macos_fseventsd::osquery::OsqueryExtension::new(&socket)
+        .timeout(timeout)
+        .interval(interval);
+    if let Err(err) = extension.run() {
+        println!("{}", err);
+        exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("The osquery extension is only supported on Unix systems");
+    exit(1);
+}
+
+/// Get a number of seconds argument value
+#[cfg(unix)]
+fn number_value(value: Option<String>, arg: &str) -> u64 {
+    match value.map(|value| value.parse::<u64>()) {
+        Some(Ok(seconds)) => seconds,
+        _ => {
+            println!("Invalid value for {}", arg);
+            exit(1);
+        }
+    }
+}
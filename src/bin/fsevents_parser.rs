@@ -0,0 +1,2193 @@
+//! Parse macOS FsEvent records to CSV, JSON, and other formats
+//!
+//! Run without an input on a live system (as root) to parse the default fseventsd directory, or
+//! use `--input` to parse FsEvent files that were acquired by another tool.
+
+use clap::{
+    error::ErrorKind, parser::ValueSource, ArgAction, ArgGroup, ArgMatches, CommandFactory, Parser,
+    Subcommand, ValueEnum,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use macos_fseventsd::{
+    self,
+    baseline::Baseline,
+    checkpoint::Checkpoint,
+    dedup::{dedup_fsevents, DedupKey},
+    error::FsEventsError,
+    flags::FsEventFlags,
+    fsevents::FsEvents,
+    iter::FsEventsIter,
+    options::ParserOptions,
+    output::{
+        bodyfile::write_bodyfile,
+        compress::{CompressedWriter, OutputCompression},
+        csv::{Column, CsvWriter},
+        ecs::write_ecs,
+        html::write_html_report,
+        jsonl::{write_jsonl, JsonLinesWriter},
+        siem::{write_cef, write_leef},
+        split::{parse_size, part_path, CountingWriter},
+        time::{TimeFormat, TimeStyle},
+        timeline::{write_l2tcsv, write_timesketch},
+    },
+    progress::ProgressObserver,
+    report::{ParseReport, Skipped},
+    sort::{sort_fsevents, sort_fsevents_by_path},
+    stats::{busiest_directories, get_stats, DirectoryCount, FsEventsStats},
+    stream::PageReader,
+    tail::DirectoryTail,
+    triage::{triage_fsevents, Triage},
+    watchlist::Watchlist,
+};
+use serde::Serialize;
+use simplelog::{ConfigBuilder, LevelFilter, WriteLogger};
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    env,
+    error::Error,
+    ffi::OsString,
+    fs::{create_dir_all, read_dir, File},
+    io::{self, stderr, stdin, stdout, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::exit,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Input value that reads FsEvent data from stdin
+const STDIN: &str = "-";
+/// Source file of records read from stdin
+const STDIN_NAME: &str = "stdin";
+
+// Process exit codes, so collection scripts can tell a partial parse from a failed one
+/// Records were saved, but some files, directories, or pages could not be parsed
+const EXIT_PARTIAL: i32 = 1;
+/// No FsEvent files or records were found
+const EXIT_NO_INPUT: i32 = 2;
+/// FsEvent data could not be read (ex: not run with sudo or Full Disk Access)
+const EXIT_PERMISSION_DENIED: i32 = 3;
+/// Invalid arguments, no FsEvent file could be parsed, or parsing or saving the records failed
+const EXIT_FAILURE: i32 = 4;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "fsevents_parser",
+    version,
+    about = "Parse macOS FsEvent records",
+    args_conflicts_with_subcommands = true,
+    after_help = "Exit codes: 0 success, 1 some files, directories, or pages were skipped, \
+                  2 no FsEvent data found, 3 permission denied, 4 invalid arguments or failure"
+)]
+#[command(group(ArgGroup::new("source").args([
+    "paths",
+    "input",
+    "file",
+    "carve_image",
+    "carve_memory",
+    "apfs_image",
+    "collection",
+    "validate",
+])))]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// FsEvent files and directories to parse in one run (ex: the primary, legacy, and external
+    /// volume fseventsd directories). Records are merged and sorted, and keep their source file
+    #[arg(value_name = "PATH")]
+    paths: Vec<String>,
+
+    /// TOML file of options to use for this run (ex: a standard parsing profile). Keys are the long
+    /// option names (ex: format = "jsonl", include_flags = ["Removed"]), options given on the
+    /// command line take precedence
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Directory containing FsEvent files, or `-` to read FsEvent files piped to stdin
+    /// [default: the live system's fseventsd directory]
+    #[arg(short, long, value_name = "DIRECTORY")]
+    input: Option<String>,
+
+    /// Search the input directory (ex: the root of a mounted image) for every .fseventsd directory
+    /// and loose FsEvent file
+    #[arg(short, long, requires = "input")]
+    recursive: bool,
+
+    /// Parse a single FsEvent file (ex: a carved or suspicious file)
+    #[arg(short, long, value_name = "FILE")]
+    file: Option<String>,
+
+    /// Directory to save the output files to
+    #[arg(short, long, value_name = "DIRECTORY", default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Also parse FsEvent files at the legacy /.fseventsd path on a live system
+    #[arg(long)]
+    legacy: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+
+    /// Comma separated CSV and TSV columns to save, in order (ex: path,flags,event_id)
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = column_value)]
+    columns: Vec<Column>,
+
+    /// Write the records to stdout instead of saving a file. CSV, TSV, and JSON Lines records are written
+    /// as they are parsed. Status messages are written to stderr
+    #[arg(long, conflicts_with_all = ["output_dir", "split_per_file"])]
+    stdout: bool,
+
+    /// Save one output file per FsEvent file (named after it) instead of one combined file
+    #[arg(long)]
+    split_per_file: bool,
+
+    /// Save csv, tsv, jsonl, or ndjson output in numbered parts (ex: output_0001.csv) of at most
+    /// this many records, for tools that cannot open very large files
+    #[arg(
+        long,
+        value_name = "COUNT",
+        conflicts_with_all = ["stdout", "split_per_file", "checkpoint"],
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    split_records: Option<u64>,
+
+    /// Save csv, tsv, jsonl, or ndjson output in numbered parts, starting a new part once one
+    /// reaches this size (ex: 500MB). Units are powers of 1024. A part can go over by the records
+    /// that were still buffered when it reached the size
+    #[arg(
+        long,
+        value_name = "SIZE",
+        conflicts_with_all = ["stdout", "split_per_file", "checkpoint"],
+        value_parser = split_size_value
+    )]
+    split_size: Option<u64>,
+
+    /// Compress the output files while they are written: gzip (.gz) or zstd (.zst, requires the
+    /// zstd feature). Not used for xlsx, parquet, or sqlite output
+    #[arg(long, value_name = "COMPRESSION", conflicts_with = "stdout", value_parser = compress_value)]
+    compress: Option<OutputCompression>,
+
+    /// Save the FsEvent files written to the output in a checkpoint file, so an interrupted run can
+    /// be resumed with the same options without parsing them again or duplicating records. Requires
+    /// csv, tsv, jsonl, or ndjson output, which is saved as the files are parsed (without sorting)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["stdout", "compress"])]
+    checkpoint: Option<String>,
+
+    /// Layout of --format json output: compact (one line array), pretty (indented array), or ndjson
+    /// (one record per line, saved while the files are parsed like --format jsonl)
+    #[arg(long, value_enum, default_value_t = JsonStyle::Compact)]
+    json_style: JsonStyle,
+
+    /// How the approximate, estimated, and live file times are written in CSV, JSON, and JSON
+    /// Lines output: unix (epoch seconds), iso8601, or a strftime pattern (ex: "%Y-%m-%d %H:%M:%S %Z")
+    #[arg(long, value_name = "FORMAT", default_value = "unix", value_parser = time_style_value)]
+    time_format: TimeStyle,
+
+    /// Time zone for --time-format: UTC, local, a fixed offset (ex: +05:30), or an IANA time zone
+    /// (ex: America/New_York)
+    #[arg(long, value_name = "ZONE", default_value = "UTC")]
+    timezone: String,
+
+    /// Remove duplicate records across the inputs before saving them. Records are duplicates if
+    /// they have the same `record` (path, event ID, flags, and node ID), `path-event-id`, or `path`
+    #[arg(
+        long,
+        value_name = "KEY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "record",
+        value_parser = dedupe_value
+    )]
+    dedupe: Option<DedupKey>,
+
+    /// Order to save records in [default: event-id, except JSON Lines and CSV written to stdout,
+    /// which are saved in the order they are parsed unless --sort is used]
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ORDER",
+        num_args = 0..=1,
+        default_missing_value = "event-id"
+    )]
+    sort: Option<SortOrder>,
+
+    /// Only keep records with a path matching the glob, or regex with a `regex:` prefix
+    /// (ex: `~/Library/LaunchAgents/**`). `~/` matches any user's home. Can be used multiple times
+    #[arg(long, value_name = "PATTERN")]
+    path_match: Vec<String>,
+
+    /// Drop records with a path matching the glob, or regex with a `regex:` prefix. Can be used
+    /// multiple times
+    #[arg(long, value_name = "PATTERN")]
+    path_exclude: Vec<String>,
+
+    /// Only keep records with a path matching the regex. Can be used multiple times
+    #[arg(long, value_name = "PATTERN")]
+    regex: Vec<String>,
+
+    /// Drop records with a path matching the regex. Can be used multiple times
+    #[arg(long, value_name = "PATTERN")]
+    exclude_regex: Vec<String>,
+
+    /// Only keep records with at least one of the comma separated flags (ex: Removed,Renamed)
+    #[arg(long, value_name = "FLAGS", value_parser = flags_value)]
+    include_flags: Option<FsEventFlags>,
+
+    /// Drop records with any of the comma separated flags (ex: IsDirectory)
+    #[arg(long, value_name = "FLAGS", value_parser = flags_value)]
+    exclude_flags: Option<FsEventFlags>,
+
+    /// Only keep records with an event ID at or above the ID (decimal or 0x hex)
+    #[arg(long, value_name = "ID", value_parser = event_id_value)]
+    min_event_id: Option<u64>,
+
+    /// Only keep records with an event ID at or below the ID (decimal or 0x hex)
+    #[arg(long, value_name = "ID", value_parser = event_id_value)]
+    max_event_id: Option<u64>,
+
+    /// Only keep records matching the built-in triage rules (launch agents and daemons, executables
+    /// in temporary directories, browser downloads, SSH keys, and other persistence locations).
+    /// The matching rule names are saved in the Triage Rule column
+    #[arg(long)]
+    triage: bool,
+
+    /// Newline delimited file of IOC paths, file names, or globs to look for. Every matching
+    /// record is saved to watchlist_hits.json in the output directory
+    #[arg(long, value_name = "FILE")]
+    watchlist: Option<String>,
+
+    /// Save a baseline of the parsed paths and flags to the file. Parse a known clean system with
+    /// this, then use the baseline with --baseline to hide the same activity on another system
+    #[arg(long, value_name = "FILE", conflicts_with = "checkpoint")]
+    save_baseline: Option<String>,
+
+    /// Baseline file of normal paths and flags, from --save-baseline or written as an allowlist.
+    /// Matching records are suppressed so only anomalous activity is saved
+    #[arg(long, value_name = "FILE", conflicts_with = "checkpoint")]
+    baseline: Option<String>,
+
+    /// Also save report.html in the output directory, a standalone HTML summary with the headline
+    /// stats, records per flag, busiest directories, and deletion spikes, linked to the export
+    #[arg(long, conflicts_with = "checkpoint")]
+    report: bool,
+
+    /// Skip FsEvent files larger than the size in bytes [default: 2GB]
+    #[arg(long, value_name = "BYTES")]
+    max_file_size: Option<u64>,
+
+    /// Stop at the first FsEvent file or directory that cannot be parsed instead of skipping it
+    #[arg(long, conflicts_with_all = ["skip_corrupt_pages", "resync_records"])]
+    fail_fast: bool,
+
+    /// Skip pages that cannot be parsed and keep parsing the rest of the file
+    #[arg(long)]
+    skip_corrupt_pages: bool,
+
+    /// Skip bad records in a page and keep the records after them
+    #[arg(long)]
+    resync_records: bool,
+
+    /// Check if each path still exists on the live system
+    #[arg(long)]
+    live_status: bool,
+
+    /// Find the current path of each record's node on the live system (macOS only)
+    #[arg(long)]
+    resolve_nodes: bool,
+
+    /// Carve records from a raw (dd) or EWF (E01) disk image instead of parsing FsEvent files
+    #[arg(long, value_name = "IMAGE")]
+    carve_image: Option<String>,
+
+    /// Carve records from a memory capture
+    #[arg(long, value_name = "IMAGE")]
+    carve_memory: Option<String>,
+
+    /// Parse the FsEvent files in the APFS volumes of a disk image (requires the apfs feature)
+    #[arg(long, value_name = "IMAGE")]
+    apfs_image: Option<String>,
+
+    /// Parse every .fseventsd directory in a Velociraptor or KAPE collection (folder or zip)
+    #[arg(long, value_name = "PATH")]
+    collection: Option<String>,
+
+    /// Check the FsEvent files in a directory for damage instead of parsing them
+    #[arg(long, value_name = "DIRECTORY")]
+    validate: Option<String>,
+
+    /// Show more log messages (-v info, -vv debug, -vvv trace). Warnings are shown by default
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Only show error log messages, and hide the progress bar
+    #[arg(short, long, conflicts_with = "verbose", global = true)]
+    quiet: bool,
+
+    /// Hide the progress bar
+    #[arg(long, global = true)]
+    no_progress: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Print a summary of the records (counts, flags, busiest directories, event ID range, and
+    /// files that could not be parsed) without saving them
+    Stats(StatsArgs),
+    /// Carve FsEvent records from a disk image, memory image, or any other data, and save them
+    /// with a recovery report of every page found
+    Carve(CarveArgs),
+    /// Record live file system events with the macOS FSEvents API until stopped (Ctrl+C), in the
+    /// same format as the parsed records (requires the watch feature)
+    Watch(WatchArgs),
+    /// Follow an fseventsd directory until stopped (Ctrl+C), saving the records of each FsEvent file
+    /// once fseventsd finishes writing it
+    Tail(TailArgs),
+    /// Print a completion script for a shell (ex: `fsevents_parser completions bash >
+    /// /usr/local/etc/bash_completion.d/fsevents_parser`)
+    Completions(CompletionsArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct StatsArgs {
+    /// FsEvent files and directories to summarize
+    /// [default: the live system's fseventsd directory]
+    #[arg(value_name = "PATH")]
+    paths: Vec<String>,
+
+    /// Also summarize the FsEvent files at the legacy /.fseventsd path on a live system
+    #[arg(long)]
+    legacy: bool,
+
+    /// Number of busiest directories to list
+    #[arg(long, value_name = "COUNT", default_value_t = 10)]
+    top: usize,
+
+    /// Print the summary as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct CarveArgs {
+    /// Disk image, memory image, or file to carve records from
+    #[arg(value_name = "PATH")]
+    path: String,
+
+    /// What the input is
+    #[arg(long, value_enum, default_value = "image")]
+    kind: CarveKind,
+
+    /// Directory to save the records and the recovery report (carve_report.json) to
+    #[arg(short, long, value_name = "DIRECTORY", default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Output format of the records
+    #[arg(long, value_enum, default_value = "csv")]
+    format: Format,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CarveKind {
+    /// Raw (dd) or EWF (E01) disk image, deleted FsEvent files and decompressed pages are carved
+    Image,
+    /// Memory image, torn pages are skipped over
+    Memory,
+    /// Any other file (ex: a damaged FsEvent file or a swap file), decompressed first if compressed
+    Blob,
+}
+
+#[derive(clap::Args, Debug)]
+struct WatchArgs {
+    /// Directories to watch
+    #[arg(value_name = "PATH", default_value = "/")]
+    paths: Vec<String>,
+
+    /// Output format of the records
+    #[arg(long, value_enum, default_value = "jsonl")]
+    format: WatchFormat,
+
+    /// File to save the records to [default: stdout]
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Replay the events after this event ID that are still in the fseventsd logs before watching
+    #[arg(long, value_name = "EVENT_ID", value_parser = event_id_value)]
+    since: Option<u64>,
+
+    /// Seconds the FSEvents API waits to group events
+    #[arg(long, value_name = "SECONDS", default_value_t = 1.0)]
+    latency: f64,
+}
+
+#[derive(clap::Args, Debug)]
+struct TailArgs {
+    /// fseventsd directory to follow
+    #[arg(
+        value_name = "DIRECTORY",
+        default_value = "/System/Volumes/Data/.fseventsd"
+    )]
+    directory: String,
+
+    /// Output format of the records
+    #[arg(long, value_enum, default_value = "jsonl")]
+    format: WatchFormat,
+
+    /// File to save the records to [default: stdout]
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Seconds to wait between checks for finished FsEvent files
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    interval: u64,
+
+    /// Also save the records of the FsEvent files that are already finished
+    #[arg(long)]
+    from_start: bool,
+
+    /// Check the directory once and exit instead of following it
+    #[arg(long)]
+    once: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompletionsArgs {
+    /// Shell to complete the options for
+    #[arg(value_enum)]
+    shell: clap_complete::Shell,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum WatchFormat {
+    /// JSON object per line
+    Jsonl,
+    /// CSV with every column
+    Csv,
+}
+
+/// Summary printed by the stats subcommand
+#[derive(Serialize)]
+struct Summary<'a> {
+    #[serde(flatten)]
+    stats: FsEventsStats,
+    busiest_directories: Vec<DirectoryCount>,
+    files_parsed: usize,
+    unparseable_files: Vec<&'a Skipped>,
+    truncated_files: &'a [String],
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SortOrder {
+    /// Event ID, so records read as a timeline
+    EventId,
+    /// Path, then event ID, so the records for a file are together
+    Path,
+    /// The order the records were parsed in
+    None,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum JsonStyle {
+    /// JSON array on a single line
+    Compact,
+    /// Indented JSON array, for reading
+    Pretty,
+    /// Newline delimited JSON, one record per line
+    Ndjson,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// CSV with the --columns
+    Csv,
+    /// Tab separated values with the --columns
+    Tsv,
+    /// JSON array of records
+    Json,
+    /// JSON Lines, saved while the files are parsed
+    Jsonl,
+    /// Elastic Common Schema JSON Lines
+    Ecs,
+    /// ArcSight Common Event Format
+    Cef,
+    /// QRadar Log Event Extended Format
+    Leef,
+    /// plaso l2tcsv timeline
+    L2tcsv,
+    /// Timesketch JSON Lines
+    Timesketch,
+    /// Sleuth Kit bodyfile for mactime
+    Bodyfile,
+    /// Excel workbook (requires the xlsx feature)
+    Xlsx,
+    /// zstd compressed Parquet (requires the parquet feature)
+    Parquet,
+    /// Indexed SQLite database (requires the sqlite feature)
+    Sqlite,
+}
+
+impl Format {
+    /// Check if the format can only be saved to a file
+    fn is_binary(self) -> bool {
+        matches!(self, Format::Xlsx | Format::Parquet | Format::Sqlite)
+    }
+
+    /// Name of the file the format is saved to in the output directory
+    fn file_name(self) -> &'static str {
+        match self {
+            Format::Csv => "output.csv",
+            Format::Tsv => "output.tsv",
+            Format::Json => "output.json",
+            Format::Jsonl => "output.jsonl",
+            Format::Ecs => "output_ecs.jsonl",
+            Format::Cef => "output.cef",
+            Format::Leef => "output.leef",
+            Format::L2tcsv => "output_l2t.csv",
+            Format::Timesketch => "output_timesketch.jsonl",
+            Format::Bodyfile => "output.body",
+            Format::Xlsx => "output.xlsx",
+            Format::Parquet => "output.parquet",
+            Format::Sqlite => "output.db",
+        }
+    }
+
+    /// Delimiter between the values of the CSV and TSV formats
+    fn delimiter(self) -> u8 {
+        match self {
+            Format::Tsv => b'\t',
+            _ => b',',
+        }
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let progress = Arc::new(ProgressBarObserver::new(!args.quiet && !args.no_progress));
+    init_logger(&args, &progress.bar);
+    match &args.command {
+        Some(Commands::Stats(stats_args)) => return stats(stats_args, progress),
+        Some(Commands::Carve(carve_args)) => return carve(carve_args, progress),
+        Some(Commands::Watch(watch_args)) => return watch(watch_args),
+        Some(Commands::Tail(tail_args)) => return tail(tail_args),
+        Some(Commands::Completions(completions_args)) => return completions(completions_args),
+        None => {}
+    }
+    if let Some(directory) = &args.validate {
+        validate(directory);
+        return;
+    }
+    if args.stdout && args.format.is_binary() {
+        let _ = Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--format {} cannot be written to stdout",
+                    args.format.to_possible_value().unwrap().get_name()
+                ),
+            )
+            .print();
+        exit(EXIT_FAILURE);
+    }
+    // Status messages go to stderr, so they do not mix with records written to stdout
+    eprintln!("Starting FSEvents parser...");
+
+    let options = match parser_options(&args) {
+        Ok(result) => result.progress_observer(progress.clone()),
+        Err(err) => {
+            eprintln!("Invalid argument: {}", err);
+            exit(EXIT_FAILURE);
+        }
+    };
+    if !args.stdout {
+        if let Err(err) = create_dir_all(&args.output_dir) {
+            eprintln!(
+                "Failed to create output directory {}: {}",
+                args.output_dir.display(),
+                err
+            );
+            exit(EXIT_FAILURE);
+        }
+    }
+    let columns = if args.columns.is_empty() {
+        Column::ALL.to_vec()
+    } else {
+        args.columns.clone()
+    };
+    if args.compress.is_some()
+        && matches!(args.format, Format::Xlsx | Format::Parquet | Format::Sqlite)
+    {
+        eprintln!("--compress cannot be used with xlsx, parquet, or sqlite output");
+        exit(EXIT_FAILURE);
+    }
+    let split = args.split_records.is_some() || args.split_size.is_some();
+    if split
+        && !matches!(args.format, Format::Csv | Format::Tsv | Format::Jsonl)
+        && !(args.format == Format::Json && args.json_style == JsonStyle::Ndjson)
+    {
+        eprintln!("--split-records and --split-size require csv, tsv, jsonl, or ndjson output");
+        exit(EXIT_FAILURE);
+    }
+    let path = compressed_path(args.output_dir.join(args.format.file_name()), args.compress);
+    let time_format = match TimeFormat::new()
+        .timezone(&args.timezone)
+        .and_then(|format| format.style(args.time_format.clone()))
+    {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Invalid argument: {}", err);
+            exit(EXIT_FAILURE);
+        }
+    };
+    let watchlist = args.watchlist.as_deref().map(|path| {
+        Watchlist::from_file(path).unwrap_or_else(|err| {
+            eprintln!("Failed to read watchlist {}: {}", path, err);
+            exit(EXIT_FAILURE);
+        })
+    });
+    let baseline = args.baseline.as_deref().map(|path| {
+        Baseline::from_file(path).unwrap_or_else(|err| {
+            eprintln!("Failed to read baseline {}: {}", path, err);
+            exit(EXIT_FAILURE);
+        })
+    });
+
+    // JSON Lines (and CSV or TSV written to stdout or checkpointed) are saved while the files are
+    // parsed, without sorting or enriching the records (so not with live status or resolved nodes)
+    let streamed = (args.format == Format::Jsonl
+        || (args.format == Format::Json && args.json_style == JsonStyle::Ndjson)
+        || ((args.stdout || args.checkpoint.is_some())
+            && matches!(args.format, Format::Csv | Format::Tsv)))
+        && !args.split_per_file
+        && args.carve_image.is_none()
+        && args.carve_memory.is_none()
+        && args.apfs_image.is_none()
+        && args.collection.is_none()
+        && !args.recursive
+        && args.dedupe.is_none()
+        && watchlist.is_none()
+        && baseline.is_none()
+        && args.save_baseline.is_none()
+        && !args.report
+        && !args.live_status
+        && !args.resolve_nodes
+        && matches!(args.sort, None | Some(SortOrder::None));
+    if args.checkpoint.is_some() && (!streamed || args.input.as_deref() == Some(STDIN)) {
+        eprintln!(
+            "--checkpoint requires csv, tsv, jsonl, or ndjson output from FsEvent files or \
+             directories, without --sort, --dedupe, --watchlist, --recursive, or --split-per-file"
+        );
+        exit(EXIT_FAILURE);
+    }
+    if streamed {
+        let mut report = ParseReport::default();
+        let output = (columns.as_slice(), &time_format, args.json_style);
+        let mut parts = 0;
+        let results = if args.stdout {
+            stream_writer(args.format, output, stdout().lock(), false)
+                .map_err(Box::from)
+                .and_then(|mut writer| {
+                    stream_output(&args, &options, &progress, &mut report, None, &mut writer)
+                })
+        } else if split {
+            SplitOutput::new(&args, output, &path)
+                .map_err(Box::from)
+                .and_then(|mut writer| {
+                    let count =
+                        stream_output(&args, &options, &progress, &mut report, None, &mut writer)?;
+                    parts = writer.finish()?.len();
+                    Ok(count)
+                })
+        } else {
+            open_checkpoint(&args, &path).and_then(|mut checkpoint| {
+                // A resumed run continues the output after the last completed file
+                let file = match &checkpoint {
+                    Some(checkpoint) => checkpoint.resume_output()?,
+                    None => File::create(&path)?,
+                };
+                let mut compressed = CompressedWriter::new(file, args.compress)?;
+                // A resumed output already has the header
+                let has_header = checkpoint
+                    .as_ref()
+                    .is_some_and(|checkpoint| checkpoint.output_size() != 0);
+                let mut writer = stream_writer(args.format, output, &mut compressed, has_header)?;
+                let count = stream_output(
+                    &args,
+                    &options,
+                    &progress,
+                    &mut report,
+                    checkpoint.as_mut(),
+                    &mut writer,
+                )?;
+                drop(writer);
+                compressed.finish()?;
+                Ok(count)
+            })
+        };
+        progress.bar.finish_and_clear();
+        print_report(&report);
+        let count = match results {
+            Ok(count) if args.stdout => {
+                eprintln!("\nFinished parsing FsEvents data. Wrote {} records", count);
+                count
+            }
+            Ok(count) if split => {
+                eprintln!(
+                    "\nFinished parsing FsEvents data. Saved {} records in {} parts to: {}",
+                    count,
+                    parts,
+                    args.output_dir.display()
+                );
+                count
+            }
+            Ok(count) => {
+                eprintln!(
+                    "\nFinished parsing FsEvents data. Saved {} records to: {}",
+                    count,
+                    path.display()
+                );
+                count
+            }
+            Err(err) => {
+                eprintln!("Failed to save FsEvents data: {:?}", err);
+                exit(error_exit_code(err.as_ref()));
+            }
+        };
+        exit_for_report(&report, count);
+        return;
+    }
+
+    let results = if let Some(path) = &args.carve_memory {
+        macos_fseventsd::carve::carve_memory(path).map(|data| (data, ParseReport::default()))
+    } else if let Some(path) = &args.carve_image {
+        macos_fseventsd::carve::carve_image(path).map(|data| (data, ParseReport::default()))
+    } else if let Some(path) = &args.apfs_image {
+        parse_apfs(path, &options)
+    } else if let Some(path) = &args.collection {
+        macos_fseventsd::collection::parse_collection_with_report(path, &options)
+    } else if !args.paths.is_empty() {
+        macos_fseventsd::parser::parse_paths_with_report(&args.paths, &options)
+    } else if let Some(path) = &args.file {
+        progress.files_discovered(path, 1);
+        macos_fseventsd::parser::parse_file_with_report(path, &options)
+    } else if args.input.as_deref() == Some(STDIN) {
+        macos_fseventsd::parser::parse_reader_with_report(stdin().lock(), STDIN_NAME, &options)
+    } else if let Some(path) = args.input.as_ref().filter(|_| args.recursive) {
+        macos_fseventsd::discover::parse_recursive_with_report(path, &options)
+    } else if let Some(path) = &args.input {
+        macos_fseventsd::parser::parse_directory_with_report(path, &options)
+    } else {
+        macos_fseventsd::parser::parse_fseventsd_data_with_report(&options)
+    };
+    progress.bar.finish_and_clear();
+    let (mut files, report) = match results {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Failed to parse FsEvents: {}", err);
+            exit(error_exit_code(&err));
+        }
+    };
+    print_report(&report);
+    // Nothing is saved if there was no FsEvent data to parse
+    if files.is_empty() && report.files_parsed == 0 {
+        exit_for_report(&report, 0);
+    }
+
+    if let Some(key) = args.dedupe {
+        let duplicates = dedup_fsevents(&mut files, key);
+        eprintln!("Removed {} duplicate records", duplicates);
+    }
+    if let Some(path) = &args.save_baseline {
+        let new_baseline = Baseline::from_records(&files);
+        match new_baseline.save(path) {
+            Ok(_) => eprintln!(
+                "Saved a baseline of {} paths and flags to: {}",
+                new_baseline.len(),
+                path
+            ),
+            Err(err) => eprintln!("Failed to save baseline: {}", err),
+        }
+    }
+    if let Some(baseline) = &baseline {
+        let suppressed = baseline.suppress(&mut files);
+        eprintln!("Suppressed {} records matching the baseline", suppressed);
+    }
+    if args.triage {
+        triage_fsevents(&mut files);
+        eprintln!("Kept {} records matching a triage rule", files.len());
+    }
+    match args.sort.unwrap_or(SortOrder::EventId) {
+        SortOrder::EventId => sort_fsevents(&mut files),
+        SortOrder::Path => sort_fsevents_by_path(&mut files),
+        SortOrder::None => {}
+    }
+    if let Some(watchlist) = &watchlist {
+        save_watchlist_hits(watchlist, &files, &args.output_dir);
+    }
+    if args.report {
+        // The report links to the export next to it, or the first part of a split export
+        let export = if args.stdout || args.split_per_file {
+            None
+        } else if split {
+            Some(part_path(&path, 1))
+        } else {
+            Some(path.clone())
+        };
+        let export = export
+            .as_deref()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().to_string());
+        save_html_report(&files, export.as_deref(), &args.output_dir);
+    }
+    if args.stdout {
+        match write_output(
+            args.format,
+            &files,
+            (&columns, &time_format, args.json_style),
+            stdout().lock(),
+        ) {
+            Ok(_) => eprintln!(
+                "\nFinished parsing FsEvents data. Wrote {} records",
+                files.len()
+            ),
+            Err(err) => {
+                eprintln!("Failed to save FsEvents data: {:?}", err);
+                exit(error_exit_code(err.as_ref()));
+            }
+        }
+        exit_for_report(&report, files.len());
+        return;
+    }
+    if args.split_per_file {
+        let mut names = HashSet::new();
+        let groups = split_by_file(files);
+        for (source, records) in &groups {
+            let path = args
+                .output_dir
+                .join(split_file_name(source, args.format, &mut names));
+            let path = compressed_path(path, args.compress);
+            let output = (columns.as_slice(), &time_format, args.json_style);
+            if let Err(err) = save(args.format, records, output, &path, args.compress) {
+                eprintln!("Failed to save FsEvents data: {:?}", err);
+                exit(error_exit_code(err.as_ref()));
+            }
+        }
+        eprintln!(
+            "\nFinished parsing FsEvents data. Saved results for {} files to: {}",
+            groups.len(),
+            args.output_dir.display()
+        );
+        let records = groups.iter().map(|(_, records)| records.len()).sum();
+        exit_for_report(&report, records);
+        return;
+    }
+
+    if split {
+        let output = (columns.as_slice(), &time_format, args.json_style);
+        match save_split(&args, &files, output, &path) {
+            Ok(parts) => eprintln!(
+                "\nFinished parsing FsEvents data. Saved {} records in {} parts to: {}",
+                files.len(),
+                parts.len(),
+                args.output_dir.display()
+            ),
+            Err(err) => {
+                eprintln!("Failed to save FsEvents data: {:?}", err);
+                exit(error_exit_code(&err));
+            }
+        }
+        exit_for_report(&report, files.len());
+        return;
+    }
+
+    match save(
+        args.format,
+        &files,
+        (&columns, &time_format, args.json_style),
+        &path,
+        args.compress,
+    ) {
+        Ok(_) => eprintln!(
+            "\nFinished parsing FsEvents data. Saved results to: {}",
+            path.display()
+        ),
+        Err(err) => {
+            eprintln!("Failed to save FsEvents data: {:?}", err);
+            exit(error_exit_code(err.as_ref()));
+        }
+    }
+    exit_for_report(&report, files.len());
+}
+
+/// Parse the command line, adding the options in the --config file
+fn parse_args() -> Args {
+    let exit_error = |err: clap::Error| -> ! {
+        let _ = err.print();
+        // Help and version are not errors
+        exit(if err.use_stderr() { EXIT_FAILURE } else { 0 });
+    };
+    let command_line: Vec<OsString> = env::args_os().collect();
+    let args = Args::try_parse_from(&command_line).unwrap_or_else(|err| exit_error(err));
+    let Some(config) = &args.config else {
+        return args;
+    };
+    let matches = Args::command()
+        .try_get_matches_from(&command_line)
+        .unwrap_or_else(|err| exit_error(err));
+    let options = match std::fs::read_to_string(config)
+        .map_err(|err| err.to_string())
+        .and_then(|text| config_args(&text, &matches))
+    {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Invalid config {}: {}", config.display(), err);
+            exit(EXIT_FAILURE);
+        }
+    };
+    // Config options go first, so the command line can add to them
+    let mut combined = command_line[..1].to_vec();
+    combined.extend(options);
+    combined.extend_from_slice(&command_line[1..]);
+    Args::try_parse_from(combined).unwrap_or_else(|err| exit_error(err))
+}
+
+/// Convert a TOML config to command line arguments. Options set on the command line, or that
+/// conflict with them (ex: output_dir and --stdout), are skipped. The config's inputs are skipped
+/// if an input is set on the command line
+fn config_args(text: &str, matches: &ArgMatches) -> Result<Vec<OsString>, String> {
+    let config: toml::Table = toml::from_str(text).map_err(|err| err.message().to_string())?;
+    let mut command = Args::command();
+    command.build();
+    let source_args: Vec<&str> = command
+        .get_groups()
+        .filter(|group| group.get_id() == "source")
+        .flat_map(|group| group.get_args())
+        .map(|id| id.as_str())
+        .collect();
+    let on_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let source_set = source_args.iter().any(|id| on_command_line(id));
+    let command_line_args: Vec<&clap::Arg> = command
+        .get_arguments()
+        .filter(|arg| on_command_line(arg.get_id().as_str()))
+        .collect();
+    let conflicts = |arg: &clap::Arg| {
+        command_line_args.iter().any(|other| {
+            command.get_arg_conflicts_with(arg).contains(other)
+                || command.get_arg_conflicts_with(other).contains(&arg)
+        })
+    };
+
+    let mut options = Vec::new();
+    let mut paths = Vec::new();
+    for (key, value) in &config {
+        let id = key.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str() && id != "config")
+            .ok_or_else(|| format!("unknown option {}", key))?;
+        if on_command_line(&id)
+            || conflicts(arg)
+            || (source_set && source_args.contains(&id.as_str()))
+        {
+            continue;
+        }
+        let values = config_values(key, value)?;
+        let Some(long) = arg.get_long() else {
+            paths.extend(values);
+            continue;
+        };
+        let flag = format!("--{}", long);
+        match (arg.get_action(), value) {
+            (ArgAction::SetTrue, toml::Value::Boolean(enabled)) => {
+                if *enabled {
+                    options.push(flag);
+                }
+            }
+            (ArgAction::Count, toml::Value::Integer(count)) => {
+                options.extend((0..*count).map(|_| flag.clone()));
+            }
+            (ArgAction::SetTrue, _) => return Err(format!("{} must be true or false", key)),
+            (ArgAction::Count, _) => return Err(format!("{} must be a number", key)),
+            // Options that take one value take arrays as a comma separated list
+            (ArgAction::Set, _) => {
+                options.push(flag);
+                options.push(values.join(","));
+            }
+            _ => {
+                for value in values {
+                    options.push(flag.clone());
+                    options.push(value);
+                }
+            }
+        }
+    }
+    // Paths in the config are only used if none were given on the command line
+    if !paths.is_empty() && !on_command_line("paths") && !source_set {
+        options.extend(paths);
+    }
+    Ok(options.into_iter().map(OsString::from).collect())
+}
+
+/// Get the values of a config option as strings
+fn config_values(key: &str, value: &toml::Value) -> Result<Vec<String>, String> {
+    match value {
+        toml::Value::String(text) => Ok(vec![text.clone()]),
+        toml::Value::Integer(number) => Ok(vec![number.to_string()]),
+        toml::Value::Float(number) => Ok(vec![number.to_string()]),
+        toml::Value::Boolean(enabled) => Ok(vec![enabled.to_string()]),
+        toml::Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                toml::Value::Array(_) | toml::Value::Table(_) => {
+                    Err(format!("{} cannot contain arrays or tables", key))
+                }
+                _ => config_values(key, value).map(|mut values| values.remove(0)),
+            })
+            .collect(),
+        _ => Err(format!(
+            "{} must be a string, number, boolean, or array",
+            key
+        )),
+    }
+}
+
+/// Exit with a partial, no input, or permission denied code if the report shows that some (or
+/// all) of the FsEvent data could not be parsed. Returns if everything was parsed
+fn exit_for_report(report: &ParseReport, records: usize) {
+    let skipped: Vec<&Skipped> = report
+        .skipped_directories
+        .iter()
+        .chain(report.skipped_files.iter())
+        .collect();
+    if records == 0 && report.files_parsed == 0 {
+        if skipped
+            .iter()
+            .any(|skipped| permission_denied(&skipped.path))
+        {
+            eprintln!(
+                "Permission denied reading FsEvent data, run with sudo (or Full Disk Access)"
+            );
+            exit(EXIT_PERMISSION_DENIED);
+        }
+        if !report.skipped_files.is_empty() {
+            eprintln!(
+                "Found {} FsEvent files, but none could be parsed",
+                report.skipped_files.len()
+            );
+            exit(EXIT_FAILURE);
+        }
+        eprintln!("No FsEvent data found");
+        exit(EXIT_NO_INPUT);
+    }
+    if !report.is_complete() {
+        exit(EXIT_PARTIAL);
+    }
+}
+
+/// Check if a skipped file or directory cannot be read because of its permissions
+fn permission_denied(path: &str) -> bool {
+    let results = if Path::new(path).is_dir() {
+        read_dir(path).map(|_| ())
+    } else {
+        File::open(path).map(|_| ())
+    };
+    matches!(results, Err(err) if err.kind() == io::ErrorKind::PermissionDenied)
+}
+
+/// Get the exit code for an error that stopped parsing or saving the records
+fn error_exit_code(err: &(dyn Error + 'static)) -> i32 {
+    let kind = match err.downcast_ref::<FsEventsError>() {
+        Some(FsEventsError::NotAFile(_) | FsEventsError::NotADirectory(_)) => return EXIT_NO_INPUT,
+        Some(FsEventsError::Io(err)) => err.kind(),
+        _ => match err.downcast_ref::<io::Error>() {
+            Some(err) => err.kind(),
+            None => return EXIT_FAILURE,
+        },
+    };
+    match kind {
+        io::ErrorKind::PermissionDenied => EXIT_PERMISSION_DENIED,
+        io::ErrorKind::NotFound => EXIT_NO_INPUT,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// Save the records that match the watchlist to watchlist_hits.json
+fn save_watchlist_hits(watchlist: &Watchlist, files: &[FsEvents], output_dir: &Path) {
+    let report = watchlist.report(files);
+    let path = output_dir.join("watchlist_hits.json");
+    let results = create_dir_all(output_dir)
+        .and_then(|_| File::create(&path))
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|file| Ok(serde_json::to_writer_pretty(BufWriter::new(file), &report)?));
+    if let Err(err) = results {
+        eprintln!("Failed to save watchlist hits: {:?}", err);
+        exit(EXIT_FAILURE);
+    }
+    eprintln!(
+        "Watchlist matched {} records ({} of {} entries). Saved hits to: {}",
+        report.hits.len(),
+        report.entries - report.unmatched.len(),
+        report.entries,
+        path.display()
+    );
+}
+
+/// Save the HTML summary report to report.html
+fn save_html_report(files: &[FsEvents], export: Option<&str>, output_dir: &Path) {
+    let path = output_dir.join("report.html");
+    let results = create_dir_all(output_dir)
+        .and_then(|_| File::create(&path))
+        .map_err(FsEventsError::Io)
+        .and_then(|file| write_html_report(files, export, file));
+    if let Err(err) = results {
+        eprintln!("Failed to save HTML report: {:?}", err);
+        exit(EXIT_FAILURE);
+    }
+    eprintln!("Saved HTML report to: {}", path.display());
+}
+
+/// Columns to write for CSV output, the format of the record times, and the JSON layout
+type OutputStyle<'a> = (&'a [Column], &'a TimeFormat, JsonStyle);
+
+/// Save the records to a file in the format, compressed if a compression is provided
+fn save(
+    format: Format,
+    files: &[FsEvents],
+    output: OutputStyle,
+    path: &Path,
+    compress: Option<OutputCompression>,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Xlsx => output_xlsx(files, path),
+        Format::Parquet => output_parquet(files, path),
+        Format::Sqlite => output_sqlite(files, path),
+        _ => {
+            let mut writer = CompressedWriter::new(File::create(path)?, compress)?;
+            write_output(format, files, output, &mut writer)?;
+            writer.finish()?;
+            Ok(())
+        }
+    }
+}
+
+/// Save the records in numbered parts. Returns the paths of the parts
+fn save_split(
+    args: &Args,
+    files: &[FsEvents],
+    output: OutputStyle,
+    path: &Path,
+) -> Result<Vec<PathBuf>, FsEventsError> {
+    let mut writer = SplitOutput::new(args, output, path)?;
+    for fsevent in files {
+        writer.write_record(fsevent)?;
+    }
+    writer.finish()
+}
+
+/// Add the compression extension to an output path (ex: output.jsonl.gz)
+fn compressed_path(path: PathBuf, compress: Option<OutputCompression>) -> PathBuf {
+    match compress {
+        Some(compression) => {
+            let mut path = path.into_os_string();
+            path.push(".");
+            path.push(compression.extension());
+            PathBuf::from(path)
+        }
+        None => path,
+    }
+}
+
+/// Write the records in a text format to a file or stdout
+fn write_output<W: Write>(
+    format: Format,
+    files: &[FsEvents],
+    (columns, time_format, json_style): OutputStyle,
+    writer: W,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Csv | Format::Tsv => {
+            let mut writer = CsvWriter::with_delimiter(writer, format.delimiter())
+                .columns(columns)
+                .time_format(time_format.clone());
+            writer.write_header()?;
+            writer.write_records(files)?;
+            writer.flush()?;
+        }
+        Format::Json if json_style == JsonStyle::Ndjson => {
+            let output = (columns, time_format, json_style);
+            return write_output(Format::Jsonl, files, output, writer);
+        }
+        Format::Json if !time_format.is_unix() => {
+            let records = files
+                .iter()
+                .map(|fsevent| time_format.json_record(fsevent))
+                .collect::<Result<Vec<_>, _>>()?;
+            write_json(&records, json_style, writer)?;
+        }
+        Format::Json => write_json(files, json_style, writer)?,
+        Format::Jsonl if !time_format.is_unix() => {
+            let mut writer = JsonLinesWriter::new(writer).time_format(time_format.clone());
+            writer.write_records(files)?;
+            writer.flush()?;
+        }
+        Format::Jsonl => write_jsonl(files, writer)?,
+        Format::Ecs => write_ecs(files, writer)?,
+        Format::Cef => write_cef(files, writer)?,
+        Format::Leef => write_leef(files, writer)?,
+        Format::L2tcsv => write_l2tcsv(files, writer)?,
+        Format::Timesketch => write_timesketch(files, writer)?,
+        Format::Bodyfile => write_bodyfile(files, writer)?,
+        Format::Xlsx | Format::Parquet | Format::Sqlite => {
+            return Err(Box::from("binary formats can only be saved to a file"))
+        }
+    }
+    Ok(())
+}
+
+/// Write a JSON array, indented for the pretty style
+fn write_json<T: Serialize + ?Sized, W: Write>(
+    records: &T,
+    json_style: JsonStyle,
+    writer: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(writer);
+    if json_style == JsonStyle::Pretty {
+        serde_json::to_writer_pretty(&mut writer, records)?;
+    } else {
+        serde_json::to_writer(&mut writer, records)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Group records by the FsEvent file they came from, in the order the files were first seen
+fn split_by_file(files: Vec<FsEvents>) -> Vec<(String, Vec<FsEvents>)> {
+    let mut groups: Vec<(String, Vec<FsEvents>)> = Vec::new();
+    let mut index = HashMap::new();
+    for fsevent in files {
+        let group = *index.entry(fsevent.source_file.clone()).or_insert_with(|| {
+            groups.push((fsevent.source_file.clone(), Vec::new()));
+            groups.len() - 1
+        });
+        groups[group].1.push(fsevent);
+    }
+    groups
+}
+
+/// Get the output file name for an FsEvent file (ex: `0000000000030012.csv`)
+/// Files with the same name in different directories get a number added (ex: `..._2.csv`)
+fn split_file_name(source: &str, format: Format, names: &mut HashSet<String>) -> String {
+    let stem = Path::new(source)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("unknown"));
+    let extension = format.file_name().trim_start_matches("output");
+    let mut name = format!("{}{}", stem, extension);
+    let mut count = 1;
+    while !names.insert(name.clone()) {
+        count += 1;
+        name = format!("{}_{}{}", stem, count, extension);
+    }
+    name
+}
+
+/// Write log messages from the parser to stderr, at the level chosen with -v or -q
+/// Log messages are written while the progress bar is hidden, so they do not break it
+fn init_logger(args: &Args, bar: &ProgressBar) {
+    let level = if args.quiet {
+        LevelFilter::Error
+    } else {
+        match args.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    };
+    let config = ConfigBuilder::new()
+        .set_time_level(LevelFilter::Off)
+        .build();
+    let writer = ProgressWriter { bar: bar.clone() };
+    if let Err(err) = WriteLogger::init(level, config, writer) {
+        eprintln!("Failed to start logger: {}", err);
+    }
+}
+
+/// Progress bar on stderr with the files and records parsed, and the records parsed per second
+struct ProgressBarObserver {
+    bar: ProgressBar,
+    records: AtomicUsize,
+}
+
+impl ProgressBarObserver {
+    /// Create a progress bar, which is hidden if `show` is false or stderr is not a terminal
+    fn new(show: bool) -> ProgressBarObserver {
+        let bar = if show {
+            ProgressBar::new(0)
+        } else {
+            ProgressBar::hidden()
+        };
+        let style = ProgressStyle::with_template(
+            "{spinner} [{elapsed_precise}] {bar:30} {pos}/{len} files, {msg}",
+        )
+        .unwrap_or_else(|_| ProgressStyle::default_bar());
+        bar.set_style(style);
+        bar.enable_steady_tick(Duration::from_millis(200));
+        ProgressBarObserver {
+            bar,
+            records: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ProgressObserver for ProgressBarObserver {
+    fn files_discovered(&self, _directory: &str, count: usize) {
+        self.bar.inc_length(count as u64);
+    }
+
+    fn records_parsed(&self, _file: &str, count: usize) {
+        let records = self.records.fetch_add(count, Ordering::Relaxed) + count;
+        let seconds = self.bar.elapsed().as_secs_f64().max(0.001);
+        self.bar.set_message(format!(
+            "{} records ({:.0} records/s)",
+            records,
+            records as f64 / seconds
+        ));
+    }
+
+    fn file_finished(&self, _file: &str, _records: usize) {
+        self.bar.inc(1);
+    }
+}
+
+/// Writes log messages to stderr while the progress bar is hidden
+struct ProgressWriter {
+    bar: ProgressBar,
+}
+
+impl Write for ProgressWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bar.suspend(|| stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        stderr().flush()
+    }
+}
+
+/// Build the parser options from the arguments
+fn parser_options(args: &Args) -> Result<ParserOptions, FsEventsError> {
+    let mut options = ParserOptions::new()
+        .attach_uuid(true)
+        .approx_times(true)
+        .estimate_times(true)
+        .include_legacy(args.legacy)
+        .lenient(!args.fail_fast)
+        .skip_corrupt_pages(args.skip_corrupt_pages)
+        .resync_records(args.resync_records)
+        .live_status(args.live_status)
+        .resolve_nodes(args.resolve_nodes);
+    for pattern in &args.path_match {
+        options = path_pattern(options, pattern, true)?;
+    }
+    for pattern in &args.path_exclude {
+        options = path_pattern(options, pattern, false)?;
+    }
+    for pattern in &args.regex {
+        options = options.include_regex(pattern)?;
+    }
+    for pattern in &args.exclude_regex {
+        options = options.exclude_regex(pattern)?;
+    }
+    if let Some(flags) = args.include_flags {
+        options = options.include_flags(flags);
+    }
+    if let Some(flags) = args.exclude_flags {
+        options = options.exclude_flags(flags);
+    }
+    if let Some(event_id) = args.min_event_id {
+        options = options.min_event_id(event_id);
+    }
+    if let Some(event_id) = args.max_event_id {
+        options = options.max_event_id(event_id);
+    }
+    if let Some(size) = args.max_file_size {
+        options = options.max_file_size(size);
+    }
+    Ok(options)
+}
+
+/// Add a --path-match (include) or --path-exclude pattern to the options
+fn path_pattern(
+    options: ParserOptions,
+    pattern: &str,
+    include: bool,
+) -> Result<ParserOptions, FsEventsError> {
+    if let Some(regex) = pattern.strip_prefix("regex:") {
+        return if include {
+            options.include_regex(regex)
+        } else {
+            options.exclude_regex(regex)
+        };
+    }
+    let glob = match pattern.strip_prefix("~/") {
+        Some(home) => format!("/Users/*/{}", home),
+        None => pattern.to_string(),
+    };
+    if include {
+        options.include_glob(&glob)
+    } else {
+        options.exclude_glob(&glob)
+    }
+}
+
+/// Print the files, directories, and pages that were skipped while parsing to stderr
+fn print_report(report: &ParseReport) {
+    for skipped in report
+        .skipped_directories
+        .iter()
+        .chain(report.skipped_files.iter())
+    {
+        eprintln!("Skipped {}: {}", skipped.path, skipped.reason);
+    }
+    for truncated in &report.truncated_files {
+        eprintln!("Truncated {}", truncated);
+    }
+    for page in &report.skipped_pages {
+        eprintln!(
+            "Skipped page {} at offset {} in {}: {}",
+            page.page, page.offset, page.path, page.reason
+        );
+    }
+    for page in &report.resynced_pages {
+        eprintln!(
+            "Skipped {} bytes of bad records in page {} at offset {} in {}",
+            page.skipped_bytes, page.page, page.offset, page.path
+        );
+    }
+}
+
+/// Follow an fseventsd directory, writing the records of each FsEvent file once it is finished
+fn tail(args: &TailArgs) {
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("Failed to create {}: {}", path.display(), err);
+                exit(EXIT_FAILURE);
+            }
+        },
+        None => Box::new(stdout().lock()),
+    };
+    let mut writer = match args.format {
+        WatchFormat::Csv => StreamWriter::Csv(Box::new(CsvWriter::new(output))),
+        WatchFormat::Jsonl => StreamWriter::Jsonl(JsonLinesWriter::new(output)),
+    };
+    let options = ParserOptions::new().lenient(true);
+    let results = if args.from_start {
+        DirectoryTail::from_start(&args.directory, options)
+    } else {
+        DirectoryTail::new(&args.directory, options)
+    };
+    let mut directory_tail = match results {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Failed to follow {}: {}", args.directory, err);
+            exit(error_exit_code(&err));
+        }
+    };
+    if let StreamWriter::Csv(csv_writer) = &mut writer {
+        if let Err(err) = csv_writer.write_header() {
+            eprintln!("Failed to save FsEvents data: {}", err);
+            exit(EXIT_FAILURE);
+        }
+    }
+    eprintln!("Following {}...", args.directory);
+
+    loop {
+        let results = directory_tail.poll().and_then(|(records, report)| {
+            print_report(&report);
+            for record in &records {
+                writer.write_record(record)?;
+            }
+            // Flush each file's records so they can be followed as they are saved
+            writer.flush()
+        });
+        if let Err(err) = results {
+            eprintln!("Failed to follow {}: {}", args.directory, err);
+            exit(error_exit_code(&err));
+        }
+        if args.once {
+            return;
+        }
+        std::thread::sleep(Duration::from_secs(args.interval));
+    }
+}
+
+/// Print the completion script for a shell to stdout
+fn completions(args: &CompletionsArgs) {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(args.shell, &mut command, name, &mut stdout());
+}
+
+/// Parse the inputs and print a summary of the records
+fn stats(args: &StatsArgs, progress: Arc<ProgressBarObserver>) {
+    let options = ParserOptions::new()
+        .include_legacy(args.legacy)
+        .progress_observer(progress.clone());
+    let results = if args.paths.is_empty() {
+        macos_fseventsd::parser::parse_fseventsd_data_with_report(&options)
+    } else {
+        macos_fseventsd::parser::parse_paths_with_report(&args.paths, &options)
+    };
+    progress.bar.finish_and_clear();
+    let (files, report) = match results {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Failed to parse FsEvents: {}", err);
+            exit(EXIT_FAILURE);
+        }
+    };
+
+    let summary = Summary {
+        stats: get_stats(&files),
+        busiest_directories: busiest_directories(&files, args.top),
+        files_parsed: report.files_parsed,
+        unparseable_files: report
+            .skipped_directories
+            .iter()
+            .chain(report.skipped_files.iter())
+            .collect(),
+        truncated_files: &report.truncated_files,
+    };
+    if args.json {
+        match serde_json::to_string_pretty(&summary) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("Failed to serialize summary: {}", err);
+                exit(EXIT_FAILURE);
+            }
+        }
+        return;
+    }
+    print_summary(&summary);
+}
+
+/// Carve records from the input and save them with the recovery report
+fn carve(args: &CarveArgs, progress: Arc<ProgressBarObserver>) {
+    let results = match args.kind {
+        CarveKind::Image => macos_fseventsd::carve::carve_image_with_report(&args.path),
+        CarveKind::Memory => macos_fseventsd::carve::carve_memory_with_report(&args.path),
+        CarveKind::Blob => macos_fseventsd::carve::carve_file_with_report(&args.path),
+    };
+    progress.bar.finish_and_clear();
+    let (files, report) = match results {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Failed to carve FsEvents: {}", err);
+            exit(EXIT_FAILURE);
+        }
+    };
+
+    if let Err(err) = create_dir_all(&args.output_dir) {
+        eprintln!("Failed to create output directory: {}", err);
+        exit(EXIT_FAILURE);
+    }
+    let path = args.output_dir.join(args.format.file_name());
+    let output = (
+        Column::ALL.as_slice(),
+        &TimeFormat::new(),
+        JsonStyle::Compact,
+    );
+    if let Err(err) = save(args.format, &files, output, &path, None) {
+        eprintln!("Failed to save FsEvents data: {:?}", err);
+        exit(EXIT_FAILURE);
+    }
+    let report_path = args.output_dir.join("carve_report.json");
+    let results = File::create(&report_path)
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|file| Ok(serde_json::to_writer_pretty(BufWriter::new(file), &report)?));
+    if let Err(err) = results {
+        eprintln!("Failed to save carve report: {:?}", err);
+        exit(EXIT_FAILURE);
+    }
+    eprintln!(
+        "Carved {} records from {} pages ({} truncated). Saved records to: {} and the recovery report to: {}",
+        files.len(),
+        report.pages.len(),
+        report.truncated_pages(),
+        path.display(),
+        report_path.display()
+    );
+}
+
+#[cfg(all(feature = "watch", target_os = "macos"))]
+/// Write live events as records until a write fails or the process is stopped
+fn watch(args: &WatchArgs) {
+    let writer: Box<dyn Write> = match &args.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("Failed to create {}: {}", path.display(), err);
+                exit(EXIT_FAILURE);
+            }
+        },
+        None => Box::new(stdout().lock()),
+    };
+    // Flush every record so they can be followed as they happen
+    let results = match args.format {
+        WatchFormat::Csv => {
+            let mut writer = CsvWriter::new(writer).flush_every(1);
+            writer
+                .write_header()
+                .and_then(|_| watch_records(args, |record| writer.write_record(record)))
+        }
+        WatchFormat::Jsonl => {
+            let mut writer = JsonLinesWriter::new(writer).flush_every(1);
+            watch_records(args, |record| writer.write_record(record))
+        }
+    };
+    if let Err(err) = results {
+        eprintln!("Failed to watch FsEvents: {}", err);
+        exit(EXIT_FAILURE);
+    }
+}
+
+#[cfg(all(feature = "watch", target_os = "macos"))]
+/// Pass live events to the writer, stopping at the first write error
+fn watch_records(
+    args: &WatchArgs,
+    mut write: impl FnMut(&FsEvents) -> Result<(), FsEventsError>,
+) -> Result<(), FsEventsError> {
+    let mut write_error = None;
+    macos_fseventsd::watch::watch_paths(
+        &args.paths,
+        args.since,
+        args.latency,
+        |record| match write(&record) {
+            Ok(()) => true,
+            Err(err) => {
+                write_error = Some(err);
+                false
+            }
+        },
+    )?;
+    write_error.map_or(Ok(()), Err)
+}
+
+#[cfg(not(all(feature = "watch", target_os = "macos")))]
+/// Watching live events requires macOS and the watch feature
+fn watch(args: &WatchArgs) {
+    eprintln!(
+        "Cannot watch {}: the watch subcommand requires macOS and the watch feature",
+        args.paths.join(", ")
+    );
+    exit(EXIT_FAILURE);
+}
+
+/// Print a stats summary as text
+fn print_summary(summary: &Summary) {
+    let stats = &summary.stats;
+    println!("Records: {}", stats.total_records);
+    println!("Unique paths: {}", stats.unique_paths);
+    println!("Invalid paths: {}", stats.invalid_paths);
+    println!(
+        "Event IDs: {} - {} ({:#x} - {:#x})",
+        stats.min_event_id, stats.max_event_id, stats.min_event_id, stats.max_event_id
+    );
+    println!("Files parsed: {}", summary.files_parsed);
+
+    println!("\nFlags:");
+    for (flag, count) in &stats.flags {
+        println!("  {:<30} {}", flag, count);
+    }
+    println!("\nBusiest directories:");
+    for directory in &summary.busiest_directories {
+        println!("  {:<60} {}", directory.directory, directory.records);
+    }
+    println!("\nUnparseable files: {}", summary.unparseable_files.len());
+    for skipped in &summary.unparseable_files {
+        println!("  {}: {}", skipped.path, skipped.reason);
+    }
+    println!("Truncated files: {}", summary.truncated_files.len());
+    for truncated in summary.truncated_files {
+        println!("  {}", truncated);
+    }
+}
+
+/// Print the problems found in each FsEvent file in a directory
+fn validate(directory: &str) {
+    let report = match macos_fseventsd::validate::validate_fseventsd(directory) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Failed to validate FsEvents: {}", err);
+            exit(EXIT_FAILURE);
+        }
+    };
+    for file in &report.files {
+        println!(
+            "{}: {} pages, {} records, event IDs {:#x}-{:#x}",
+            file.path, file.pages, file.records, file.min_event_id, file.max_event_id
+        );
+        for issue in &file.issues {
+            println!("  {:?}", issue);
+        }
+    }
+    if report.is_valid() {
+        println!("\nNo problems found in {} files", report.files.len());
+    }
+}
+
+#[cfg(feature = "apfs")]
+/// Parse the FsEvent files in the APFS volumes of a disk image
+fn parse_apfs(
+    path: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    macos_fseventsd::apfs::parse_apfs_image_with_report(path, options)
+}
+
+#[cfg(not(feature = "apfs"))]
+/// APFS images can only be parsed with the apfs feature
+fn parse_apfs(
+    _path: &str,
+    _options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    eprintln!("Parsing APFS images requires the apfs feature");
+    exit(EXIT_FAILURE);
+}
+
+/// Open the --checkpoint for the output file, if one was provided
+fn open_checkpoint(args: &Args, output: &Path) -> Result<Option<Checkpoint>, Box<dyn Error>> {
+    let Some(path) = &args.checkpoint else {
+        return Ok(None);
+    };
+    let checkpoint = Checkpoint::open(path, &output.display().to_string())?;
+    if checkpoint.completed() != 0 {
+        eprintln!(
+            "Resuming from checkpoint {}, skipping {} completed files",
+            path,
+            checkpoint.completed()
+        );
+    }
+    Ok(Some(checkpoint))
+}
+
+/// Output that records are written to one at a time
+trait RecordOutput {
+    /// Write a record
+    fn write_record(&mut self, fsevent: &FsEvents) -> Result<(), FsEventsError>;
+
+    /// Flush buffered records to the output
+    fn flush(&mut self) -> Result<(), FsEventsError>;
+}
+
+/// Writer for the formats that can be written one record at a time
+enum StreamWriter<W: Write> {
+    Csv(Box<CsvWriter<W>>),    // CSV or TSV
+    Jsonl(JsonLinesWriter<W>), // JSON Lines
+}
+
+impl<W: Write> StreamWriter<W> {
+    /// Flush the output and return the underlying writer
+    fn into_inner(self) -> Result<W, FsEventsError> {
+        match self {
+            StreamWriter::Csv(writer) => writer.into_inner(),
+            StreamWriter::Jsonl(writer) => writer.into_inner(),
+        }
+    }
+}
+
+impl<W: Write> RecordOutput for StreamWriter<W> {
+    fn write_record(&mut self, fsevent: &FsEvents) -> Result<(), FsEventsError> {
+        match self {
+            StreamWriter::Csv(writer) => writer.write_record(fsevent),
+            StreamWriter::Jsonl(writer) => writer.write_record(fsevent),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), FsEventsError> {
+        match self {
+            StreamWriter::Csv(writer) => writer.flush(),
+            StreamWriter::Jsonl(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Create the CSV, TSV, or JSON Lines writer for the format, writing the CSV header unless the
+/// output already has one
+fn stream_writer<W: Write>(
+    format: Format,
+    (columns, time_format, _): OutputStyle,
+    writer: W,
+    has_header: bool,
+) -> Result<StreamWriter<W>, FsEventsError> {
+    if matches!(format, Format::Csv | Format::Tsv) {
+        let mut writer = CsvWriter::with_delimiter(writer, format.delimiter())
+            .columns(columns)
+            .time_format(time_format.clone())
+            .flush_every(1000);
+        if has_header {
+            writer = writer.skip_header();
+        }
+        writer.write_header()?;
+        return Ok(StreamWriter::Csv(Box::new(writer)));
+    }
+    let mut writer = JsonLinesWriter::new(writer).flush_every(1000);
+    if !time_format.is_unix() {
+        writer = writer.time_format(time_format.clone());
+    }
+    Ok(StreamWriter::Jsonl(writer))
+}
+
+/// Writer for a part of --split-records or --split-size output
+type PartWriter = StreamWriter<CompressedWriter<CountingWriter<File>>>;
+
+/// Output saved in numbered parts, starting a new part once the current one reaches
+/// --split-records or --split-size
+struct SplitOutput<'a> {
+    args: &'a Args,          // Format, compression, and part limits
+    output: OutputStyle<'a>, // Columns, time format, and JSON layout
+    path: PathBuf,           // Output path, the part number is added to the file name
+    parts: Vec<PathBuf>,     // Parts created, the last is being written
+    records: u64,            // Records written to the current part
+    size: Rc<Cell<u64>>,     // Bytes written to the current part
+    writer: PartWriter,      // Writer for the current part
+}
+
+impl<'a> SplitOutput<'a> {
+    /// Create the first part
+    fn new(args: &'a Args, output: OutputStyle<'a>, path: &Path) -> Result<Self, FsEventsError> {
+        let (writer, size) = SplitOutput::create_part(args, output, &part_path(path, 1))?;
+        Ok(SplitOutput {
+            args,
+            output,
+            path: path.to_path_buf(),
+            parts: vec![part_path(path, 1)],
+            records: 0,
+            size,
+            writer,
+        })
+    }
+
+    /// Create a part file and its writer
+    fn create_part(
+        args: &Args,
+        output: OutputStyle,
+        path: &Path,
+    ) -> Result<(PartWriter, Rc<Cell<u64>>), FsEventsError> {
+        let file = CountingWriter::new(File::create(path)?);
+        let size = file.counter();
+        let writer = CompressedWriter::new(file, args.compress)?;
+        Ok((stream_writer(args.format, output, writer, false)?, size))
+    }
+
+    /// Check if the current part has reached the record count or size
+    fn is_full(&self) -> bool {
+        self.records != 0
+            && (self
+                .args
+                .split_records
+                .is_some_and(|limit| self.records >= limit)
+                || self
+                    .args
+                    .split_size
+                    .is_some_and(|limit| self.size.get() >= limit))
+    }
+
+    /// Finish the current part and start the next one
+    fn next_part(&mut self) -> Result<(), FsEventsError> {
+        let path = part_path(&self.path, self.parts.len() + 1);
+        let (writer, size) = SplitOutput::create_part(self.args, self.output, &path)?;
+        let full = std::mem::replace(&mut self.writer, writer);
+        full.into_inner()?.finish()?;
+        self.parts.push(path);
+        self.records = 0;
+        self.size = size;
+        Ok(())
+    }
+
+    /// Finish the last part. Returns the paths of the parts
+    fn finish(self) -> Result<Vec<PathBuf>, FsEventsError> {
+        self.writer.into_inner()?.finish()?;
+        Ok(self.parts)
+    }
+}
+
+impl RecordOutput for SplitOutput<'_> {
+    fn write_record(&mut self, fsevent: &FsEvents) -> Result<(), FsEventsError> {
+        if self.is_full() {
+            self.next_part()?;
+        }
+        self.writer.write_record(fsevent)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FsEventsError> {
+        self.writer.flush()
+    }
+}
+
+/// Parse the input one page at a time, writing each record that matches the filters as CSV, TSV, or
+/// JSON Lines as soon as it is parsed. Returns the number of records written
+fn stream_output(
+    args: &Args,
+    options: &ParserOptions,
+    progress: &ProgressBarObserver,
+    report: &mut ParseReport,
+    checkpoint: Option<&mut Checkpoint>,
+    writer: &mut impl RecordOutput,
+) -> Result<usize, Box<dyn Error>> {
+    let count = stream_records(args, options, progress, report, checkpoint, writer)?;
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Get the FsEvent files to parse from the paths, file, or input directory in the arguments, or
+/// the default paths
+fn input_files(args: &Args) -> Result<Vec<String>, FsEventsError> {
+    if let Some(file) = &args.file {
+        return Ok(vec![file.clone()]);
+    }
+    if let Some(path) = &args.input {
+        return macos_fseventsd::parser::fseventsd(path);
+    }
+    if !args.paths.is_empty() {
+        let mut files = Vec::new();
+        for path in &args.paths {
+            if Path::new(path).is_file() {
+                files.push(path.clone());
+            } else {
+                files.extend(macos_fseventsd::parser::fseventsd(path)?);
+            }
+        }
+        return Ok(files);
+    }
+
+    let mut files = macos_fseventsd::parser::get_fseventsd()?;
+    if args.legacy {
+        files.extend(macos_fseventsd::parser::get_fseventsd_legacy()?);
+    }
+    Ok(files)
+}
+
+/// Parse FsEvent files, the files in directories, stdin, or the default paths one page at a time,
+/// writing each record that matches the filters. Files that fail to parse are added to the report,
+/// or stop parsing with --fail-fast. Files in the checkpoint are skipped, and each file is added to
+/// it once its records are written. Returns the number of records written
+fn stream_records(
+    args: &Args,
+    options: &ParserOptions,
+    progress: &ProgressBarObserver,
+    report: &mut ParseReport,
+    mut checkpoint: Option<&mut Checkpoint>,
+    writer: &mut impl RecordOutput,
+) -> Result<usize, Box<dyn Error>> {
+    let mut count = 0;
+    let triage = args.triage.then(Triage::new);
+    // Triage tags records, so it runs after the other filters
+    let keep = |record: &mut FsEvents| {
+        options.matches(record) && triage.as_ref().is_none_or(|rules| rules.tag(record))
+    };
+    let input = args.input.as_deref();
+    if input == Some(STDIN) {
+        report.files_parsed = 1;
+        let mut reader = PageReader::new(stdin().lock())
+            .path(STDIN_NAME)
+            .page_options(options);
+        for records in reader.by_ref() {
+            let records = records?;
+            progress.records_parsed(STDIN_NAME, records.len());
+            for mut record in records {
+                if keep(&mut record) {
+                    record.source_file = STDIN_NAME.to_string();
+                    writer.write_record(&record)?;
+                    count += 1;
+                }
+            }
+        }
+        reader.add_to_report(report);
+        return Ok(count);
+    }
+
+    let mut files = input_files(args)?;
+    let file_count = files.len();
+    if let Some(checkpoint) = &checkpoint {
+        files.retain(|file| !checkpoint.is_completed(file));
+    }
+    progress.files_discovered(
+        args.file.as_deref().or(input).unwrap_or_default(),
+        files.len(),
+    );
+    // The iterator does not report progress, so it is updated when the next file starts
+    let mut current_file = String::new();
+    let mut file_records = 0;
+    // Files before this index have been added to the checkpoint
+    let mut checkpointed = 0;
+    let mut records = FsEventsIter::new(files.clone()).options(options);
+    while let Some(record) = records.next() {
+        if let Some(checkpoint) = checkpoint.as_deref_mut() {
+            let current = Some(records.current_file());
+            checkpoint_files(checkpoint, writer, &files, &mut checkpointed, current)?;
+        }
+        // Files that fail to parse are logged and skipped by the iterator
+        let mut record = match record {
+            Ok(result) => result,
+            Err(err) if args.fail_fast => return Err(Box::from(err)),
+            Err(err) => {
+                report.skipped_files.push(Skipped {
+                    path: records.current_file().to_string(),
+                    reason: err.to_string(),
+                });
+                continue;
+            }
+        };
+        if record.source_file != current_file {
+            file_progress(progress, &current_file, file_records);
+            current_file = record.source_file.clone();
+            file_records = 0;
+        }
+        file_records += 1;
+        if keep(&mut record) {
+            writer.write_record(&record)?;
+            count += 1;
+        }
+    }
+    file_progress(progress, &current_file, file_records);
+    if let Some(checkpoint) = checkpoint {
+        checkpoint_files(checkpoint, writer, &files, &mut checkpointed, None)?;
+    }
+    report.files_parsed = file_count - report.skipped_files.len();
+    let pages = records.report();
+    report
+        .truncated_files
+        .extend_from_slice(&pages.truncated_files);
+    report.skipped_pages.extend_from_slice(&pages.skipped_pages);
+    report
+        .resynced_pages
+        .extend_from_slice(&pages.resynced_pages);
+    Ok(count)
+}
+
+/// Add the files before the current file (or every file, if None) to the checkpoint, after
+/// flushing their records to the output. Files that failed part way are added too, since their
+/// records before the failure were written
+fn checkpoint_files(
+    checkpoint: &mut Checkpoint,
+    writer: &mut impl RecordOutput,
+    files: &[String],
+    checkpointed: &mut usize,
+    current: Option<&str>,
+) -> Result<(), FsEventsError> {
+    let mut flushed = false;
+    while let Some(file) = files.get(*checkpointed) {
+        if Some(file.as_str()) == current {
+            break;
+        }
+        if !flushed {
+            writer.flush()?;
+            flushed = true;
+        }
+        checkpoint.complete(file)?;
+        *checkpointed += 1;
+    }
+    Ok(())
+}
+
+/// Report the records parsed from a streamed file
+fn file_progress(progress: &ProgressBarObserver, file: &str, records: usize) {
+    if !file.is_empty() {
+        progress.records_parsed(file, records);
+        progress.file_finished(file, records);
+    }
+}
+
+#[cfg(feature = "parquet")]
+/// Save the records to a zstd compressed Parquet file
+fn output_parquet(data: &[FsEvents], path: &Path) -> Result<(), Box<dyn Error>> {
+    macos_fseventsd::output::parquet::write_parquet(data, File::create(path)?)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+/// Parquet output requires the parquet feature
+fn output_parquet(_data: &[FsEvents], _path: &Path) -> Result<(), Box<dyn Error>> {
+    eprintln!("Parquet output requires the parquet feature");
+    exit(EXIT_FAILURE);
+}
+
+#[cfg(feature = "xlsx")]
+/// Save the records and summary sheets to an Excel workbook
+fn output_xlsx(data: &[FsEvents], path: &Path) -> Result<(), Box<dyn Error>> {
+    macos_fseventsd::output::xlsx::write_xlsx(data, File::create(path)?)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "xlsx"))]
+/// XLSX output requires the xlsx feature
+fn output_xlsx(_data: &[FsEvents], _path: &Path) -> Result<(), Box<dyn Error>> {
+    eprintln!("XLSX output requires the xlsx feature");
+    exit(EXIT_FAILURE);
+}
+
+#[cfg(feature = "sqlite")]
+/// Save the records to an indexed SQLite database
+fn output_sqlite(data: &[FsEvents], path: &Path) -> Result<(), Box<dyn Error>> {
+    macos_fseventsd::output::sqlite::write_sqlite(data, &path.to_string_lossy())?;
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+/// SQLite output requires the sqlite feature
+fn output_sqlite(_data: &[FsEvents], _path: &Path) -> Result<(), Box<dyn Error>> {
+    eprintln!("SQLite output requires the sqlite feature");
+    exit(EXIT_FAILURE);
+}
+
+/// Parse a CSV column name
+fn column_value(name: &str) -> Result<Column, String> {
+    Column::from_name(name).ok_or_else(|| format!("unknown column: {}", name))
+}
+
+/// Parse comma separated flag names (ex: Created,Removed)
+fn flags_value(value: &str) -> Result<FsEventFlags, String> {
+    value.parse::<FsEventFlags>().map_err(|err| err.to_string())
+}
+
+/// Parse a deduplication key name
+fn dedupe_value(name: &str) -> Result<DedupKey, String> {
+    DedupKey::from_name(name).ok_or_else(|| format!("unknown deduplication key: {}", name))
+}
+
+/// Parse a time format name or strftime pattern
+fn time_style_value(name: &str) -> Result<TimeStyle, String> {
+    TimeStyle::from_name(name).ok_or_else(|| format!("unknown time format: {}", name))
+}
+
+/// Parse an output compression name
+fn compress_value(name: &str) -> Result<OutputCompression, String> {
+    OutputCompression::from_name(name).ok_or_else(|| format!("unknown compression: {}", name))
+}
+
+/// Parse a --split-size (ex: 500MB)
+fn split_size_value(value: &str) -> Result<u64, String> {
+    match parse_size(value) {
+        Some(0) | None => Err(format!("invalid size: {} (ex: 500MB)", value)),
+        Some(size) => Ok(size),
+    }
+}
+
+/// Parse an event ID, in decimal or hex (0x prefix)
+fn event_id_value(value: &str) -> Result<u64, String> {
+    let results = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    };
+    results.map_err(|err| err.to_string())
+}
@@ -0,0 +1,583 @@
+//! Carve FsEvent records from arbitrary data
+//!
+//! Recovers records from unallocated space, memory, pagefiles, or damaged files. Pages are located
+//! by their DLS1/DLS2/DLS3 signature and records are read until the next page signature or until
+//! the data no longer looks like a record. The stream size in the page header is ignored, so pages
+//! with a wrong or overwritten size are still recovered.
+//!
+//! Raw disk images are read in chunks. Deleted FsEvent files are found by their gzip header and
+//! decompressed from the image, decompressed pages (ex: in swap files) are carved directly.
+//! Memory images are carved the same way, but torn pages are expected so carving skips over data that
+//! is not a record instead of stopping at the end of the page's first run of records.
+//!
+//! The `_with_report` functions also list every carved page with its offset, how much of it was
+//! parsed as records (the confidence), and whether the page was cut off before its stream size.
+
+use crate::{error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents};
+#[cfg(feature = "filesystem")]
+use crate::{image::open_image, parser::decompress_recover};
+use flate2::read::GzDecoder;
+use log::debug;
+use memchr::memmem;
+use serde::Serialize;
+use std::io::{Read, Seek, SeekFrom};
+
+const HEADER_SIZE: usize = 12;
+
+// Longest path that will be accepted as a carved record
+const MAX_PATH_SIZE: usize = 1024;
+
+// Size of each chunk read from a disk image
+const IMAGE_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+// Bytes past the end of a chunk that are also read, so pages that cross chunks are not cut off
+const IMAGE_CHUNK_OVERLAP: usize = 1024 * 1024;
+// How far past the last record carving searches for another record in a memory image
+const MAX_RESYNC_SIZE: usize = 4096;
+// Largest decompressed FsEvent file that will be carved from a disk image
+const MAX_MEMBER_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct CarveReport {
+    pub pages: Vec<CarvedPage>, // Every page that was found, in the order found
+}
+
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct CarvedPage {
+    pub source: String, // Image or file the page was carved from (the records' source file)
+    pub page: u32,      // Index of the page in the order found
+    pub offset: u64,    // Offset to the page signature, relative to the same data as records
+    pub version: u8,    // DLS version from the page signature
+    pub records: usize, // Number of records carved from the page
+    pub skipped_bytes: u64, // Bytes in the page that were not part of a record
+    pub confidence: f64, // Share of the page (0.0 to 1.0) that was parsed as records
+    pub truncated: bool, // The page ends before its stream size (ex: overwritten or cut off)
+}
+
+impl CarveReport {
+    /// Get the number of pages that were cut off
+    pub fn truncated_pages(&self) -> usize {
+        self.pages.iter().filter(|page| page.truncated).count()
+    }
+}
+
+/// Carve FsEvent records from decompressed data
+/// Record offsets are relative to the start of the data, pages are numbered in the order found
+pub fn carve_fsevents(data: &[u8]) -> Vec<FsEvents> {
+    carve_fsevents_with_report(data).0
+}
+
+/// Carve FsEvent records from decompressed data, and report each page that was found
+pub fn carve_fsevents_with_report(data: &[u8]) -> (Vec<FsEvents>, CarveReport) {
+    let (fsevents_data, pages) = carve_pages(data, data.len(), 0, false);
+    (fsevents_data, CarveReport { pages })
+}
+
+/// Carve records from pages with a signature before the max offset
+/// Returns the records and the pages found
+fn carve_pages(
+    data: &[u8],
+    max_offset: usize,
+    first_page: u32,
+    resync: bool,
+) -> (Vec<FsEvents>, Vec<CarvedPage>) {
+    let signatures = find_page_signatures(data);
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    let mut pages: Vec<CarvedPage> = Vec::new();
+
+    for (index, (offset, signature)) in signatures.iter().enumerate() {
+        if *offset >= max_offset {
+            break;
+        }
+        let end = signatures
+            .get(index + 1)
+            .map(|(next_offset, _)| *next_offset)
+            .unwrap_or(data.len());
+        let start = (offset + HEADER_SIZE).min(end);
+        let page = first_page + pages.len() as u32;
+
+        let max_resync = if resync { MAX_RESYNC_SIZE } else { 0 };
+        let (mut records, skipped) = carve_records(&data[start..end], *signature, max_resync);
+        for fsevent in records.iter_mut() {
+            fsevent.offset += start as u64;
+            fsevent.page = page;
+        }
+
+        // Data past the stream size is not part of the page, so it does not lower the confidence
+        let stream_size = data
+            .get(offset + 8..offset + HEADER_SIZE)
+            .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize);
+        let page_end = match stream_size {
+            Some(size) if size >= HEADER_SIZE => end.min(offset + size),
+            _ => end,
+        };
+        let record_bytes = (end - start) - skipped;
+        let confidence = if page_end > start {
+            (record_bytes as f64 / (page_end - start) as f64).min(1.0)
+        } else {
+            0.0
+        };
+        pages.push(CarvedPage {
+            page,
+            offset: *offset as u64,
+            version: data[*offset] - b'0',
+            records: records.len(),
+            skipped_bytes: skipped as u64,
+            confidence,
+            truncated: stream_size.is_none_or(|size| offset + size > end),
+            ..Default::default()
+        });
+        fsevents_data.append(&mut records);
+    }
+    (fsevents_data, pages)
+}
+
+#[cfg(feature = "filesystem")]
+/// Carve FsEvent records from a raw (dd) or EWF (E01) disk image
+/// Records from deleted FsEvent files have a source file of `<image>@<offset of the gzip data>`
+/// and offsets relative to the decompressed file. Records from decompressed pages in the image
+/// have offsets relative to the start of the image
+pub fn carve_image(path: &str) -> Result<Vec<FsEvents>, FsEventsError> {
+    carve_image_with_report(path).map(|(data, _)| data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Carve FsEvent records from a raw (dd) or EWF (E01) disk image, and report each page that was found
+pub fn carve_image_with_report(path: &str) -> Result<(Vec<FsEvents>, CarveReport), FsEventsError> {
+    let mut reader = open_image(path)?;
+    carve_image_chunks(
+        &mut reader,
+        path,
+        IMAGE_CHUNK_SIZE,
+        IMAGE_CHUNK_OVERLAP,
+        false,
+    )
+}
+
+/// Carve FsEvent records from any seekable image reader (ex: an evidence image)
+pub fn carve_image_reader<R: Read + Seek>(
+    reader: &mut R,
+    name: &str,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    carve_image_chunks(reader, name, IMAGE_CHUNK_SIZE, IMAGE_CHUNK_OVERLAP, false)
+        .map(|(data, _)| data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Carve FsEvent records from a memory image (ex: a raw RAM capture)
+/// Torn pages and partial records are skipped over, records are kept until no record is found for
+/// 4KB. Records from decompressed pages have offsets relative to the start of the image, which is
+/// the physical address for a raw memory capture
+pub fn carve_memory(path: &str) -> Result<Vec<FsEvents>, FsEventsError> {
+    carve_memory_with_report(path).map(|(data, _)| data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Carve FsEvent records from a memory image, and report each page that was found
+pub fn carve_memory_with_report(path: &str) -> Result<(Vec<FsEvents>, CarveReport), FsEventsError> {
+    let mut reader = open_image(path)?;
+    carve_image_chunks(
+        &mut reader,
+        path,
+        IMAGE_CHUNK_SIZE,
+        IMAGE_CHUNK_OVERLAP,
+        true,
+    )
+}
+
+/// Carve FsEvent records from any seekable memory image reader
+pub fn carve_memory_reader<R: Read + Seek>(
+    reader: &mut R,
+    name: &str,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    carve_image_chunks(reader, name, IMAGE_CHUNK_SIZE, IMAGE_CHUNK_OVERLAP, true)
+        .map(|(data, _)| data)
+}
+
+/// Read the image in chunks, carving gzip members and decompressed pages that start in each chunk
+fn carve_image_chunks<R: Read + Seek>(
+    reader: &mut R,
+    name: &str,
+    chunk_size: usize,
+    overlap: usize,
+    resync: bool,
+) -> Result<(Vec<FsEvents>, CarveReport), FsEventsError> {
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    let mut report = CarveReport::default();
+    let mut position: u64 = 0;
+    let mut page = 0;
+
+    loop {
+        reader.seek(SeekFrom::Start(position))?;
+        let mut chunk = Vec::with_capacity(chunk_size + overlap);
+        reader
+            .by_ref()
+            .take((chunk_size + overlap) as u64)
+            .read_to_end(&mut chunk)?;
+        if chunk.is_empty() {
+            break;
+        }
+        let is_last = chunk.len() <= chunk_size;
+        let max_offset = chunk.len().min(chunk_size);
+
+        let (mut records, mut pages) = carve_pages(&chunk, max_offset, page, resync);
+        page += pages.len() as u32;
+        for fsevent in records.iter_mut() {
+            fsevent.offset += position;
+            fsevent.source_file = name.to_string();
+        }
+        for carved in pages.iter_mut() {
+            carved.offset += position;
+            carved.source = name.to_string();
+        }
+        fsevents_data.append(&mut records);
+        report.pages.append(&mut pages);
+
+        for offset in find_gzip_headers(&chunk[..max_offset]) {
+            let member_offset = position + offset as u64;
+            let (mut records, mut member_report) = carve_gzip_member(reader, member_offset)?;
+            let source = format!("{}@{:#x}", name, member_offset);
+            for fsevent in records.iter_mut() {
+                fsevent.source_file = source.clone();
+            }
+            for carved in member_report.pages.iter_mut() {
+                carved.source = source.clone();
+            }
+            fsevents_data.append(&mut records);
+            report.pages.append(&mut member_report.pages);
+        }
+
+        if is_last {
+            break;
+        }
+        position += chunk_size as u64;
+    }
+    Ok((fsevents_data, report))
+}
+
+/// Decompress a gzip member from the image and carve it, if it contains FsEvent pages
+fn carve_gzip_member<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> Result<(Vec<FsEvents>, CarveReport), FsEventsError> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::new();
+    let decoder = GzDecoder::new(reader.by_ref());
+    if let Err(err) = decoder.take(MAX_MEMBER_SIZE).read_to_end(&mut data) {
+        debug!("Gzip data at {:#x} ended early, err: {}", offset, err);
+    }
+
+    let signatures = find_page_signatures(&data[..data.len().min(HEADER_SIZE)]);
+    if signatures.first().map(|(page_offset, _)| *page_offset) != Some(0) {
+        return Ok((Vec::new(), CarveReport::default()));
+    }
+    Ok(carve_fsevents_with_report(&data))
+}
+
+/// Find offsets that look like the start of a gzip member (magic, deflate, and no reserved flags)
+fn find_gzip_headers(data: &[u8]) -> Vec<usize> {
+    memmem::find_iter(data, &[0x1f, 0x8b, 0x08])
+        .filter(|offset| data.get(offset + 3).is_some_and(|flags| flags & 0xe0 == 0))
+        .collect()
+}
+
+#[cfg(feature = "filesystem")]
+/// Carve FsEvent records from a file. Compressed files are decompressed first, if the compressed
+/// data is damaged records are carved from the data decompressed before the error
+pub fn carve_file(path: &str) -> Result<Vec<FsEvents>, FsEventsError> {
+    carve_file_with_report(path).map(|(data, _)| data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Carve FsEvent records from a file (ex: a blob of unknown data), and report each page that was found
+pub fn carve_file_with_report(path: &str) -> Result<(Vec<FsEvents>, CarveReport), FsEventsError> {
+    let decompressed = decompress_recover(path)?;
+    let (mut fsevents_data, mut report) = carve_fsevents_with_report(&decompressed.data);
+    for fsevent in fsevents_data.iter_mut() {
+        fsevent.source_file = path.to_string();
+    }
+    for carved in report.pages.iter_mut() {
+        carved.source = path.to_string();
+    }
+    Ok((fsevents_data, report))
+}
+
+/// Find the offset and value of every DLS1/DLS2/DLS3 page signature in the data
+pub fn find_page_signatures(data: &[u8]) -> Vec<(usize, u32)> {
+    let mut signatures = Vec::new();
+    // Signatures are stored little endian, so the version byte comes first (ex: 2SLD)
+    for index in memmem::find_iter(data, b"SLD") {
+        if index == 0 {
+            continue;
+        }
+        let offset = index - 1;
+        let signature = match data[offset] {
+            b'1' => FsEvents::DISKLOGGERV1,
+            b'2' => FsEvents::DISKLOGGERV2,
+            b'3' => FsEvents::DISKLOGGERV3,
+            _ => continue,
+        };
+        signatures.push((offset, signature));
+    }
+    signatures
+}
+
+/// Parse a page that failed to parse, skipping data that is not a record until the next record
+/// Returns None if the page header is not valid, otherwise the records and the number of bytes skipped
+pub(crate) fn resync_page(
+    data: &[u8],
+    page: u32,
+    page_offset: u64,
+) -> Option<(Vec<FsEvents>, u64)> {
+    let header = data.get(..HEADER_SIZE)?;
+    let signature = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    FsEvents::check_signature(signature).ok()?;
+
+    let stream_size = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    let end = (stream_size as usize).clamp(HEADER_SIZE, data.len());
+    let (mut records, skipped) = carve_records(&data[HEADER_SIZE..end], signature, end);
+    for fsevent in records.iter_mut() {
+        fsevent.offset += page_offset + HEADER_SIZE as u64;
+        fsevent.page = page;
+    }
+    Some((records, skipped as u64))
+}
+
+/// Read records until the data does not look like a record, also returns the number of bytes
+/// that were not part of a record. Data that is not a record is skipped until a record is found or
+/// `max_resync` bytes pass without one
+fn carve_records(data: &[u8], signature: u32, max_resync: usize) -> (Vec<FsEvents>, usize) {
+    let mut records = Vec::new();
+    let mut position = 0;
+    let mut last_record = 0;
+    let mut record_bytes = 0;
+    while position < data.len() {
+        let input = &data[position..];
+        // Skip parsing if the path cannot end before the max path size
+        let results = if memchr::memchr(0, &input[..input.len().min(MAX_PATH_SIZE + 1)]).is_some() {
+            FsEvents::get_fsevent_data(input, &signature).ok()
+        } else {
+            None
+        };
+
+        match results {
+            Some((remaining, mut fsevent)) if is_plausible(&fsevent) => {
+                fsevent.offset = position as u64;
+                records.push(fsevent);
+                record_bytes += data.len() - remaining.len() - position;
+                position = data.len() - remaining.len();
+                last_record = position;
+            }
+            _ if position - last_record < max_resync => position += 1,
+            _ => break,
+        }
+    }
+    (records, data.len() - record_bytes)
+}
+
+/// Check if a parsed record looks like a real FsEvent record
+fn is_plausible(fsevent: &FsEvents) -> bool {
+    fsevent.raw_flags != 0
+        && FsEventFlags::from_bits(fsevent.raw_flags).is_some()
+        && !fsevent.has_invalid_path()
+        && fsevent.path.len() <= MAX_PATH_SIZE
+        && !fsevent.path.chars().any(|c| c.is_control())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "filesystem")]
+    use super::{carve_file, carve_image, carve_image_with_report, carve_memory};
+    use super::{
+        carve_fsevents, carve_fsevents_with_report, carve_image_chunks, find_gzip_headers,
+        find_page_signatures, is_plausible,
+    };
+    use crate::{fsevents::FsEvents, parser::decompress_reader};
+    use std::{fs, io::Cursor, path::PathBuf};
+    #[cfg(feature = "filesystem")]
+    use tempfile::tempdir;
+
+    fn dls3_data() -> Vec<u8> {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        decompress_reader(fs::read(test_location).unwrap().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_carve_fsevents() {
+        let mut data = vec![0x41; 100];
+        data.extend_from_slice(&dls3_data());
+        data.extend_from_slice(&[0xff; 64]);
+
+        // Overwrite the stream size of the first page
+        data[108..112].copy_from_slice(&0xffffu32.to_le_bytes());
+
+        let results = carve_fsevents(&data);
+        assert!(results.len() == 5);
+        assert!(results[0].path == "/Users/bob/Downloads/invoice.pdf");
+        assert!(results[0].offset == 112);
+        assert!(results[3].page == 1);
+        assert!(results[3].offset == 284);
+        assert!(results[4].path == "/Users/bob/.ssh/authorized_keys");
+    }
+
+    #[test]
+    fn test_carve_fsevents_with_report() {
+        let data = dls3_data();
+        let (results, report) = carve_fsevents_with_report(&data);
+        assert!(results.len() == 5);
+        assert!(report.pages.len() == 2);
+        assert!(report.pages[1].offset == 172);
+        assert!(report.pages[1].version == 3);
+        assert!(report.pages[0].records + report.pages[1].records == 5);
+        assert!(report.pages.iter().all(|page| page.confidence == 1.0));
+        assert!(report.truncated_pages() == 0);
+
+        // Page cut off in the middle of its third record
+        let (results, report) = carve_fsevents_with_report(&data[..150]);
+        assert!(results.len() == 2);
+        assert!(report.pages[0].truncated);
+        assert!(report.pages[0].confidence < 1.0);
+    }
+
+    #[test]
+    fn test_carve_fsevents_dls2() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let data = decompress_reader(fs::read(test_location).unwrap().as_slice()).unwrap();
+        let results = carve_fsevents(&data);
+        assert!(results.len() == 736);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_carve_file() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("carve_file");
+        let mut data = vec![0; 512];
+        data.extend_from_slice(&dls3_data());
+        fs::write(&test_location, data).unwrap();
+
+        let path = test_location.display().to_string();
+        let results = carve_file(&path).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].source_file == path);
+    }
+
+    fn test_image() -> Vec<u8> {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+
+        // Deleted gzip FsEvent file followed by a decompressed page
+        let mut image = vec![0; 4096];
+        image.extend_from_slice(&fs::read(test_location).unwrap());
+        image.resize(8192, 0);
+        image.extend_from_slice(&dls3_data()[..172]);
+        image.resize(12288, 0);
+        image
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_carve_image() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("carve_image.dd");
+        fs::write(&test_location, test_image()).unwrap();
+
+        let path = test_location.display().to_string();
+        let results = carve_image(&path).unwrap();
+        assert!(results.len() == 8);
+
+        let raw: Vec<&FsEvents> = results
+            .iter()
+            .filter(|fsevent| fsevent.source_file == path)
+            .collect();
+        assert!(raw.len() == 3);
+        assert!(raw[0].offset == 8192 + 12);
+
+        let deleted: Vec<&FsEvents> = results
+            .iter()
+            .filter(|fsevent| fsevent.source_file == format!("{}@0x1000", path))
+            .collect();
+        assert!(deleted.len() == 5);
+        assert!(deleted[3].offset == 184);
+
+        let (_, report) = carve_image_with_report(&path).unwrap();
+        assert!(report.pages.len() == 3);
+        assert!(report.pages[0].source == path);
+        assert!(report.pages[0].offset == 8192);
+        assert!(report.pages[1].source == format!("{}@0x1000", path));
+        assert!(report.pages[2].offset == 172);
+    }
+
+    #[test]
+    fn test_carve_image_chunks() {
+        // Small chunks so the gzip data and the page cross chunk boundaries
+        let mut image = Cursor::new(test_image());
+        let (results, _) = carve_image_chunks(&mut image, "image", 1000, 500, false).unwrap();
+        assert!(results.len() == 8);
+        assert!(
+            results
+                .iter()
+                .filter(|fsevent| fsevent.source_file == "image")
+                .count()
+                == 3
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_carve_memory() {
+        let data = dls3_data();
+        let records = carve_fsevents(&data);
+        let torn_offset = records[2].offset as usize;
+
+        // Memory image with unrelated data between the second and third record of the first page
+        let mut memory = vec![0; 4096];
+        memory.extend_from_slice(&data[..torn_offset]);
+        memory.extend_from_slice(&[0xff; 37]);
+        memory.extend_from_slice(&data[torn_offset..]);
+        assert!(carve_fsevents(&memory).len() == 4);
+
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("carve_memory.raw");
+        fs::write(&test_location, memory).unwrap();
+
+        let results = carve_memory(&test_location.display().to_string()).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].offset == 4096 + 12);
+        assert!(results[2].path == records[2].path);
+        assert!(results[2].offset == 4096 + 37 + records[2].offset);
+    }
+
+    #[test]
+    fn test_find_gzip_headers() {
+        let data = [0, 0x1f, 0x8b, 0x08, 0x00, 0x1f, 0x8b, 0x08, 0xff];
+        assert!(find_gzip_headers(&data) == vec![1]);
+    }
+
+    #[test]
+    fn test_find_page_signatures() {
+        let signatures = find_page_signatures(b"SLD 2SLD xx3SLD 9SLD");
+        assert!(signatures == vec![(4, 0x444c5332), (11, 0x444c5333)]);
+    }
+
+    #[test]
+    fn test_is_plausible() {
+        let mut fsevent = FsEvents {
+            path: String::from("/private/tmp"),
+            raw_flags: 0x800001,
+            ..Default::default()
+        };
+        assert!(is_plausible(&fsevent));
+
+        fsevent.raw_flags = 0;
+        assert!(!is_plausible(&fsevent));
+
+        fsevent.raw_flags = 0x80000000;
+        assert!(!is_plausible(&fsevent));
+
+        fsevent.raw_flags = 0x800001;
+        fsevent.path = String::from("/private/\x01tmp");
+        assert!(!is_plausible(&fsevent));
+    }
+}
@@ -0,0 +1,230 @@
+//! Checkpoints for resuming interrupted runs
+//!
+//! A checkpoint lists the FsEvent files whose records have all been written to an output file,
+//! with the size of the output after each one. An interrupted run is resumed by skipping the
+//! completed files and cutting the output back to the last recorded size, which removes the
+//! records of a file that was only partly written. Entries are appended as JSON lines, so a
+//! checkpoint that was cut off while it was written only loses its last entry.
+
+use crate::error::FsEventsError;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs::{metadata, read_to_string, File, OpenOptions},
+    io::{ErrorKind, Seek, SeekFrom, Write},
+};
+
+/// A completed FsEvent file
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointEntry {
+    file: String,     // FsEvent file whose records were all written
+    output: String,   // Output file the records were written to
+    output_size: u64, // Size of the output after the file's records were written
+}
+
+/// FsEvent files that have been written to an output file
+#[derive(Debug)]
+pub struct Checkpoint {
+    writer: File,   // Checkpoint file, entries are appended as files are completed
+    output: String, // Output file the checkpoint tracks
+    completed: HashSet<String>, // FsEvent files whose records have all been written
+    output_size: u64, // Size of the output after the last completed file
+}
+
+impl Checkpoint {
+    /// Open the checkpoint for an output file, or create it if it does not exist
+    pub fn open(path: &str, output: &str) -> Result<Checkpoint, FsEventsError> {
+        let text = match read_to_string(path) {
+            Ok(result) => result,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(FsEventsError::Io(err)),
+        };
+
+        let mut completed = HashSet::new();
+        let mut output_size = 0;
+        // Bytes of the checkpoint that hold complete entries
+        let mut valid_size = 0;
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            let entry = match serde_json::from_str::<CheckpointEntry>(line) {
+                // The newline is written last, so an entry without one was cut off when the run
+                // was interrupted
+                _ if !line.ends_with('\n') => break,
+                Ok(result) => result,
+                Err(err) => {
+                    return Err(FsEventsError::Checkpoint(format!(
+                        "{} line {}: {}",
+                        path,
+                        index + 1,
+                        err
+                    )))
+                }
+            };
+            if entry.output != output {
+                return Err(FsEventsError::Checkpoint(format!(
+                    "{} is the checkpoint for {}, not {}",
+                    path, entry.output, output
+                )));
+            }
+            output_size = entry.output_size;
+            completed.insert(entry.file);
+            valid_size += line.len() as u64;
+        }
+
+        let mut writer = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)?;
+        writer.set_len(valid_size)?;
+        writer.seek(SeekFrom::End(0))?;
+        Ok(Checkpoint {
+            writer,
+            output: output.to_string(),
+            completed,
+            output_size,
+        })
+    }
+
+    /// Check if all of a FsEvent file's records have been written to the output
+    pub fn is_completed(&self, file: &str) -> bool {
+        self.completed.contains(file)
+    }
+
+    /// Get the number of completed FsEvent files
+    pub fn completed(&self) -> usize {
+        self.completed.len()
+    }
+
+    /// Get the size of the output after the last completed file
+    pub fn output_size(&self) -> u64 {
+        self.output_size
+    }
+
+    /// Open the output to continue writing after the last completed file. Anything written after
+    /// it (records from a file that was not completed) is removed
+    pub fn resume_output(&self) -> Result<File, FsEventsError> {
+        let current_size = match metadata(&self.output) {
+            Ok(result) => result.len(),
+            Err(err) if err.kind() == ErrorKind::NotFound => 0,
+            Err(err) => return Err(FsEventsError::Io(err)),
+        };
+        if current_size < self.output_size {
+            return Err(FsEventsError::Checkpoint(format!(
+                "{} is smaller than when the checkpoint was saved",
+                self.output
+            )));
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&self.output)?;
+        file.set_len(self.output_size)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(file)
+    }
+
+    /// Add a FsEvent file to the checkpoint once all of its records are written
+    /// The output must be flushed first, so its size includes the file's records
+    pub fn complete(&mut self, file: &str) -> Result<(), FsEventsError> {
+        let entry = CheckpointEntry {
+            file: file.to_string(),
+            output: self.output.clone(),
+            output_size: metadata(&self.output)?.len(),
+        };
+        let mut line =
+            serde_json::to_vec(&entry).map_err(|err| FsEventsError::Output(err.to_string()))?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.writer.sync_data()?;
+
+        self.output_size = entry.output_size;
+        self.completed.insert(entry.file);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Checkpoint;
+    use crate::error::FsEventsError;
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_checkpoint_resume() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        let path = root.join("checkpoint.jsonl").display().to_string();
+        let output = root.join("output.jsonl").display().to_string();
+
+        let mut checkpoint = Checkpoint::open(&path, &output).unwrap();
+        assert!(checkpoint.completed() == 0);
+        let mut writer = checkpoint.resume_output().unwrap();
+        writer.write_all(b"{\"event_id\":1}\n").unwrap();
+        checkpoint.complete("0000000000000002").unwrap();
+        // Interrupted part way through the second file
+        writer.write_all(b"{\"event_id\":3}\n{\"eve").unwrap();
+        drop(checkpoint);
+
+        let checkpoint = Checkpoint::open(&path, &output).unwrap();
+        assert!(checkpoint.is_completed("0000000000000002"));
+        assert!(!checkpoint.is_completed("0000000000000004"));
+        assert!(checkpoint.output_size() == 15);
+        let mut writer = checkpoint.resume_output().unwrap();
+        writer.write_all(b"{\"event_id\":3}\n").unwrap();
+        assert!(fs::read_to_string(&output).unwrap() == "{\"event_id\":1}\n{\"event_id\":3}\n");
+    }
+
+    #[test]
+    fn test_checkpoint_cut_off_entry() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        let path = root.join("checkpoint.jsonl").display().to_string();
+        let output = root.join("output.csv").display().to_string();
+        fs::write(&output, "Path\n/tmp/a.txt\n").unwrap();
+
+        let mut checkpoint = Checkpoint::open(&path, &output).unwrap();
+        checkpoint.complete("0000000000000002").unwrap();
+        drop(checkpoint);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"file\":\"00000000").unwrap();
+
+        // The cut off entry is dropped, and the next entry starts on its own line
+        let mut checkpoint = Checkpoint::open(&path, &output).unwrap();
+        assert!(checkpoint.completed() == 1);
+        checkpoint.complete("0000000000000004").unwrap();
+        let checkpoint = Checkpoint::open(&path, &output).unwrap();
+        assert!(checkpoint.completed() == 2);
+        assert!(checkpoint.output_size() == 16);
+    }
+
+    #[test]
+    fn test_checkpoint_invalid() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        let path = root.join("checkpoint.jsonl").display().to_string();
+        let output = root.join("output.jsonl").display().to_string();
+        fs::write(&output, "").unwrap();
+
+        let mut checkpoint = Checkpoint::open(&path, &output).unwrap();
+        checkpoint.complete("0000000000000002").unwrap();
+        let results = Checkpoint::open(&path, "other.jsonl");
+        assert!(matches!(results, Err(FsEventsError::Checkpoint(_))));
+
+        fs::write(&path, "not a checkpoint\n{}\n").unwrap();
+        let results = Checkpoint::open(&path, &output);
+        assert!(matches!(results, Err(FsEventsError::Checkpoint(_))));
+
+        // The output was replaced after the checkpoint was saved
+        fs::write(&path, "").unwrap();
+        let mut checkpoint = Checkpoint::open(&path, &output).unwrap();
+        fs::write(&output, "{\"event_id\":1}\n").unwrap();
+        checkpoint.complete("0000000000000002").unwrap();
+        fs::remove_file(&output).unwrap();
+        assert!(checkpoint.resume_output().is_err());
+    }
+}
@@ -0,0 +1,481 @@
+//! Find and parse FsEvent files in forensic collections
+//!
+//! Collection tools copy files with their original paths preserved under an output folder:
+//! - Velociraptor offline collectors save files under `uploads/<accessor>/<original path>`, with
+//!   `collection_context.json` or `client_info.json` next to `uploads`. Names may be percent encoded
+//! - KAPE target output saves files under `<volume>/<original path>`, with `*_CopyLog.csv` and
+//!   `*_ConsoleLog.txt` logs in the output folder
+//!
+//! Every `.fseventsd` directory in a collection is parsed and its records are tagged with the
+//! collection tool and the original volume path (ex: `Velociraptor:/System/Volumes/Data`).
+//! Velociraptor zips can be parsed without extracting them with the `zip` feature.
+
+use crate::{
+    discover::{find_fseventsd_directories, parse_tagged_directories},
+    error::FsEventsError,
+    fsevents::FsEvents,
+    options::ParserOptions,
+    report::ParseReport,
+};
+use std::{
+    fs::read_dir,
+    path::{Component, Path},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollectionLayout {
+    Velociraptor, // Velociraptor offline collector output
+    Kape,         // KAPE target output
+    #[default]
+    Directory, // Any other folder with preserved paths
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsEventsCollection {
+    pub layout: CollectionLayout, // Tool that created the collection
+    pub tag: String,              // Collection tool and original volume path
+    pub directory: String,        // Path to the .fseventsd directory in the collection
+}
+
+// Files Velociraptor writes next to the uploads directory
+const VELOCIRAPTOR_FILES: [&str; 4] = [
+    "collection_context.json",
+    "client_info.json",
+    "log.json",
+    "uploads.json",
+];
+
+/// Find every .fseventsd directory in a collection folder
+pub fn collection_fseventsd(root: &str) -> Result<Vec<FsEventsCollection>, FsEventsError> {
+    let root_path = Path::new(root);
+    if !root_path.is_dir() {
+        return Err(FsEventsError::NotADirectory(root.to_string()));
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    for entry in read_dir(root_path)? {
+        names.push(entry?.file_name().to_string_lossy().to_string());
+    }
+    let layout = collection_layout(&names);
+
+    let mut directories = Vec::new();
+    find_fseventsd_directories(root_path, 0, &mut directories);
+    let mut collections: Vec<FsEventsCollection> = directories
+        .into_iter()
+        .map(|directory| {
+            let components = relative_components(
+                directory
+                    .strip_prefix(root_path)
+                    .unwrap_or(&directory)
+                    .parent()
+                    .unwrap_or(Path::new("")),
+            );
+            FsEventsCollection {
+                layout,
+                tag: collection_tag(layout, &components),
+                directory: directory.display().to_string(),
+            }
+        })
+        .collect();
+    collections.sort_by(|first, second| first.directory.cmp(&second.directory));
+    Ok(collections)
+}
+
+/// Parse every .fseventsd directory in a collection folder, or a Velociraptor collection zip
+/// Each record is tagged with the collection source, records are sorted by event ID
+pub fn parse_collection(
+    root: &str,
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_collection_with_report(root, options).map(|(data, _)| data)
+}
+
+/// Parse every .fseventsd directory in a collection folder or zip, and report any files or
+/// directories that were skipped
+pub fn parse_collection_with_report(
+    root: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let mut report = ParseReport::default();
+    if Path::new(root).is_file() {
+        let fsevents_data = parse_collection_zip(root, options, &mut report)?;
+        return Ok((fsevents_data, report));
+    }
+    let directories = collection_fseventsd(root)?
+        .into_iter()
+        .map(|collection| (collection.tag, collection.directory))
+        .collect::<Vec<(String, String)>>();
+    let fsevents_data = parse_tagged_directories(&directories, options, &mut report)?;
+    Ok((fsevents_data, report))
+}
+
+/// Detect the collection layout from the names at the root of the collection
+pub(crate) fn collection_layout<T: AsRef<str>>(names: &[T]) -> CollectionLayout {
+    let has_uploads = names.iter().any(|name| name.as_ref() == "uploads");
+    if has_uploads
+        && names
+            .iter()
+            .any(|name| VELOCIRAPTOR_FILES.contains(&name.as_ref()))
+    {
+        return CollectionLayout::Velociraptor;
+    }
+    if names.iter().any(|name| {
+        let name = name.as_ref();
+        name.ends_with("_CopyLog.csv") || name.ends_with("_ConsoleLog.txt")
+    }) {
+        return CollectionLayout::Kape;
+    }
+    CollectionLayout::Directory
+}
+
+/// Get the record tag from the components of the path to the volume containing .fseventsd
+pub(crate) fn collection_tag<T: AsRef<str>>(layout: CollectionLayout, components: &[T]) -> String {
+    let components: Vec<String> = components
+        .iter()
+        .map(|component| decode_name(component.as_ref()))
+        .collect();
+    match layout {
+        CollectionLayout::Velociraptor => {
+            // Skip uploads/<accessor>
+            let path = components.get(2..).unwrap_or_default().join("/");
+            format!("Velociraptor:/{}", path)
+        }
+        // The first component is the volume (ex: drive letter)
+        CollectionLayout::Kape => format!("KAPE:{}", components.join("/")),
+        CollectionLayout::Directory => format!("/{}", components.join("/")),
+    }
+}
+
+/// Check if a directory name is .fseventsd, Velociraptor may percent encode the dot
+pub(crate) fn is_fseventsd_name(name: &str) -> bool {
+    decode_name(name) == ".fseventsd"
+}
+
+/// Decode percent encoded characters in a file name (ex: %2Efseventsd)
+fn decode_name(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap_or_default();
+            if let Ok(value) = u8::from_str_radix(hex, 16) {
+                decoded.push(value);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Get the normal components of a relative path as strings
+fn relative_components(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(feature = "zip")]
+/// Parse the FsEvent files in every .fseventsd directory in a collection zip
+/// Records do not get approximate or estimated times, the zip does not keep the file times needed
+fn parse_collection_zip(
+    path: &str,
+    options: &ParserOptions,
+    report: &mut ParseReport,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    use crate::{
+        parser::{parse_fseventsd_uuid, parse_reader_pages},
+        sort::sort_fsevents,
+    };
+    use log::{error, warn};
+    use std::{
+        collections::BTreeMap,
+        fs::File,
+        io::{Cursor, Read},
+    };
+
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|err| FsEventsError::InvalidCollection(format!("{}: {}", path, err)))?;
+
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    let root_names: Vec<&str> = names
+        .iter()
+        .filter_map(|name| name.split('/').next())
+        .collect();
+    let layout = collection_layout(&root_names);
+
+    // Files in each .fseventsd directory, by the components of the path to the directory
+    let mut directories: BTreeMap<Vec<&str>, Vec<&str>> = BTreeMap::new();
+    for name in &names {
+        let components: Vec<&str> = name.split('/').collect();
+        if components.len() < 2 || components[components.len() - 1].is_empty() {
+            continue;
+        }
+        if !is_fseventsd_name(components[components.len() - 2]) {
+            continue;
+        }
+        directories
+            .entry(components[..components.len() - 2].to_vec())
+            .or_default()
+            .push(name);
+    }
+
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    for (volume, mut files) in directories {
+        let tag = collection_tag(layout, &volume);
+        files.sort();
+
+        let mut volume_uuid = String::new();
+        let mut volume_data: Vec<FsEvents> = Vec::new();
+        for name in files {
+            if options.is_cancelled() {
+                break;
+            }
+            let file = format!("{}/{}", path, name);
+            let entry = archive
+                .by_name(name)
+                .map_err(|err| FsEventsError::InvalidCollection(format!("{}: {}", file, err)));
+            let results = entry.and_then(|mut entry| {
+                if name.ends_with("/fseventsd-uuid") {
+                    if options.attach_uuid {
+                        let mut data = Vec::new();
+                        entry.read_to_end(&mut data)?;
+                        volume_uuid = parse_fseventsd_uuid(&data)?;
+                    }
+                    return Ok(None);
+                }
+                // The size in the zip header may be wrong, so the read is limited too
+                let too_large = FsEventsError::FileTooLarge(file.clone(), options.max_file_size);
+                if entry.size() >= options.max_file_size {
+                    return Err(too_large);
+                }
+                let mut data = Vec::new();
+                entry.take(options.max_file_size).read_to_end(&mut data)?;
+                if data.len() as u64 >= options.max_file_size {
+                    return Err(too_large);
+                }
+                parse_reader_pages(Cursor::new(data), &file, options, report).map(Some)
+            });
+            match results {
+                Ok(Some(mut data)) => {
+                    report.files_parsed += 1;
+                    volume_data.append(&mut data);
+                }
+                Ok(None) => {}
+                // Large files are always skipped, even if not lenient
+                Err(err @ FsEventsError::FileTooLarge(..)) => {
+                    warn!("Skipping FsEvent file {}, err: {}", file, err);
+                    report.skip_file(&file, err.to_string());
+                }
+                Err(err) if options.lenient => {
+                    error!("Failed to parse FsEvent file {}, err: {}", file, err);
+                    report.skip_file(&file, err.to_string());
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        if options.attach_uuid && volume_uuid.is_empty() {
+            warn!("No fseventsd UUID found for {} in {}", tag, path);
+        }
+        for mut fsevent in volume_data {
+            fsevent.source_tag = tag.clone();
+            fsevent.volume_uuid = volume_uuid.clone();
+            fsevents_data.push(fsevent);
+        }
+    }
+    sort_fsevents(&mut fsevents_data);
+    Ok(fsevents_data)
+}
+
+#[cfg(not(feature = "zip"))]
+/// Collection zips can only be parsed with the zip feature
+fn parse_collection_zip(
+    path: &str,
+    _options: &ParserOptions,
+    _report: &mut ParseReport,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    Err(FsEventsError::InvalidCollection(format!(
+        "{} is a file, parsing collection zips requires the zip feature",
+        path
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        collection_fseventsd, collection_layout, collection_tag, decode_name, is_fseventsd_name,
+        parse_collection, parse_collection_with_report, CollectionLayout,
+    };
+    use crate::{options::ParserOptions, test_util::copy_test_data};
+    use std::{fs::write, path::Path};
+    use tempfile::tempdir;
+
+    fn test_collection(test_location: &Path, layout: &[&str], files: &[&str]) {
+        for directory in layout {
+            copy_test_data("DLS3/0000000000030012", &test_location.join(directory));
+        }
+        for file in files {
+            write(test_location.join(file), b"{}").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_collection_layout() {
+        assert!(
+            collection_layout(&["uploads", "client_info.json"]) == CollectionLayout::Velociraptor
+        );
+        assert!(
+            collection_layout(&["E", "2024-01-01T000000_CopyLog.csv"]) == CollectionLayout::Kape
+        );
+        assert!(collection_layout(&["uploads"]) == CollectionLayout::Directory);
+    }
+
+    #[test]
+    fn test_collection_tag() {
+        assert!(
+            collection_tag(
+                CollectionLayout::Velociraptor,
+                &["uploads", "auto", "System", "Volumes", "Data"]
+            ) == "Velociraptor:/System/Volumes/Data"
+        );
+        assert!(
+            collection_tag(CollectionLayout::Velociraptor, &["uploads", "auto"])
+                == "Velociraptor:/"
+        );
+        assert!(collection_tag(CollectionLayout::Kape, &["E"]) == "KAPE:E");
+        assert!(collection_tag(CollectionLayout::Directory, &["Volumes", "USB"]) == "/Volumes/USB");
+    }
+
+    #[test]
+    fn test_decode_name() {
+        assert!(decode_name("%2Efseventsd") == ".fseventsd");
+        assert!(decode_name("Macintosh%20HD") == "Macintosh HD");
+        assert!(decode_name("100%") == "100%");
+        assert!(decode_name("%zz") == "%zz");
+        assert!(is_fseventsd_name("%2efseventsd"));
+        assert!(!is_fseventsd_name("fseventsd"));
+    }
+
+    #[test]
+    fn test_parse_velociraptor_collection() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        test_collection(
+            test_location,
+            &[
+                "uploads/auto/System/Volumes/Data/%2Efseventsd",
+                "uploads/auto/Volumes/USB/.fseventsd",
+            ],
+            &["collection_context.json"],
+        );
+        let collections = collection_fseventsd(&test_location.display().to_string()).unwrap();
+        assert!(collections.len() == 2);
+        assert!(collections[0].layout == CollectionLayout::Velociraptor);
+        assert!(collections[0].tag == "Velociraptor:/System/Volumes/Data");
+        assert!(collections[1].tag == "Velociraptor:/Volumes/USB");
+
+        let results =
+            parse_collection(&test_location.display().to_string(), &ParserOptions::new()).unwrap();
+        assert!(results.len() == 10);
+        assert!(results[0].source_tag == "Velociraptor:/System/Volumes/Data");
+        assert!(results[1].source_tag == "Velociraptor:/Volumes/USB");
+    }
+
+    #[test]
+    fn test_parse_kape_collection() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        test_collection(
+            test_location,
+            &["E/.fseventsd"],
+            &["2024-01-01T000000_ConsoleLog.txt"],
+        );
+        let (results, report) = parse_collection_with_report(
+            &test_location.display().to_string(),
+            &ParserOptions::new(),
+        )
+        .unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].source_tag == "KAPE:E");
+        assert!(report.files_parsed == 1);
+        assert!(report.is_complete());
+    }
+
+    #[test]
+    #[cfg(feature = "zip")]
+    fn test_parse_collection_zip() {
+        use crate::test_util::test_data;
+        use std::{fs::File, io::Write};
+        use zip::{write::SimpleFileOptions, ZipWriter};
+
+        let source = test_data("DLS3");
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("collection.zip");
+
+        let mut writer = ZipWriter::new(File::create(&test_location).unwrap());
+        let options = SimpleFileOptions::default();
+        writer.start_file("client_info.json", options).unwrap();
+        writer.write_all(b"{}").unwrap();
+        for file in ["0000000000030012", "fseventsd-uuid"] {
+            writer
+                .start_file(
+                    format!("uploads/auto/System/Volumes/Data/%2Efseventsd/{}", file),
+                    options,
+                )
+                .unwrap();
+            writer
+                .write_all(&std::fs::read(source.join(file)).unwrap())
+                .unwrap();
+        }
+        writer
+            .start_file(
+                "uploads/auto/System/Volumes/Data/%2Efseventsd/0000000000099999",
+                options,
+            )
+            .unwrap();
+        writer.write_all(b"not an FsEvent file").unwrap();
+        writer.finish().unwrap();
+
+        let options = ParserOptions::new().attach_uuid(true);
+        let results = parse_collection(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].source_tag == "Velociraptor:/System/Volumes/Data");
+        assert!(!results[0].volume_uuid.is_empty());
+        assert!(results[0]
+            .source_file
+            .ends_with(".zip/uploads/auto/System/Volumes/Data/%2Efseventsd/0000000000030012"));
+
+        let path = test_location.display().to_string();
+        let (results, report) = parse_collection_with_report(&path, &ParserOptions::new()).unwrap();
+        assert!(results.len() == 5);
+        assert!(report.files_parsed == 1);
+        assert!(report.skipped_files.len() == 1);
+        assert!(report.skipped_files[0].path.ends_with("0000000000099999"));
+
+        // Large files are skipped even if not lenient
+        let options = ParserOptions::new().lenient(false).max_file_size(10);
+        let (results, report) = parse_collection_with_report(&path, &options).unwrap();
+        assert!(results.is_empty());
+        assert!(report.files_parsed == 0);
+        assert!(report.skipped_files.len() == 2);
+        assert!(report.skipped_files[0].reason.contains("10 bytes"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "zip"))]
+    fn test_parse_collection_zip_disabled() {
+        use crate::error::FsEventsError;
+
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("collection_disabled.zip");
+        write(&test_location, b"PK").unwrap();
+        let results = parse_collection(&test_location.display().to_string(), &ParserOptions::new());
+        assert!(matches!(results, Err(FsEventsError::InvalidCollection(_))));
+    }
+}
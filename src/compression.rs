@@ -0,0 +1,271 @@
+//! Detect how FsEvent data is compressed
+//!
+//! FsEvent files are gzip compressed, but carved or exported data is often already decompressed.
+//! Collected files are sometimes re-compressed with zstd, bzip2, or xz, those decoders are enabled
+//! with the `zstd`, `bzip2`, and `xz` features. Re-compressed files still contain the gzip data
+//! written by fseventsd, so the data inside those layers is checked for gzip again.
+//! The first bytes of the data are checked for a compression signature, anything else is read as is.
+
+use flate2::read::MultiGzDecoder;
+use std::io::{Chain, Cursor, Error, Read, Result};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: [u8; 3] = [0x42, 0x5a, 0x68]; // BZh
+const XZ_MAGIC: [u8; 6] = [0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+// Longest magic value
+const MAGIC_SIZE: usize = 6;
+
+type Input<R> = Chain<Cursor<Vec<u8>>, R>;
+
+pub(crate) enum Decoder<R: Read> {
+    Gzip(Box<MultiGzDecoder<Input<R>>>), // Gzip compressed data
+    #[cfg(feature = "zstd")]
+    Zstd(Box<zstd::Decoder<'static, std::io::BufReader<Input<R>>>>), // Zstd compressed data
+    #[cfg(feature = "bzip2")]
+    Bzip2(Box<bzip2::read::MultiBzDecoder<Input<R>>>), // Bzip2 compressed data
+    #[cfg(feature = "xz")]
+    Xz(Box<xz2::read::XzDecoder<Input<R>>>), // Xz compressed data
+    Raw(Input<R>),                       // Uncompressed data
+    #[cfg(any(feature = "zstd", feature = "bzip2", feature = "xz"))]
+    Nested(Box<MultiGzDecoder<Input<Decoder<R>>>>), // Gzip data inside zstd, bzip2, or xz data
+    #[cfg(any(feature = "zstd", feature = "bzip2", feature = "xz"))]
+    NestedRaw(Box<Input<Decoder<R>>>), // Uncompressed data inside zstd, bzip2, or xz data
+}
+
+impl<R: Read> Decoder<R> {
+    /// Check the first bytes of the data and create the matching decoder
+    pub(crate) fn detect(mut reader: R) -> Result<Decoder<R>> {
+        let mut magic = Vec::with_capacity(MAGIC_SIZE);
+        (&mut reader)
+            .take(MAGIC_SIZE as u64)
+            .read_to_end(&mut magic)?;
+
+        let format = Format::from_magic(&magic);
+        let input = Cursor::new(magic).chain(reader);
+        match format {
+            Format::Gzip => Ok(Decoder::Gzip(Box::new(MultiGzDecoder::new(input)))),
+            #[cfg(feature = "zstd")]
+            Format::Zstd => Decoder::Zstd(Box::new(zstd::Decoder::new(input)?)).inner_gzip(),
+            #[cfg(feature = "bzip2")]
+            Format::Bzip2 => {
+                Decoder::Bzip2(Box::new(bzip2::read::MultiBzDecoder::new(input))).inner_gzip()
+            }
+            #[cfg(feature = "xz")]
+            Format::Xz => {
+                Decoder::Xz(Box::new(xz2::read::XzDecoder::new_multi_decoder(input))).inner_gzip()
+            }
+            Format::Raw => Ok(Decoder::Raw(input)),
+            #[allow(unreachable_patterns)]
+            unsupported => Err(Error::other(format!(
+                "{} compressed data requires the {} feature",
+                unsupported.name(),
+                unsupported.name()
+            ))),
+        }
+    }
+
+    #[cfg(any(feature = "zstd", feature = "bzip2", feature = "xz"))]
+    /// Check if the data inside a zstd, bzip2, or xz layer is gzip compressed, and decode it too
+    fn inner_gzip(mut self) -> Result<Decoder<R>> {
+        let mut magic = Vec::with_capacity(MAGIC_SIZE);
+        (&mut self)
+            .take(MAGIC_SIZE as u64)
+            .read_to_end(&mut magic)?;
+        let is_gzip = Format::from_magic(&magic) == Format::Gzip;
+        let input = Cursor::new(magic).chain(self);
+        if is_gzip {
+            return Ok(Decoder::Nested(Box::new(MultiGzDecoder::new(input))));
+        }
+        Ok(Decoder::NestedRaw(Box::new(input)))
+    }
+
+    /// Check if the data is compressed
+    pub(crate) fn is_compressed(&self) -> bool {
+        !matches!(self, Decoder::Raw(_))
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            Decoder::Gzip(reader) => reader.read(buf),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(reader) => reader.read(buf),
+            #[cfg(feature = "bzip2")]
+            Decoder::Bzip2(reader) => reader.read(buf),
+            #[cfg(feature = "xz")]
+            Decoder::Xz(reader) => reader.read(buf),
+            Decoder::Raw(reader) => reader.read(buf),
+            #[cfg(any(feature = "zstd", feature = "bzip2", feature = "xz"))]
+            Decoder::Nested(reader) => reader.read(buf),
+            #[cfg(any(feature = "zstd", feature = "bzip2", feature = "xz"))]
+            Decoder::NestedRaw(reader) => reader.read(buf),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+    Raw,
+}
+
+impl Format {
+    /// Get the compression format from the first bytes of the data
+    fn from_magic(magic: &[u8]) -> Format {
+        if magic.starts_with(&GZIP_MAGIC) {
+            Format::Gzip
+        } else if magic.starts_with(&ZSTD_MAGIC) {
+            Format::Zstd
+        } else if magic.starts_with(&BZIP2_MAGIC) {
+            Format::Bzip2
+        } else if magic.starts_with(&XZ_MAGIC) {
+            Format::Xz
+        } else {
+            Format::Raw
+        }
+    }
+
+    /// Name of the compression format, also the name of the feature that enables it
+    fn name(&self) -> &'static str {
+        match self {
+            Format::Gzip => "gzip",
+            Format::Zstd => "zstd",
+            Format::Bzip2 => "bzip2",
+            Format::Xz => "xz",
+            Format::Raw => "raw",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, Format};
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::{Read, Write};
+
+    /// Get a real gzip compressed FsEvent file and its decompressed data
+    #[cfg(any(feature = "zstd", feature = "bzip2", feature = "xz"))]
+    fn fsevent_file() -> (Vec<u8>, Vec<u8>) {
+        let mut test_location = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let compressed = std::fs::read(test_location).unwrap();
+        let mut data = Vec::new();
+        Decoder::detect(&compressed[..])
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        (compressed, data)
+    }
+
+    /// Decode re-compressed FsEvent data and check it contains the original records
+    #[cfg(any(feature = "zstd", feature = "bzip2", feature = "xz"))]
+    fn check_recompressed(recompressed: &[u8], expected: &[u8]) {
+        let mut decoder = Decoder::detect(recompressed).unwrap();
+        assert!(matches!(decoder, Decoder::Nested(_)));
+        assert!(decoder.is_compressed());
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert!(data == expected);
+        assert!(crate::parser::parse_bytes(recompressed).unwrap().len() == 736);
+    }
+
+    #[test]
+    fn test_detect_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"1SLD data").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = Decoder::detect(&compressed[..]).unwrap();
+        assert!(decoder.is_compressed());
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert!(data == b"1SLD data");
+    }
+
+    #[test]
+    fn test_detect_raw() {
+        let mut decoder = Decoder::detect(&b"1SLD data"[..]).unwrap();
+        assert!(!decoder.is_compressed());
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert!(data == b"1SLD data");
+    }
+
+    #[test]
+    fn test_detect_short() {
+        let mut decoder = Decoder::detect(&b"1"[..]).unwrap();
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert!(data == b"1");
+    }
+
+    #[test]
+    fn test_from_magic() {
+        assert!(Format::from_magic(&[0x28, 0xb5, 0x2f, 0xfd, 0, 0]) == Format::Zstd);
+        assert!(Format::from_magic(b"BZh91A") == Format::Bzip2);
+        assert!(Format::from_magic(&[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]) == Format::Xz);
+        assert!(Format::from_magic(b"1SLD") == Format::Raw);
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn test_detect_zstd_disabled() {
+        let results = Decoder::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0, 0][..]);
+        assert!(results.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_detect_zstd() {
+        let compressed = zstd::encode_all(&b"1SLD data"[..], 0).unwrap();
+        let mut decoder = Decoder::detect(&compressed[..]).unwrap();
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert!(data == b"1SLD data");
+
+        let (gzip, expected) = fsevent_file();
+        check_recompressed(&zstd::encode_all(&gzip[..], 0).unwrap(), &expected);
+    }
+
+    #[test]
+    #[cfg(feature = "bzip2")]
+    fn test_detect_bzip2() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(b"1SLD data").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = Decoder::detect(&compressed[..]).unwrap();
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert!(data == b"1SLD data");
+
+        let (gzip, expected) = fsevent_file();
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(&gzip).unwrap();
+        check_recompressed(&encoder.finish().unwrap(), &expected);
+    }
+
+    #[test]
+    #[cfg(feature = "xz")]
+    fn test_detect_xz() {
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(b"1SLD data").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = Decoder::detect(&compressed[..]).unwrap();
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert!(data == b"1SLD data");
+
+        let (gzip, expected) = fsevent_file();
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(&gzip).unwrap();
+        check_recompressed(&encoder.finish().unwrap(), &expected);
+    }
+}
@@ -0,0 +1,105 @@
+//! Remove duplicate FsEvent records
+//!
+//! FsEvent files often contain the same record multiple times, especially after merging records
+//! from the current and legacy fseventsd directories.
+
+use crate::fsevents::FsEvents;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupKey {
+    Record,      // Path, event ID, flags, and node ID
+    PathEventId, // Path and event ID
+    Path,        // Path only
+}
+
+impl DedupKey {
+    /// Get a key from its name: `record`, `path-event-id`, or `path`
+    pub fn from_name(name: &str) -> Option<DedupKey> {
+        match name.trim().replace('_', "-").to_lowercase().as_str() {
+            "record" => Some(DedupKey::Record),
+            "path-event-id" => Some(DedupKey::PathEventId),
+            "path" => Some(DedupKey::Path),
+            _ => None,
+        }
+    }
+}
+
+/// Remove duplicate records based on the key, keeping the first record
+/// Returns the number of duplicates removed
+pub fn dedup_fsevents(fsevents: &mut Vec<FsEvents>, key: DedupKey) -> usize {
+    let original_count = fsevents.len();
+    let mut seen: HashSet<(String, u64, u32, u64)> = HashSet::new();
+
+    fsevents.retain(|fsevent| {
+        let record_key = match key {
+            DedupKey::Record => (
+                fsevent.path.clone(),
+                fsevent.event_id,
+                fsevent.raw_flags,
+                fsevent.node,
+            ),
+            DedupKey::PathEventId => (fsevent.path.clone(), fsevent.event_id, 0, 0),
+            DedupKey::Path => (fsevent.path.clone(), 0, 0, 0),
+        };
+        seen.insert(record_key)
+    });
+
+    original_count - fsevents.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dedup_fsevents, DedupKey};
+    use crate::fsevents::FsEvents;
+
+    fn test_records() -> Vec<FsEvents> {
+        let records = [
+            ("/a", 10, 0x01),
+            ("/a", 10, 0x01),
+            ("/a", 10, 0x02),
+            ("/a", 11, 0x02),
+            ("/b", 11, 0x02),
+        ];
+        records
+            .iter()
+            .map(|(path, event_id, raw_flags)| FsEvents {
+                path: path.to_string(),
+                event_id: *event_id,
+                raw_flags: *raw_flags,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_dedup_record() {
+        let mut fsevents = test_records();
+        assert!(dedup_fsevents(&mut fsevents, DedupKey::Record) == 1);
+        assert!(fsevents.len() == 4);
+    }
+
+    #[test]
+    fn test_dedup_path_event_id() {
+        let mut fsevents = test_records();
+        assert!(dedup_fsevents(&mut fsevents, DedupKey::PathEventId) == 2);
+        assert!(fsevents.len() == 3);
+        assert!(fsevents[0].raw_flags == 0x01);
+    }
+
+    #[test]
+    fn test_dedup_key_from_name() {
+        assert!(DedupKey::from_name("record") == Some(DedupKey::Record));
+        assert!(DedupKey::from_name("path_event_id") == Some(DedupKey::PathEventId));
+        assert!(DedupKey::from_name("Path") == Some(DedupKey::Path));
+        assert!(DedupKey::from_name("node").is_none());
+    }
+
+    #[test]
+    fn test_dedup_path() {
+        let mut fsevents = test_records();
+        assert!(dedup_fsevents(&mut fsevents, DedupKey::Path) == 3);
+        assert!(fsevents.len() == 2);
+        assert!(fsevents[1].path == "/b");
+    }
+}
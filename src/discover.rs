@@ -0,0 +1,588 @@
+//! Discover fseventsd directories outside of the default system paths
+//!
+//! External volumes (USB drives, disk images, network mounts) keep their own `.fseventsd`
+//! directory at the root of the volume. Time Machine backups keep a copy of each backed up volume,
+//! including its `.fseventsd` directory. Full filesystem iOS/iPadOS extractions contain `.fseventsd`
+//! directories for the system and data volumes. Any other layout (ex: a mounted disk image or a
+//! folder of copied files) can be searched recursively for `.fseventsd` directories and loose
+//! FsEvent files.
+
+use crate::{
+    collection::is_fseventsd_name,
+    dedup::{dedup_fsevents, DedupKey},
+    error::FsEventsError,
+    fsevents::FsEvents,
+    inventory::filename_event_id,
+    options::ParserOptions,
+    parser::{fseventsd, parse_directory, parse_file_list},
+    report::ParseReport,
+    sort::sort_fsevents,
+};
+use log::{error, info, warn};
+use std::{
+    collections::BTreeMap,
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+const VOLUMES_PATH: &str = "/Volumes";
+
+#[derive(Debug, Clone, Default)]
+pub struct FsEventsVolume {
+    pub name: String,       // Volume name (ex: USB for /Volumes/USB)
+    pub directory: String,  // Path to the volume .fseventsd directory
+    pub files: Vec<String>, // FsEvents files in the directory
+}
+
+/// Get FsEvents files on all mounted volumes under /Volumes
+pub fn get_fseventsd_volumes() -> Result<Vec<FsEventsVolume>, FsEventsError> {
+    fseventsd_volumes(VOLUMES_PATH)
+}
+
+/// Get FsEvents files for each volume in a mount directory
+/// Volumes without a .fseventsd directory are skipped
+pub fn fseventsd_volumes(mount_directory: &str) -> Result<Vec<FsEventsVolume>, FsEventsError> {
+    if !Path::new(mount_directory).is_dir() {
+        return Err(FsEventsError::NotADirectory(mount_directory.to_string()));
+    }
+
+    let mut volumes: Vec<FsEventsVolume> = Vec::new();
+    for entry in read_dir(mount_directory)? {
+        let entry = entry?;
+        let directory = entry.path().join(".fseventsd");
+        if !directory.is_dir() {
+            continue;
+        }
+
+        let directory = directory.display().to_string();
+        match fseventsd(&directory) {
+            Ok(files) => volumes.push(FsEventsVolume {
+                name: entry.file_name().to_string_lossy().to_string(),
+                directory,
+                files,
+            }),
+            Err(err) => warn!(
+                "Failed to list FsEvent directory {}, err: {}",
+                directory, err
+            ),
+        }
+    }
+    volumes.sort_by(|first, second| first.name.cmp(&second.name));
+    Ok(volumes)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsEventsSnapshot {
+    pub snapshot: String,  // Backup snapshot name (ex: 2023-01-01-120000)
+    pub directory: String, // Path to the .fseventsd directory in the snapshot
+}
+
+/// Find every .fseventsd directory in a Time Machine backup
+/// Supports HFS+ backups (Backups.backupdb) and APFS backups (*.backup snapshot directories)
+pub fn time_machine_fseventsd(backup_root: &str) -> Result<Vec<FsEventsSnapshot>, FsEventsError> {
+    if !Path::new(backup_root).is_dir() {
+        return Err(FsEventsError::NotADirectory(backup_root.to_string()));
+    }
+
+    let mut directories: Vec<PathBuf> = Vec::new();
+    find_fseventsd_directories(Path::new(backup_root), 0, &mut directories);
+
+    let mut snapshots: Vec<FsEventsSnapshot> = Vec::new();
+    for directory in directories {
+        let snapshot = directory
+            .ancestors()
+            .filter_map(|ancestor| ancestor.file_name())
+            .map(|name| name.to_string_lossy())
+            .find(|name| is_snapshot_name(name));
+        let snapshot = match snapshot {
+            Some(name) => name.trim_end_matches(".backup").to_string(),
+            None => continue,
+        };
+
+        snapshots.push(FsEventsSnapshot {
+            snapshot,
+            directory: directory.display().to_string(),
+        });
+    }
+    snapshots.sort_by(|first, second| {
+        first
+            .snapshot
+            .cmp(&second.snapshot)
+            .then_with(|| first.directory.cmp(&second.directory))
+    });
+    Ok(snapshots)
+}
+
+/// Parse every .fseventsd directory in a Time Machine backup
+/// Each record is tagged with the snapshot it came from, records are sorted by event ID
+pub fn parse_time_machine(
+    backup_root: &str,
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    let directories = time_machine_fseventsd(backup_root)?
+        .into_iter()
+        .map(|snapshot| (snapshot.snapshot, snapshot.directory))
+        .collect::<Vec<(String, String)>>();
+    parse_tagged_directories(&directories, options, &mut ParseReport::default())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsEventsDomain {
+    pub domain: String,    // iOS data domain (ex: System, Data, Data/mobile)
+    pub directory: String, // Path to the .fseventsd directory in the extraction
+}
+
+/// Find every .fseventsd directory in a full filesystem iOS/iPadOS extraction
+pub fn ios_fseventsd(extraction_root: &str) -> Result<Vec<FsEventsDomain>, FsEventsError> {
+    if !Path::new(extraction_root).is_dir() {
+        return Err(FsEventsError::NotADirectory(extraction_root.to_string()));
+    }
+
+    let root = Path::new(extraction_root);
+    let mut directories: Vec<PathBuf> = Vec::new();
+    find_fseventsd_directories(root, 0, &mut directories);
+
+    let mut domains: Vec<FsEventsDomain> = directories
+        .into_iter()
+        .map(|directory| FsEventsDomain {
+            domain: ios_domain(directory.strip_prefix(root).unwrap_or(&directory)),
+            directory: directory.display().to_string(),
+        })
+        .collect();
+    domains.sort_by(|first, second| first.directory.cmp(&second.directory));
+    Ok(domains)
+}
+
+/// Parse every .fseventsd directory in an iOS/iPadOS extraction
+/// Each record is tagged with the data domain it came from, records are sorted by event ID
+pub fn parse_ios_extraction(
+    extraction_root: &str,
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    let directories = ios_fseventsd(extraction_root)?
+        .into_iter()
+        .map(|domain| (domain.domain, domain.directory))
+        .collect::<Vec<(String, String)>>();
+    parse_tagged_directories(&directories, options, &mut ParseReport::default())
+}
+
+/// Get the iOS data domain for a .fseventsd directory relative to the extraction root
+/// `private/var` is the Data volume, anything outside of it is the System volume
+fn ios_domain(relative_path: &Path) -> String {
+    let components: Vec<String> = relative_path
+        .parent()
+        .unwrap_or(relative_path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+
+    let data_index = components
+        .windows(2)
+        .position(|pair| pair[0] == "private" && pair[1] == "var");
+    match data_index {
+        Some(index) => {
+            let mut domain = vec![String::from("Data")];
+            domain.extend_from_slice(&components[index + 2..]);
+            domain.join("/")
+        }
+        None => String::from("System"),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsEventsSearch {
+    pub directories: Vec<String>, // .fseventsd directories under the root
+    pub files: Vec<String>,       // FsEvent files outside of a .fseventsd directory
+}
+
+/// Recursively find every .fseventsd directory, and every FsEvent file (named with 16 hex digits)
+/// outside of one, under a root directory. Symlinks are not followed
+pub fn find_fsevents(root: &str) -> Result<FsEventsSearch, FsEventsError> {
+    let root = Path::new(root);
+    if !root.is_dir() {
+        return Err(FsEventsError::NotADirectory(root.display().to_string()));
+    }
+
+    let mut search = FsEventsSearch::default();
+    let is_fseventsd = root
+        .file_name()
+        .is_some_and(|name| is_fseventsd_name(&name.to_string_lossy()));
+    if is_fseventsd {
+        search.directories.push(root.display().to_string());
+    } else {
+        search_directory(root, 0, &mut search);
+    }
+    search.directories.sort();
+    search.files.sort();
+    Ok(search)
+}
+
+/// Parse every .fseventsd directory and loose FsEvent file under a root directory, and report any
+/// files or directories that were skipped
+/// Each record is tagged with the volume its directory is in, relative to the root (ex: `/` or
+/// `/Volumes/USB`). Loose files are tagged with the directory they are in. Records are
+/// deduplicated and sorted by event ID
+pub fn parse_recursive_with_report(
+    root: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let search = find_fsevents(root)?;
+    let mut loose_files: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for file in search.files {
+        let directory = Path::new(&file)
+            .parent()
+            .unwrap_or(Path::new(root))
+            .display()
+            .to_string();
+        loose_files.entry(directory).or_default().push(file);
+    }
+
+    let mut report = ParseReport::default();
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    let groups = search
+        .directories
+        .iter()
+        .map(|directory| (directory, None))
+        .chain(
+            loose_files
+                .iter()
+                .map(|(directory, files)| (directory, Some(files))),
+        );
+    for (directory, files) in groups {
+        if options.is_cancelled() {
+            break;
+        }
+        let results = match files {
+            Some(files) => parse_file_list(directory, files, options, &mut report),
+            None => parse_directory(directory, options, &mut report),
+        };
+        match results {
+            Ok(data) => {
+                let volume = match files {
+                    Some(_) => Path::new(directory),
+                    None => Path::new(directory).parent().unwrap_or(Path::new(root)),
+                };
+                let tag = relative_tag(Path::new(root), volume);
+                for mut fsevent in data {
+                    fsevent.source_tag = tag.clone();
+                    fsevents_data.push(fsevent);
+                }
+            }
+            Err(err) if options.lenient => {
+                error!(
+                    "Failed to parse FsEvent directory {}, err: {}",
+                    directory, err
+                );
+                report.skip_directory(directory, err.to_string());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let duplicates = dedup_fsevents(&mut fsevents_data, DedupKey::Record);
+    if duplicates != 0 {
+        info!("Removed {} duplicate FsEvent records", duplicates);
+    }
+    sort_fsevents(&mut fsevents_data);
+    Ok((fsevents_data, report))
+}
+
+/// Get the path of a directory relative to the root, starting with `/`
+fn relative_tag(root: &Path, directory: &Path) -> String {
+    let relative: Vec<String> = directory
+        .strip_prefix(root)
+        .unwrap_or(directory)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    format!("/{}", relative.join("/"))
+}
+
+/// Parse (tag, directory) pairs, tagging each record with its directory tag
+/// Skipped files and directories are added to the report
+pub(crate) fn parse_tagged_directories(
+    directories: &[(String, String)],
+    options: &ParserOptions,
+    report: &mut ParseReport,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    for (tag, directory) in directories {
+        if options.is_cancelled() {
+            break;
+        }
+        let results = parse_directory(directory, options, report);
+        match results {
+            Ok(data) => {
+                for mut fsevent in data {
+                    fsevent.source_tag = tag.clone();
+                    fsevents_data.push(fsevent);
+                }
+            }
+            Err(err) if options.lenient => {
+                error!(
+                    "Failed to parse FsEvent directory {}, err: {}",
+                    directory, err
+                );
+                report.skip_directory(directory, err.to_string());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    sort_fsevents(&mut fsevents_data);
+    Ok(fsevents_data)
+}
+
+// Backups are nested a few directories deep (Backups.backupdb/<machine>/<snapshot>/<volume>)
+const MAX_SEARCH_DEPTH: usize = 6;
+
+/// Recursively find .fseventsd directories, symlinks are not followed (ex: the Latest snapshot link)
+pub(crate) fn find_fseventsd_directories(directory: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    if depth > MAX_SEARCH_DEPTH {
+        return;
+    }
+    let entries = match read_dir(directory) {
+        Ok(result) => result,
+        Err(err) => {
+            warn!(
+                "Failed to read directory {}, err: {}",
+                directory.display(),
+                err
+            );
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let is_dir = entry
+            .file_type()
+            .map(|file_type| file_type.is_dir())
+            .unwrap_or(false);
+        if !is_dir {
+            continue;
+        }
+        if is_fseventsd_name(&entry.file_name().to_string_lossy()) {
+            found.push(entry.path());
+            continue;
+        }
+        find_fseventsd_directories(&entry.path(), depth + 1, found);
+    }
+}
+
+// Mounted images and collections can keep volumes deep below the root
+const MAX_RECURSIVE_DEPTH: usize = 32;
+
+/// Recursively find .fseventsd directories and loose FsEvent files, symlinks are not followed
+fn search_directory(directory: &Path, depth: usize, search: &mut FsEventsSearch) {
+    if depth > MAX_RECURSIVE_DEPTH {
+        return;
+    }
+    let entries = match read_dir(directory) {
+        Ok(result) => result,
+        Err(err) => {
+            warn!(
+                "Failed to read directory {}, err: {}",
+                directory.display(),
+                err
+            );
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let file_type = match entry.file_type() {
+            Ok(result) => result,
+            Err(_) => continue,
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            if is_fseventsd_name(&entry.file_name().to_string_lossy()) {
+                search.directories.push(path.display().to_string());
+            } else {
+                search_directory(&path, depth + 1, search);
+            }
+        } else if file_type.is_file() && filename_event_id(&path.display().to_string()).is_some() {
+            search.files.push(path.display().to_string());
+        }
+    }
+}
+
+/// Check if a directory name is a Time Machine snapshot (ex: 2023-01-01-120000 or 2023-01-01-120000.backup)
+fn is_snapshot_name(name: &str) -> bool {
+    let name = name.strip_suffix(".backup").unwrap_or(name);
+    let dashes = [4, 7, 10];
+    name.len() == 17
+        && name.char_indices().all(|(index, c)| {
+            if dashes.contains(&index) {
+                c == '-'
+            } else {
+                c.is_ascii_digit()
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_fsevents, fseventsd_volumes, ios_domain, ios_fseventsd, is_snapshot_name,
+        parse_ios_extraction, parse_recursive_with_report, parse_time_machine,
+        time_machine_fseventsd,
+    };
+    use crate::{
+        error::FsEventsError,
+        options::ParserOptions,
+        test_util::{copy_test_data, test_data},
+    };
+    use std::{
+        fs::{copy, create_dir_all},
+        path::Path,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_fseventsd_volumes() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("volumes");
+
+        let fseventsd = test_location.join("USB/.fseventsd");
+        for file in ["DLS3/0000000000030012", "DLS3/fseventsd-uuid"] {
+            copy_test_data(file, &fseventsd);
+        }
+        create_dir_all(test_location.join("Empty")).unwrap();
+
+        let volumes = fseventsd_volumes(&test_location.display().to_string()).unwrap();
+        assert!(volumes.len() == 1);
+        assert!(volumes[0].name == "USB");
+        assert!(volumes[0].directory.ends_with("USB/.fseventsd"));
+        assert!(volumes[0].files.len() == 1);
+        assert!(volumes[0].files[0].ends_with("0000000000030012"));
+    }
+
+    #[test]
+    fn test_fseventsd_volumes_missing() {
+        let results = fseventsd_volumes("/this/path/does/not/exist");
+        assert!(matches!(results, Err(FsEventsError::NotADirectory(_))));
+    }
+
+    fn test_backup(test_location: &Path) {
+        for snapshot in [
+            "Backups.backupdb/MacBook/2023-01-01-120000/Macintosh HD - Data/.fseventsd",
+            "2023-02-01-120000.backup/2023-02-01-120000.backup/Data/.fseventsd",
+        ] {
+            copy_test_data("DLS3/0000000000030012", &test_location.join(snapshot));
+        }
+        create_dir_all(test_location.join("Other/.fseventsd")).unwrap();
+    }
+
+    #[test]
+    fn test_time_machine_fseventsd() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        test_backup(test_location);
+        let snapshots = time_machine_fseventsd(&test_location.display().to_string()).unwrap();
+        assert!(snapshots.len() == 2);
+        assert!(snapshots[0].snapshot == "2023-01-01-120000");
+        assert!(snapshots[0]
+            .directory
+            .ends_with("Macintosh HD - Data/.fseventsd"));
+        assert!(snapshots[1].snapshot == "2023-02-01-120000");
+    }
+
+    #[test]
+    fn test_parse_time_machine() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        test_backup(test_location);
+        let results =
+            parse_time_machine(&test_location.display().to_string(), &ParserOptions::new())
+                .unwrap();
+        assert!(results.len() == 10);
+        assert!(results[0].source_tag == "2023-01-01-120000");
+        assert!(results[1].source_tag == "2023-02-01-120000");
+    }
+
+    #[test]
+    fn test_is_snapshot_name() {
+        assert!(is_snapshot_name("2023-01-01-120000"));
+        assert!(is_snapshot_name("2023-01-01-120000.backup"));
+        assert!(!is_snapshot_name("Latest"));
+        assert!(!is_snapshot_name("2023-01-01"));
+    }
+
+    #[test]
+    fn test_parse_ios_extraction() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("ios_extraction");
+        for directory in [
+            "filesystem1/.fseventsd",
+            "filesystem1/private/var/.fseventsd",
+        ] {
+            copy_test_data("DLS3/0000000000030012", &test_location.join(directory));
+        }
+
+        let domains = ios_fseventsd(&test_location.display().to_string()).unwrap();
+        assert!(domains.len() == 2);
+        assert!(domains[0].domain == "System");
+        assert!(domains[1].domain == "Data");
+
+        let results =
+            parse_ios_extraction(&test_location.display().to_string(), &ParserOptions::new())
+                .unwrap();
+        assert!(results.len() == 10);
+        assert!(results[0].source_tag == "System");
+        assert!(results[1].source_tag == "Data");
+    }
+
+    #[test]
+    fn test_parse_recursive() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("recursive");
+        for directory in [".fseventsd", "Volumes/USB/.fseventsd", "cases/copied"] {
+            create_dir_all(test_location.join(directory)).unwrap();
+        }
+        let copies = [
+            ("DLS3/0000000000030012", ".fseventsd/0000000000030012"),
+            ("DLS3/fseventsd-uuid", ".fseventsd/fseventsd-uuid"),
+            (
+                "DLS2/000000000002469c",
+                "Volumes/USB/.fseventsd/000000000002469c",
+            ),
+            ("DLS2/0000000000027d79", "cases/copied/0000000000027d79"),
+            ("DLS3/fseventsd-uuid", "cases/copied/notes.txt"),
+        ];
+        for (from, to) in copies {
+            copy(test_data(from), test_location.join(to)).unwrap();
+        }
+
+        let root = test_location.display().to_string();
+        let search = find_fsevents(&root).unwrap();
+        assert!(search.directories.len() == 2);
+        assert!(search.directories[0].ends_with("recursive/.fseventsd"));
+        assert!(search.directories[1].ends_with("Volumes/USB/.fseventsd"));
+        assert!(search.files.len() == 1);
+        assert!(search.files[0].ends_with("cases/copied/0000000000027d79"));
+
+        let options = ParserOptions::new().lenient(true);
+        let (results, report) = parse_recursive_with_report(&root, &options).unwrap();
+        assert!(report.files_parsed == 3);
+        for tag in ["/", "/Volumes/USB", "/cases/copied"] {
+            assert!(results.iter().any(|fsevent| fsevent.source_tag == tag));
+        }
+        assert!(results
+            .windows(2)
+            .all(|pair| pair[0].event_id <= pair[1].event_id));
+    }
+
+    #[test]
+    fn test_parse_recursive_missing() {
+        let results =
+            parse_recursive_with_report("/this/path/does/not/exist", &ParserOptions::new());
+        assert!(matches!(results, Err(FsEventsError::NotADirectory(_))));
+    }
+
+    #[test]
+    fn test_ios_domain() {
+        assert!(ios_domain(Path::new(".fseventsd")) == "System");
+        assert!(ios_domain(Path::new("private/var/.fseventsd")) == "Data");
+        assert!(ios_domain(Path::new("root/private/var/mobile/.fseventsd")) == "Data/mobile");
+    }
+}
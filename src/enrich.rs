@@ -0,0 +1,398 @@
+//! Enrich parsed FsEvent records with additional data
+//!
+//! FsEvent records do not contain timestamps. The modified time of a FsEvent file is when the last
+//! records were written to it, and the modified time of the previous file is roughly when the
+//! file was started, so together they bound when the file's events occurred.
+//!
+//! File names are one more than the highest event ID in the file, so each file also has an event ID
+//! range. Assuming events happen at a steady rate, a record's time can be estimated by interpolating
+//! its event ID between the file's time bounds. The estimate is a window covering the interpolated
+//! times of the lowest and highest event IDs in the record's page.
+//!
+//! On the system being examined, each record's path can also be checked against the live
+//! filesystem to separate files that still exist from deleted ones.
+
+use crate::{fsevents::FsEvents, inventory::filename_event_id, parser::fseventsd};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{metadata, symlink_metadata},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct LiveStatus {
+    pub exists: bool,       // Path exists on the live filesystem
+    pub is_directory: bool, // Path is a directory
+    pub size: u64,          // Current size of the file
+    pub modified: i64,      // Current modified time (Unix epoch), 0 if unknown
+    pub accessed: i64,      // Current accessed time (Unix epoch), 0 if unknown
+    pub created: i64,       // Current created time (Unix epoch), 0 if unknown
+}
+
+/// Event ID range and time bounds of a FsEvent file, used to interpolate record times
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileSpan {
+    ids: (u64, u64),   // Event ID from the previous file's name and this file's name
+    times: (i64, i64), // Modified time of the previous file and this file
+}
+
+/// Add approximate earliest and latest times to records from the modified times of their source files
+pub fn add_approx_times(fsevents: &mut [FsEvents]) {
+    let mut bounds: HashMap<String, (i64, i64)> = HashMap::new();
+    for files in source_directory_files(fsevents) {
+        bounds.extend(file_time_bounds(&files));
+    }
+
+    for fsevent in fsevents.iter_mut() {
+        if let Some((earliest, latest)) = bounds.get(&fsevent.source_file) {
+            fsevent.approx_earliest_time = *earliest;
+            fsevent.approx_latest_time = *latest;
+        }
+    }
+}
+
+/// Add estimated time windows to records by interpolating their event IDs between the modified
+/// times of their source file and the previous file. Records in the first file of a directory, or
+/// in files without an event ID name, do not get an estimate
+pub fn add_estimated_times(fsevents: &mut [FsEvents]) {
+    let mut spans: HashMap<String, FileSpan> = HashMap::new();
+    for files in source_directory_files(fsevents) {
+        let times = files
+            .iter()
+            .map(|file| (file.clone(), modified_time(file)))
+            .collect();
+        spans.extend(file_spans(times));
+    }
+    apply_estimated_times(fsevents, &spans);
+}
+
+/// Add estimated time windows to records from the modified times of FsEvent files in the same directory
+#[cfg(feature = "apfs")]
+pub(crate) fn estimated_times(fsevents: &mut [FsEvents], files: Vec<(String, i64)>) {
+    apply_estimated_times(fsevents, &file_spans(files));
+}
+
+/// Set each record's time window from the interpolated times of the lowest and highest event IDs
+/// in its page
+fn apply_estimated_times(fsevents: &mut [FsEvents], spans: &HashMap<String, FileSpan>) {
+    // Lowest and highest event IDs in each page
+    let mut pages: HashMap<(&str, u32), (u64, u64)> = HashMap::new();
+    for fsevent in fsevents.iter() {
+        let bounds = pages
+            .entry((&fsevent.source_file, fsevent.page))
+            .or_insert((fsevent.event_id, fsevent.event_id));
+        bounds.0 = bounds.0.min(fsevent.event_id);
+        bounds.1 = bounds.1.max(fsevent.event_id);
+    }
+    let windows: HashMap<(String, u32), (i64, i64)> = pages
+        .into_iter()
+        .filter_map(|((file, page), (lowest, highest))| {
+            let span = spans.get(file)?;
+            let window = (interpolate(span, lowest)?, interpolate(span, highest)?);
+            Some(((file.to_string(), page), window))
+        })
+        .collect();
+
+    for fsevent in fsevents.iter_mut() {
+        if let Some((start, end)) = windows.get(&(fsevent.source_file.clone(), fsevent.page)) {
+            fsevent.est_time_start = *start;
+            fsevent.est_time_end = *end;
+        }
+    }
+}
+
+/// Check each record's path on the live filesystem, paths are joined to the volume root
+/// (ex: `/System/Volumes/Data` for the default FsEvents directory). Each unique path is only checked once
+/// Symlinks are not followed, so the status is for the link itself
+pub fn add_live_status(fsevents: &mut [FsEvents], root: &str) {
+    let mut statuses: HashMap<String, LiveStatus> = HashMap::new();
+    for fsevent in fsevents.iter_mut() {
+        let status = statuses
+            .entry(fsevent.path.clone())
+            .or_insert_with(|| live_status(root, &fsevent.path));
+        fsevent.live_status = Some(status.clone());
+    }
+}
+
+/// Get the current status of a record path under the volume root
+fn live_status(root: &str, path: &str) -> LiveStatus {
+    let full_path = Path::new(root).join(path.trim_start_matches('/'));
+    let meta = match symlink_metadata(full_path) {
+        Ok(result) => result,
+        Err(_) => return LiveStatus::default(),
+    };
+    LiveStatus {
+        exists: true,
+        is_directory: meta.is_dir(),
+        size: meta.len(),
+        modified: unix_time(meta.modified()),
+        accessed: unix_time(meta.accessed()),
+        created: unix_time(meta.created()),
+    }
+}
+
+/// Convert a file time to seconds since the Unix epoch, 0 if unknown
+fn unix_time(time: std::io::Result<SystemTime>) -> i64 {
+    time.ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// List the FsEvent files in each directory that contains a record's source file
+fn source_directory_files(fsevents: &[FsEvents]) -> Vec<Vec<String>> {
+    let mut directories: HashSet<String> = HashSet::new();
+    for fsevent in fsevents.iter() {
+        if let Some(parent) = Path::new(&fsevent.source_file).parent() {
+            directories.insert(parent.display().to_string());
+        }
+    }
+
+    let mut files = Vec::new();
+    for directory in directories {
+        match fseventsd(&directory) {
+            Ok(result) => files.push(result),
+            Err(err) => warn!(
+                "Failed to list FsEvent directory {}, err: {}",
+                directory, err
+            ),
+        }
+    }
+    files
+}
+
+/// Get the event ID range and time bounds for FsEvent files in the same directory
+/// Files without an event ID name or a modified time are skipped
+fn file_spans(files: Vec<(String, i64)>) -> HashMap<String, FileSpan> {
+    let mut files: Vec<(u64, String, i64)> = files
+        .into_iter()
+        .filter_map(|(file, time)| Some((filename_event_id(&file)?, file, time)))
+        .filter(|(_, _, time)| *time != 0)
+        .collect();
+    files.sort();
+
+    let mut spans = HashMap::new();
+    for pair in files.windows(2) {
+        let (start_id, _, start_time) = &pair[0];
+        let (end_id, file, end_time) = &pair[1];
+        let span = FileSpan {
+            ids: (*start_id, *end_id),
+            times: (*start_time, *end_time),
+        };
+        spans.insert(file.clone(), span);
+    }
+    spans
+}
+
+/// Estimate the time of an event ID, assuming events happened at a steady rate during the span
+fn interpolate(span: &FileSpan, event_id: u64) -> Option<i64> {
+    let (start_id, end_id) = span.ids;
+    let (start_time, end_time) = span.times;
+    if end_id <= start_id || end_time < start_time {
+        return None;
+    }
+    let event_id = event_id.clamp(start_id, end_id);
+    let fraction = (event_id - start_id) as f64 / (end_id - start_id) as f64;
+    Some(start_time + ((end_time - start_time) as f64 * fraction).round() as i64)
+}
+
+/// Get the (earliest, latest) time bounds for FsEvent files in the same directory
+/// File names are hex event IDs, so sorting by name puts the files in the order they were written
+pub fn file_time_bounds(files: &[String]) -> HashMap<String, (i64, i64)> {
+    let times = files
+        .iter()
+        .map(|file| (file.clone(), modified_time(file)))
+        .collect();
+    time_bounds(times)
+}
+
+/// Get the (earliest, latest) time bounds from the modified times of FsEvent files in the same directory
+pub(crate) fn time_bounds(mut files: Vec<(String, i64)>) -> HashMap<String, (i64, i64)> {
+    files.sort_by_key(|(file, _)| {
+        Path::new(file)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+
+    let mut bounds = HashMap::new();
+    let mut previous_time = 0;
+    for (file, latest) in files {
+        bounds.insert(file, (previous_time, latest));
+        if latest != 0 {
+            previous_time = latest;
+        }
+    }
+    bounds
+}
+
+/// Get the modified time of a file in seconds since the Unix epoch, 0 if unknown
+fn modified_time(path: &str) -> i64 {
+    let modified = metadata(path).and_then(|meta| meta.modified());
+    match modified {
+        Ok(time) => match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(_) => 0,
+        },
+        Err(err) => {
+            warn!("Failed to get modified time for {}, err: {:?}", path, err);
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add_approx_times, add_estimated_times, add_live_status, file_spans, file_time_bounds,
+        interpolate, modified_time, FileSpan,
+    };
+    use crate::{fsevents::FsEvents, test_util::copy_test_data};
+    use std::{
+        fs::{create_dir_all, File},
+        path::Path,
+        time::{Duration, UNIX_EPOCH},
+    };
+    use tempfile::tempdir;
+
+    fn test_directory(test_location: &Path) {
+        for (file, time) in [
+            ("DLS2/000000000002469c", 1000),
+            ("DLS2/0000000000027d79", 2000),
+        ] {
+            let target = copy_test_data(file, test_location);
+            let output = File::options().write(true).open(&target).unwrap();
+            output
+                .set_modified(UNIX_EPOCH + Duration::from_secs(time))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_file_time_bounds() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        test_directory(test_location);
+        let first = test_location.join("000000000002469c").display().to_string();
+        let second = test_location.join("0000000000027d79").display().to_string();
+
+        let bounds = file_time_bounds(&[second.clone(), first.clone()]);
+        assert!(bounds[&first] == (0, 1000));
+        assert!(bounds[&second] == (1000, 2000));
+    }
+
+    #[test]
+    fn test_add_approx_times() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        test_directory(test_location);
+        let mut fsevents = vec![FsEvents {
+            source_file: test_location.join("0000000000027d79").display().to_string(),
+            ..Default::default()
+        }];
+        add_approx_times(&mut fsevents);
+        assert!(fsevents[0].approx_earliest_time == 1000);
+        assert!(fsevents[0].approx_latest_time == 2000);
+    }
+
+    #[test]
+    fn test_file_spans() {
+        let spans = file_spans(vec![
+            (String::from("/.fseventsd/0000000000000064"), 1000),
+            (String::from("/.fseventsd/00000000000000c8"), 2000),
+            (String::from("/.fseventsd/fseventsd-uuid"), 3000),
+        ]);
+        assert!(spans.len() == 1);
+        let span = spans["/.fseventsd/00000000000000c8"];
+        assert!(span.ids == (100, 200));
+        assert!(span.times == (1000, 2000));
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let span = FileSpan {
+            ids: (100, 200),
+            times: (1000, 2000),
+        };
+        assert!(interpolate(&span, 150) == Some(1500));
+        assert!(interpolate(&span, 50) == Some(1000));
+        assert!(interpolate(&span, 199) == Some(1990));
+
+        let span = FileSpan {
+            ids: (100, 100),
+            times: (1000, 2000),
+        };
+        assert!(interpolate(&span, 100).is_none());
+    }
+
+    #[test]
+    fn test_add_estimated_times() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        test_directory(test_location);
+        let source_file = test_location.join("0000000000027d79").display().to_string();
+        let mut fsevents = vec![
+            FsEvents {
+                source_file: source_file.clone(),
+                event_id: 0x2469c + 3511,
+                ..Default::default()
+            },
+            FsEvents {
+                source_file: source_file.clone(),
+                event_id: 0x2469c + 7022,
+                ..Default::default()
+            },
+            FsEvents {
+                source_file: test_location.join("000000000002469c").display().to_string(),
+                event_id: 0x2469b,
+                ..Default::default()
+            },
+        ];
+        add_estimated_times(&mut fsevents);
+        assert!(fsevents[0].est_time_start == 1250);
+        assert!(fsevents[0].est_time_end == 1500);
+        assert!(fsevents[1].est_time_start == 1250);
+
+        // No earlier file to interpolate from
+        assert!(fsevents[2].est_time_start == 0);
+    }
+
+    #[test]
+    fn test_add_live_status() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("live_status");
+        create_dir_all(test_location.join("Users/bob")).unwrap();
+        std::fs::write(test_location.join("Users/bob/invoice.pdf"), b"invoice").unwrap();
+
+        let mut fsevents = vec![
+            FsEvents {
+                path: String::from("/Users/bob/invoice.pdf"),
+                ..Default::default()
+            },
+            FsEvents {
+                path: String::from("/Users/bob/deleted.pdf"),
+                ..Default::default()
+            },
+            FsEvents {
+                path: String::from("/Users/bob"),
+                ..Default::default()
+            },
+        ];
+        add_live_status(&mut fsevents, &test_location.display().to_string());
+
+        let status = fsevents[0].live_status.as_ref().unwrap();
+        assert!(status.exists && !status.is_directory);
+        assert!(status.size == 7);
+        assert!(status.modified != 0);
+        assert!(!fsevents[1].live_status.as_ref().unwrap().exists);
+        assert!(fsevents[2].live_status.as_ref().unwrap().is_directory);
+    }
+
+    #[test]
+    fn test_modified_time_missing() {
+        assert!(modified_time("/this/path/does/not/exist") == 0);
+    }
+}
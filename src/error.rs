@@ -0,0 +1,109 @@
+//! Errors returned when reading and parsing FsEvent data
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum FsEventsError {
+    Io(std::io::Error),         // Failed to read a FsEvent file or directory
+    Decompress(std::io::Error), // Failed to decompress gzip data
+    NotAFile(String),           // Provided path is not a file
+    FileTooLarge(String, u64),  // FsEvent file is not smaller than the max file size
+    NotADirectory(String),      // Provided path is not a directory
+    InvalidHeader,              // FsEvent page header is malformed
+    TruncatedRecord,            // FsEvent page or record ended before all data could be read
+    UnsupportedVersion(u32),    // FsEvent page has a DLS signature with an unknown version
+    InvalidFlag(String),        // Unknown FsEvent flag name
+    InvalidUuid(String),        // fseventsd-uuid file does not contain a UUID
+    InvalidPattern(String),     // Path filter pattern could not be compiled
+    InvalidImage(String),       // Evidence image is malformed or not supported
+    Output(String),             // Failed to convert or write records to an output format
+    InvalidCollection(String),  // Collection zip is malformed or not supported
+    Extension(String),          // osquery extension failed to register or serve a call
+    Watch(String),              // FSEvents API stream could not be created or started
+    InvalidTimeFormat(String),  // Unknown time zone or invalid time format pattern
+    Checkpoint(String),         // Checkpoint file is malformed or does not match the output
+    InvalidBaseline(String),    // Baseline file has an entry that could not be read
+}
+
+impl fmt::Display for FsEventsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsEventsError::Io(err) => write!(f, "Failed to read FsEvent data: {}", err),
+            FsEventsError::Decompress(err) => {
+                write!(f, "Failed to decompress FsEvent data: {}", err)
+            }
+            FsEventsError::NotAFile(path) => write!(f, "Not a file: {}", path),
+            FsEventsError::FileTooLarge(path, size) => {
+                write!(f, "File size exceeds {} bytes: {}", size, path)
+            }
+            FsEventsError::NotADirectory(path) => write!(f, "Not a directory: {}", path),
+            FsEventsError::InvalidHeader => write!(f, "Invalid FsEvent page header"),
+            FsEventsError::TruncatedRecord => write!(f, "Truncated FsEvent page or record"),
+            FsEventsError::UnsupportedVersion(sig) => {
+                write!(f, "Unsupported FsEvent version: {:#x}", sig)
+            }
+            FsEventsError::InvalidFlag(name) => write!(f, "Unknown FsEvent flag: {}", name),
+            FsEventsError::InvalidUuid(uuid) => write!(f, "Invalid fseventsd UUID: {}", uuid),
+            FsEventsError::InvalidPattern(err) => write!(f, "Invalid path pattern: {}", err),
+            FsEventsError::InvalidImage(err) => write!(f, "Invalid evidence image: {}", err),
+            FsEventsError::Output(err) => write!(f, "Failed to output FsEvent records: {}", err),
+            FsEventsError::InvalidCollection(err) => write!(f, "Invalid collection: {}", err),
+            FsEventsError::Extension(err) => write!(f, "osquery extension failed: {}", err),
+            FsEventsError::Watch(err) => write!(f, "Failed to watch live events: {}", err),
+            FsEventsError::InvalidTimeFormat(err) => write!(f, "Invalid time format: {}", err),
+            FsEventsError::Checkpoint(err) => write!(f, "Invalid checkpoint: {}", err),
+            FsEventsError::InvalidBaseline(err) => write!(f, "Invalid baseline: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for FsEventsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FsEventsError::Io(err) | FsEventsError::Decompress(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FsEventsError {
+    fn from(err: std::io::Error) -> Self {
+        FsEventsError::Io(err)
+    }
+}
+
+/// Any nom failure while parsing FsEvent records means the data ended early
+impl<I> From<nom::Err<nom::error::Error<I>>> for FsEventsError {
+    fn from(_err: nom::Err<nom::error::Error<I>>) -> Self {
+        FsEventsError::TruncatedRecord
+    }
+}
+
+#[cfg(feature = "osquery")]
+/// Thrift errors only come from talking to osquery
+impl From<thrift::Error> for FsEventsError {
+    fn from(err: thrift::Error) -> Self {
+        FsEventsError::Extension(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FsEventsError;
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn test_error_display() {
+        let err = FsEventsError::UnsupportedVersion(0x444c5334);
+        assert!(err.to_string() == "Unsupported FsEvent version: 0x444c5334");
+
+        let err = FsEventsError::NotADirectory(String::from("/tmp/missing"));
+        assert!(err.to_string() == "Not a directory: /tmp/missing");
+    }
+
+    #[test]
+    fn test_error_from_io() {
+        let err: FsEventsError = Error::new(ErrorKind::NotFound, "missing").into();
+        assert!(matches!(err, FsEventsError::Io(_)));
+    }
+}
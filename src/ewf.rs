@@ -0,0 +1,649 @@
+//! Read EWF (EnCase E01) evidence images
+//!
+//! A pure Rust reader for EWF-E01 images, so records can be carved from an evidence image without
+//! converting it to a raw image first. Segment files (image.E01, image.E02, ...) are opened together
+//! and the media data is decompressed one chunk at a time as it is read. The Adler-32 checksums of
+//! the section descriptors, volume, tables, and chunks are verified, and data that does not match
+//! is rejected instead of being carved.
+
+use crate::{error::FsEventsError, image::EWF_SIGNATURE};
+use flate2::read::ZlibDecoder;
+use log::{debug, warn};
+use std::{
+    fs::File,
+    io::{Error, ErrorKind, Read, Result as IoResult, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+const FILE_HEADER_SIZE: usize = 13;
+const SECTION_SIZE: usize = 76;
+// Volume fields that are read, the full volume is 1052 bytes in E01 images and 94 in S01 images
+const VOLUME_SIZE: usize = 24;
+const MAX_VOLUME_SIZE: u64 = 1052;
+const TABLE_HEADER_SIZE: usize = 24;
+const CHECKSUM_SIZE: usize = 4;
+// Table entries with the high bit set point to zlib compressed chunks
+const COMPRESSED_CHUNK: u32 = 0x80000000;
+// Largest chunk size that will be accepted, EnCase uses 32KB chunks
+const MAX_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Seekable reader over the media data stored in an EWF image
+#[derive(Debug)]
+pub struct EwfReader {
+    segments: Vec<File>,             // Open segment files, in order
+    chunks: Vec<Chunk>,              // Location of every chunk in the segment files
+    chunk_size: u64,                 // Size of a decompressed chunk
+    media_size: u64,                 // Size of the media data
+    position: u64,                   // Current position in the media data
+    cache: Option<(usize, Vec<u8>)>, // Last chunk that was read
+}
+
+#[derive(Debug, Clone)]
+struct Chunk {
+    segment: usize,   // Index of the segment file containing the chunk
+    offset: u64,      // Offset to the chunk in the segment file
+    size: u64,        // Size of the stored chunk
+    compressed: bool, // Chunk is zlib compressed
+}
+
+impl EwfReader {
+    /// Open an EWF image using the path to the first segment file (ex: image.E01)
+    /// Following segment files are found by their extension (E02, E03, ... E99, EAA, ...)
+    pub fn open(path: &str) -> Result<EwfReader, FsEventsError> {
+        let mut reader = EwfReader {
+            segments: Vec::new(),
+            chunks: Vec::new(),
+            chunk_size: 0,
+            media_size: 0,
+            position: 0,
+            cache: None,
+        };
+
+        let first = Path::new(path);
+        let mut segment_path = first.to_path_buf();
+        for number in 2.. {
+            let file = File::open(&segment_path)?;
+            reader.read_segment(file, &segment_path.display().to_string())?;
+
+            segment_path = match next_segment(first, number) {
+                Some(next) if next.is_file() => next,
+                _ => break,
+            };
+        }
+
+        if reader.chunk_size == 0 {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} has no volume section",
+                path
+            )));
+        }
+        if reader.media_size == 0 {
+            reader.media_size = reader.chunks.len() as u64 * reader.chunk_size;
+        }
+        debug!(
+            "Opened EWF image {} with {} segments and {} chunks",
+            path,
+            reader.segments.len(),
+            reader.chunks.len()
+        );
+        Ok(reader)
+    }
+
+    /// Size of the media data in the image
+    pub fn media_size(&self) -> u64 {
+        self.media_size
+    }
+
+    /// Read the sections of a segment file and add its chunks
+    fn read_segment(&mut self, mut file: File, path: &str) -> Result<(), FsEventsError> {
+        let mut header = [0; FILE_HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        if header[..EWF_SIGNATURE.len()] != EWF_SIGNATURE {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} is not an EWF segment file",
+                path
+            )));
+        }
+
+        let segment = self.segments.len();
+        let mut offset = FILE_HEADER_SIZE as u64;
+        // End of the last sectors section, chunks in the following table end there
+        let mut sectors_end = None;
+        loop {
+            let mut section = [0; SECTION_SIZE];
+            file.seek(SeekFrom::Start(offset))?;
+            if let Err(err) = file.read_exact(&mut section) {
+                warn!("EWF segment {} ended early, err: {}", path, err);
+                break;
+            }
+            let section_type = section_type(&section);
+            if !is_valid_checksum(&section) {
+                return Err(FsEventsError::InvalidImage(format!(
+                    "{} has a {} section at {:#x} with an invalid checksum",
+                    path, section_type, offset
+                )));
+            }
+            let next = read_u64(&section, 16);
+            let size = read_u64(&section, 24);
+            let data_size = size.saturating_sub(SECTION_SIZE as u64);
+
+            match section_type.as_str() {
+                "volume" | "disk" if self.chunk_size == 0 => {
+                    self.read_volume(&mut file, path, data_size)?;
+                }
+                "sectors" => sectors_end = Some(offset + size),
+                "table" => {
+                    let table_end = sectors_end.unwrap_or(offset + size);
+                    self.read_table(&mut file, path, segment, table_end, data_size)?;
+                }
+                "next" | "done" => break,
+                _ => {}
+            }
+
+            if next <= offset {
+                break;
+            }
+            offset = next;
+        }
+        self.segments.push(file);
+        Ok(())
+    }
+
+    /// Read the chunk and media sizes from the volume section
+    fn read_volume(
+        &mut self,
+        file: &mut File,
+        path: &str,
+        data_size: u64,
+    ) -> Result<(), FsEventsError> {
+        let volume_size = data_size.min(MAX_VOLUME_SIZE) as usize;
+        if volume_size < VOLUME_SIZE + CHECKSUM_SIZE {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} has a volume section of only {} bytes",
+                path, data_size
+            )));
+        }
+        let mut volume = vec![0; volume_size];
+        file.read_exact(&mut volume)?;
+        if !is_valid_checksum(&volume) {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} has a volume section with an invalid checksum",
+                path
+            )));
+        }
+        let sectors_per_chunk = read_u32(&volume, 8) as u64;
+        let bytes_per_sector = read_u32(&volume, 12) as u64;
+        let sector_count = read_u64(&volume, 16);
+
+        self.chunk_size = sectors_per_chunk * bytes_per_sector;
+        if self.chunk_size == 0 || self.chunk_size > MAX_CHUNK_SIZE {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} has an invalid chunk size: {}",
+                path, self.chunk_size
+            )));
+        }
+        self.media_size = sector_count.saturating_mul(bytes_per_sector);
+        Ok(())
+    }
+
+    /// Read the chunk offsets from a table section, the last chunk ends at the table end
+    fn read_table(
+        &mut self,
+        file: &mut File,
+        path: &str,
+        segment: usize,
+        table_end: u64,
+        data_size: u64,
+    ) -> Result<(), FsEventsError> {
+        if self.chunk_size == 0 {
+            return Err(FsEventsError::InvalidImage(String::from(
+                "Table section found before the volume section",
+            )));
+        }
+        let mut header = [0; TABLE_HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        if !is_valid_checksum(&header) {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} has a table header with an invalid checksum",
+                path
+            )));
+        }
+        let count = read_u32(&header, 0) as usize;
+        let base_offset = read_u64(&header, 8);
+
+        // The entries are followed by their checksum, except in images from EnCase 1
+        let entries_size = count as u64 * 4;
+        let has_checksum = data_size >= (TABLE_HEADER_SIZE + CHECKSUM_SIZE) as u64 + entries_size;
+        let mut entries = Vec::new();
+        file.by_ref()
+            .take(
+                entries_size
+                    + if has_checksum {
+                        CHECKSUM_SIZE as u64
+                    } else {
+                        0
+                    },
+            )
+            .read_to_end(&mut entries)?;
+        let expected_size = count * 4 + if has_checksum { CHECKSUM_SIZE } else { 0 };
+        if entries.len() != expected_size {
+            return Err(FsEventsError::InvalidImage(format!(
+                "Table has {} entries but only {} bytes",
+                count,
+                entries.len()
+            )));
+        }
+        if has_checksum {
+            if !is_valid_checksum(&entries) {
+                return Err(FsEventsError::InvalidImage(format!(
+                    "{} has table entries with an invalid checksum",
+                    path
+                )));
+            }
+            entries.truncate(count * 4);
+        }
+
+        let offsets: Vec<u32> = entries
+            .chunks_exact(4)
+            .map(|entry| read_u32(entry, 0))
+            .collect();
+        for (index, entry) in offsets.iter().enumerate() {
+            let offset = base_offset + (entry & !COMPRESSED_CHUNK) as u64;
+            let end = offsets
+                .get(index + 1)
+                .map(|next| base_offset + (next & !COMPRESSED_CHUNK) as u64)
+                .unwrap_or(table_end);
+            // Compressed chunks can be slightly larger than the chunk size
+            if end <= offset || end - offset > self.chunk_size * 2 {
+                return Err(FsEventsError::InvalidImage(format!(
+                    "Chunk {} has an invalid offset: {:#x}",
+                    self.chunks.len(),
+                    offset
+                )));
+            }
+            self.chunks.push(Chunk {
+                segment,
+                offset,
+                size: end - offset,
+                compressed: entry & COMPRESSED_CHUNK != 0,
+            });
+        }
+        Ok(())
+    }
+
+    /// Read and decompress a chunk, the last chunk read is cached
+    fn load_chunk(&mut self, index: usize) -> IoResult<&[u8]> {
+        if !matches!(&self.cache, Some((cached, _)) if *cached == index) {
+            let chunk = self.chunks.get(index).cloned().ok_or_else(|| {
+                Error::new(ErrorKind::UnexpectedEof, format!("Missing chunk {}", index))
+            })?;
+            let file = &mut self.segments[chunk.segment];
+            file.seek(SeekFrom::Start(chunk.offset))?;
+            let mut stored = vec![0; chunk.size as usize];
+            file.read_exact(&mut stored)?;
+
+            let data = if chunk.compressed {
+                let mut data = Vec::with_capacity(self.chunk_size as usize);
+                // Read past the chunk size so the decoder reaches the checksum at the end
+                ZlibDecoder::new(&stored[..])
+                    .take(self.chunk_size + 1)
+                    .read_to_end(&mut data)?;
+                if data.len() as u64 > self.chunk_size {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Chunk {} is larger than the chunk size", index),
+                    ));
+                }
+                data
+            } else {
+                // Uncompressed chunks are followed by a checksum, the zlib data of compressed
+                // chunks has its own checksum that is checked while decompressing
+                stored.truncate(self.chunk_size as usize + CHECKSUM_SIZE);
+                if !is_valid_checksum(&stored) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Chunk {} has an invalid checksum", index),
+                    ));
+                }
+                stored.truncate(stored.len() - CHECKSUM_SIZE);
+                stored
+            };
+            self.cache = Some((index, data));
+        }
+        Ok(self
+            .cache
+            .as_ref()
+            .map(|(_, data)| data.as_slice())
+            .unwrap_or_default())
+    }
+}
+
+impl Read for EwfReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if buf.is_empty() || self.position >= self.media_size {
+            return Ok(0);
+        }
+        let remaining = self.media_size - self.position;
+        let index = (self.position / self.chunk_size) as usize;
+        let chunk_offset = (self.position % self.chunk_size) as usize;
+
+        let data = self.load_chunk(index)?;
+        if chunk_offset >= data.len() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Chunk {} is smaller than the chunk size", index),
+            ));
+        }
+        let size = buf
+            .len()
+            .min(data.len() - chunk_offset)
+            .min(remaining as usize);
+        buf[..size].copy_from_slice(&data[chunk_offset..chunk_offset + size]);
+        self.position += size as u64;
+        Ok(size)
+    }
+}
+
+impl Seek for EwfReader {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.media_size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "Seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// Get the path to a segment file. Segments 2-99 use E02-E99, then EAA, EAB, ... EZZ, FAA, ...
+fn next_segment(first: &Path, number: u32) -> Option<PathBuf> {
+    let extension = first.extension()?.to_str()?;
+    let letter = extension.chars().next()?;
+    if extension.len() != 3 || !letter.is_ascii_alphabetic() {
+        return None;
+    }
+
+    let next = if number < 100 {
+        format!("{}{:02}", letter, number)
+    } else {
+        let index = number - 100;
+        let base = if letter.is_ascii_lowercase() {
+            b'a'
+        } else {
+            b'A'
+        };
+        let first_letter = letter as u32 + index / (26 * 26);
+        let letters = [
+            char::from_u32(first_letter)?,
+            (base + (index / 26 % 26) as u8) as char,
+            (base + (index % 26) as u8) as char,
+        ];
+        letters.iter().collect()
+    };
+    Some(first.with_extension(next))
+}
+
+/// Check the Adler-32 checksum at the end of the data
+fn is_valid_checksum(data: &[u8]) -> bool {
+    if data.len() < CHECKSUM_SIZE {
+        return false;
+    }
+    let (data, checksum) = data.split_at(data.len() - CHECKSUM_SIZE);
+    read_u32(checksum, 0) == adler32(data)
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let modulus = 65521;
+    let mut first = 1;
+    let mut second = 0;
+    for value in data {
+        first = (first + *value as u32) % modulus;
+        second = (second + first) % modulus;
+    }
+    (second << 16) | first
+}
+
+/// Get the section type, stored as a null padded string
+fn section_type(section: &[u8]) -> String {
+    let name = &section[..16];
+    let end = name.iter().position(|value| *value == 0).unwrap_or(16);
+    String::from_utf8_lossy(&name[..end]).to_lowercase()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, next_segment, EwfReader, SECTION_SIZE};
+    use crate::{
+        carve::carve_image, error::FsEventsError, image::EWF_SIGNATURE, parser::decompress,
+        test_util::test_data,
+    };
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::{
+        fs,
+        io::{Read, Seek, SeekFrom, Write},
+        path::Path,
+    };
+    use tempfile::tempdir;
+
+    fn add_section(segment: &mut Vec<u8>, name: &str, body: &[u8], last: bool) {
+        let offset = segment.len() as u64;
+        let size = (SECTION_SIZE + body.len()) as u64;
+        let mut section_type = [0; 16];
+        section_type[..name.len()].copy_from_slice(name.as_bytes());
+
+        segment.extend_from_slice(&section_type);
+        let next = if last { offset } else { offset + size };
+        segment.extend_from_slice(&next.to_le_bytes());
+        segment.extend_from_slice(&size.to_le_bytes());
+        segment.extend_from_slice(&[0; 40]);
+        let checksum = adler32(&segment[offset as usize..]);
+        segment.extend_from_slice(&checksum.to_le_bytes());
+        segment.extend_from_slice(body);
+    }
+
+    /// Add the Adler-32 checksum of the data to its end
+    fn add_checksum(data: &mut Vec<u8>) {
+        let checksum = adler32(data);
+        data.extend_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// Write an EWF image, every other chunk is compressed
+    fn write_image(path: &Path, data: &[u8], chunks_per_segment: usize) {
+        let chunk_size = 1024;
+        let chunks: Vec<&[u8]> = data.chunks(chunk_size).collect();
+        let segments: Vec<&[&[u8]]> = chunks.chunks(chunks_per_segment).collect();
+
+        for (index, segment_chunks) in segments.iter().enumerate() {
+            let mut segment = EWF_SIGNATURE.to_vec();
+            segment.push(1);
+            segment.extend_from_slice(&(index as u16 + 1).to_le_bytes());
+            segment.extend_from_slice(&[0, 0]);
+
+            if index == 0 {
+                let mut volume = vec![1, 0, 0, 0];
+                volume.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+                volume.extend_from_slice(&2u32.to_le_bytes());
+                volume.extend_from_slice(&512u32.to_le_bytes());
+                volume.extend_from_slice(&(data.len() as u64 / 512).to_le_bytes());
+                volume.resize(1048, 0);
+                add_checksum(&mut volume);
+                add_section(&mut segment, "volume", &volume, false);
+            }
+
+            let sectors_start = segment.len() + SECTION_SIZE;
+            let mut sectors = Vec::new();
+            let mut table = Vec::new();
+            for (chunk_index, chunk) in segment_chunks.iter().enumerate() {
+                let mut entry = (sectors_start + sectors.len()) as u32;
+                if chunk_index % 2 == 0 {
+                    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(chunk).unwrap();
+                    sectors.extend_from_slice(&encoder.finish().unwrap());
+                    entry |= 0x80000000;
+                } else {
+                    sectors.extend_from_slice(chunk);
+                    sectors.extend_from_slice(&adler32(chunk).to_le_bytes());
+                }
+                table.extend_from_slice(&entry.to_le_bytes());
+            }
+            let mut table_header = (segment_chunks.len() as u32).to_le_bytes().to_vec();
+            table_header.resize(20, 0);
+            add_checksum(&mut table_header);
+            add_checksum(&mut table);
+            table_header.extend_from_slice(&table);
+
+            add_section(&mut segment, "sectors", &sectors, false);
+            add_section(&mut segment, "table", &table_header, false);
+            let last = if index + 1 == segments.len() {
+                "done"
+            } else {
+                "next"
+            };
+            add_section(&mut segment, last, &[], true);
+
+            let segment_path = match index {
+                0 => path.to_path_buf(),
+                _ => next_segment(path, index as u32 + 1).unwrap(),
+            };
+            fs::write(segment_path, segment).unwrap();
+        }
+    }
+
+    fn media_data() -> Vec<u8> {
+        (0..5120).map(|value| (value % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_ewf_reader() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("ewf_reader.E01");
+        let data = media_data();
+        write_image(&test_location, &data, 2);
+
+        let mut reader = EwfReader::open(&test_location.display().to_string()).unwrap();
+        assert!(reader.media_size() == 5120);
+        assert!(reader.segments.len() == 3);
+        assert!(reader.chunks.len() == 5);
+
+        let mut results = Vec::new();
+        reader.read_to_end(&mut results).unwrap();
+        assert!(results == data);
+
+        // Read across a chunk and segment boundary
+        reader.seek(SeekFrom::Start(2000)).unwrap();
+        let mut buffer = vec![0; 200];
+        reader.read_exact(&mut buffer).unwrap();
+        assert!(buffer == data[2000..2200]);
+
+        assert!(reader.seek(SeekFrom::End(-20)).unwrap() == 5100);
+        assert!(reader.seek(SeekFrom::Current(-6000)).is_err());
+    }
+
+    #[test]
+    fn test_ewf_reader_invalid() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("ewf_invalid.E01");
+        fs::write(&test_location, b"not an ewf image").unwrap();
+        assert!(EwfReader::open(&test_location.display().to_string()).is_err());
+    }
+
+    #[test]
+    fn test_carve_ewf_image() {
+        let test_location = test_data("DLS3/0000000000030012");
+        let mut data = vec![0; 1024];
+        data.extend_from_slice(&decompress(&test_location.display().to_string()).unwrap());
+        data.resize(3072, 0);
+
+        let temp = tempdir().unwrap();
+        let image_location = temp.path().join("carve_ewf.E01");
+        write_image(&image_location, &data, 1);
+
+        let path = image_location.display().to_string();
+        let results = carve_image(&path).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].offset == 1024 + 12);
+        assert!(results[0].source_file == path);
+    }
+
+    #[test]
+    fn test_ewf_reader_image() {
+        // EnCase 6 layout with header, hash, and table2 sections. The first chunk is compressed,
+        // the second chunk does not compress and is stored with a checksum
+        let test_location = test_data("EWF/fsevents.E01");
+        let mut reader = EwfReader::open(&test_location.display().to_string()).unwrap();
+        assert!(reader.media_size() == 65536);
+        assert!(reader.chunks.len() == 2);
+        assert!(reader.chunks[0].compressed && !reader.chunks[1].compressed);
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        let fsevents = fs::read(test_data("DLS3/0000000000030012")).unwrap();
+        assert!(data[4096..4096 + fsevents.len()] == fsevents);
+
+        let path = test_location.display().to_string();
+        let results = carve_image(&path).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].source_file == format!("{}@0x1000", path));
+    }
+
+    #[test]
+    fn test_ewf_reader_checksums() {
+        let fixture = test_data("EWF/fsevents.E01");
+        let image = fs::read(&fixture).unwrap();
+        let reader = EwfReader::open(&fixture.display().to_string()).unwrap();
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("checksums.E01");
+        let path = test_location.display().to_string();
+
+        // Changed section descriptor
+        let mut damaged = image.clone();
+        damaged[13 + 40] ^= 0xff;
+        fs::write(&test_location, &damaged).unwrap();
+        assert!(matches!(
+            EwfReader::open(&path),
+            Err(FsEventsError::InvalidImage(_))
+        ));
+
+        // Changed checksum at the end of each chunk
+        for (index, chunk) in reader.chunks.iter().enumerate() {
+            let mut damaged = image.clone();
+            damaged[(chunk.offset + chunk.size) as usize - 2] ^= 0xff;
+            fs::write(&test_location, &damaged).unwrap();
+            let mut reader = EwfReader::open(&path).unwrap();
+            reader
+                .seek(SeekFrom::Start(index as u64 * reader.chunk_size))
+                .unwrap();
+            let mut buffer = [0; 16];
+            assert!(reader.read_exact(&mut buffer).is_err());
+        }
+    }
+
+    #[test]
+    fn test_next_segment() {
+        let first = Path::new("/cases/image.E01");
+        assert!(next_segment(first, 2).unwrap() == Path::new("/cases/image.E02"));
+        assert!(next_segment(first, 99).unwrap() == Path::new("/cases/image.E99"));
+        assert!(next_segment(first, 100).unwrap() == Path::new("/cases/image.EAA"));
+        assert!(next_segment(first, 127).unwrap() == Path::new("/cases/image.EBB"));
+        assert!(next_segment(Path::new("image.e01"), 100).unwrap() == Path::new("image.eaa"));
+        assert!(next_segment(Path::new("image.dd"), 2).is_none());
+    }
+}
@@ -0,0 +1,184 @@
+//! C interface to the parser, for embedding in C, C++, and Swift tools
+//!
+//! Parse functions return the records as a null terminated JSON array, or null if parsing failed.
+//! The reason for the last failure on the calling thread is returned by `fsevents_last_error`.
+//! Every buffer returned by a parse function must be released with `fsevents_free_buffer`.
+//! The header for these functions is `include/macos_fseventsd.h`, generated by cbindgen in build.rs.
+
+use crate::{
+    error::FsEventsError,
+    fsevents::FsEvents,
+    options::ParserOptions,
+    parser::{parse_directory_with_options, parse_file_with_options},
+};
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr::null_mut,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Parse a FsEvent file and return its records as a JSON array
+/// Returns null on failure, see `fsevents_last_error`
+///
+/// # Safety
+/// `path` must be a valid null terminated string
+#[no_mangle]
+pub unsafe extern "C" fn fsevents_parse_file(path: *const c_char) -> *mut c_char {
+    parse_to_json(path, |file| {
+        parse_file_with_options(file, &ParserOptions::new())
+    })
+}
+
+/// Parse every FsEvent file in a directory and return the records as a JSON array
+/// Returns null on failure, see `fsevents_last_error`
+///
+/// # Safety
+/// `path` must be a valid null terminated string
+#[no_mangle]
+pub unsafe extern "C" fn fsevents_parse_directory(path: *const c_char) -> *mut c_char {
+    parse_to_json(path, |directory| {
+        parse_directory_with_options(directory, &ParserOptions::new())
+    })
+}
+
+/// Release a buffer returned by a parse function. Null is ignored
+///
+/// # Safety
+/// `buffer` must be returned by a parse function and not already released
+#[no_mangle]
+pub unsafe extern "C" fn fsevents_free_buffer(buffer: *mut c_char) {
+    if buffer.is_null() {
+        return;
+    }
+    drop(CString::from_raw(buffer));
+}
+
+/// Get the reason the last parse function failed on this thread. Empty if none failed
+/// The string is owned by the library and is valid until the next parse function call
+#[no_mangle]
+pub extern "C" fn fsevents_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| error.borrow().as_ptr())
+}
+
+/// Run a parser on a C string path and convert the records to a JSON buffer
+unsafe fn parse_to_json<F>(path: *const c_char, parse: F) -> *mut c_char
+where
+    F: Fn(&str) -> Result<Vec<FsEvents>, FsEventsError>,
+{
+    if path.is_null() {
+        set_last_error("Path is null");
+        return null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(result) => result.to_string(),
+        Err(err) => {
+            set_last_error(&format!("Path is not valid UTF-8: {}", err));
+            return null_mut();
+        }
+    };
+
+    // Panics cannot unwind into C, report them as errors instead. Nothing is shared with the
+    // caller, so a panic cannot leave its data in a broken state
+    let results = match catch_unwind(AssertUnwindSafe(|| parse(&path))) {
+        Ok(result) => result,
+        Err(_) => {
+            set_last_error(&format!("Parser panicked while parsing {}", path));
+            return null_mut();
+        }
+    };
+    let fsevents_data = match results {
+        Ok(result) => result,
+        Err(err) => {
+            set_last_error(&err.to_string());
+            return null_mut();
+        }
+    };
+    // serde_json escapes null characters in paths, so the JSON never contains one
+    let json = match serde_json::to_string(&fsevents_data) {
+        Ok(result) => result,
+        Err(err) => {
+            set_last_error(&format!("Failed to serialize records: {}", err));
+            return null_mut();
+        }
+    };
+    set_last_error("");
+    match CString::new(json) {
+        Ok(buffer) => buffer.into_raw(),
+        Err(err) => {
+            set_last_error(&format!("Failed to create JSON buffer: {}", err));
+            null_mut()
+        }
+    }
+}
+
+/// Save the reason for a failure so it can be read with `fsevents_last_error`
+fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        fsevents_free_buffer, fsevents_last_error, fsevents_parse_directory, fsevents_parse_file,
+    };
+    use std::{
+        ffi::{CStr, CString},
+        path::PathBuf,
+        ptr::null,
+    };
+
+    #[test]
+    fn test_fsevents_parse_file() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let path = CString::new(test_location.display().to_string()).unwrap();
+
+        unsafe {
+            let buffer = fsevents_parse_file(path.as_ptr());
+            assert!(!buffer.is_null());
+            let json = CStr::from_ptr(buffer).to_str().unwrap();
+            let records: Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
+            assert!(records.len() == 5);
+            assert!(records[0]["source_file"] == test_location.display().to_string());
+            fsevents_free_buffer(buffer);
+        }
+    }
+
+    #[test]
+    fn test_fsevents_parse_directory() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let path = CString::new(test_location.display().to_string()).unwrap();
+
+        unsafe {
+            let buffer = fsevents_parse_directory(path.as_ptr());
+            assert!(!buffer.is_null());
+            let json = CStr::from_ptr(buffer).to_str().unwrap();
+            let records: Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
+            assert!(records.len() == 5736);
+            fsevents_free_buffer(buffer);
+        }
+    }
+
+    #[test]
+    fn test_fsevents_parse_errors() {
+        let path = CString::new("/tmp/fsevents_test_missing_file").unwrap();
+        unsafe {
+            assert!(fsevents_parse_file(path.as_ptr()).is_null());
+            let error = CStr::from_ptr(fsevents_last_error()).to_str().unwrap();
+            assert!(error == "Not a file: /tmp/fsevents_test_missing_file");
+
+            assert!(fsevents_parse_directory(null()).is_null());
+            let error = CStr::from_ptr(fsevents_last_error()).to_str().unwrap();
+            assert!(error == "Path is null");
+
+            fsevents_free_buffer(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Typed FsEvent record flags
+//!
+//! Flag values and names follow the FsEvents documentation referenced in `fsevents.rs`.
+
+use crate::error::FsEventsError;
+use bitflags::bitflags;
+use serde::{ser::SerializeSeq, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct FsEventFlags: u32 {
+        const CREATED = 0x01;
+        const REMOVED = 0x02;
+        const INODE_METADATA_MODIFIED = 0x04;
+        const RENAMED = 0x08;
+        const MODIFIED = 0x10;
+        const EXCHANGE = 0x20;
+        const FINDER_INFO_MODIFIED = 0x40;
+        const DIRECTORY_CREATED = 0x80;
+        const PERMISSION_CHANGED = 0x100;
+        const EXTENDED_ATTRIBUTE_MODIFIED = 0x200;
+        const EXTENDED_ATTRIBUTE_REMOVED = 0x400;
+        const DOCUMENT_CREATED = 0x800;
+        const DOCUMENT_REVISION = 0x1000;
+        const UNMOUNT_PENDING = 0x2000;
+        const ITEM_CLONED = 0x4000;
+        const NOTIFICATION_CLONE = 0x10000;
+        const ITEM_TRUNCATED = 0x20000;
+        const DIRECTORY_EVENT = 0x40000;
+        const LAST_HARD_LINK_REMOVED = 0x80000;
+        const IS_HARD_LINK = 0x100000;
+        const IS_SYMBOLIC_LINK = 0x400000;
+        const IS_FILE = 0x800000;
+        const IS_DIRECTORY = 0x1000000;
+        const MOUNT = 0x2000000;
+        const UNMOUNT = 0x4000000;
+        const END_OF_TRANSACTION = 0x20000000;
+    }
+}
+
+/// Display names for each flag, in the order they are output
+const FLAG_NAMES: [(FsEventFlags, &str); 26] = [
+    (FsEventFlags::CREATED, "Created"),
+    (FsEventFlags::REMOVED, "Removed"),
+    (
+        FsEventFlags::INODE_METADATA_MODIFIED,
+        "InodeMetadataModified",
+    ),
+    (FsEventFlags::RENAMED, "Renamed"),
+    (FsEventFlags::MODIFIED, "Modified"),
+    (FsEventFlags::EXCHANGE, "Exchange"),
+    (FsEventFlags::FINDER_INFO_MODIFIED, "FinderInfoModified"),
+    (FsEventFlags::DIRECTORY_CREATED, "DirectoryCreated"),
+    (FsEventFlags::PERMISSION_CHANGED, "PermissionChanged"),
+    (
+        FsEventFlags::EXTENDED_ATTRIBUTE_MODIFIED,
+        "ExtendedAttributeModified",
+    ),
+    // Misspelling is kept so output matches previous versions
+    (
+        FsEventFlags::EXTENDED_ATTRIBUTE_REMOVED,
+        "ExtenedAttributeRemoved",
+    ),
+    (FsEventFlags::DOCUMENT_CREATED, "DocumentCreated"),
+    (FsEventFlags::DOCUMENT_REVISION, "DocumentRevision"),
+    (FsEventFlags::UNMOUNT_PENDING, "UnmountPending"),
+    (FsEventFlags::ITEM_CLONED, "ItemCloned"),
+    (FsEventFlags::NOTIFICATION_CLONE, "NotificationClone"),
+    (FsEventFlags::ITEM_TRUNCATED, "ItemTruncated"),
+    (FsEventFlags::DIRECTORY_EVENT, "DirectoryEvent"),
+    (FsEventFlags::LAST_HARD_LINK_REMOVED, "LastHardLinkRemoved"),
+    (FsEventFlags::IS_HARD_LINK, "IsHardLink"),
+    (FsEventFlags::IS_SYMBOLIC_LINK, "IsSymbolicLink"),
+    (FsEventFlags::IS_FILE, "IsFile"),
+    (FsEventFlags::IS_DIRECTORY, "IsDirectory"),
+    (FsEventFlags::MOUNT, "Mount"),
+    (FsEventFlags::UNMOUNT, "Unmount"),
+    (FsEventFlags::END_OF_TRANSACTION, "EndOfTransaction"),
+];
+
+impl FsEventFlags {
+    /// Iterate over the display names of all known flags that are set
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        FLAG_NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+    }
+
+    /// Get a single flag from its display name (case insensitive)
+    pub fn from_display_name(name: &str) -> Option<FsEventFlags> {
+        if name.eq_ignore_ascii_case("ExtendedAttributeRemoved") {
+            return Some(FsEventFlags::EXTENDED_ATTRIBUTE_REMOVED);
+        }
+        FLAG_NAMES
+            .iter()
+            .find(|(_, flag_name)| flag_name.eq_ignore_ascii_case(name))
+            .map(|(flag, _)| *flag)
+    }
+}
+
+impl fmt::Display for FsEventFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.names().collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+/// Parse comma separated flag names (ex: `Created,Removed`)
+impl FromStr for FsEventFlags {
+    type Err = FsEventsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut flags = FsEventFlags::empty();
+        for name in value.split(',') {
+            let name = name.trim();
+            if name.is_empty() {
+                continue;
+            }
+            match FsEventFlags::from_display_name(name) {
+                Some(flag) => flags |= flag,
+                None => return Err(FsEventsError::InvalidFlag(name.to_string())),
+            }
+        }
+        Ok(flags)
+    }
+}
+
+/// Serialize as a list of flag names
+impl Serialize for FsEventFlags {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let names: Vec<&str> = self.names().collect();
+        let mut seq = serializer.serialize_seq(Some(names.len()))?;
+        for name in names {
+            seq.serialize_element(name)?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FsEventFlags;
+    use crate::error::FsEventsError;
+
+    #[test]
+    fn test_flags_display() {
+        let flags = FsEventFlags::from_bits_retain(0x7000002);
+        assert!(flags.to_string() == "Removed,IsDirectory,Mount,Unmount");
+        assert!(FsEventFlags::empty().to_string().is_empty());
+    }
+
+    #[test]
+    fn test_flags_unknown_bits() {
+        let flags = FsEventFlags::from_bits_retain(0x8000 | 0x01);
+        assert!(flags.to_string() == "Created");
+        assert!(flags.bits() == 0x8001);
+    }
+
+    #[test]
+    fn test_flags_from_str() {
+        let flags: FsEventFlags = "Created, removed,IsFile".parse().unwrap();
+        assert!(flags == FsEventFlags::CREATED | FsEventFlags::REMOVED | FsEventFlags::IS_FILE);
+
+        let flags: FsEventFlags = "ExtendedAttributeRemoved".parse().unwrap();
+        assert!(flags == FsEventFlags::EXTENDED_ATTRIBUTE_REMOVED);
+
+        let results = "Created,Deleted".parse::<FsEventFlags>();
+        assert!(matches!(results, Err(FsEventsError::InvalidFlag(name)) if name == "Deleted"));
+    }
+
+    #[test]
+    fn test_flags_names() {
+        let flags = FsEventFlags::RENAMED | FsEventFlags::IS_SYMBOLIC_LINK;
+        let names: Vec<&str> = flags.names().collect();
+        assert!(names == vec!["Renamed", "IsSymbolicLink"]);
+    }
+
+    #[test]
+    fn test_flags_serialize() {
+        let flags = FsEventFlags::CREATED | FsEventFlags::IS_FILE;
+        let results = serde_json::to_string(&flags).unwrap();
+        assert!(results == r#"["Created","IsFile"]"#);
+    }
+}
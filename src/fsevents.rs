@@ -2,77 +2,269 @@
 //!
 //! Provides a library to decompress and parse FsEvent files.
 
-use log::warn;
+#[cfg(feature = "filesystem")]
+use crate::enrich::LiveStatus;
+use crate::{error::FsEventsError, flags::FsEventFlags};
+use log::{debug, warn};
+use memchr::memchr;
 use nom::{
-    bytes::complete::{take, take_while},
+    bytes::complete::take,
     number::complete::{le_u32, le_u64},
 };
-use serde::Serialize;
-use std::{mem::size_of, str::from_utf8};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, mem::size_of, str::from_utf8};
 
-#[derive(Debug, Serialize)]
+/// Field names are kept stable so exported JSON can be loaded back with `Deserialize`. Fields missing
+/// from older exports are set to their default values
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct FsEvents {
-    pub flags: String, // Flags associated with FsEvent record
-    pub path: String,  // File path for FsEvent record
-    pub node: u64,     // Node ID for FsEvent record
-    pub event_id: u64, // Event ID for for FsEvent record
+    pub flags: String,             // Flags associated with FsEvent record
+    pub raw_flags: u32,            // Raw flags value for FsEvent record
+    pub path: String,              // File path for FsEvent record
+    pub node: u64,                 // Node ID for FsEvent record
+    pub event_id: u64,             // Event ID for for FsEvent record
+    pub extra_id: u32,             // Unknown ID for FsEvent record, only found in DLS3 records
+    pub source_file: String,       // FsEvent file that contained the record
+    pub offset: u64,               // Offset to the record in the decompressed FsEvent data
+    pub page: u32,                 // Index of the page that contained the record
+    pub volume_uuid: String,       // Volume UUID from the fseventsd-uuid file, if attached
+    pub approx_earliest_time: i64, // Approximate earliest time (Unix epoch) of the record
+    pub approx_latest_time: i64,   // Approximate latest time (Unix epoch) of the record
+    pub est_time_start: i64, // Start of the interpolated time window (Unix epoch) of the record
+    pub est_time_end: i64,   // End of the interpolated time window (Unix epoch) of the record
+    pub source_tag: String,  // Where the FsEvent directory came from (ex: a backup snapshot)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub raw_path: Vec<u8>, // Original path bytes, only set if the path is not valid UTF-8
+    pub current_path: String, // Current path of the record's node on the live volume, if resolved
+    pub triage_rule: String, // Comma separated triage rules the record path matched, if triaged
+    #[cfg(feature = "filesystem")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub live_status: Option<LiveStatus>, // Current status of the path on the live filesystem, if checked
 }
 
-#[derive(Debug)]
-struct FsEventsHeader {
-    signature: u32,   // File signature DLS1 or DLS2
-    padding: u32,     // Unknown, possibly padding
-    stream_size: u32, // Size of stream of FsEvent records, includes header size
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FsEventsPageHeader {
+    pub signature: u32,   // Page signature DLS1, DLS2, or DLS3
+    pub unknown: u32,     // Unknown value, previously assumed to be padding
+    pub stream_size: u32, // Size of stream of FsEvent records, includes header size
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DlsVersion {
+    Dls1, // Records have a path, event ID, and flags
+    Dls2, // Adds a node ID to each record
+    Dls3, // Adds an unknown 4 byte value to each record (macOS Ventura and higher)
+}
+
+/// A record that borrows its path from the decompressed FsEvent data, so no memory is allocated
+/// for each record. Use `into_owned` for a `FsEvents` record that outlives the data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsEventRef<'a> {
+    pub path: Cow<'a, str>, // Path without the root slash, only owned if it is not valid UTF-8
+    pub raw_path: &'a [u8], // Original path bytes
+    pub raw_flags: u32,     // Raw flags value for FsEvent record
+    pub node: u64,          // Node ID for FsEvent record, 0 in DLS1 records
+    pub event_id: u64,      // Event ID for FsEvent record
+    pub extra_id: u32,      // Unknown ID for FsEvent record, only found in DLS3 records
+    pub offset: u64,        // Offset to the record in the decompressed FsEvent data
+    pub page: u32,          // Index of the page that contained the record
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FsEventsPage {
+    pub index: u32,                 // Index of the page in the file
+    pub offset: u64,                // Offset to the page in the decompressed FsEvent data
+    pub header: FsEventsPageHeader, // Page header, all zeros if the header could not be read
+    pub records: Vec<FsEvents>,     // Records in the page
+}
+
+impl FsEventsPageHeader {
+    /// Parse a page header from the start of decompressed FsEvent data
+    /// The signature is not checked, use `version` to get the DLS version
+    pub fn parse(data: &[u8]) -> Result<FsEventsPageHeader, FsEventsError> {
+        let (_, header) =
+            FsEvents::fsevents_header(data).map_err(|_| FsEventsError::InvalidHeader)?;
+        Ok(header)
+    }
+
+    /// Get the DLS version from the signature, None if the signature is not a supported version
+    pub fn version(&self) -> Option<DlsVersion> {
+        match self.signature {
+            FsEvents::DISKLOGGERV1 => Some(DlsVersion::Dls1),
+            FsEvents::DISKLOGGERV2 => Some(DlsVersion::Dls2),
+            FsEvents::DISKLOGGERV3 => Some(DlsVersion::Dls3),
+            _ => None,
+        }
+    }
 }
 
 // FSEvents documentation:
 // https://github.com/libyal/dtformats/blob/main/documentation/MacOS%20File%20System%20Events%20Disk%20Log%20Stream%20format.asciidoc
 // http://www.osdfcon.org/presentations/2017/Ibrahim-Understanding-MacOS-File-Ststem-Events-with-FSEvents-Parser.pdf
 impl FsEvents {
-    const DISKLOGGERV2: u32 = 0x444c5332;
-    const DISKLOGGERV1: u32 = 0x444c5331;
+    pub(crate) const DISKLOGGERV2: u32 = 0x444c5332;
+    pub(crate) const DISKLOGGERV1: u32 = 0x444c5331;
+    pub(crate) const DISKLOGGERV3: u32 = 0x444c5333;
 
     /// Parse provided FsEvent data
-    pub(crate) fn fsevents_data(data: &[u8]) -> nom::IResult<&[u8], Vec<FsEvents>> {
+    pub(crate) fn fsevents_data(data: &[u8]) -> Result<Vec<FsEvents>, FsEventsError> {
         let mut total_fsevents: Vec<FsEvents> = Vec::new();
         let mut input = data;
 
         // Loop through all the FsEvent data
         // Parse header to get FsEvent stream size
         // Parse FsEvent stream data
-        loop {
-            let (fsevents_data, fsevents_header) = FsEvents::fsevents_header(input)?;
-            if fsevents_header.signature != FsEvents::DISKLOGGERV1
-                && fsevents_header.signature != FsEvents::DISKLOGGERV2
-            {
-                warn!("Not a FSEvent file: {:?}", data);
-                break;
-            }
+        let mut page = 0;
+        while !input.is_empty() {
+            let page_offset = (data.len() - input.len()) as u64;
+            let (stream_input, mut fsevents) = FsEvents::fsevents_page(input, page, page_offset)?;
+            total_fsevents.append(&mut fsevents);
+            input = stream_input;
+            page += 1;
+        }
 
-            let header_size = 12;
-            let (stream_input, fsevent_data) =
-                take(fsevents_header.stream_size - header_size)(fsevents_data)?;
+        Ok(total_fsevents)
+    }
 
-            let (_result, mut fsevents) =
-                FsEvents::get_fsevent(fsevent_data, fsevents_header.signature)?;
+    /// Parse provided FsEvent data into records that borrow their paths from the data
+    pub(crate) fn fsevents_data_ref(data: &[u8]) -> Result<Vec<FsEventRef<'_>>, FsEventsError> {
+        let mut total_fsevents: Vec<FsEventRef<'_>> = Vec::new();
+        let mut input = data;
+        let mut page = 0;
+        while !input.is_empty() {
+            let page_offset = (data.len() - input.len()) as u64;
+            let (stream_input, mut fsevents) =
+                FsEvents::fsevents_page_ref(input, page, page_offset)?;
             total_fsevents.append(&mut fsevents);
             input = stream_input;
-            if input.is_empty() {
-                break;
-            }
+            page += 1;
         }
+        Ok(total_fsevents)
+    }
 
-        Ok((input, total_fsevents))
+    /// Parse provided FsEvent data into pages with their headers
+    pub(crate) fn fsevents_pages(data: &[u8]) -> Result<Vec<FsEventsPage>, FsEventsError> {
+        let mut pages: Vec<FsEventsPage> = Vec::new();
+        let mut input = data;
+        while !input.is_empty() {
+            let offset = (data.len() - input.len()) as u64;
+            let index = pages.len() as u32;
+            let header = FsEventsPageHeader::parse(input)?;
+            let (stream_input, records) = FsEvents::fsevents_page(input, index, offset)?;
+            pages.push(FsEventsPage {
+                index,
+                offset,
+                header,
+                records,
+            });
+            input = stream_input;
+        }
+        Ok(pages)
+    }
+
+    /// Parse a single FsEvent page (header and stream of records)
+    /// Records are tagged with the page index and their offset based on where the page starts
+    pub(crate) fn fsevents_page_ref(
+        data: &[u8],
+        page: u32,
+        page_offset: u64,
+    ) -> Result<(&[u8], Vec<FsEventRef<'_>>), FsEventsError> {
+        let (fsevents_data, fsevents_header) =
+            FsEvents::fsevents_header(data).map_err(|_| FsEventsError::InvalidHeader)?;
+        FsEvents::check_signature(fsevents_header.signature)?;
+
+        let header_size = 12;
+        if fsevents_header.stream_size < header_size {
+            warn!(
+                "FsEvent page stream size smaller than header: {}",
+                fsevents_header.stream_size
+            );
+            return Err(FsEventsError::InvalidHeader);
+        }
+        let (stream_input, fsevent_data) =
+            take(fsevents_header.stream_size - header_size)(fsevents_data)?;
+
+        let (_result, mut fsevents) =
+            FsEvents::get_fsevent(fsevent_data, fsevents_header.signature)?;
+        for fsevent in fsevents.iter_mut() {
+            fsevent.offset += page_offset + header_size as u64;
+            fsevent.page = page;
+        }
+        Ok((stream_input, fsevents))
+    }
+
+    /// Parse a single FsEvent page into owned records
+    pub(crate) fn fsevents_page(
+        data: &[u8],
+        page: u32,
+        page_offset: u64,
+    ) -> Result<(&[u8], Vec<FsEvents>), FsEventsError> {
+        let (stream_input, fsevents) = FsEvents::fsevents_page_ref(data, page, page_offset)?;
+        let fsevents = fsevents.into_iter().map(FsEventRef::into_owned).collect();
+        Ok((stream_input, fsevents))
+    }
+
+    /// Parse the records in a page that ended early, records before the end of the data are kept
+    pub(crate) fn fsevents_partial_page(data: &[u8], page: u32, page_offset: u64) -> Vec<FsEvents> {
+        let header_size = 12;
+        let (fsevents_data, fsevents_header) = match FsEvents::fsevents_header(data) {
+            Ok(result) => result,
+            Err(_) => return Vec::new(),
+        };
+        if FsEvents::check_signature(fsevents_header.signature).is_err() {
+            return Vec::new();
+        }
+
+        let stream_size = (fsevents_header.stream_size as usize)
+            .saturating_sub(header_size)
+            .min(fsevents_data.len());
+        let mut input = &fsevents_data[..stream_size];
+        let mut fsevents: Vec<FsEvents> = Vec::new();
+        while !input.is_empty() {
+            let record_offset = (stream_size - input.len()) as u64;
+            let (remaining, mut fsevent) =
+                match FsEvents::get_fsevent_data(input, &fsevents_header.signature) {
+                    Ok(result) => result,
+                    Err(_) => break,
+                };
+            fsevent.offset = page_offset + header_size as u64 + record_offset;
+            fsevent.page = page;
+            fsevents.push(fsevent);
+            input = remaining;
+        }
+        fsevents
+    }
+
+    /// Verify the page signature is a supported DLS version
+    pub(crate) fn check_signature(sig: u32) -> Result<(), FsEventsError> {
+        if sig == FsEvents::DISKLOGGERV1
+            || sig == FsEvents::DISKLOGGERV2
+            || sig == FsEvents::DISKLOGGERV3
+        {
+            return Ok(());
+        }
+
+        // All versions share the "DLS" prefix, only the last byte is the version
+        let dls_prefix = 0x444c5300;
+        if sig & 0xffffff00 == dls_prefix {
+            warn!("Unsupported FsEvent version: {:#x}", sig);
+            return Err(FsEventsError::UnsupportedVersion(sig));
+        }
+        warn!("Not a FSEvent page signature: {:#x}", sig);
+        Err(FsEventsError::InvalidHeader)
     }
 
     /// Begin parsing FsEvent stream
-    fn get_fsevent(data: &[u8], sig: u32) -> nom::IResult<&[u8], Vec<FsEvents>> {
+    fn get_fsevent(data: &[u8], sig: u32) -> nom::IResult<&[u8], Vec<FsEventRef<'_>>> {
         let mut input_results = data;
-        let mut fsevents_array: Vec<FsEvents> = Vec::new();
+        let mut fsevents_array: Vec<FsEventRef<'_>> = Vec::new();
 
         // Parse FsEvent stream and get each FsEvent record
         loop {
-            let (input_data, fsevent_results) = FsEvents::get_fsevent_data(input_results, &sig)?;
+            let record_offset = (data.len() - input_results.len()) as u64;
+            let (input_data, mut fsevent_results) = FsEventRef::parse(input_results, &sig)?;
+            fsevent_results.offset = record_offset;
             input_results = input_data;
             fsevents_array.push(fsevent_results);
             if input_results.is_empty() {
@@ -84,156 +276,144 @@ impl FsEvents {
     }
 
     /// Parse FsEvent header
-    fn fsevents_header(data: &[u8]) -> nom::IResult<&[u8], FsEventsHeader> {
-        let mut fsevent = FsEventsHeader {
-            signature: 0,
-            padding: 0,
-            stream_size: 0,
-        };
+    fn fsevents_header(data: &[u8]) -> nom::IResult<&[u8], FsEventsPageHeader> {
+        let mut fsevent = FsEventsPageHeader::default();
 
         let (input, sig) = take(size_of::<u32>())(data)?;
-        let (input, padding) = take(size_of::<u32>())(input)?;
+        let (input, unknown) = take(size_of::<u32>())(input)?;
         let (input, stream_size) = take(size_of::<u32>())(input)?;
 
         let (_, fsevent_sig) = le_u32(sig)?;
-        let (_, fsevent_pad) = le_u32(padding)?;
+        let (_, fsevent_unknown) = le_u32(unknown)?;
         let (_, fsevent_stream) = le_u32(stream_size)?;
 
         fsevent.signature = fsevent_sig;
-        fsevent.padding = fsevent_pad;
+        fsevent.unknown = fsevent_unknown;
         fsevent.stream_size = fsevent_stream;
 
         Ok((input, fsevent))
     }
 
     /// Parse FsEvent stream entry
-    fn get_fsevent_data<'a>(data: &'a [u8], sig: &u32) -> nom::IResult<&'a [u8], FsEvents> {
-        let mut fsevent_data = FsEvents {
-            flags: String::new(),
-            path: String::from("/"), // Ensure every path has root slash
-            node: 0,
-            event_id: 0,
-        };
+    pub(crate) fn get_fsevent_data<'a>(
+        data: &'a [u8],
+        sig: &u32,
+    ) -> nom::IResult<&'a [u8], FsEvents> {
+        let (input, fsevent) = FsEventRef::parse(data, sig)?;
+        Ok((input, fsevent.into_owned()))
+    }
+
+    /// Identify Event flags in FsEvent entry
+    fn match_flags(flags: &u32) -> Vec<String> {
+        FsEventFlags::from_bits_retain(*flags)
+            .names()
+            .map(String::from)
+            .collect()
+    }
+
+    /// Check if the record path was not valid UTF-8 and was decoded lossily
+    pub fn has_invalid_path(&self) -> bool {
+        !self.raw_path.is_empty()
+    }
 
-        // Read path until end-of-string character
-        let (input, path) = take_while(|b: u8| b != 0)(data)?;
+    /// Get the typed flags for the FsEvent record
+    pub fn event_flags(&self) -> FsEventFlags {
+        FsEventFlags::from_bits_retain(self.raw_flags)
+    }
+}
+
+impl<'a> FsEventRef<'a> {
+    /// Parse FsEvent stream entry, borrowing the path from the data
+    pub(crate) fn parse(data: &'a [u8], sig: &u32) -> nom::IResult<&'a [u8], FsEventRef<'a>> {
+        // Read path until end-of-string character. Paths are most of the data, so the end is found
+        // with memchr, which checks many bytes at a time
+        let path_size = memchr(0, data).unwrap_or(data.len());
+        let (input, raw_path) = take(path_size)(data)?;
         // Nom end-of-string character
         let (input, _) = take(size_of::<u8>())(input)?;
         let (input, id) = take(size_of::<u64>())(input)?;
         let (input, flags) = take(size_of::<u32>())(input)?;
 
-        let (_, fsevent_id) = le_u64(id)?;
-        let (_, fsevent_flags) = le_u32(flags)?;
-
-        let flag_list = FsEvents::match_flags(&fsevent_flags);
+        let (_, event_id) = le_u64(id)?;
+        let (_, raw_flags) = le_u32(flags)?;
 
-        fsevent_data.flags = flag_list.join(",");
-        fsevent_data.event_id = fsevent_id;
-        let path_vec = path.to_vec();
-        let path_data = from_utf8(&path_vec);
-        match path_data {
-            Ok(results) => fsevent_data.path += results,
-            Err(err) => warn!("Failed to get path string: {:?}", err),
-        }
+        let path = match from_utf8(raw_path) {
+            Ok(results) => Cow::Borrowed(results),
+            Err(err) => {
+                // Keep the original bytes so no evidence is lost
+                debug!("Path is not valid UTF-8, using lossy conversion: {:?}", err);
+                String::from_utf8_lossy(raw_path)
+            }
+        };
+        // The root slash is added to every path, so a stored root slash is dropped
+        let path = match path {
+            Cow::Borrowed(path) => Cow::Borrowed(path.strip_prefix('/').unwrap_or(path)),
+            Cow::Owned(path) => Cow::Owned(path.strip_prefix('/').unwrap_or(&path).to_string()),
+        };
 
-        // Strip any paths that have duplicative root slashes
-        if fsevent_data.path.starts_with("//") {
-            fsevent_data.path = (&fsevent_data.path[1..]).to_string();
+        let mut fsevent = FsEventRef {
+            path,
+            raw_path,
+            raw_flags,
+            node: 0,
+            event_id,
+            extra_id: 0,
+            offset: 0,
+            page: 0,
+        };
+        if sig == &FsEvents::DISKLOGGERV1 {
+            return Ok((input, fsevent));
         }
 
-        if sig != &FsEvents::DISKLOGGERV1 {
-            let (input, node) = nom::bytes::streaming::take(size_of::<u64>())(input)?;
-            let (_, fsevent_node) = le_u64(node)?;
+        let (input, node) = nom::bytes::streaming::take(size_of::<u64>())(input)?;
+        let (_, node) = le_u64(node)?;
+        fsevent.node = node;
 
-            fsevent_data.node = fsevent_node;
-            return Ok((input, fsevent_data));
+        // DLS3 records contain an additional 4 byte value after the node ID
+        if sig == &FsEvents::DISKLOGGERV3 {
+            let (input, extra) = take(size_of::<u32>())(input)?;
+            let (_, extra_id) = le_u32(extra)?;
+            fsevent.extra_id = extra_id;
+            return Ok((input, fsevent));
         }
+        Ok((input, fsevent))
+    }
 
-        Ok((input, fsevent_data))
+    /// Check if the record path was not valid UTF-8 and was decoded lossily
+    pub fn has_invalid_path(&self) -> bool {
+        matches!(self.path, Cow::Owned(_))
     }
 
-    /// Identify Event flags in FsEvent entry
-    fn match_flags(flags: &u32) -> Vec<String> {
-        let mut flag_list: Vec<String> = Vec::new();
-        if (flags & 0x01) != 0 {
-            flag_list.push("Created".to_string());
-        }
-        if (flags & 0x02) != 0 {
-            flag_list.push("Removed".to_string());
-        }
-        if (flags & 0x04) != 0 {
-            flag_list.push("InodeMetadataModified".to_string());
-        }
-        if (flags & 0x08) != 0 {
-            flag_list.push("Renamed".to_string());
-        }
-        if (flags & 0x10) != 0 {
-            flag_list.push("Modified".to_string());
-        }
-        if (flags & 0x20) != 0 {
-            flag_list.push("Exchange".to_string());
-        }
-        if (flags & 0x40) != 0 {
-            flag_list.push("FinderInfoModified".to_string());
-        }
-        if (flags & 0x80) != 0 {
-            flag_list.push("DirectoryCreated".to_string());
-        }
-        if (flags & 0x100) != 0 {
-            flag_list.push("PermissionChanged".to_string());
-        }
-        if (flags & 0x200) != 0 {
-            flag_list.push("ExtendedAttributeModified".to_string());
-        }
-        if (flags & 0x400) != 0 {
-            flag_list.push("ExtenedAttributeRemoved".to_string());
-        }
-        if (flags & 0x800) != 0 {
-            flag_list.push("DocumentCreated".to_string());
-        }
-        if (flags & 0x1000) != 0 {
-            flag_list.push("DocumentRevision".to_string());
-        }
-        if (flags & 0x2000) != 0 {
-            flag_list.push("UnmountPending".to_string());
-        }
-        if (flags & 0x4000) != 0 {
-            flag_list.push("ItemCloned".to_string());
-        }
-        if (flags & 0x10000) != 0 {
-            flag_list.push("NotificationClone".to_string());
-        }
-        if (flags & 0x20000) != 0 {
-            flag_list.push("ItemTruncated".to_string());
-        }
-        if (flags & 0x40000) != 0 {
-            flag_list.push("DirectoryEvent".to_string());
-        }
-        if (flags & 0x80000) != 0 {
-            flag_list.push("LastHardLinkRemoved".to_string());
-        }
-        if (flags & 0x100000) != 0 {
-            flag_list.push("IsHardLink".to_string());
-        }
-        if (flags & 0x400000) != 0 {
-            flag_list.push("IsSymbolicLink".to_string());
-        }
-        if (flags & 0x800000) != 0 {
-            flag_list.push("IsFile".to_string());
-        }
-        if (flags & 0x1000000) != 0 {
-            flag_list.push("IsDirectory".to_string());
-        }
-        if (flags & 0x2000000) != 0 {
-            flag_list.push("Mount".to_string());
-        }
-        if (flags & 0x4000000) != 0 {
-            flag_list.push("Unmount".to_string());
-        }
-        if (flags & 0x20000000) != 0 {
-            flag_list.push("EndOfTransaction".to_string());
+    /// Get the typed flags for the FsEvent record
+    pub fn event_flags(&self) -> FsEventFlags {
+        FsEventFlags::from_bits_retain(self.raw_flags)
+    }
+
+    /// Copy the record into an owned `FsEvents` record, with the root slash added to the path
+    pub fn into_owned(self) -> FsEvents {
+        let raw_path = if self.has_invalid_path() {
+            self.raw_path.to_vec()
+        } else {
+            Vec::new()
+        };
+        FsEvents {
+            flags: FsEvents::match_flags(&self.raw_flags).join(","),
+            raw_flags: self.raw_flags,
+            path: format!("/{}", self.path),
+            node: self.node,
+            event_id: self.event_id,
+            extra_id: self.extra_id,
+            offset: self.offset,
+            page: self.page,
+            raw_path,
+            ..Default::default()
         }
-        flag_list
+    }
+}
+
+impl From<FsEventRef<'_>> for FsEvents {
+    fn from(fsevent: FsEventRef<'_>) -> Self {
+        fsevent.into_owned()
     }
 }
 
@@ -241,9 +421,38 @@ impl FsEvents {
 mod tests {
     use std::{fs, path::PathBuf};
 
+    #[cfg(feature = "filesystem")]
     use crate::parser::decompress;
+    use crate::{error::FsEventsError, flags::FsEventFlags};
 
-    use super::FsEvents;
+    use super::{DlsVersion, FsEventRef, FsEvents, FsEventsPageHeader};
+    use std::borrow::Cow;
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_json_round_trip() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let data = decompress(&test_location.display().to_string()).unwrap();
+        let records = FsEvents::fsevents_data(&data).unwrap();
+
+        let json = serde_json::to_string(&records).unwrap();
+        let loaded: Vec<FsEvents> = serde_json::from_str(&json).unwrap();
+        assert!(loaded.len() == 5);
+        assert!(loaded[4].path == "/Users/bob/.ssh/authorized_keys");
+        assert!(loaded[4].event_flags() == records[4].event_flags());
+        assert!(serde_json::to_string(&loaded).unwrap() == json);
+    }
+
+    #[test]
+    fn test_json_missing_fields() {
+        let json =
+            r#"[{"flags":"Created,IsFile","raw_flags":8388609,"path":"/tmp/a.txt","event_id":10}]"#;
+        let loaded: Vec<FsEvents> = serde_json::from_str(json).unwrap();
+        assert!(loaded[0].event_id == 10);
+        assert!(loaded[0].node == 0);
+        assert!(loaded[0].source_file.is_empty());
+    }
 
     #[test]
     fn test_match_flags() {
@@ -255,13 +464,25 @@ mod tests {
     }
 
     #[test]
+    fn test_event_flags() {
+        let fsevent = FsEvents {
+            raw_flags: 0x800011,
+            ..Default::default()
+        };
+        let flags = fsevent.event_flags();
+        assert!(flags.contains(FsEventFlags::CREATED | FsEventFlags::MODIFIED));
+        assert!(flags.contains(FsEventFlags::IS_FILE));
+        assert!(!flags.contains(FsEventFlags::REMOVED));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
     fn test_fsevents_data() {
         let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         test_location.push("tests/test_data/DLS2/0000000000027d79");
         let test_path: &str = &test_location.display().to_string();
         let files = decompress(test_path).unwrap();
-        let (results, data) = FsEvents::fsevents_data(&files).unwrap();
-        assert!(results.len() == 0);
+        let data = FsEvents::fsevents_data(&files).unwrap();
         assert!(data.len() == 736);
     }
 
@@ -272,8 +493,31 @@ mod tests {
         let buffer = fs::read(test_location).unwrap();
         let (_, header) = FsEvents::fsevents_header(&buffer).unwrap();
         assert!(header.signature == 1145852722);
-        assert!(header.padding == 779163104);
+        assert!(header.unknown == 779163104);
         assert!(header.stream_size == 78970);
+
+        let header = FsEventsPageHeader::parse(&buffer).unwrap();
+        assert!(header.version() == Some(DlsVersion::Dls2));
+        assert!(matches!(
+            FsEventsPageHeader::parse(&buffer[..8]),
+            Err(FsEventsError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_fsevents_pages() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let data = decompress(&test_location.display().to_string()).unwrap();
+        let pages = FsEvents::fsevents_pages(&data).unwrap();
+        assert!(pages.len() == 2);
+        assert!(pages[0].header.version() == Some(DlsVersion::Dls3));
+        assert!(pages[0].header.stream_size == 172);
+        assert!(pages[0].records.len() == 3);
+        assert!(pages[1].index == 1);
+        assert!(pages[1].offset == 172);
+        assert!(pages[1].records[0].offset == 184);
     }
 
     #[test]
@@ -289,6 +533,73 @@ mod tests {
         assert!(results.path == "/Volumes/Preboot");
         assert!(results.node == 0);
         assert!(results.flags == "Removed,IsDirectory,Mount,Unmount");
+        assert!(results.raw_flags == 0x7000002);
+    }
+
+    #[test]
+    fn test_get_fsevent_data_invalid_utf8() {
+        let mut data = b"Users/bob/\xffbad.txt\0".to_vec();
+        data.extend_from_slice(&20u64.to_le_bytes());
+        data.extend_from_slice(&0x800001u32.to_le_bytes());
+        data.extend_from_slice(&30u64.to_le_bytes());
+
+        let (input, results) = FsEvents::get_fsevent_data(&data, &0x444c5332).unwrap();
+        assert!(input.is_empty());
+        assert!(results.path == "/Users/bob/\u{FFFD}bad.txt");
+        assert!(results.raw_path == b"Users/bob/\xffbad.txt");
+        assert!(results.has_invalid_path());
+        assert!(results.event_id == 20);
+        assert!(results.node == 30);
+
+        let json = serde_json::to_string(&results).unwrap();
+        assert!(json.contains("raw_path"));
+    }
+
+    #[test]
+    fn test_fsevent_ref() {
+        let mut data = b"/private/tmp/a.txt\0".to_vec();
+        data.extend_from_slice(&20u64.to_le_bytes());
+        data.extend_from_slice(&0x800011u32.to_le_bytes());
+        data.extend_from_slice(&30u64.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+
+        let (input, results) = FsEventRef::parse(&data, &0x444c5333).unwrap();
+        assert!(input.is_empty());
+        assert!(matches!(results.path, Cow::Borrowed("private/tmp/a.txt")));
+        assert!(results.raw_path == b"/private/tmp/a.txt");
+        assert!(!results.has_invalid_path());
+        assert!(
+            results.event_flags()
+                == FsEventFlags::CREATED | FsEventFlags::MODIFIED | FsEventFlags::IS_FILE
+        );
+        assert!(results.extra_id == 40);
+
+        let owned = results.into_owned();
+        assert!(owned.path == "/private/tmp/a.txt");
+        assert!(owned.flags == "Created,Modified,IsFile");
+        assert!(owned.raw_path.is_empty());
+        assert!(owned.node == 30);
+    }
+
+    #[test]
+    fn test_fsevent_ref_no_terminator() {
+        assert!(FsEventRef::parse(b"Users/bob/file.txt", &0x444c5332).is_err());
+        assert!(FsEventRef::parse(b"", &0x444c5332).is_err());
+        // Path ends, but the event ID is missing
+        assert!(FsEventRef::parse(b"Users/bob/file.txt\0\x01", &0x444c5332).is_err());
+    }
+
+    #[test]
+    fn test_fsevent_ref_invalid_utf8() {
+        let mut data = b"Users/bob/\xffbad.txt\0".to_vec();
+        data.extend_from_slice(&20u64.to_le_bytes());
+        data.extend_from_slice(&0x800001u32.to_le_bytes());
+
+        let (_, results) = FsEventRef::parse(&data, &0x444c5331).unwrap();
+        assert!(results.has_invalid_path());
+        let owned = FsEvents::from(results);
+        assert!(owned.path == "/Users/bob/\u{FFFD}bad.txt");
+        assert!(owned.raw_path == b"Users/bob/\xffbad.txt");
     }
 
     #[test]
@@ -300,6 +611,55 @@ mod tests {
 
         let (input, results) = FsEvents::get_fsevent(input, header.signature).unwrap();
         assert!(results.len() == 736);
-        assert!(input.len() == 0);
+        assert!(input.is_empty());
+        assert!(results[0].offset == 0);
+        assert!(results[1].offset == 37);
+    }
+
+    #[test]
+    fn test_check_signature() {
+        assert!(FsEvents::check_signature(0x444c5332).is_ok());
+        assert!(matches!(
+            FsEvents::check_signature(0x444c5334),
+            Err(FsEventsError::UnsupportedVersion(0x444c5334))
+        ));
+        assert!(matches!(
+            FsEvents::check_signature(0x11223344),
+            Err(FsEventsError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_fsevents_page_bad_stream_size() {
+        let mut data = 0x444c5332u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&4u32.to_le_bytes());
+        assert!(matches!(
+            FsEvents::fsevents_page(&data, 0, 0),
+            Err(FsEventsError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_fsevents_data_version3() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let test_path: &str = &test_location.display().to_string();
+        let files = decompress(test_path).unwrap();
+        let data = FsEvents::fsevents_data(&files).unwrap();
+        assert!(data.len() == 5);
+
+        assert!(data[2].path == "/private/var/folders/tmp");
+        assert!(data[2].event_id == 196610);
+        assert!(data[2].flags == "DirectoryCreated,IsDirectory");
+        assert!(data[2].raw_flags == 0x1000080);
+        assert!(data[2].node == 7654321);
+        assert!(data[2].extra_id == 1);
+        assert!(data[2].offset == 124);
+        assert!(data[2].page == 0);
+
+        assert!(data[3].offset == 184);
+        assert!(data[3].page == 1);
     }
 }
@@ -0,0 +1,92 @@
+//! Open evidence images
+//!
+//! Raw (dd) images are read as is. EWF (E01) images are detected by their signature and read with
+//! `ewf::EwfReader` when the `ewf` feature is enabled. Sparse bundle directories and sparse image
+//! files are reassembled into the disk image they contain.
+
+use crate::{
+    error::FsEventsError,
+    sparse::{SparseBundleReader, SparseImageReader, SPARSE_IMAGE_SIGNATURE},
+};
+use std::{
+    fs::File,
+    io::{Read, Seek},
+    path::Path,
+};
+
+// Signature at the start of an EWF (E01) segment file
+pub(crate) const EWF_SIGNATURE: [u8; 8] = [0x45, 0x56, 0x46, 0x09, 0x0d, 0x0a, 0xff, 0x00];
+
+/// Seekable reader over the media data in an image
+pub(crate) trait ImageReader: Read + Seek {}
+
+impl<T: Read + Seek> ImageReader for T {}
+
+/// Open a raw, EWF, or sparse image. The image format is detected by its signature
+pub(crate) fn open_image(path: &str) -> Result<Box<dyn ImageReader>, FsEventsError> {
+    if Path::new(path).is_dir() {
+        return Ok(Box::new(SparseBundleReader::open(path)?));
+    }
+    let mut file = File::open(path)?;
+    let mut signature = Vec::new();
+    (&mut file)
+        .take(EWF_SIGNATURE.len() as u64)
+        .read_to_end(&mut signature)?;
+    if signature == EWF_SIGNATURE {
+        return open_ewf_image(path);
+    }
+    if signature.starts_with(SPARSE_IMAGE_SIGNATURE) {
+        return Ok(Box::new(SparseImageReader::open(path)?));
+    }
+
+    file.rewind()?;
+    Ok(Box::new(file))
+}
+
+#[cfg(feature = "ewf")]
+/// Open the media data in an EWF image
+fn open_ewf_image(path: &str) -> Result<Box<dyn ImageReader>, FsEventsError> {
+    Ok(Box::new(crate::ewf::EwfReader::open(path)?))
+}
+
+#[cfg(not(feature = "ewf"))]
+/// EWF images can only be read with the ewf feature
+fn open_ewf_image(path: &str) -> Result<Box<dyn ImageReader>, FsEventsError> {
+    Err(FsEventsError::InvalidImage(format!(
+        "{} is an EWF image, reading it requires the ewf feature",
+        path
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::open_image;
+    use std::{fs, io::Read};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_open_image_raw() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("open_image.dd");
+        fs::write(&test_location, b"raw image data").unwrap();
+
+        let mut reader = open_image(&test_location.display().to_string()).unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert!(data == b"raw image data");
+    }
+
+    #[test]
+    #[cfg(not(feature = "ewf"))]
+    fn test_open_image_ewf_disabled() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("open_image.E01");
+        fs::write(&test_location, super::EWF_SIGNATURE).unwrap();
+
+        let results = open_image(&test_location.display().to_string());
+        assert!(matches!(
+            results,
+            Err(crate::error::FsEventsError::InvalidImage(_))
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+//! Map FsEvent file names to the event IDs they contain
+//!
+//! fseventsd names each file with 16 hex digits: one more than the highest event ID in the file.
+//! Sorted by name, a file covers the event IDs from the previous file's name up to its own name.
+//! The inventory lists each file with the range its name implies and the range it really contains,
+//! so files that were renamed or modified stand out.
+
+use crate::{error::FsEventsError, parser::fseventsd, stream::PageReader};
+use log::warn;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct FileInventory {
+    pub path: String,        // Path to the FsEvent file
+    pub name_event_id: u64,  // Event ID decoded from the file name
+    pub expected_start: u64, // Lowest event ID expected, from the previous file's name
+    pub records: usize,      // Number of records in the file
+    pub min_event_id: u64,   // Lowest event ID in the file
+    pub max_event_id: u64,   // Highest event ID in the file
+    pub matches_name: bool,  // All event IDs are between the expected start and the file name
+    pub complete: bool,      // Every page in the file could be read
+}
+
+/// Decode a FsEvent file name (16 hex digits) into an event ID. Returns None for other files
+pub fn filename_event_id(path: &str) -> Option<u64> {
+    let name = Path::new(path).file_name()?.to_str()?;
+    if name.len() != 16 || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u64::from_str_radix(name, 16).ok()
+}
+
+/// List the FsEvent files in a directory sorted by the event ID in their name, with the event ID
+/// range of each file. Files without an event ID name are skipped
+pub fn fseventsd_inventory(directory: &str) -> Result<Vec<FileInventory>, FsEventsError> {
+    let mut files: Vec<(u64, String)> = fseventsd(directory)?
+        .into_iter()
+        .filter_map(|file| filename_event_id(&file).map(|event_id| (event_id, file)))
+        .collect();
+    files.sort();
+
+    let mut inventory: Vec<FileInventory> = Vec::new();
+    let mut expected_start = 0;
+    for (name_event_id, path) in files {
+        let mut entry = FileInventory {
+            path,
+            name_event_id,
+            expected_start,
+            ..Default::default()
+        };
+        file_event_ids(&mut entry);
+        entry.matches_name = entry.records == 0
+            || (entry.min_event_id >= expected_start && entry.max_event_id < name_event_id);
+        if !entry.matches_name {
+            warn!(
+                "FsEvent file {} has event IDs {:#x}-{:#x} outside of the range {:#x}-{:#x} from its name",
+                entry.path, entry.min_event_id, entry.max_event_id, expected_start, name_event_id
+            );
+        }
+        expected_start = name_event_id;
+        inventory.push(entry);
+    }
+    Ok(inventory)
+}
+
+/// Read the event ID range of a file. Records before a page that cannot be read are still counted
+fn file_event_ids(entry: &mut FileInventory) {
+    let reader = match PageReader::from_path(&entry.path) {
+        Ok(result) => result,
+        Err(err) => {
+            warn!("Could not read FsEvent file {}: {}", entry.path, err);
+            return;
+        }
+    };
+    let mut min_event_id = u64::MAX;
+    let mut max_event_id = 0;
+    entry.complete = true;
+    for records in reader {
+        let records = match records {
+            Ok(result) => result,
+            Err(err) => {
+                warn!("Could not read all pages in {}: {}", entry.path, err);
+                entry.complete = false;
+                break;
+            }
+        };
+        for fsevent in &records {
+            min_event_id = min_event_id.min(fsevent.event_id);
+            max_event_id = max_event_id.max(fsevent.event_id);
+        }
+        entry.records += records.len();
+    }
+    if entry.records != 0 {
+        entry.min_event_id = min_event_id;
+        entry.max_event_id = max_event_id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{filename_event_id, fseventsd_inventory};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_filename_event_id() {
+        assert!(filename_event_id("/.fseventsd/0000000000027d79") == Some(0x27d79));
+        assert!(filename_event_id("000000000002469C") == Some(0x2469c));
+        assert!(filename_event_id("/.fseventsd/fseventsd-uuid").is_none());
+        assert!(filename_event_id("27d79").is_none());
+    }
+
+    #[test]
+    fn test_fseventsd_inventory() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let inventory = fseventsd_inventory(&test_location.display().to_string()).unwrap();
+        assert!(inventory.len() == 2);
+
+        assert!(inventory[0].name_event_id == 0x2469c);
+        assert!(inventory[0].expected_start == 0);
+        assert!(inventory[0].records == 5000);
+        assert!(inventory[0].max_event_id == 0x2469b);
+        assert!(inventory[0].matches_name);
+
+        assert!(inventory[1].expected_start == 0x2469c);
+        assert!(inventory[1].min_event_id == 0x246a1);
+        assert!(inventory[1].max_event_id == 0x27d78);
+        assert!(inventory[1].matches_name && inventory[1].complete);
+    }
+
+    #[test]
+    fn test_fseventsd_inventory_mismatch() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS1");
+        let inventory = fseventsd_inventory(&test_location.display().to_string()).unwrap();
+        assert!(inventory.len() == 1);
+        assert!(inventory[0].max_event_id == 0x27d7b);
+        assert!(!inventory[0].matches_name);
+    }
+}
@@ -0,0 +1,212 @@
+//! Lazily iterate over FsEvent records
+//!
+//! Decompresses and parses one page at a time from one FsEvent file at a time,
+//! so only a single page is held in memory.
+
+use crate::{
+    error::FsEventsError, fsevents::FsEvents, options::ParserOptions, parser::fseventsd,
+    report::ParseReport, stream::PageReader,
+};
+use log::error;
+use std::{fs::File, io::BufReader, vec::IntoIter};
+
+pub struct FsEventsIter {
+    files: IntoIter<String>,                     // FsEvent files left to parse
+    current_file: String,                        // FsEvent file currently being parsed
+    reader: Option<PageReader<BufReader<File>>>, // Page reader for the current file
+    records: IntoIter<FsEvents>,                 // Records left from the current page
+    options: ParserOptions,                      // Max file size and page options for each file
+    report: ParseReport,                         // Truncated files and skipped or resynced pages
+}
+
+impl FsEventsIter {
+    /// Create an iterator over the provided FsEvent files
+    pub fn new(files: Vec<String>) -> FsEventsIter {
+        FsEventsIter {
+            files: files.into_iter(),
+            current_file: String::new(),
+            reader: None,
+            records: Vec::new().into_iter(),
+            options: ParserOptions::default(),
+            report: ParseReport::default(),
+        }
+    }
+
+    /// Use the max file size and page options from the parser options when opening each file
+    /// Files that are not smaller than the max file size return `FsEventsError::FileTooLarge`
+    pub fn options(mut self, options: &ParserOptions) -> FsEventsIter {
+        self.options = options.clone();
+        self
+    }
+
+    /// Create an iterator over all FsEvent files in a directory
+    pub fn from_directory(directory: &str) -> Result<FsEventsIter, FsEventsError> {
+        let files = fseventsd(directory)?;
+        Ok(FsEventsIter::new(files))
+    }
+
+    /// FsEvent file the iterator is currently reading from
+    pub fn current_file(&self) -> &str {
+        &self.current_file
+    }
+
+    /// Get the truncated files and the skipped or resynced pages of the files read so far
+    /// Files that could not be opened or parsed are returned as errors by the iterator instead
+    pub fn report(&self) -> &ParseReport {
+        &self.report
+    }
+
+    /// Add the pages the current reader could not fully parse to the report, and close it
+    fn close_reader(&mut self) {
+        if let Some(reader) = self.reader.take() {
+            reader.add_to_report(&mut self.report);
+        }
+    }
+
+    /// Parse the next page in the current file, returns None when the file has no more pages
+    fn next_page(&mut self) -> Option<Result<(), FsEventsError>> {
+        let results = self.reader.as_mut()?.next_page();
+        match results {
+            Ok(Some(records)) => {
+                self.records = records.into_iter();
+                Some(Ok(()))
+            }
+            Ok(None) => {
+                self.close_reader();
+                None
+            }
+            Err(err) => {
+                // Cannot locate the next page after a bad page, skip rest of file
+                error!(
+                    "Failed to parse FsEvent file {}, err: {}",
+                    self.current_file, err
+                );
+                self.close_reader();
+                Some(Err(err))
+            }
+        }
+    }
+
+    /// Open the next file
+    fn next_file(&mut self) -> Option<Result<(), FsEventsError>> {
+        let file = self.files.next()?;
+        self.current_file = file;
+        match PageReader::from_path_with_options(&self.current_file, &self.options) {
+            Ok(reader) => {
+                self.reader = Some(reader);
+                Some(Ok(()))
+            }
+            Err(err) => {
+                error!(
+                    "Failed to open FsEvent file {}, err: {}",
+                    self.current_file, err
+                );
+                self.reader = None;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl Iterator for FsEventsIter {
+    type Item = Result<FsEvents, FsEventsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(mut record) = self.records.next() {
+                record.source_file = self.current_file.clone();
+                return Some(Ok(record));
+            }
+
+            if let Some(results) = self.next_page() {
+                if let Err(err) = results {
+                    return Some(Err(err));
+                }
+                continue;
+            }
+
+            if let Err(err) = self.next_file()? {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FsEventsIter;
+    use crate::{error::FsEventsError, options::ParserOptions, test_util::test_data};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_fsevents_iter() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let iter = FsEventsIter::new(vec![test_location.display().to_string()]);
+
+        let mut count = 0;
+        for record in iter {
+            let record = record.unwrap();
+            assert!(record.path.starts_with('/'));
+            count += 1;
+        }
+        assert!(count == 736);
+    }
+
+    #[test]
+    fn test_fsevents_iter_directory() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let mut iter = FsEventsIter::from_directory(&test_location.display().to_string()).unwrap();
+
+        let record = iter.next().unwrap().unwrap();
+        assert!(record.path == "/Users/bob/Downloads/invoice.pdf");
+        assert!(record.source_file.ends_with("0000000000030012"));
+        assert!(iter.current_file().ends_with("0000000000030012"));
+        let records: Vec<_> = iter.map(|record| record.unwrap()).collect();
+        assert!(records.len() == 4);
+        assert!(records[2].page == 1);
+        assert!(records[2].offset == 184);
+    }
+
+    #[test]
+    fn test_fsevents_iter_malformed() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/Malformed/malformed");
+        let mut dls1_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dls1_location.push("tests/test_data/DLS1/0000000000027d7a");
+
+        let iter = FsEventsIter::new(vec![
+            test_location.display().to_string(),
+            dls1_location.display().to_string(),
+        ]);
+        let results: Vec<_> = iter.collect();
+        assert!(matches!(results[0], Err(FsEventsError::TruncatedRecord)));
+        assert!(results[1..].iter().all(|record| record.is_ok()));
+        assert!(results.len() == 3);
+    }
+
+    #[test]
+    fn test_fsevents_iter_skip_corrupt_pages() {
+        let test_location = test_data("Malformed/malformed");
+        let mut iter = FsEventsIter::new(vec![test_location.display().to_string()])
+            .options(&ParserOptions::new().skip_corrupt_pages(true));
+        assert!(iter.by_ref().all(|record| record.is_ok()));
+        assert!(iter.report().skipped_pages.len() == 1);
+        assert!(iter.report().skipped_pages[0].path.ends_with("malformed"));
+    }
+
+    #[test]
+    fn test_fsevents_iter_max_size() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let iter = FsEventsIter::new(vec![test_location.display().to_string()])
+            .options(&ParserOptions::new().max_file_size(10));
+        let results: Vec<_> = iter.collect();
+        assert!(results.len() == 1);
+        assert!(matches!(
+            results[0],
+            Err(FsEventsError::FileTooLarge(_, 10))
+        ));
+    }
+}
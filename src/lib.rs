@@ -1,3 +1,60 @@
+#[cfg(feature = "apfs")]
+pub mod apfs;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "async")]
+pub mod async_parser;
+pub mod baseline;
+pub mod carve;
+#[cfg(feature = "filesystem")]
+pub mod checkpoint;
+#[cfg(feature = "filesystem")]
+pub mod collection;
+mod compression;
+pub mod dedup;
+#[cfg(feature = "filesystem")]
+pub mod discover;
+#[cfg(feature = "filesystem")]
+pub mod enrich;
+pub mod error;
+#[cfg(feature = "ewf")]
+pub mod ewf;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod flags;
 pub mod fsevents;
+#[cfg(feature = "filesystem")]
+mod image;
+#[cfg(feature = "filesystem")]
+pub mod inventory;
+#[cfg(feature = "filesystem")]
+pub mod iter;
+pub mod node;
+#[cfg(feature = "nodejs")]
+mod nodejs;
+pub mod options;
+#[cfg(all(feature = "osquery", unix))]
+pub mod osquery;
+pub mod output;
 pub mod parser;
+pub mod progress;
+pub mod report;
+#[cfg(feature = "filesystem")]
 mod size;
+pub mod sort;
+#[cfg(feature = "filesystem")]
+pub mod sparse;
+pub mod stats;
+pub mod stream;
+#[cfg(feature = "filesystem")]
+pub mod tail;
+#[cfg(all(test, feature = "filesystem"))]
+mod test_util;
+pub mod triage;
+#[cfg(feature = "filesystem")]
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "watch")]
+pub mod watch;
+pub mod watchlist;
@@ -0,0 +1,171 @@
+//! Group FsEvent records by node ID
+//!
+//! The node ID stays the same when a file is renamed or moved, so grouping by node follows a single
+//! file across creates, renames, and deletes. DLS1 records do not have a node ID and are skipped.
+//!
+//! On a live macOS system the node ID can also be looked up on the volume to find where a renamed
+//! or moved file is now.
+
+use crate::fsevents::FsEvents;
+use std::collections::BTreeMap;
+#[cfg(feature = "filesystem")]
+use std::collections::HashMap;
+
+/// Group records by node ID, each group is ordered by event ID
+pub fn group_by_node(fsevents: &[FsEvents]) -> BTreeMap<u64, Vec<&FsEvents>> {
+    let mut nodes: BTreeMap<u64, Vec<&FsEvents>> = BTreeMap::new();
+    for fsevent in fsevents {
+        if fsevent.node == 0 {
+            continue;
+        }
+        nodes.entry(fsevent.node).or_default().push(fsevent);
+    }
+
+    for records in nodes.values_mut() {
+        records.sort_by_key(|fsevent| fsevent.event_id);
+    }
+    nodes
+}
+
+/// Get every path associated with a group of node records, in the order they were first seen
+pub fn node_paths(records: &[&FsEvents]) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for fsevent in records {
+        if !paths.contains(&fsevent.path) {
+            paths.push(fsevent.path.clone());
+        }
+    }
+    paths
+}
+
+#[cfg(feature = "filesystem")]
+/// Add the current path of each record's node on the live volume mounted at root (ex: `/`)
+/// Records whose node no longer exists (or DLS1 records) keep an empty current path
+pub fn add_current_paths(fsevents: &mut [FsEvents], root: &str) {
+    let mut paths: HashMap<u64, Option<String>> = HashMap::new();
+    for fsevent in fsevents.iter_mut() {
+        if fsevent.node == 0 {
+            continue;
+        }
+        let path = paths
+            .entry(fsevent.node)
+            .or_insert_with(|| resolve_node_path(root, fsevent.node));
+        if let Some(path) = path {
+            fsevent.current_path = path.clone();
+        }
+    }
+}
+
+/// Find the current path of a node ID on the live volume mounted at root
+/// macOS exposes every file by device and node ID at `/.vol/<device>/<node>`. The file is opened
+/// there for metadata only and its current path is read back with `fcntl(F_GETPATH)`. Nothing is
+/// read, so access times on the evidence are not changed, a FIFO does not block, and a symlink
+/// is not followed (its path is not resolved)
+#[cfg(all(feature = "filesystem", target_os = "macos"))]
+pub fn resolve_node_path(root: &str, node: u64) -> Option<String> {
+    use std::{
+        ffi::CStr,
+        fs::{metadata, OpenOptions},
+        os::{
+            fd::AsRawFd,
+            raw::{c_char, c_int},
+            unix::fs::{MetadataExt, OpenOptionsExt},
+        },
+    };
+    extern "C" {
+        fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+    }
+    const F_GETPATH: c_int = 50;
+    const MAXPATHLEN: usize = 1024;
+    const O_NONBLOCK: c_int = 0x0004;
+    const O_NOFOLLOW: c_int = 0x0100;
+    // Open for event notifications only, the descriptor cannot read the file
+    const O_EVTONLY: c_int = 0x8000;
+
+    if node == 0 {
+        return None;
+    }
+    let device = metadata(root).ok()?.dev();
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(O_EVTONLY | O_NONBLOCK | O_NOFOLLOW)
+        .open(format!("/.vol/{}/{}", device, node))
+        .ok()?;
+    let mut buffer = [0 as c_char; MAXPATHLEN];
+    // Safety: F_GETPATH writes a null terminated path of at most MAXPATHLEN bytes to the buffer
+    let status = unsafe { fcntl(file.as_raw_fd(), F_GETPATH, buffer.as_mut_ptr()) };
+    if status == -1 {
+        return None;
+    }
+    // Safety: the buffer is null terminated by fcntl and is not modified after
+    let path = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+    Some(path.to_string_lossy().to_string())
+}
+
+/// Node IDs can only be looked up on macOS
+#[cfg(all(feature = "filesystem", not(target_os = "macos")))]
+pub fn resolve_node_path(_root: &str, _node: u64) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "filesystem")]
+    use super::{add_current_paths, resolve_node_path};
+    use super::{group_by_node, node_paths};
+    use crate::fsevents::FsEvents;
+
+    fn test_record(path: &str, event_id: u64, node: u64) -> FsEvents {
+        FsEvents {
+            path: path.to_string(),
+            event_id,
+            node,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_group_by_node() {
+        let fsevents = vec![
+            test_record("/Users/bob/Downloads/b.txt", 30, 5),
+            test_record("/Users/bob/Downloads/a.txt", 10, 5),
+            test_record("/private/tmp/c", 20, 6),
+            test_record("/sl-compat", 1, 0),
+            test_record("/Users/bob/Downloads/a.txt", 25, 5),
+        ];
+        let nodes = group_by_node(&fsevents);
+        assert!(nodes.len() == 2);
+
+        let records = &nodes[&5];
+        let event_ids: Vec<u64> = records.iter().map(|fsevent| fsevent.event_id).collect();
+        assert!(event_ids == vec![10, 25, 30]);
+
+        let paths = node_paths(records);
+        assert!(paths == vec!["/Users/bob/Downloads/a.txt", "/Users/bob/Downloads/b.txt"]);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_add_current_paths_no_node() {
+        let mut fsevents = vec![test_record("/sl-compat", 1, 0)];
+        add_current_paths(&mut fsevents, "/");
+        assert!(fsevents[0].current_path.is_empty());
+        assert!(resolve_node_path("/", 0).is_none());
+    }
+
+    #[test]
+    #[cfg(all(feature = "filesystem", target_os = "macos"))]
+    fn test_resolve_node_path() {
+        use std::os::unix::fs::MetadataExt;
+        use tempfile::tempdir;
+
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("resolve_node");
+        std::fs::write(&test_location, b"node").unwrap();
+        let node = std::fs::metadata(&test_location).unwrap().ino();
+
+        let mut fsevents = vec![test_record("/old/name", 1, node)];
+        add_current_paths(&mut fsevents, &temp.path().display().to_string());
+        assert!(fsevents[0].current_path.ends_with("resolve_node"));
+    }
+}
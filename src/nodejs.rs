@@ -0,0 +1,156 @@
+//! Node.js bindings, for Electron apps and Node scripts
+//!
+//! Built as a native addon with the `nodejs` feature (ex: with `napi build`). Records are returned
+//! as plain JavaScript objects with the same fields as the JSON output, except `event_id`, `node`,
+//! and `offset` are decimal strings. They are 64 bit values, and JavaScript numbers lose precision
+//! above 2^53 (use `BigInt(record.event_id)` to compare them). `FsEventsStream` reads a directory
+//! in batches, so large collections can be piped through a Node stream without holding every
+//! record in memory.
+
+use crate::{
+    error::FsEventsError,
+    fsevents::FsEvents,
+    iter::FsEventsIter,
+    options::ParserOptions,
+    parser::{self, parse_directory_with_options, parse_file_with_options},
+};
+use napi::{bindgen_prelude::Buffer, Error, Result};
+use napi_derive::napi;
+use serde_json::Value;
+
+// 64 bit record fields, returned as strings so they keep their precision in JavaScript
+const U64_FIELDS: [&str; 3] = ["event_id", "node", "offset"];
+
+/// Parse a FsEvent file and return its records
+#[napi]
+pub fn parse_file(path: String) -> Result<Value> {
+    to_js(parse_file_with_options(&path, &ParserOptions::new()))
+}
+
+/// Parse every FsEvent file in a directory and return the records
+#[napi]
+pub fn parse_directory(path: String) -> Result<Value> {
+    to_js(parse_directory_with_options(&path, &ParserOptions::new()))
+}
+
+/// Parse the bytes of a FsEvent file, compressed or already decompressed, and return the records
+#[napi]
+pub fn parse_bytes(data: Buffer) -> Result<Value> {
+    to_js(parser::parse_bytes(&data))
+}
+
+/// Read the records in a FsEvents directory in batches
+#[napi]
+pub struct FsEventsStream {
+    records: FsEventsIter, // Records left in the directory
+}
+
+#[napi]
+impl FsEventsStream {
+    /// Open a FsEvents directory for reading
+    #[napi(factory)]
+    pub fn open(directory: String) -> Result<FsEventsStream> {
+        let records = FsEventsIter::from_directory(&directory).map_err(to_error)?;
+        Ok(FsEventsStream { records })
+    }
+
+    /// Get up to `size` records, returns an empty array when every record has been read
+    #[napi]
+    pub fn next_batch(&mut self, size: u32) -> Result<Value> {
+        let mut batch: Vec<FsEvents> = Vec::new();
+        for record in self.records.by_ref().take(size as usize) {
+            batch.push(record.map_err(to_error)?);
+        }
+        to_js(Ok(batch))
+    }
+
+    /// FsEvent file the stream is currently reading from
+    #[napi(getter)]
+    pub fn current_file(&self) -> String {
+        self.records.current_file().to_string()
+    }
+}
+
+/// Convert parsed records to a value napi converts to an array of JavaScript objects
+fn to_js(results: std::result::Result<Vec<FsEvents>, FsEventsError>) -> Result<Value> {
+    let fsevents_data = results.map_err(to_error)?;
+    let mut records =
+        serde_json::to_value(fsevents_data).map_err(|err| Error::from_reason(err.to_string()))?;
+    for record in records.as_array_mut().into_iter().flatten() {
+        for field in U64_FIELDS {
+            if let Some(value) = record.get_mut(field) {
+                *value = Value::String(value.to_string());
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Convert a parser error to a JavaScript error
+fn to_error(err: FsEventsError) -> Error {
+    Error::from_reason(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_bytes, parse_directory, parse_file, to_js, FsEventsStream};
+    use crate::fsevents::FsEvents;
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn test_parse_file() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let path = test_location.display().to_string();
+
+        let records = parse_file(path.clone()).unwrap();
+        assert!(records.as_array().unwrap().len() == 5);
+        assert!(records[0]["source_file"] == path.as_str());
+        assert!(records[0]["event_id"] == "196608");
+        assert!(records[0]["raw_flags"].is_number());
+
+        let records = parse_bytes(fs::read(&test_location).unwrap().into()).unwrap();
+        assert!(records.as_array().unwrap().len() == 5);
+    }
+
+    #[test]
+    fn test_to_js_u64_fields() {
+        let fsevents = vec![FsEvents {
+            event_id: u64::MAX,
+            node: (1 << 53) + 1,
+            ..Default::default()
+        }];
+        let records = to_js(Ok(fsevents)).unwrap();
+        assert!(records[0]["event_id"] == "18446744073709551615");
+        assert!(records[0]["node"] == "9007199254740993");
+        assert!(records[0]["offset"] == "0");
+    }
+
+    #[test]
+    fn test_parse_directory_errors() {
+        let results = parse_directory(String::from("/tmp/fsevents_test_missing_directory"));
+        assert!(results.is_err());
+        assert!(
+            FsEventsStream::open(String::from("/tmp/fsevents_test_missing_directory")).is_err()
+        );
+    }
+
+    #[test]
+    fn test_fsevents_stream() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let mut stream = FsEventsStream::open(test_location.display().to_string()).unwrap();
+
+        let mut total = 0;
+        loop {
+            let batch = stream.next_batch(1000).unwrap();
+            let size = batch.as_array().unwrap().len();
+            if size == 0 {
+                break;
+            }
+            assert!(size <= 1000);
+            total += size;
+        }
+        assert!(total == 5736);
+    }
+}
@@ -0,0 +1,434 @@
+//! Options to control how FsEvent files are discovered, parsed, and filtered
+
+use crate::{
+    error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents, progress::ProgressObserver,
+};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+#[cfg(feature = "filesystem")]
+use std::sync::atomic::Ordering;
+use std::sync::{atomic::AtomicBool, Arc};
+
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    pub(crate) max_file_size: u64, // Skip FsEvent files larger than this size
+    pub(crate) include_legacy: bool, // Also parse files at the legacy /.fseventsd path
+    pub(crate) skip_uuid: bool,    // Skip the fseventsd-uuid file in a directory
+    pub(crate) lenient: bool,      // Log and skip files that fail to parse
+    pub(crate) skip_corrupt_pages: bool, // Skip pages that fail to parse and continue with the file
+    pub(crate) resync_records: bool, // Skip bad records and continue with the rest of the page
+    pub(crate) attach_uuid: bool,  // Add the volume UUID from fseventsd-uuid to each record
+    pub(crate) approx_times: bool, // Add approximate time bounds from file modified times
+    pub(crate) estimate_times: bool, // Add interpolated time windows from file modified times and names
+    pub(crate) live_status: bool,    // Check each record path on the live filesystem
+    pub(crate) resolve_nodes: bool, // Look up the current path of each record node on the live volume
+    pub(crate) path_prefixes: Vec<String>, // Only keep records under these path prefixes
+    pub(crate) include_flags: FsEventFlags, // Only keep records with at least one of these flags
+    pub(crate) exclude_flags: FsEventFlags, // Drop records with any of these flags
+    pub(crate) min_event_id: u64,   // Drop records with an event ID below this value
+    pub(crate) max_event_id: u64,   // Drop records with an event ID above this value
+    pub(crate) include_globs: Vec<GlobMatcher>, // Only keep records with a path matching a glob
+    pub(crate) exclude_globs: Vec<GlobMatcher>, // Drop records with a path matching a glob
+    pub(crate) include_regex: Vec<Regex>, // Only keep records with a path matching a regex
+    pub(crate) exclude_regex: Vec<Regex>, // Drop records with a path matching a regex
+    pub(crate) observer: Option<Arc<dyn ProgressObserver>>, // Notified as files are parsed
+    pub(crate) cancel: Option<Arc<AtomicBool>>, // Stop parsing when set to true
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            max_file_size: 2147483648, // 2GB
+            include_legacy: false,
+            skip_uuid: true,
+            lenient: true,
+            skip_corrupt_pages: false,
+            resync_records: false,
+            attach_uuid: false,
+            approx_times: false,
+            estimate_times: false,
+            live_status: false,
+            resolve_nodes: false,
+            path_prefixes: Vec::new(),
+            include_flags: FsEventFlags::empty(),
+            exclude_flags: FsEventFlags::empty(),
+            min_event_id: 0,
+            max_event_id: u64::MAX,
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
+            include_regex: Vec::new(),
+            exclude_regex: Vec::new(),
+            observer: None,
+            cancel: None,
+        }
+    }
+}
+
+impl ParserOptions {
+    /// Create options with the default parser behavior
+    pub fn new() -> ParserOptions {
+        ParserOptions::default()
+    }
+
+    /// Set the max size of a FsEvent file that will be parsed
+    pub fn max_file_size(mut self, size: u64) -> ParserOptions {
+        self.max_file_size = size;
+        self
+    }
+
+    /// Also parse FsEvent files at the legacy /.fseventsd path
+    pub fn include_legacy(mut self, include: bool) -> ParserOptions {
+        self.include_legacy = include;
+        self
+    }
+
+    /// Skip the fseventsd-uuid file when listing a directory
+    pub fn skip_uuid(mut self, skip: bool) -> ParserOptions {
+        self.skip_uuid = skip;
+        self
+    }
+
+    /// Log and skip files that fail to parse (lenient) or return the first error (strict)
+    pub fn lenient(mut self, lenient: bool) -> ParserOptions {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Skip pages that fail to parse and keep parsing the rest of the file
+    /// Without this a corrupt page stops the file, and any earlier records in it are dropped
+    pub fn skip_corrupt_pages(mut self, skip: bool) -> ParserOptions {
+        self.skip_corrupt_pages = skip;
+        self
+    }
+
+    /// Skip bad records in a page and keep parsing the records after them
+    /// The page is searched for the next null terminated path followed by valid flags
+    pub fn resync_records(mut self, resync: bool) -> ParserOptions {
+        self.resync_records = resync;
+        self
+    }
+
+    /// Add the volume UUID from the directory's fseventsd-uuid file to each record
+    pub fn attach_uuid(mut self, attach: bool) -> ParserOptions {
+        self.attach_uuid = attach;
+        self
+    }
+
+    /// Add approximate time bounds to each record from the FsEvent file modified times
+    pub fn approx_times(mut self, approx: bool) -> ParserOptions {
+        self.approx_times = approx;
+        self
+    }
+
+    /// Add interpolated time windows to each record from the FsEvent file modified times and names
+    pub fn estimate_times(mut self, estimate: bool) -> ParserOptions {
+        self.estimate_times = estimate;
+        self
+    }
+
+    /// Check if each record's path still exists on the live filesystem, and get its current size and times
+    /// Paths are checked under the volume containing the FsEvents directory. Only useful on the system
+    /// being examined, not on collected FsEvent files
+    pub fn live_status(mut self, live: bool) -> ParserOptions {
+        self.live_status = live;
+        self
+    }
+
+    /// Look up the current path of each record's node ID on the live volume, to find where renamed
+    /// or moved files are now. Only supported on macOS
+    pub fn resolve_nodes(mut self, resolve: bool) -> ParserOptions {
+        self.resolve_nodes = resolve;
+        self
+    }
+
+    /// Only keep records with a path starting with the prefix. Can be called multiple times
+    pub fn path_prefix(mut self, prefix: &str) -> ParserOptions {
+        self.path_prefixes.push(prefix.to_string());
+        self
+    }
+
+    /// Only keep records that have at least one of the flags (ex: `Removed | Renamed`)
+    pub fn include_flags(mut self, flags: FsEventFlags) -> ParserOptions {
+        self.include_flags = flags;
+        self
+    }
+
+    /// Drop records that have any of the flags (ex: `IsDirectory`)
+    pub fn exclude_flags(mut self, flags: FsEventFlags) -> ParserOptions {
+        self.exclude_flags = flags;
+        self
+    }
+
+    /// Drop records with an event ID below the value
+    pub fn min_event_id(mut self, event_id: u64) -> ParserOptions {
+        self.min_event_id = event_id;
+        self
+    }
+
+    /// Drop records with an event ID above the value
+    pub fn max_event_id(mut self, event_id: u64) -> ParserOptions {
+        self.max_event_id = event_id;
+        self
+    }
+
+    /// Only keep records with a path matching the glob (ex: `/Users/*/Downloads/**`)
+    /// `*` does not match path separators, `**` matches any number of directories
+    pub fn include_glob(mut self, pattern: &str) -> Result<ParserOptions, FsEventsError> {
+        self.include_globs.push(compile_glob(pattern)?);
+        Ok(self)
+    }
+
+    /// Drop records with a path matching the glob
+    pub fn exclude_glob(mut self, pattern: &str) -> Result<ParserOptions, FsEventsError> {
+        self.exclude_globs.push(compile_glob(pattern)?);
+        Ok(self)
+    }
+
+    /// Only keep records with a path matching the regex (ex: `\.(sh|command)$`)
+    pub fn include_regex(mut self, pattern: &str) -> Result<ParserOptions, FsEventsError> {
+        self.include_regex.push(compile_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Drop records with a path matching the regex
+    pub fn exclude_regex(mut self, pattern: &str) -> Result<ParserOptions, FsEventsError> {
+        self.exclude_regex.push(compile_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Notify the observer about parsing progress
+    pub fn progress_observer(mut self, observer: Arc<dyn ProgressObserver>) -> ParserOptions {
+        self.observer = Some(observer);
+        self
+    }
+
+    #[cfg(feature = "filesystem")]
+    /// Send a progress event to the observer, if there is one
+    pub(crate) fn notify<F: Fn(&dyn ProgressObserver)>(&self, event: F) {
+        if let Some(observer) = &self.observer {
+            event(observer.as_ref());
+        }
+    }
+
+    /// Stop parsing when the flag is set to true. Checked between files and between pages
+    /// Records parsed before the flag was set are still returned
+    pub fn cancel_flag(mut self, cancel: Arc<AtomicBool>) -> ParserOptions {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    #[cfg(feature = "filesystem")]
+    /// Check if parsing has been cancelled
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Check if a record passes the path and flag filters
+    pub fn matches(&self, fsevent: &FsEvents) -> bool {
+        if fsevent.event_id < self.min_event_id || fsevent.event_id > self.max_event_id {
+            return false;
+        }
+
+        if !self.path_prefixes.is_empty()
+            && !self
+                .path_prefixes
+                .iter()
+                .any(|prefix| fsevent.path.starts_with(prefix))
+        {
+            return false;
+        }
+
+        let event_flags = fsevent.event_flags();
+        if !self.include_flags.is_empty() && !event_flags.intersects(self.include_flags) {
+            return false;
+        }
+
+        if event_flags.intersects(self.exclude_flags) {
+            return false;
+        }
+
+        if !self.include_globs.is_empty()
+            && !self
+                .include_globs
+                .iter()
+                .any(|glob| glob.is_match(&fsevent.path))
+        {
+            return false;
+        }
+
+        if self
+            .exclude_globs
+            .iter()
+            .any(|glob| glob.is_match(&fsevent.path))
+        {
+            return false;
+        }
+
+        if !self.include_regex.is_empty()
+            && !self
+                .include_regex
+                .iter()
+                .any(|regex| regex.is_match(&fsevent.path))
+        {
+            return false;
+        }
+
+        if self
+            .exclude_regex
+            .iter()
+            .any(|regex| regex.is_match(&fsevent.path))
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// Compile a path glob
+fn compile_glob(pattern: &str) -> Result<GlobMatcher, FsEventsError> {
+    let glob = GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|err| FsEventsError::InvalidPattern(err.to_string()))?;
+    Ok(glob.compile_matcher())
+}
+
+/// Compile a path regex
+fn compile_regex(pattern: &str) -> Result<Regex, FsEventsError> {
+    Regex::new(pattern).map_err(|err| FsEventsError::InvalidPattern(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParserOptions;
+    use crate::{error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents};
+
+    fn test_record() -> FsEvents {
+        FsEvents {
+            flags: String::from("Created,Removed,IsFile"),
+            raw_flags: 0x800003,
+            path: String::from("/Users/bob/Downloads/invoice.pdf"),
+            node: 0,
+            event_id: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_options() {
+        let options = ParserOptions::default();
+        assert!(options.max_file_size == 2147483648);
+        assert!(!options.include_legacy);
+        assert!(options.skip_uuid);
+        assert!(options.lenient);
+        assert!(!options.attach_uuid);
+        assert!(options.matches(&test_record()));
+    }
+
+    #[test]
+    fn test_options_builder() {
+        let options = ParserOptions::new()
+            .max_file_size(1024)
+            .include_legacy(true)
+            .skip_uuid(false)
+            .lenient(false)
+            .attach_uuid(true)
+            .approx_times(true);
+        assert!(options.max_file_size == 1024);
+        assert!(options.approx_times);
+        assert!(options.attach_uuid);
+        assert!(options.include_legacy);
+        assert!(!options.skip_uuid);
+        assert!(!options.lenient);
+    }
+
+    #[test]
+    fn test_matches_path_prefix() {
+        let options = ParserOptions::new()
+            .path_prefix("/Library")
+            .path_prefix("/Users/bob");
+        assert!(options.matches(&test_record()));
+
+        let options = ParserOptions::new().path_prefix("/private");
+        assert!(!options.matches(&test_record()));
+    }
+
+    #[test]
+    fn test_matches_event_id() {
+        let options = ParserOptions::new().min_event_id(10).max_event_id(10);
+        assert!(options.matches(&test_record()));
+
+        let options = ParserOptions::new().min_event_id(11);
+        assert!(!options.matches(&test_record()));
+
+        let options = ParserOptions::new().max_event_id(9);
+        assert!(!options.matches(&test_record()));
+    }
+
+    #[test]
+    fn test_matches_globs() {
+        let options = ParserOptions::new()
+            .include_glob("/Users/*/Downloads/**")
+            .unwrap();
+        assert!(options.matches(&test_record()));
+
+        let options = ParserOptions::new().include_glob("/Users/*.pdf").unwrap();
+        assert!(!options.matches(&test_record()));
+
+        let options = ParserOptions::new()
+            .include_glob("/Users/**")
+            .unwrap()
+            .exclude_glob("**/*.pdf")
+            .unwrap();
+        assert!(!options.matches(&test_record()));
+    }
+
+    #[test]
+    fn test_matches_regex() {
+        let options = ParserOptions::new()
+            .include_regex(r"(?i)/downloads/.*\.pdf$")
+            .unwrap();
+        assert!(options.matches(&test_record()));
+
+        let options = ParserOptions::new()
+            .include_regex("invoice")
+            .unwrap()
+            .exclude_regex("^/Users/")
+            .unwrap();
+        assert!(!options.matches(&test_record()));
+
+        let results = ParserOptions::new().exclude_regex("(unclosed");
+        assert!(matches!(results, Err(FsEventsError::InvalidPattern(_))));
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let results = ParserOptions::new().include_glob("/Users/[bob");
+        assert!(matches!(results, Err(FsEventsError::InvalidPattern(_))));
+    }
+
+    #[test]
+    fn test_matches_flags() {
+        let options =
+            ParserOptions::new().include_flags(FsEventFlags::RENAMED | FsEventFlags::REMOVED);
+        assert!(options.matches(&test_record()));
+
+        let options = ParserOptions::new().include_flags(FsEventFlags::IS_DIRECTORY);
+        assert!(!options.matches(&test_record()));
+
+        let options = ParserOptions::new()
+            .include_flags(FsEventFlags::REMOVED)
+            .path_prefix("/private");
+        assert!(!options.matches(&test_record()));
+
+        let options = ParserOptions::new()
+            .include_flags(FsEventFlags::REMOVED | FsEventFlags::RENAMED)
+            .exclude_flags(FsEventFlags::IS_FILE);
+        assert!(!options.matches(&test_record()));
+
+        let options = ParserOptions::new().exclude_flags(FsEventFlags::IS_DIRECTORY);
+        assert!(options.matches(&test_record()));
+    }
+}
@@ -0,0 +1,735 @@
+//! Run as an osquery extension with a `fsevents_records` table
+//!
+//! osquery starts extensions with `--socket <path>`. The extension registers its table with the
+//! osquery extension manager on that socket, then serves table calls on `<path>.<uuid>`. Both use
+//! Thrift's binary protocol over a Unix socket.
+//!
+//! Queries parse the system `.fseventsd` directory unless the hidden `directory` column is
+//! constrained (ex: `WHERE directory = '/Volumes/USB/.fseventsd'`). `event_id` ranges and `path`
+//! equality are used to skip records while parsing, osquery applies every other constraint.
+
+use crate::{error::FsEventsError, options::ParserOptions, parser::parse_directories};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs::remove_file,
+    io::ErrorKind,
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use thrift::{
+    protocol::{
+        TBinaryInputProtocol, TBinaryOutputProtocol, TFieldIdentifier, TInputProtocol,
+        TListIdentifier, TMapIdentifier, TMessageIdentifier, TMessageType, TOutputProtocol,
+        TStructIdentifier, TType,
+    },
+    transport::{TBufferedReadTransport, TBufferedWriteTransport},
+    ApplicationError, ApplicationErrorKind,
+};
+
+/// Name of the table the extension adds
+pub const TABLE_NAME: &str = "fsevents_records";
+const EXTENSION_NAME: &str = "fsevents";
+// osquery SDK version the extension was written against
+const SDK_VERSION: &str = "5.0.0";
+const DEFAULT_DIRECTORY: &str = "/System/Volumes/Data/.fseventsd";
+
+/// Table columns and their osquery types. `directory` is hidden and only used in constraints
+const COLUMNS: [(&str, &str); 11] = [
+    ("path", "TEXT"),
+    ("flags", "TEXT"),
+    ("raw_flags", "INTEGER"),
+    ("node", "BIGINT"),
+    ("event_id", "BIGINT"),
+    ("extra_id", "INTEGER"),
+    ("source_file", "TEXT"),
+    ("volume_uuid", "TEXT"),
+    ("approx_earliest_time", "BIGINT"),
+    ("approx_latest_time", "BIGINT"),
+    ("directory", "TEXT"),
+];
+// osquery column options, ADDITIONAL | HIDDEN
+const DIRECTORY_OPTIONS: &str = "20";
+
+// osquery constraint operators
+const EQUALS: u64 = 2;
+const GREATER_THAN: u64 = 4;
+const LESS_THAN_OR_EQUALS: u64 = 8;
+const LESS_THAN: u64 = 16;
+const GREATER_THAN_OR_EQUALS: u64 = 32;
+
+/// Table row, column name to value
+pub type Row = BTreeMap<String, String>;
+
+/// Get the table columns in the form osquery expects when a table is registered
+pub fn table_columns() -> Vec<Row> {
+    COLUMNS
+        .iter()
+        .map(|(name, column_type)| {
+            let options = if *name == "directory" {
+                DIRECTORY_OPTIONS
+            } else {
+                "0"
+            };
+            Row::from([
+                (String::from("id"), String::from("column")),
+                (String::from("name"), name.to_string()),
+                (String::from("type"), column_type.to_string()),
+                (String::from("op"), options.to_string()),
+            ])
+        })
+        .collect()
+}
+
+/// Generate table rows for an osquery query context (JSON with the query constraints)
+pub fn generate(context: &str) -> Result<Vec<Row>, FsEventsError> {
+    let context: Value = serde_json::from_str(context).unwrap_or(Value::Null);
+    let mut options = ParserOptions::new().attach_uuid(true).approx_times(true);
+    let mut directories: Vec<String> = Vec::new();
+    let (mut min_event_id, mut max_event_id) = (0, u64::MAX);
+
+    let constraints = context["constraints"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    for constraint in &constraints {
+        let name = constraint["name"].as_str().unwrap_or_default();
+        for item in constraint["list"].as_array().unwrap_or(&Vec::new()) {
+            let op = item["op"].as_u64().unwrap_or_default();
+            let expr = item["expr"].as_str().unwrap_or_default();
+            match (name, op) {
+                ("directory", EQUALS) => directories.push(expr.to_string()),
+                ("path", EQUALS) => options = options.path_prefix(expr),
+                ("event_id", _) => {
+                    let event_id = match expr.parse::<u64>() {
+                        Ok(result) => result,
+                        Err(_) => continue,
+                    };
+                    match op {
+                        EQUALS => {
+                            min_event_id = min_event_id.max(event_id);
+                            max_event_id = max_event_id.min(event_id);
+                        }
+                        GREATER_THAN => min_event_id = min_event_id.max(event_id.saturating_add(1)),
+                        GREATER_THAN_OR_EQUALS => min_event_id = min_event_id.max(event_id),
+                        LESS_THAN => max_event_id = max_event_id.min(event_id.saturating_sub(1)),
+                        LESS_THAN_OR_EQUALS => max_event_id = max_event_id.min(event_id),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if directories.is_empty() {
+        directories.push(DEFAULT_DIRECTORY.to_string());
+    }
+    let options = options
+        .min_event_id(min_event_id)
+        .max_event_id(max_event_id);
+
+    let fsevents = parse_directories(&directories, &options)?;
+    let rows = fsevents
+        .iter()
+        .map(|fsevent| {
+            let directory = Path::new(&fsevent.source_file)
+                .parent()
+                .map(|parent| parent.display().to_string())
+                .unwrap_or_default();
+            Row::from([
+                (String::from("path"), fsevent.path.clone()),
+                (String::from("flags"), fsevent.event_flags().to_string()),
+                (String::from("raw_flags"), fsevent.raw_flags.to_string()),
+                (String::from("node"), fsevent.node.to_string()),
+                (String::from("event_id"), fsevent.event_id.to_string()),
+                (String::from("extra_id"), fsevent.extra_id.to_string()),
+                (String::from("source_file"), fsevent.source_file.clone()),
+                (String::from("volume_uuid"), fsevent.volume_uuid.clone()),
+                (
+                    String::from("approx_earliest_time"),
+                    fsevent.approx_earliest_time.to_string(),
+                ),
+                (
+                    String::from("approx_latest_time"),
+                    fsevent.approx_latest_time.to_string(),
+                ),
+                (String::from("directory"), directory),
+            ])
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// osquery extension serving the `fsevents_records` table
+pub struct OsqueryExtension {
+    socket: String,     // osquery extension manager socket
+    interval: Duration, // How often to check that osquery is still running
+    timeout: Duration,  // How long to wait for the extension manager socket
+}
+
+impl OsqueryExtension {
+    /// Create an extension that registers with the extension manager at the socket path
+    pub fn new(socket: &str) -> OsqueryExtension {
+        OsqueryExtension {
+            socket: socket.to_string(),
+            interval: Duration::from_secs(3),
+            timeout: Duration::from_secs(3),
+        }
+    }
+
+    /// Seconds between checks that osquery is still running. The extension stops when it is not
+    pub fn interval(mut self, seconds: u64) -> OsqueryExtension {
+        self.interval = Duration::from_secs(seconds.max(1));
+        self
+    }
+
+    /// Seconds to wait for the extension manager socket to exist
+    pub fn timeout(mut self, seconds: u64) -> OsqueryExtension {
+        self.timeout = Duration::from_secs(seconds);
+        self
+    }
+
+    /// Register the table and serve queries until osquery exits or asks the extension to stop
+    pub fn run(&self) -> Result<(), FsEventsError> {
+        let start = Instant::now();
+        while !Path::new(&self.socket).exists() && start.elapsed() < self.timeout {
+            thread::sleep(Duration::from_millis(200));
+        }
+        let manager = UnixStream::connect(&self.socket)?;
+        let uuid = register_extension(&manager)?;
+
+        let path = format!("{}.{}", self.socket, uuid);
+        if Path::new(&path).exists() {
+            remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut last_ping = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    let stop = stop.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = serve_connection(stream, uuid, &stop) {
+                            log::warn!("osquery connection failed, err: {}", err);
+                        }
+                    });
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(100));
+                }
+                Err(err) => return Err(FsEventsError::Io(err)),
+            }
+
+            if last_ping.elapsed() >= self.interval {
+                if ping_manager(&manager).is_err() {
+                    log::info!("osquery extension manager is gone, stopping");
+                    break;
+                }
+                last_ping = Instant::now();
+            }
+        }
+        let _ = remove_file(&path);
+        Ok(())
+    }
+}
+
+/// Register the extension and its table with the extension manager, returns the extension UUID
+pub(crate) fn register_extension(stream: &UnixStream) -> Result<i64, FsEventsError> {
+    let (mut input, mut output) = protocols(stream)?;
+    output.write_message_begin(&TMessageIdentifier::new(
+        "registerExtension",
+        TMessageType::Call,
+        1,
+    ))?;
+    output.write_struct_begin(&TStructIdentifier::new("registerExtension_args"))?;
+
+    output.write_field_begin(&TFieldIdentifier::new("info", TType::Struct, 1))?;
+    output.write_struct_begin(&TStructIdentifier::new("InternalExtensionInfo"))?;
+    let info = [
+        EXTENSION_NAME,
+        env!("CARGO_PKG_VERSION"),
+        SDK_VERSION,
+        SDK_VERSION,
+    ];
+    let names = ["name", "version", "sdk_version", "min_sdk_version"];
+    for (id, (name, value)) in names.iter().zip(info).enumerate() {
+        output.write_field_begin(&TFieldIdentifier::new(*name, TType::String, id as i16 + 1))?;
+        output.write_string(value)?;
+        output.write_field_end()?;
+    }
+    output.write_field_stop()?;
+    output.write_struct_end()?;
+    output.write_field_end()?;
+
+    // Registry is {"table": {"fsevents_records": columns}}
+    output.write_field_begin(&TFieldIdentifier::new("registry", TType::Map, 2))?;
+    output.write_map_begin(&TMapIdentifier::new(TType::String, TType::Map, 1))?;
+    output.write_string("table")?;
+    output.write_map_begin(&TMapIdentifier::new(TType::String, TType::List, 1))?;
+    output.write_string(TABLE_NAME)?;
+    write_rows(&mut output, &table_columns())?;
+    output.write_map_end()?;
+    output.write_map_end()?;
+    output.write_field_end()?;
+
+    output.write_field_stop()?;
+    output.write_struct_end()?;
+    output.write_message_end()?;
+    output.flush()?;
+
+    let (code, message, uuid) = read_status_reply(&mut input)?;
+    if code != 0 {
+        return Err(FsEventsError::Extension(format!(
+            "Failed to register extension: {}",
+            message
+        )));
+    }
+    Ok(uuid)
+}
+
+/// Check that the extension manager is still running
+fn ping_manager(stream: &UnixStream) -> Result<(), FsEventsError> {
+    let (mut input, mut output) = protocols(stream)?;
+    output.write_message_begin(&TMessageIdentifier::new("ping", TMessageType::Call, 2))?;
+    output.write_struct_begin(&TStructIdentifier::new("ping_args"))?;
+    output.write_field_stop()?;
+    output.write_struct_end()?;
+    output.write_message_end()?;
+    output.flush()?;
+    read_status_reply(&mut input).map(|_| ())
+}
+
+/// Serve extension calls from osquery until the connection closes
+pub(crate) fn serve_connection(
+    stream: UnixStream,
+    uuid: i64,
+    stop: &AtomicBool,
+) -> Result<(), FsEventsError> {
+    let (mut input, mut output) = protocols(&stream)?;
+    loop {
+        let message = match input.read_message_begin() {
+            Ok(result) => result,
+            // osquery closed the connection
+            Err(thrift::Error::Transport(_)) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let reply = |name: &str| {
+            TMessageIdentifier::new(name, TMessageType::Reply, message.sequence_number)
+        };
+
+        match message.name.as_str() {
+            "ping" => {
+                input.skip(TType::Struct)?;
+                input.read_message_end()?;
+                output.write_message_begin(&reply("ping"))?;
+                write_result(&mut output, |output| write_status(output, 0, "OK", uuid))?;
+            }
+            "call" => {
+                let (registry, item, request) = read_call_args(&mut input)?;
+                input.read_message_end()?;
+                let (code, message, rows) = handle_call(&registry, &item, &request);
+                output.write_message_begin(&reply("call"))?;
+                write_result(&mut output, |output| {
+                    output.write_struct_begin(&TStructIdentifier::new("ExtensionResponse"))?;
+                    output.write_field_begin(&TFieldIdentifier::new("status", TType::Struct, 1))?;
+                    write_status(output, code, &message, uuid)?;
+                    output.write_field_end()?;
+                    output.write_field_begin(&TFieldIdentifier::new("response", TType::List, 2))?;
+                    write_rows(output, &rows)?;
+                    output.write_field_end()?;
+                    output.write_field_stop()?;
+                    output.write_struct_end()
+                })?;
+            }
+            "shutdown" => {
+                input.skip(TType::Struct)?;
+                input.read_message_end()?;
+                output.write_message_begin(&reply("shutdown"))?;
+                output.write_struct_begin(&TStructIdentifier::new("shutdown_result"))?;
+                output.write_field_stop()?;
+                output.write_struct_end()?;
+                output.write_message_end()?;
+                output.flush()?;
+                stop.store(true, Ordering::Relaxed);
+                return Ok(());
+            }
+            name => {
+                input.skip(TType::Struct)?;
+                input.read_message_end()?;
+                let err = ApplicationError::new(
+                    ApplicationErrorKind::UnknownMethod,
+                    format!("Unknown method {}", name),
+                );
+                output.write_message_begin(&TMessageIdentifier::new(
+                    name,
+                    TMessageType::Exception,
+                    message.sequence_number,
+                ))?;
+                thrift::Error::write_application_error_to_out_protocol(&err, &mut output)?;
+                output.write_message_end()?;
+                output.flush()?;
+            }
+        }
+    }
+}
+
+/// Handle a registry call, returns the status code, message, and rows
+fn handle_call(registry: &str, item: &str, request: &Row) -> (i32, String, Vec<Row>) {
+    if registry != "table" || item != TABLE_NAME {
+        return (
+            1,
+            format!("Unknown registry item {}/{}", registry, item),
+            Vec::new(),
+        );
+    }
+    let action = request
+        .get("action")
+        .map(String::as_str)
+        .unwrap_or_default();
+    match action {
+        "columns" => (0, String::from("OK"), table_columns()),
+        "generate" => {
+            let context = request.get("context").map(String::as_str).unwrap_or("{}");
+            match generate(context) {
+                Ok(rows) => (0, String::from("OK"), rows),
+                Err(err) => (1, err.to_string(), Vec::new()),
+            }
+        }
+        _ => (1, format!("Unknown table action {}", action), Vec::new()),
+    }
+}
+
+type InputProtocol = TBinaryInputProtocol<TBufferedReadTransport<UnixStream>>;
+type OutputProtocol = TBinaryOutputProtocol<TBufferedWriteTransport<UnixStream>>;
+
+/// Create buffered binary protocols that read from and write to a socket
+fn protocols(stream: &UnixStream) -> Result<(InputProtocol, OutputProtocol), FsEventsError> {
+    Ok((
+        TBinaryInputProtocol::new(TBufferedReadTransport::new(stream.try_clone()?), true),
+        TBinaryOutputProtocol::new(TBufferedWriteTransport::new(stream.try_clone()?), true),
+    ))
+}
+
+/// Write a reply result struct, the return value is field 0
+fn write_result<F>(output: &mut dyn TOutputProtocol, write_value: F) -> thrift::Result<()>
+where
+    F: FnOnce(&mut dyn TOutputProtocol) -> thrift::Result<()>,
+{
+    output.write_struct_begin(&TStructIdentifier::new("result"))?;
+    output.write_field_begin(&TFieldIdentifier::new("success", TType::Struct, 0))?;
+    write_value(output)?;
+    output.write_field_end()?;
+    output.write_field_stop()?;
+    output.write_struct_end()?;
+    output.write_message_end()?;
+    output.flush()
+}
+
+/// Write an ExtensionStatus struct
+fn write_status(
+    output: &mut dyn TOutputProtocol,
+    code: i32,
+    message: &str,
+    uuid: i64,
+) -> thrift::Result<()> {
+    output.write_struct_begin(&TStructIdentifier::new("ExtensionStatus"))?;
+    output.write_field_begin(&TFieldIdentifier::new("code", TType::I32, 1))?;
+    output.write_i32(code)?;
+    output.write_field_end()?;
+    output.write_field_begin(&TFieldIdentifier::new("message", TType::String, 2))?;
+    output.write_string(message)?;
+    output.write_field_end()?;
+    output.write_field_begin(&TFieldIdentifier::new("uuid", TType::I64, 3))?;
+    output.write_i64(uuid)?;
+    output.write_field_end()?;
+    output.write_field_stop()?;
+    output.write_struct_end()
+}
+
+/// Write a list of string maps
+fn write_rows(output: &mut dyn TOutputProtocol, rows: &[Row]) -> thrift::Result<()> {
+    output.write_list_begin(&TListIdentifier::new(TType::Map, rows.len() as i32))?;
+    for row in rows {
+        output.write_map_begin(&TMapIdentifier::new(
+            TType::String,
+            TType::String,
+            row.len() as i32,
+        ))?;
+        for (key, value) in row {
+            output.write_string(key)?;
+            output.write_string(value)?;
+        }
+        output.write_map_end()?;
+    }
+    output.write_list_end()
+}
+
+/// Read the (registry, item, request) arguments of a call
+fn read_call_args(input: &mut dyn TInputProtocol) -> thrift::Result<(String, String, Row)> {
+    let (mut registry, mut item, mut request) = (String::new(), String::new(), Row::new());
+    input.read_struct_begin()?;
+    loop {
+        let field = input.read_field_begin()?;
+        match (field.id, field.field_type) {
+            (_, TType::Stop) => break,
+            (Some(1), TType::String) => registry = input.read_string()?,
+            (Some(2), TType::String) => item = input.read_string()?,
+            (Some(3), TType::Map) => {
+                let map = input.read_map_begin()?;
+                for _ in 0..map.size {
+                    let key = input.read_string()?;
+                    request.insert(key, input.read_string()?);
+                }
+                input.read_map_end()?;
+            }
+            (_, field_type) => input.skip(field_type)?,
+        }
+        input.read_field_end()?;
+    }
+    input.read_struct_end()?;
+    Ok((registry, item, request))
+}
+
+/// Read a reply with an ExtensionStatus result, returns the status code, message, and UUID
+fn read_status_reply(input: &mut dyn TInputProtocol) -> Result<(i32, String, i64), FsEventsError> {
+    let message = input.read_message_begin()?;
+    if message.message_type == TMessageType::Exception {
+        let err = thrift::Error::read_application_error_from_in_protocol(input)?;
+        input.read_message_end()?;
+        return Err(FsEventsError::Extension(err.message));
+    }
+
+    let (mut code, mut status_message, mut uuid) = (-1, String::new(), 0);
+    input.read_struct_begin()?;
+    loop {
+        let field = input.read_field_begin()?;
+        match (field.id, field.field_type) {
+            (_, TType::Stop) => break,
+            (Some(0), TType::Struct) => {
+                input.read_struct_begin()?;
+                loop {
+                    let field = input.read_field_begin()?;
+                    match (field.id, field.field_type) {
+                        (_, TType::Stop) => break,
+                        (Some(1), TType::I32) => code = input.read_i32()?,
+                        (Some(2), TType::String) => status_message = input.read_string()?,
+                        (Some(3), TType::I64) => uuid = input.read_i64()?,
+                        (_, field_type) => input.skip(field_type)?,
+                    }
+                    input.read_field_end()?;
+                }
+                input.read_struct_end()?;
+            }
+            (_, field_type) => input.skip(field_type)?,
+        }
+        input.read_field_end()?;
+    }
+    input.read_struct_end()?;
+    input.read_message_end()?;
+    Ok((code, status_message, uuid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        generate, protocols, register_extension, serve_connection, table_columns, write_result,
+        write_status, OsqueryExtension, Row, TABLE_NAME,
+    };
+    use std::{
+        os::unix::net::{UnixListener, UnixStream},
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+    use tempfile::tempdir;
+    use thrift::protocol::{
+        TFieldIdentifier, TInputProtocol, TMapIdentifier, TMessageIdentifier, TMessageType,
+        TOutputProtocol, TStructIdentifier, TType,
+    };
+
+    fn test_context(constraints: &str) -> String {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        format!(
+            r#"{{"constraints":[{{"name":"directory","list":[{{"op":2,"expr":"{}"}}]}}{}]}}"#,
+            test_location.display(),
+            constraints
+        )
+    }
+
+    #[test]
+    fn test_table_columns() {
+        let columns = table_columns();
+        assert!(columns.len() == 11);
+        assert!(columns[0]["name"] == "path");
+        assert!(columns[0]["type"] == "TEXT");
+        assert!(columns[10]["name"] == "directory");
+        assert!(columns[10]["op"] == "20");
+    }
+
+    #[test]
+    fn test_generate() {
+        let rows = generate(&test_context("")).unwrap();
+        assert!(rows.len() == 5);
+        assert!(rows[0]["path"] == "/Users/bob/Downloads/invoice.pdf");
+        assert!(rows[0]["flags"] == "Created,IsFile");
+        assert!(rows[0]["directory"].ends_with("tests/test_data/DLS3"));
+        assert!(!rows[0]["volume_uuid"].is_empty());
+
+        let rows = generate(&test_context(
+            r#",{"name":"event_id","list":[{"op":32,"expr":"196625"}]}"#,
+        ))
+        .unwrap();
+        assert!(rows.len() == 1);
+        assert!(rows[0]["path"] == "/Users/bob/.ssh/authorized_keys");
+    }
+
+    #[test]
+    fn test_serve_connection() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let stop = std::sync::Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let handle = thread::spawn(move || serve_connection(server, 7, &server_stop));
+
+        let (mut input, mut output) = protocols(&client).unwrap();
+        output
+            .write_message_begin(&TMessageIdentifier::new("call", TMessageType::Call, 1))
+            .unwrap();
+        output
+            .write_struct_begin(&TStructIdentifier::new("call_args"))
+            .unwrap();
+        for (id, value) in [(1, "table"), (2, TABLE_NAME)] {
+            output
+                .write_field_begin(&TFieldIdentifier::new("", TType::String, id))
+                .unwrap();
+            output.write_string(value).unwrap();
+            output.write_field_end().unwrap();
+        }
+        let request = Row::from([
+            (String::from("action"), String::from("generate")),
+            (String::from("context"), test_context("")),
+        ]);
+        output
+            .write_field_begin(&TFieldIdentifier::new("request", TType::Map, 3))
+            .unwrap();
+        output
+            .write_map_begin(&TMapIdentifier::new(TType::String, TType::String, 2))
+            .unwrap();
+        for (key, value) in &request {
+            output.write_string(key).unwrap();
+            output.write_string(value).unwrap();
+        }
+        output.write_map_end().unwrap();
+        output.write_field_end().unwrap();
+        output.write_field_stop().unwrap();
+        output.write_struct_end().unwrap();
+        output.write_message_end().unwrap();
+        output.flush().unwrap();
+
+        let message = input.read_message_begin().unwrap();
+        assert!(message.message_type == TMessageType::Reply);
+        // result struct, success field, ExtensionResponse struct, status field
+        input.read_struct_begin().unwrap();
+        assert!(input.read_field_begin().unwrap().id == Some(0));
+        input.read_struct_begin().unwrap();
+        assert!(input.read_field_begin().unwrap().id == Some(1));
+        input.skip(TType::Struct).unwrap();
+        input.read_field_end().unwrap();
+        assert!(input.read_field_begin().unwrap().id == Some(2));
+        let rows = input.read_list_begin().unwrap();
+        assert!(rows.size == 5);
+        input.skip(TType::Map).unwrap();
+        drop(input);
+        drop(output);
+
+        // Closing the connection ends the server loop
+        drop(client);
+        handle.join().unwrap().unwrap();
+        assert!(!stop.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_register_extension() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut input, mut output) = protocols(&server).unwrap();
+            let message = input.read_message_begin().unwrap();
+            assert!(message.name == "registerExtension");
+            input.skip(TType::Struct).unwrap();
+            input.read_message_end().unwrap();
+
+            output
+                .write_message_begin(&TMessageIdentifier::new(
+                    "registerExtension",
+                    TMessageType::Reply,
+                    message.sequence_number,
+                ))
+                .unwrap();
+            write_result(&mut output, |output| write_status(output, 0, "OK", 42)).unwrap();
+        });
+        assert!(register_extension(&client).unwrap() == 42);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_run_shutdown() {
+        let temp = tempdir().unwrap();
+        let socket = temp.path().join("osquery.em");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        // Extension manager that answers registerExtension and ping until the extension disconnects
+        let manager = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let (mut input, mut output) = protocols(&stream).unwrap();
+            while let Ok(message) = input.read_message_begin() {
+                input.skip(TType::Struct).unwrap();
+                input.read_message_end().unwrap();
+                output
+                    .write_message_begin(&TMessageIdentifier::new(
+                        &message.name,
+                        TMessageType::Reply,
+                        message.sequence_number,
+                    ))
+                    .unwrap();
+                write_result(&mut output, |output| write_status(output, 0, "OK", 5)).unwrap();
+            }
+        });
+
+        let path = socket.display().to_string();
+        let extension = thread::spawn(move || OsqueryExtension::new(&path).interval(1).run());
+        let extension_socket = format!("{}.5", socket.display());
+        while !Path::new(&extension_socket).exists() {
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        let client = UnixStream::connect(&extension_socket).unwrap();
+        let (mut input, mut output) = protocols(&client).unwrap();
+        output
+            .write_message_begin(&TMessageIdentifier::new("shutdown", TMessageType::Call, 1))
+            .unwrap();
+        output
+            .write_struct_begin(&TStructIdentifier::new("shutdown_args"))
+            .unwrap();
+        output.write_field_stop().unwrap();
+        output.write_struct_end().unwrap();
+        output.write_message_end().unwrap();
+        output.flush().unwrap();
+        let message = input.read_message_begin().unwrap();
+        assert!(message.name == "shutdown");
+
+        extension.join().unwrap().unwrap();
+        manager.join().unwrap();
+        assert!(!Path::new(&extension_socket).exists());
+    }
+}
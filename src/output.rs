@@ -0,0 +1,117 @@
+//! Write parsed records to output formats
+//!
+//! Each format is in its own module and most are behind a feature, so only the formats that are
+//! used add dependencies.
+
+pub mod bodyfile;
+pub mod compress;
+pub mod csv;
+pub mod ecs;
+pub mod html;
+pub mod jsonl;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod siem;
+pub mod split;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "timezone")]
+pub mod time;
+pub mod timeline;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+use crate::{flags::FsEventFlags, fsevents::FsEvents};
+
+/// Get the latest time (Unix epoch) a record could have happened, or 0 if no times were added
+/// The end of the estimated time window is preferred over the approximate latest time
+pub(crate) fn latest_time(fsevent: &FsEvents) -> i64 {
+    if fsevent.est_time_end != 0 {
+        fsevent.est_time_end
+    } else {
+        fsevent.approx_latest_time
+    }
+}
+
+/// Get which timeline times the flags change: (modified, changed, born)
+/// FsEvents do not record file access, so there is no accessed time
+pub(crate) fn macb_flags(flags: FsEventFlags) -> (bool, bool, bool) {
+    let modified = flags.intersects(
+        FsEventFlags::MODIFIED | FsEventFlags::ITEM_TRUNCATED | FsEventFlags::DOCUMENT_REVISION,
+    );
+    let changed = flags.intersects(
+        FsEventFlags::INODE_METADATA_MODIFIED
+            | FsEventFlags::RENAMED
+            | FsEventFlags::PERMISSION_CHANGED
+            | FsEventFlags::FINDER_INFO_MODIFIED
+            | FsEventFlags::EXTENDED_ATTRIBUTE_MODIFIED
+            | FsEventFlags::EXTENDED_ATTRIBUTE_REMOVED,
+    );
+    let born = flags.intersects(
+        FsEventFlags::CREATED
+            | FsEventFlags::DIRECTORY_CREATED
+            | FsEventFlags::DOCUMENT_CREATED
+            | FsEventFlags::ITEM_CLONED,
+    );
+    (modified, changed, born)
+}
+
+/// Format a Unix epoch time in seconds as an ISO 8601 UTC time (ex: `2023-11-14T22:13:20Z`)
+pub(crate) fn iso8601(seconds: i64) -> String {
+    let (year, month, day) = civil_date(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) date in the proleptic Gregorian calendar
+/// https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{iso8601, latest_time};
+    use crate::fsevents::FsEvents;
+
+    #[test]
+    fn test_iso8601() {
+        assert!(iso8601(0) == "1970-01-01T00:00:00Z");
+        assert!(iso8601(1700000000) == "2023-11-14T22:13:20Z");
+        assert!(iso8601(951782400) == "2000-02-29T00:00:00Z");
+        assert!(iso8601(-86401) == "1969-12-30T23:59:59Z");
+    }
+
+    #[test]
+    fn test_latest_time() {
+        let mut record = FsEvents {
+            approx_latest_time: 10,
+            ..Default::default()
+        };
+        assert!(latest_time(&record) == 10);
+        record.est_time_end = 20;
+        assert!(latest_time(&record) == 20);
+    }
+}
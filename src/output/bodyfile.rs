@@ -0,0 +1,101 @@
+//! Write records in the Sleuth Kit bodyfile format for mactime timelines
+//!
+//! Each record becomes a bodyfile 3 line: `MD5|name|inode|mode|UID|GID|size|atime|mtime|ctime|crtime`.
+//! The name is the path followed by the record flags in parentheses, the inode is the node ID, and
+//! the mode shows if the record is a file, directory, or symlink. The latest time the record could
+//! have happened (if times were added) is used for the times its flags change: content changes set
+//! `mtime`, metadata changes and renames set `ctime`, and creations set `crtime`. Records with none
+//! of those flags (ex: Removed) use `ctime`, so they still appear in the timeline.
+
+use super::{latest_time, macb_flags};
+use crate::{error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents};
+use std::io::{BufWriter, Write};
+
+/// Format a record as a bodyfile line
+pub fn bodyfile_line(fsevent: &FsEvents) -> String {
+    let flags = fsevent.event_flags();
+    let time = latest_time(fsevent);
+    let (modified, mut changed, born) = macb_flags(flags);
+    if !modified && !born {
+        changed = true;
+    }
+    let timestamp = |set: bool| if set { time } else { 0 };
+
+    format!(
+        "0|{} ({})|{}|{}|0|0|0|0|{}|{}|{}",
+        fsevent.path.replace('|', "\\|"),
+        flags,
+        fsevent.node,
+        file_mode(flags),
+        timestamp(modified),
+        timestamp(changed),
+        timestamp(born)
+    )
+}
+
+/// Write records as bodyfile lines to any writer
+pub fn write_bodyfile<W: Write>(fsevents: &[FsEvents], writer: W) -> Result<(), FsEventsError> {
+    let mut writer = BufWriter::new(writer);
+    for fsevent in fsevents {
+        writer.write_all(bodyfile_line(fsevent).as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Get the bodyfile mode string for the record type, permissions are not known
+fn file_mode(flags: FsEventFlags) -> &'static str {
+    if flags.contains(FsEventFlags::IS_SYMBOLIC_LINK) {
+        "l/l---------"
+    } else if flags.contains(FsEventFlags::IS_DIRECTORY) {
+        "d/d---------"
+    } else if flags.contains(FsEventFlags::IS_FILE) {
+        "r/r---------"
+    } else {
+        "-/----------"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bodyfile_line, write_bodyfile};
+    use crate::{fsevents::FsEvents, parser::parse_bytes};
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn test_bodyfile_line() {
+        let mut record = FsEvents {
+            path: String::from("/Users/bob/a|b.txt"),
+            raw_flags: 0x01 | 0x10 | 0x800000,
+            node: 42,
+            est_time_end: 1700000000,
+            ..Default::default()
+        };
+        assert!(
+            bodyfile_line(&record)
+                == "0|/Users/bob/a\\|b.txt (Created,Modified,IsFile)|42|r/r---------|0|0|0|0|1700000000|0|1700000000"
+        );
+
+        record.raw_flags = 0x02 | 0x1000000;
+        assert!(
+            bodyfile_line(&record)
+                == "0|/Users/bob/a\\|b.txt (Removed,IsDirectory)|42|d/d---------|0|0|0|0|0|1700000000|0"
+        );
+    }
+
+    #[test]
+    fn test_write_bodyfile() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let records = parse_bytes(&fs::read(test_location).unwrap()).unwrap();
+
+        let mut output = Vec::new();
+        write_bodyfile(&records, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.len() == 5);
+        assert!(lines[0].starts_with("0|/Users/bob/Downloads/invoice.pdf (Created,IsFile)|"));
+        assert!(lines[0].split('|').count() == 11);
+    }
+}
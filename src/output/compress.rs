@@ -0,0 +1,151 @@
+//! Compress output while it is written
+//!
+//! Full volume exports can be several gigabytes of JSON Lines or CSV, so any format writer can be
+//! wrapped in a `CompressedWriter` to gzip or zstd compress the records as they are written.
+//! Gzip is always available, zstd requires the `zstd` feature.
+
+use crate::error::FsEventsError;
+use flate2::write::GzEncoder;
+use std::io::{Result, Write};
+
+/// Compression for output files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputCompression {
+    Gzip, // Gzip (.gz), readable by zcat and most SIEM importers
+    Zstd, // Zstd (.zst), faster and smaller than gzip
+}
+
+impl OutputCompression {
+    /// Get the compression from its name: `gzip` (or `gz`) or `zstd` (or `zst`)
+    pub fn from_name(name: &str) -> Option<OutputCompression> {
+        match name.trim().to_lowercase().as_str() {
+            "gzip" | "gz" => Some(OutputCompression::Gzip),
+            "zstd" | "zst" => Some(OutputCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// File extension added to compressed output files
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputCompression::Gzip => "gz",
+            OutputCompression::Zstd => "zst",
+        }
+    }
+}
+
+/// Writer that compresses everything written to it, or passes it through uncompressed
+pub enum CompressedWriter<W: Write> {
+    Plain(W),           // Uncompressed output
+    Gzip(GzEncoder<W>), // Gzip compressed output
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>), // Zstd compressed output
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Wrap a writer, None writes the output uncompressed
+    pub fn new(
+        writer: W,
+        compression: Option<OutputCompression>,
+    ) -> std::result::Result<CompressedWriter<W>, FsEventsError> {
+        match compression {
+            None => Ok(CompressedWriter::Plain(writer)),
+            Some(OutputCompression::Gzip) => Ok(CompressedWriter::Gzip(GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "zstd")]
+            Some(OutputCompression::Zstd) => {
+                Ok(CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?))
+            }
+            #[cfg(not(feature = "zstd"))]
+            Some(OutputCompression::Zstd) => Err(FsEventsError::Output(String::from(
+                "zstd compressed output requires the zstd feature",
+            ))),
+        }
+    }
+
+    /// Write the end of the compressed data and return the inner writer
+    /// Must be called once writing is done, or the compressed output may be incomplete
+    pub fn finish(self) -> std::result::Result<W, FsEventsError> {
+        let mut writer = match self {
+            CompressedWriter::Plain(writer) => writer,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompressedWriter, OutputCompression};
+    use flate2::read::GzDecoder;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_compressed_writer_gzip() {
+        let mut writer = CompressedWriter::new(Vec::new(), Some(OutputCompression::Gzip)).unwrap();
+        writer.write_all(b"{\"path\":\"/tmp/a.txt\"}\n").unwrap();
+        let data = writer.finish().unwrap();
+        assert!(data.starts_with(&[0x1f, 0x8b]));
+
+        let mut output = String::new();
+        GzDecoder::new(data.as_slice())
+            .read_to_string(&mut output)
+            .unwrap();
+        assert!(output == "{\"path\":\"/tmp/a.txt\"}\n");
+    }
+
+    #[test]
+    fn test_compressed_writer_plain() {
+        let mut writer = CompressedWriter::new(Vec::new(), None).unwrap();
+        writer.write_all(b"Path,Flags\n").unwrap();
+        assert!(writer.finish().unwrap() == b"Path,Flags\n");
+    }
+
+    #[test]
+    fn test_output_compression_from_name() {
+        assert!(OutputCompression::from_name("GZIP") == Some(OutputCompression::Gzip));
+        assert!(OutputCompression::from_name("zst") == Some(OutputCompression::Zstd));
+        assert!(OutputCompression::from_name("lz4").is_none());
+        assert!(OutputCompression::Zstd.extension() == "zst");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compressed_writer_zstd() {
+        let mut writer = CompressedWriter::new(Vec::new(), Some(OutputCompression::Zstd)).unwrap();
+        writer.write_all(b"Path,Flags\n").unwrap();
+        let data = writer.finish().unwrap();
+        assert!(zstd::decode_all(data.as_slice()).unwrap() == b"Path,Flags\n");
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_compressed_writer_zstd_feature() {
+        assert!(CompressedWriter::new(Vec::new(), Some(OutputCompression::Zstd)).is_err());
+    }
+}
@@ -0,0 +1,385 @@
+//! Write records to CSV (or TSV)
+//!
+//! `CsvWriter` writes to any `Write` sink (files, stdout, sockets, memory buffers). The columns and
+//! their order can be selected, the delimiter can be changed (ex: tabs for TSV), the header is
+//! written before the first record, and the output can be flushed every N records so it can be
+//! streamed while records are still being parsed.
+
+#[cfg(feature = "timezone")]
+use super::time::TimeFormat;
+use crate::{error::FsEventsError, fsevents::FsEvents};
+use std::io::Write;
+
+/// A column that can be written for each record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Path,
+    Flags,
+    RawFlags,
+    Node,
+    EventId,
+    ExtraId,
+    SourceFile,
+    Offset,
+    Page,
+    VolumeUuid,
+    ApproxEarliestTime,
+    ApproxLatestTime,
+    EstTimeStart,
+    EstTimeEnd,
+    SourceTag,
+    LiveExists,
+    LiveSize,
+    LiveModified,
+    LiveCreated,
+    CurrentPath,
+    TriageRule,
+}
+
+impl Column {
+    /// Every column, in the default order
+    pub const ALL: [Column; 21] = [
+        Column::Path,
+        Column::Flags,
+        Column::RawFlags,
+        Column::Node,
+        Column::EventId,
+        Column::ExtraId,
+        Column::SourceFile,
+        Column::Offset,
+        Column::Page,
+        Column::VolumeUuid,
+        Column::ApproxEarliestTime,
+        Column::ApproxLatestTime,
+        Column::EstTimeStart,
+        Column::EstTimeEnd,
+        Column::SourceTag,
+        Column::LiveExists,
+        Column::LiveSize,
+        Column::LiveModified,
+        Column::LiveCreated,
+        Column::CurrentPath,
+        Column::TriageRule,
+    ];
+
+    /// Name of the column in the CSV header
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Path => "Path",
+            Column::Flags => "Flags",
+            Column::RawFlags => "Raw Flags",
+            Column::Node => "Node",
+            Column::EventId => "Event ID",
+            Column::ExtraId => "Extra ID",
+            Column::SourceFile => "Source File",
+            Column::Offset => "Offset",
+            Column::Page => "Page",
+            Column::VolumeUuid => "Volume UUID",
+            Column::ApproxEarliestTime => "Approx Earliest Time",
+            Column::ApproxLatestTime => "Approx Latest Time",
+            Column::EstTimeStart => "Est Time Start",
+            Column::EstTimeEnd => "Est Time End",
+            Column::SourceTag => "Source Tag",
+            Column::LiveExists => "Live Exists",
+            Column::LiveSize => "Live Size",
+            Column::LiveModified => "Live Modified",
+            Column::LiveCreated => "Live Created",
+            Column::CurrentPath => "Current Path",
+            Column::TriageRule => "Triage Rule",
+        }
+    }
+
+    /// Get a column from its header name or record field name (ex: `Event ID` or `event_id`)
+    pub fn from_name(name: &str) -> Option<Column> {
+        let name = name.trim().replace(['_', ' '], "");
+        Column::ALL
+            .into_iter()
+            .find(|column| column.header().replace(' ', "").eq_ignore_ascii_case(&name))
+    }
+
+    /// Get the value of the column for a record
+    pub(crate) fn value(&self, fsevent: &FsEvents) -> String {
+        match self {
+            Column::Path => fsevent.path.clone(),
+            Column::Flags => fsevent.flags.clone(),
+            Column::RawFlags => fsevent.raw_flags.to_string(),
+            Column::Node => fsevent.node.to_string(),
+            Column::EventId => fsevent.event_id.to_string(),
+            Column::ExtraId => fsevent.extra_id.to_string(),
+            Column::SourceFile => fsevent.source_file.clone(),
+            Column::Offset => fsevent.offset.to_string(),
+            Column::Page => fsevent.page.to_string(),
+            Column::VolumeUuid => fsevent.volume_uuid.clone(),
+            Column::ApproxEarliestTime => fsevent.approx_earliest_time.to_string(),
+            Column::ApproxLatestTime => fsevent.approx_latest_time.to_string(),
+            Column::EstTimeStart => fsevent.est_time_start.to_string(),
+            Column::EstTimeEnd => fsevent.est_time_end.to_string(),
+            Column::SourceTag => fsevent.source_tag.clone(),
+            Column::CurrentPath => fsevent.current_path.clone(),
+            Column::TriageRule => fsevent.triage_rule.clone(),
+            Column::LiveExists | Column::LiveSize | Column::LiveModified | Column::LiveCreated => {
+                live_value(self, fsevent)
+            }
+        }
+    }
+
+    #[cfg(feature = "timezone")]
+    /// Get the time (Unix epoch) of a time column, None for other columns and unchecked live times
+    pub(crate) fn time(&self, fsevent: &FsEvents) -> Option<i64> {
+        match self {
+            Column::ApproxEarliestTime => Some(fsevent.approx_earliest_time),
+            Column::ApproxLatestTime => Some(fsevent.approx_latest_time),
+            Column::EstTimeStart => Some(fsevent.est_time_start),
+            Column::EstTimeEnd => Some(fsevent.est_time_end),
+            #[cfg(feature = "filesystem")]
+            Column::LiveModified => fsevent.live_status.as_ref().map(|status| status.modified),
+            #[cfg(feature = "filesystem")]
+            Column::LiveCreated => fsevent.live_status.as_ref().map(|status| status.created),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "filesystem")]
+/// Get a live status column value, empty if the live status was not checked
+fn live_value(column: &Column, fsevent: &FsEvents) -> String {
+    let status = match &fsevent.live_status {
+        Some(result) => result,
+        None => return String::new(),
+    };
+    match column {
+        Column::LiveExists => status.exists.to_string(),
+        Column::LiveSize => status.size.to_string(),
+        Column::LiveModified => status.modified.to_string(),
+        Column::LiveCreated => status.created.to_string(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(not(feature = "filesystem"))]
+/// Live status is only available with the filesystem feature
+fn live_value(_column: &Column, _fsevent: &FsEvents) -> String {
+    String::new()
+}
+
+/// Write records as CSV to any writer
+pub struct CsvWriter<W: Write> {
+    writer: csv::Writer<W>, // CSV writer for the output
+    columns: Vec<Column>,   // Columns to write, in order
+    flush_every: usize,     // Flush the output after this many records, 0 to only flush when done
+    pending: usize,         // Records written since the last flush
+    has_header: bool,       // Header has been written
+    #[cfg(feature = "timezone")]
+    time_format: Option<TimeFormat>, // Format for the time columns, Unix epoch seconds if not set
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Create a CSV writer with every column
+    pub fn new(writer: W) -> CsvWriter<W> {
+        CsvWriter::with_delimiter(writer, b',')
+    }
+
+    /// Create a writer with every column that separates values with a delimiter (ex: `b'\t'`
+    /// for TSV). Values containing the delimiter are quoted
+    pub fn with_delimiter(writer: W, delimiter: u8) -> CsvWriter<W> {
+        CsvWriter {
+            writer: csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(writer),
+            columns: Column::ALL.to_vec(),
+            flush_every: 0,
+            pending: 0,
+            has_header: false,
+            #[cfg(feature = "timezone")]
+            time_format: None,
+        }
+    }
+
+    /// Only write these columns, in this order
+    pub fn columns(mut self, columns: &[Column]) -> CsvWriter<W> {
+        self.columns = columns.to_vec();
+        self
+    }
+
+    /// Do not write the header (ex: when adding records to a CSV file that already has one)
+    pub fn skip_header(mut self) -> CsvWriter<W> {
+        self.has_header = true;
+        self
+    }
+
+    /// Flush the output after every `records` records, so it can be read while it is written
+    pub fn flush_every(mut self, records: usize) -> CsvWriter<W> {
+        self.flush_every = records;
+        self
+    }
+
+    #[cfg(feature = "timezone")]
+    /// Write the time columns in a time zone and format
+    pub fn time_format(mut self, format: TimeFormat) -> CsvWriter<W> {
+        self.time_format = Some(format);
+        self
+    }
+
+    /// Write a record, the header is written before the first record
+    pub fn write_record(&mut self, fsevent: &FsEvents) -> Result<(), FsEventsError> {
+        self.write_header()?;
+        let values: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| self.value(column, fsevent))
+            .collect();
+        self.writer.write_record(values).map_err(output_error)?;
+
+        self.pending += 1;
+        if self.flush_every != 0 && self.pending >= self.flush_every {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write multiple records
+    pub fn write_records(&mut self, fsevents: &[FsEvents]) -> Result<(), FsEventsError> {
+        for fsevent in fsevents {
+            self.write_record(fsevent)?;
+        }
+        Ok(())
+    }
+
+    /// Get a column value, with times in the time format if one was set
+    fn value(&self, column: &Column, fsevent: &FsEvents) -> String {
+        #[cfg(feature = "timezone")]
+        if let (Some(format), Some(seconds)) = (&self.time_format, column.time(fsevent)) {
+            return format.format(seconds);
+        }
+        column.value(fsevent)
+    }
+
+    /// Write the header if it has not been written yet
+    pub fn write_header(&mut self) -> Result<(), FsEventsError> {
+        if self.has_header {
+            return Ok(());
+        }
+        let headers = self.columns.iter().map(Column::header);
+        self.writer.write_record(headers).map_err(output_error)?;
+        self.has_header = true;
+        Ok(())
+    }
+
+    /// Flush buffered records to the output
+    pub fn flush(&mut self) -> Result<(), FsEventsError> {
+        self.pending = 0;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flush the output and return the underlying writer
+    pub fn into_inner(self) -> Result<W, FsEventsError> {
+        self.writer
+            .into_inner()
+            .map_err(|err| FsEventsError::Io(err.into_error()))
+    }
+}
+
+/// Write records as CSV with every column to any writer
+pub fn write_csv<W: Write>(fsevents: &[FsEvents], writer: W) -> Result<(), FsEventsError> {
+    let mut csv_writer = CsvWriter::new(writer);
+    csv_writer.write_header()?;
+    csv_writer.write_records(fsevents)?;
+    csv_writer.flush()
+}
+
+/// Convert a CSV error to an output error
+fn output_error(err: csv::Error) -> FsEventsError {
+    FsEventsError::Output(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_csv, Column, CsvWriter};
+    use crate::{fsevents::FsEvents, parser::parse_bytes};
+    use std::{fs, path::PathBuf};
+
+    fn test_records() -> Vec<FsEvents> {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        parse_bytes(&fs::read(test_location).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut output = Vec::new();
+        write_csv(&test_records(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.len() == 6);
+        assert!(lines[0].starts_with("Path,Flags,Raw Flags,Node,Event ID"));
+        assert!(lines[0].ends_with("Current Path,Triage Rule"));
+        assert!(lines[1].starts_with("/Users/bob/Downloads/invoice.pdf,\"Created,IsFile\",8388609"));
+    }
+
+    #[test]
+    fn test_csv_writer_columns() {
+        let mut writer = CsvWriter::new(Vec::new()).columns(&[Column::EventId, Column::Path]);
+        writer.write_records(&test_records()).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0] == "Event ID,Path");
+        assert!(lines[5].ends_with(",/Users/bob/.ssh/authorized_keys"));
+    }
+
+    #[test]
+    fn test_csv_writer_skip_header() {
+        let mut writer = CsvWriter::new(Vec::new())
+            .columns(&[Column::Path])
+            .skip_header();
+        writer.write_header().unwrap();
+        writer.write_records(&test_records()[..2]).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(output == "/Users/bob/Downloads/invoice.pdf\n/Users/bob/Downloads/invoice.pdf\n");
+    }
+
+    #[test]
+    fn test_csv_writer_tsv() {
+        let mut writer = CsvWriter::with_delimiter(Vec::new(), b'\t').columns(&[
+            Column::Path,
+            Column::Flags,
+            Column::EventId,
+        ]);
+        writer.write_records(&test_records()).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0] == "Path\tFlags\tEvent ID");
+        assert!(lines[1] == "/Users/bob/Downloads/invoice.pdf\tCreated,IsFile\t196608");
+    }
+
+    #[test]
+    fn test_csv_writer_flush_every() {
+        let records = test_records();
+        let mut writer = CsvWriter::new(Vec::new())
+            .columns(&[Column::Path])
+            .flush_every(2);
+        writer.write_record(&records[0]).unwrap();
+        assert!(writer.writer.get_ref().is_empty());
+
+        writer.write_record(&records[1]).unwrap();
+        writer.write_record(&records[2]).unwrap();
+        // The header and first two records were flushed, the third is still buffered
+        assert!(writer.writer.get_ref().split(|b| *b == b'\n').count() == 4);
+        assert!(
+            String::from_utf8(writer.into_inner().unwrap())
+                .unwrap()
+                .lines()
+                .count()
+                == 4
+        );
+    }
+
+    #[test]
+    fn test_column_from_name() {
+        assert!(Column::from_name("Event ID") == Some(Column::EventId));
+        assert!(Column::from_name("event_id") == Some(Column::EventId));
+        assert!(Column::from_name("approx_earliest_time") == Some(Column::ApproxEarliestTime));
+        assert!(Column::from_name("volume uuid") == Some(Column::VolumeUuid));
+        assert!(Column::from_name("timestamp").is_none());
+    }
+}
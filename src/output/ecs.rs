@@ -0,0 +1,292 @@
+//! Map records to Elastic Common Schema (ECS) documents
+//!
+//! Each record becomes an ECS event in the `file` category. The flags decide `event.type` and
+//! `event.action`, the event ID is `event.id`, and the node ID is `file.inode`. Estimated time
+//! windows become `event.start`/`event.end`, and `@timestamp` is the latest time the event could
+//! have happened, if times were added. Fields without an ECS equivalent are kept under the custom
+//! `fsevents` namespace. Documents are written one per line, ready for the Elasticsearch bulk API
+//! or Filebeat.
+
+use super::{iso8601, latest_time};
+use crate::{error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents};
+use serde::Serialize;
+use std::{
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// ECS version the documents follow
+pub const ECS_VERSION: &str = "8.11.0";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EcsRecord {
+    #[serde(rename = "@timestamp", skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>, // Latest time the event could have happened, if known
+    pub ecs: EcsVersion,       // ECS version of the document
+    pub event: EcsEvent,       // What happened
+    pub file: EcsFile,         // File the event happened to
+    pub fsevents: EcsFsEvents, // Record fields without an ECS equivalent
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EcsVersion {
+    pub version: &'static str,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EcsEvent {
+    pub kind: &'static str,          // Always "event"
+    pub category: Vec<&'static str>, // Always ["file"]
+    #[serde(rename = "type")]
+    pub event_type: Vec<&'static str>, // creation, deletion, change, or info
+    pub action: &'static str,        // Most significant change in the record flags
+    pub id: String,                  // FsEvent event ID
+    pub module: &'static str,        // Always "fsevents"
+    pub dataset: &'static str,       // Always "fsevents.records"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>, // Start of the estimated time window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>, // End of the estimated time window
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EcsFile {
+    pub path: String,      // Full path of the file
+    pub name: String,      // File name
+    pub directory: String, // Directory containing the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>, // File extension without the dot
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<&'static str>, // file, dir, or symlink
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inode: Option<String>, // Node ID, not set for DLS1 records
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EcsFsEvents {
+    pub flags: Vec<&'static str>, // Flag names
+    pub raw_flags: u32,           // Raw flags value
+    pub node: u64,                // Node ID
+    pub extra_id: u32,            // Unknown ID, only found in DLS3 records
+    pub source_file: String,      // FsEvent file that contained the record
+    pub offset: u64,              // Offset to the record in the decompressed FsEvent data
+    pub page: u32,                // Index of the page that contained the record
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub volume_uuid: String, // Volume UUID from the fseventsd-uuid file
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub source_tag: String, // Where the FsEvent directory came from
+}
+
+/// Flags that map to each ECS event type
+const CREATION: FsEventFlags = FsEventFlags::CREATED
+    .union(FsEventFlags::DIRECTORY_CREATED)
+    .union(FsEventFlags::DOCUMENT_CREATED)
+    .union(FsEventFlags::ITEM_CLONED);
+const DELETION: FsEventFlags = FsEventFlags::REMOVED.union(FsEventFlags::LAST_HARD_LINK_REMOVED);
+const CHANGE: FsEventFlags = FsEventFlags::INODE_METADATA_MODIFIED
+    .union(FsEventFlags::RENAMED)
+    .union(FsEventFlags::MODIFIED)
+    .union(FsEventFlags::EXCHANGE)
+    .union(FsEventFlags::FINDER_INFO_MODIFIED)
+    .union(FsEventFlags::PERMISSION_CHANGED)
+    .union(FsEventFlags::EXTENDED_ATTRIBUTE_MODIFIED)
+    .union(FsEventFlags::EXTENDED_ATTRIBUTE_REMOVED)
+    .union(FsEventFlags::DOCUMENT_REVISION)
+    .union(FsEventFlags::ITEM_TRUNCATED);
+
+/// Actions in order of significance, the first flag set in a record is its action
+const ACTIONS: [(FsEventFlags, &str); 12] = [
+    (DELETION, "deleted"),
+    (FsEventFlags::RENAMED, "renamed"),
+    (CREATION, "created"),
+    (FsEventFlags::MODIFIED, "modified"),
+    (FsEventFlags::ITEM_TRUNCATED, "truncated"),
+    (FsEventFlags::EXCHANGE, "exchanged"),
+    (FsEventFlags::DOCUMENT_REVISION, "revised"),
+    (FsEventFlags::PERMISSION_CHANGED, "permissions-changed"),
+    (
+        FsEventFlags::EXTENDED_ATTRIBUTE_MODIFIED.union(FsEventFlags::EXTENDED_ATTRIBUTE_REMOVED),
+        "extended-attributes-changed",
+    ),
+    (
+        FsEventFlags::INODE_METADATA_MODIFIED.union(FsEventFlags::FINDER_INFO_MODIFIED),
+        "metadata-changed",
+    ),
+    (FsEventFlags::MOUNT, "mounted"),
+    (
+        FsEventFlags::UNMOUNT.union(FsEventFlags::UNMOUNT_PENDING),
+        "unmounted",
+    ),
+];
+
+/// Map a record to an ECS document
+pub fn to_ecs(fsevent: &FsEvents) -> EcsRecord {
+    let flags = fsevent.event_flags();
+    let mut event_type = Vec::new();
+    for (mask, name) in [
+        (CREATION, "creation"),
+        (DELETION, "deletion"),
+        (CHANGE, "change"),
+    ] {
+        if flags.intersects(mask) {
+            event_type.push(name);
+        }
+    }
+    if event_type.is_empty() {
+        event_type.push("info");
+    }
+
+    let latest = latest_time(fsevent);
+    let estimate = |time: i64| (fsevent.est_time_start != 0).then(|| iso8601(time));
+
+    let path = Path::new(&fsevent.path);
+    EcsRecord {
+        timestamp: (latest != 0).then(|| iso8601(latest)),
+        ecs: EcsVersion {
+            version: ECS_VERSION,
+        },
+        event: EcsEvent {
+            kind: "event",
+            category: vec!["file"],
+            event_type,
+            action: event_action(flags),
+            id: fsevent.event_id.to_string(),
+            module: "fsevents",
+            dataset: "fsevents.records",
+            start: estimate(fsevent.est_time_start),
+            end: estimate(fsevent.est_time_end),
+        },
+        file: EcsFile {
+            path: fsevent.path.clone(),
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            directory: path
+                .parent()
+                .map(|parent| parent.display().to_string())
+                .unwrap_or_default(),
+            extension: path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_string()),
+            file_type: file_type(flags),
+            inode: (fsevent.node != 0).then(|| fsevent.node.to_string()),
+        },
+        fsevents: EcsFsEvents {
+            flags: flags.names().collect(),
+            raw_flags: fsevent.raw_flags,
+            node: fsevent.node,
+            extra_id: fsevent.extra_id,
+            source_file: fsevent.source_file.clone(),
+            offset: fsevent.offset,
+            page: fsevent.page,
+            volume_uuid: fsevent.volume_uuid.clone(),
+            source_tag: fsevent.source_tag.clone(),
+        },
+    }
+}
+
+/// Write records as ECS documents to any writer, one JSON document per line
+pub fn write_ecs<W: Write>(fsevents: &[FsEvents], writer: W) -> Result<(), FsEventsError> {
+    let mut writer = BufWriter::new(writer);
+    for fsevent in fsevents {
+        serde_json::to_writer(&mut writer, &to_ecs(fsevent))
+            .map_err(|err| FsEventsError::Output(err.to_string()))?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Get the most significant action in the flags
+pub(crate) fn event_action(flags: FsEventFlags) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|(mask, _)| flags.intersects(*mask))
+        .map(|(_, action)| *action)
+        .unwrap_or("other")
+}
+
+/// Get the ECS file type from the flags
+fn file_type(flags: FsEventFlags) -> Option<&'static str> {
+    if flags.contains(FsEventFlags::IS_SYMBOLIC_LINK) {
+        Some("symlink")
+    } else if flags.contains(FsEventFlags::IS_DIRECTORY) {
+        Some("dir")
+    } else if flags.contains(FsEventFlags::IS_FILE) {
+        Some("file")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_ecs, write_ecs};
+    use crate::{fsevents::FsEvents, parser::parse_bytes};
+    use std::{fs, path::PathBuf};
+
+    fn test_records() -> Vec<FsEvents> {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        parse_bytes(&fs::read(test_location).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_to_ecs() {
+        let records = test_records();
+        let ecs = to_ecs(&records[0]);
+        assert!(ecs.timestamp.is_none());
+        assert!(ecs.event.action == "created");
+        assert!(ecs.event.event_type == vec!["creation"]);
+        assert!(ecs.event.id == "196608");
+        assert!(ecs.file.name == "invoice.pdf");
+        assert!(ecs.file.directory == "/Users/bob/Downloads");
+        assert!(ecs.file.extension.as_deref() == Some("pdf"));
+        assert!(ecs.file.file_type == Some("file"));
+        assert!(ecs.fsevents.flags == vec!["Created", "IsFile"]);
+
+        let ecs = to_ecs(&records[4]);
+        assert!(ecs.event.action == "renamed");
+        assert!(ecs.event.event_type == vec!["change"]);
+    }
+
+    #[test]
+    fn test_to_ecs_times() {
+        let mut record = FsEvents {
+            path: String::from("/tmp/a.txt"),
+            raw_flags: 0x02 | 0x10,
+            est_time_start: 1700000000,
+            est_time_end: 1700000060,
+            ..Default::default()
+        };
+        let ecs = to_ecs(&record);
+        assert!(ecs.timestamp.as_deref() == Some("2023-11-14T22:14:20Z"));
+        assert!(ecs.event.start.as_deref() == Some("2023-11-14T22:13:20Z"));
+        assert!(ecs.event.action == "deleted");
+        assert!(ecs.event.event_type == vec!["deletion", "change"]);
+        assert!(ecs.file.inode.is_none());
+
+        record.est_time_start = 0;
+        record.est_time_end = 0;
+        record.approx_latest_time = 1700000000;
+        let ecs = to_ecs(&record);
+        assert!(ecs.timestamp.as_deref() == Some("2023-11-14T22:13:20Z"));
+        assert!(ecs.event.start.is_none());
+    }
+
+    #[test]
+    fn test_write_ecs() {
+        let mut output = Vec::new();
+        write_ecs(&test_records(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.len() == 5);
+
+        let document: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert!(document["file"]["path"] == "/Users/bob/Downloads/invoice.pdf");
+        assert!(document["event"]["category"][0] == "file");
+        assert!(document["ecs"]["version"] == "8.11.0");
+        assert!(document.get("@timestamp").is_none());
+    }
+}
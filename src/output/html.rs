@@ -0,0 +1,317 @@
+//! Write a standalone HTML summary report
+//!
+//! The report is a single HTML file with no scripts or external resources, so it can be attached
+//! to a case or opened offline. It has the headline stats, a chart of the records per flag, the
+//! busiest directories, FsEvent files with deletion spikes, and a summary of every FsEvent file.
+//! Sections and FsEvent files have anchors (ex: `report.html#deletions`), and the report links to
+//! the detailed export the records were saved to.
+
+use super::iso8601;
+use crate::{
+    error::FsEventsError,
+    fsevents::FsEvents,
+    stats::{
+        busiest_directories, deletion_spikes, get_stats, source_file_summaries, SourceFileSummary,
+    },
+};
+use std::io::{BufWriter, Write};
+
+/// Number of busiest directories in the report
+const REPORT_DIRECTORIES: usize = 25;
+
+/// Styles for the tables and bar charts
+const STYLE: &str = concat!(
+    "body{font-family:-apple-system,Helvetica,Arial,sans-serif;margin:2em;color:#222}",
+    "table{border-collapse:collapse;margin-bottom:1.5em}",
+    "th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}",
+    "th{background:#f2f2f2}",
+    "td.number{text-align:right;font-variant-numeric:tabular-nums}",
+    "td.path{font-family:Menlo,Consolas,monospace;word-break:break-all}",
+    ".bar{background:#4a7bd0;height:12px;min-width:1px}",
+    ".spike .bar{background:#c8423b}",
+    "nav a{margin-right:1em}",
+);
+
+/// Write an HTML summary report of the records
+/// `export` is the link to the detailed export (ex: `output.csv`), relative to the report
+pub fn write_html_report<W: Write>(
+    fsevents: &[FsEvents],
+    export: Option<&str>,
+    writer: W,
+) -> Result<(), FsEventsError> {
+    let mut writer = BufWriter::new(writer);
+    let stats = get_stats(fsevents);
+    let summaries = source_file_summaries(fsevents);
+    let spikes = deletion_spikes(&summaries);
+
+    writeln!(writer, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>")?;
+    writeln!(writer, "<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>FsEvents summary report</title>")?;
+    writeln!(writer, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(writer, "<h1>FsEvents summary report</h1>")?;
+    writeln!(
+        writer,
+        "<nav><a href=\"#summary\">Summary</a><a href=\"#flags\">Flags</a>\
+         <a href=\"#directories\">Busiest directories</a>\
+         <a href=\"#deletions\">Deletion spikes</a><a href=\"#files\">FsEvent files</a></nav>"
+    )?;
+
+    writeln!(writer, "<h2 id=\"summary\">Summary</h2>\n<table>")?;
+    if let Some(export) = export {
+        let export = escape(export);
+        summary_row(
+            &mut writer,
+            "Detailed export",
+            &format!("<a href=\"{}\">{}</a>", export, export),
+        )?;
+    }
+    summary_row(&mut writer, "Records", &stats.total_records.to_string())?;
+    summary_row(&mut writer, "Unique paths", &stats.unique_paths.to_string())?;
+    summary_row(
+        &mut writer,
+        "Invalid paths",
+        &stats.invalid_paths.to_string(),
+    )?;
+    summary_row(
+        &mut writer,
+        "Event IDs",
+        &format!(
+            "{} - {} ({:#x} - {:#x})",
+            stats.min_event_id, stats.max_event_id, stats.min_event_id, stats.max_event_id
+        ),
+    )?;
+    let earliest = summaries
+        .iter()
+        .map(|summary| summary.approx_earliest_time)
+        .filter(|time| *time != 0)
+        .min()
+        .unwrap_or_default();
+    let latest = summaries
+        .iter()
+        .map(|summary| summary.approx_latest_time)
+        .max()
+        .unwrap_or_default();
+    let times = time_range(earliest, latest);
+    if !times.is_empty() {
+        summary_row(&mut writer, "Approximate times", &times)?;
+    }
+    summary_row(&mut writer, "FsEvent files", &summaries.len().to_string())?;
+    summary_row(&mut writer, "Deletion spikes", &spikes.len().to_string())?;
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h2 id=\"flags\">Records per flag</h2>")?;
+    let mut flags: Vec<(&String, &usize)> = stats.flags.iter().collect();
+    flags.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+    let rows: Vec<(String, usize)> = flags
+        .into_iter()
+        .map(|(flag, count)| (escape(flag), *count))
+        .collect();
+    bar_chart(&mut writer, "Flag", &rows, stats.total_records)?;
+
+    writeln!(writer, "<h2 id=\"directories\">Busiest directories</h2>")?;
+    let rows: Vec<(String, usize)> = busiest_directories(fsevents, REPORT_DIRECTORIES)
+        .into_iter()
+        .map(|count| (escape(&count.directory), count.records))
+        .collect();
+    bar_chart(&mut writer, "Directory", &rows, stats.total_records)?;
+
+    writeln!(writer, "<h2 id=\"deletions\">Deletion spikes</h2>")?;
+    if spikes.is_empty() {
+        writeln!(
+            writer,
+            "<p>No FsEvent file has far more removed records than the others.</p>"
+        )?;
+    } else {
+        writeln!(
+            writer,
+            "<p>FsEvent files with at least 50 removed records, and three times the median.</p>"
+        )?;
+        files_table(&mut writer, &summaries, &spikes, &spikes, false)?;
+    }
+
+    writeln!(writer, "<h2 id=\"files\">FsEvent files</h2>")?;
+    let all: Vec<&SourceFileSummary> = summaries.iter().collect();
+    files_table(&mut writer, &summaries, &all, &spikes, true)?;
+    writeln!(writer, "</body>\n</html>")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write a row of the summary table
+fn summary_row<W: Write>(writer: &mut W, name: &str, value: &str) -> Result<(), FsEventsError> {
+    writeln!(writer, "<tr><th>{}</th><td>{}</td></tr>", name, value)?;
+    Ok(())
+}
+
+/// Write a table of names and counts with a bar for each count's share of the total
+fn bar_chart<W: Write>(
+    writer: &mut W,
+    name: &str,
+    rows: &[(String, usize)],
+    total: usize,
+) -> Result<(), FsEventsError> {
+    writeln!(
+        writer,
+        "<table>\n<tr><th>{}</th><th>Records</th><th>Share</th></tr>",
+        name
+    )?;
+    for (value, count) in rows {
+        let share = *count as f64 * 100.0 / total.max(1) as f64;
+        writeln!(
+            writer,
+            "<tr><td class=\"path\">{}</td><td class=\"number\">{}</td>\
+             <td style=\"width:300px\"><div class=\"bar\" style=\"width:{:.1}%\" title=\"{:.1}%\"></div></td></tr>",
+            value, count, share, share
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+    Ok(())
+}
+
+/// Write a table of FsEvent file summaries, highlighting the deletion spikes. Rows in the FsEvent
+/// files section have an anchor (ex: `#file-3`), and the rows in other sections link to it
+fn files_table<W: Write>(
+    writer: &mut W,
+    summaries: &[SourceFileSummary],
+    rows: &[&SourceFileSummary],
+    spikes: &[&SourceFileSummary],
+    anchors: bool,
+) -> Result<(), FsEventsError> {
+    writeln!(
+        writer,
+        "<table>\n<tr><th>FsEvent file</th><th>Records</th><th>Removed</th>\
+         <th>Event IDs</th><th>Approximate times</th></tr>"
+    )?;
+    for summary in rows {
+        // Anchors are numbered in event ID order, starting at 1
+        let number = summaries
+            .iter()
+            .position(|file| file.source_file == summary.source_file)
+            .unwrap_or_default()
+            + 1;
+        let name = escape(&summary.source_file);
+        let (id, file) = if anchors {
+            (format!(" id=\"file-{}\"", number), name)
+        } else {
+            (
+                String::new(),
+                format!("<a href=\"#file-{}\">{}</a>", number, name),
+            )
+        };
+        let spike = spikes
+            .iter()
+            .any(|spike| spike.source_file == summary.source_file);
+        writeln!(
+            writer,
+            "<tr{}{}><td class=\"path\">{}</td><td class=\"number\">{}</td>\
+             <td class=\"number\">{}</td><td>{} - {}</td><td>{}</td></tr>",
+            id,
+            if spike { " class=\"spike\"" } else { "" },
+            file,
+            summary.records,
+            summary.deletions,
+            summary.first_event_id,
+            summary.last_event_id,
+            time_range(summary.approx_earliest_time, summary.approx_latest_time)
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+    Ok(())
+}
+
+/// Format an approximate time range. Times that were not added (0) are unknown, and the range is
+/// empty if neither was
+fn time_range(earliest: i64, latest: i64) -> String {
+    let time = |seconds: i64| {
+        if seconds == 0 {
+            String::from("unknown")
+        } else {
+            iso8601(seconds)
+        }
+    };
+    if earliest == 0 && latest == 0 {
+        return String::new();
+    }
+    format!("{} - {}", time(earliest), time(latest))
+}
+
+/// Escape text for HTML
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, time_range, write_html_report};
+    use crate::{fsevents::FsEvents, parser::parse_bytes};
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn test_write_html_report() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let mut records = parse_bytes(&fs::read(test_location).unwrap()).unwrap();
+        for record in &mut records {
+            record.source_file = String::from("0000000000030012");
+        }
+
+        let mut output = Vec::new();
+        write_html_report(&records, Some("output.csv"), &mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.ends_with("</html>\n"));
+        assert!(html.contains("<a href=\"output.csv\">output.csv</a>"));
+        assert!(html.contains("<tr><th>Records</th><td>5</td></tr>"));
+        assert!(html
+            .contains("<td class=\"path\">/Users/bob/Downloads</td><td class=\"number\">2</td>"));
+        assert!(html.contains("<tr id=\"file-1\"><td class=\"path\">0000000000030012</td>"));
+        assert!(html.contains("<p>No FsEvent file has far more removed records"));
+    }
+
+    #[test]
+    fn test_write_html_report_spike() {
+        let mut records = Vec::new();
+        for (source, deletions) in [("quiet", 1), ("busy", 80)] {
+            for event_id in 0..deletions {
+                records.push(FsEvents {
+                    path: String::from("/Users/bob/<b>.txt"),
+                    source_file: source.to_string(),
+                    event_id,
+                    raw_flags: 0x02,
+                    ..Default::default()
+                });
+            }
+        }
+        let mut output = Vec::new();
+        write_html_report(&records, None, &mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+        assert!(
+            html.contains("<tr class=\"spike\"><td class=\"path\"><a href=\"#file-2\">busy</a>")
+        );
+        assert!(html.contains("<tr id=\"file-2\" class=\"spike\">"));
+        assert!(!html.contains("<b>"));
+        assert!(!html.contains("Detailed export"));
+    }
+
+    #[test]
+    fn test_time_range() {
+        assert!(time_range(0, 1700000000) == "unknown - 2023-11-14T22:13:20Z");
+        assert!(time_range(0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert!(escape("/tmp/<a & \"b\">") == "/tmp/&lt;a &amp; &quot;b&quot;&gt;");
+    }
+}
@@ -0,0 +1,139 @@
+//! Write records as JSON Lines (NDJSON)
+//!
+//! Each record is written as one JSON object on its own line, so records can be written as they
+//! are parsed (ex: from `iter::FsEventsIter`) and piped into jq, Splunk, or Elasticsearch without
+//! holding every record in memory.
+
+#[cfg(feature = "timezone")]
+use super::time::TimeFormat;
+use crate::{error::FsEventsError, fsevents::FsEvents};
+use std::io::{BufWriter, Write};
+
+/// Write records as JSON Lines to any writer
+pub struct JsonLinesWriter<W: Write> {
+    writer: BufWriter<W>, // Buffered output
+    flush_every: usize,   // Flush the output after this many records, 0 to only flush when done
+    pending: usize,       // Records written since the last flush
+    #[cfg(feature = "timezone")]
+    time_format: Option<TimeFormat>, // Format for the record times, Unix epoch seconds if not set
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    /// Create a JSON Lines writer
+    pub fn new(writer: W) -> JsonLinesWriter<W> {
+        JsonLinesWriter {
+            writer: BufWriter::new(writer),
+            flush_every: 0,
+            pending: 0,
+            #[cfg(feature = "timezone")]
+            time_format: None,
+        }
+    }
+
+    /// Flush the output after every `records` records, so it can be read while it is written
+    pub fn flush_every(mut self, records: usize) -> JsonLinesWriter<W> {
+        self.flush_every = records;
+        self
+    }
+
+    #[cfg(feature = "timezone")]
+    /// Write the record times in a time zone and format
+    pub fn time_format(mut self, format: TimeFormat) -> JsonLinesWriter<W> {
+        self.time_format = Some(format);
+        self
+    }
+
+    /// Write a record as a single line
+    pub fn write_record(&mut self, fsevent: &FsEvents) -> Result<(), FsEventsError> {
+        #[cfg(feature = "timezone")]
+        if let Some(format) = &self.time_format {
+            let record = format.json_record(fsevent)?;
+            serde_json::to_writer(&mut self.writer, &record)
+                .map_err(|err| FsEventsError::Output(err.to_string()))?;
+            return self.end_record();
+        }
+        serde_json::to_writer(&mut self.writer, fsevent)
+            .map_err(|err| FsEventsError::Output(err.to_string()))?;
+        self.end_record()
+    }
+
+    /// End the record's line and flush if needed
+    fn end_record(&mut self) -> Result<(), FsEventsError> {
+        self.writer.write_all(b"\n")?;
+
+        self.pending += 1;
+        if self.flush_every != 0 && self.pending >= self.flush_every {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write multiple records
+    pub fn write_records(&mut self, fsevents: &[FsEvents]) -> Result<(), FsEventsError> {
+        for fsevent in fsevents {
+            self.write_record(fsevent)?;
+        }
+        Ok(())
+    }
+
+    /// Flush buffered records to the output
+    pub fn flush(&mut self) -> Result<(), FsEventsError> {
+        self.pending = 0;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flush the output and return the underlying writer
+    pub fn into_inner(self) -> Result<W, FsEventsError> {
+        self.writer
+            .into_inner()
+            .map_err(|err| FsEventsError::Io(err.into_error()))
+    }
+}
+
+/// Write records as JSON Lines to any writer
+pub fn write_jsonl<W: Write>(fsevents: &[FsEvents], writer: W) -> Result<(), FsEventsError> {
+    let mut jsonl_writer = JsonLinesWriter::new(writer);
+    jsonl_writer.write_records(fsevents)?;
+    jsonl_writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_jsonl;
+    use crate::{fsevents::FsEvents, parser::parse_bytes};
+    use std::{fs, path::PathBuf};
+    #[cfg(feature = "filesystem")]
+    use {super::JsonLinesWriter, crate::iter::FsEventsIter};
+
+    #[test]
+    fn test_write_jsonl() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let records = parse_bytes(&fs::read(test_location).unwrap()).unwrap();
+
+        let mut output = Vec::new();
+        write_jsonl(&records, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.len() == 5);
+
+        let record: FsEvents = serde_json::from_str(lines[4]).unwrap();
+        assert!(record.path == "/Users/bob/.ssh/authorized_keys");
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_jsonl_writer_stream() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let records = FsEventsIter::from_directory(&test_location.display().to_string()).unwrap();
+
+        let mut writer = JsonLinesWriter::new(Vec::new()).flush_every(1000);
+        for record in records {
+            writer.write_record(&record.unwrap()).unwrap();
+        }
+        let output = writer.into_inner().unwrap();
+        assert!(output.iter().filter(|b| **b == b'\n').count() == 5736);
+    }
+}
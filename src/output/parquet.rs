@@ -0,0 +1,87 @@
+//! Write records to Parquet files
+//!
+//! Records are converted to Arrow RecordBatches (see `arrow::fsevents_schema` for the columns) and
+//! written with zstd compression. Parquet files are much smaller than CSV for multi-million record
+//! collections and can be queried directly by analytics tools.
+
+use crate::{
+    arrow::{fsevents_schema, to_record_batches},
+    error::FsEventsError,
+    fsevents::FsEvents,
+};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+#[cfg(feature = "filesystem")]
+use std::fs::File;
+use std::io::Write;
+
+/// Number of records converted to a RecordBatch at a time
+const BATCH_SIZE: usize = 65536;
+
+/// Write records as Parquet to any writer
+pub fn write_parquet<W: Write + Send>(
+    fsevents: &[FsEvents],
+    writer: W,
+) -> Result<(), FsEventsError> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut parquet_writer =
+        ArrowWriter::try_new(writer, fsevents_schema(), Some(properties)).map_err(output_error)?;
+    for batch in to_record_batches(fsevents, BATCH_SIZE)? {
+        parquet_writer.write(&batch).map_err(output_error)?;
+    }
+    parquet_writer.close().map_err(output_error)?;
+    Ok(())
+}
+
+#[cfg(feature = "filesystem")]
+/// Write records to a Parquet file, replacing the file if it exists
+pub fn write_parquet_file(fsevents: &[FsEvents], path: &str) -> Result<(), FsEventsError> {
+    let file = File::create(path)?;
+    write_parquet(fsevents, file)
+}
+
+/// Convert a Parquet error to an output error
+fn output_error(err: parquet::errors::ParquetError) -> FsEventsError {
+    FsEventsError::Output(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_parquet, write_parquet_file};
+    use crate::{options::ParserOptions, parser::parse_directory_with_options};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use std::{fs::File, path::PathBuf};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_parquet() {
+        let mut data = Vec::new();
+        write_parquet(&[], &mut data).unwrap();
+        assert!(data.starts_with(b"PAR1") && data.ends_with(b"PAR1"));
+    }
+
+    #[test]
+    fn test_write_parquet_file() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let fsevents = parse_directory_with_options(
+            &test_location.display().to_string(),
+            &ParserOptions::new(),
+        )
+        .unwrap();
+
+        let temp = tempdir().unwrap();
+        let output = temp.path().join("output.parquet");
+        write_parquet_file(&fsevents, &output.display().to_string()).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&output).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert!(metadata.file_metadata().num_rows() == 5736);
+        assert!(metadata.file_metadata().schema_descr().num_columns() == 9);
+    }
+}
@@ -0,0 +1,378 @@
+//! Format records as CEF or LEEF events for SIEM ingestion
+//!
+//! CEF (ArcSight Common Event Format) and LEEF (QRadar Log Event Extended Format) are single line
+//! formats that most SIEMs accept over syslog or from a file. The record flags become the event name,
+//! and the action (ex: `deleted`) becomes the event class ID.
+//!
+//! Severity is based on the action, and is raised when a record is part of a burst: many deletions
+//! (or renames) within a small range of event IDs. Bursts are common when files are wiped or
+//! encrypted in bulk, so they are worth a closer look than a single deleted file.
+
+use super::{ecs::event_action, iso8601, latest_time};
+use crate::{error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents};
+use std::io::{BufWriter, Write};
+
+/// Vendor, product, and version in the CEF and LEEF headers
+const VENDOR: &str = "Apple";
+const PRODUCT: &str = "macOS FsEvents";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Default number of deletions (or renames) that make a burst
+pub const DEFAULT_BURST_SIZE: usize = 100;
+/// Default range of event IDs a burst must fit in
+pub const DEFAULT_BURST_WINDOW: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiemFormat {
+    Cef,  // ArcSight Common Event Format version 0
+    Leef, // QRadar Log Event Extended Format version 1.0, tab delimited
+}
+
+/// Writes records as CEF or LEEF events to any writer, one event per line
+pub struct SiemWriter<W: Write> {
+    writer: BufWriter<W>,
+    format: SiemFormat,
+    burst_size: usize,
+    burst_window: u64,
+}
+
+impl<W: Write> SiemWriter<W> {
+    /// Create a writer for a format, with the default burst size and window
+    pub fn new(writer: W, format: SiemFormat) -> SiemWriter<W> {
+        SiemWriter {
+            writer: BufWriter::new(writer),
+            format,
+            burst_size: DEFAULT_BURST_SIZE,
+            burst_window: DEFAULT_BURST_WINDOW,
+        }
+    }
+
+    /// Number of deletions (or renames) within the burst window that raise their severity
+    pub fn burst_size(mut self, size: usize) -> Self {
+        self.burst_size = size;
+        self
+    }
+
+    /// Range of event IDs that a burst must fit in
+    pub fn burst_window(mut self, window: u64) -> Self {
+        self.burst_window = window;
+        self
+    }
+
+    /// Write records as events. Bursts are only found within the records passed in one call
+    pub fn write_records(&mut self, fsevents: &[FsEvents]) -> Result<(), FsEventsError> {
+        let severities = severities(fsevents, self.burst_size, self.burst_window);
+        for (fsevent, severity) in fsevents.iter().zip(severities) {
+            let line = match self.format {
+                SiemFormat::Cef => cef_line(fsevent, severity),
+                SiemFormat::Leef => leef_line(fsevent, severity),
+            };
+            self.writer.write_all(line.as_bytes())?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Flush the buffered events to the underlying writer
+    pub fn flush(&mut self) -> Result<(), FsEventsError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flush the buffered events and get back the underlying writer
+    pub fn into_inner(self) -> Result<W, FsEventsError> {
+        self.writer
+            .into_inner()
+            .map_err(|err| FsEventsError::Io(err.into_error()))
+    }
+}
+
+/// Write records as CEF events with the default severity rules
+pub fn write_cef<W: Write>(fsevents: &[FsEvents], writer: W) -> Result<(), FsEventsError> {
+    let mut writer = SiemWriter::new(writer, SiemFormat::Cef);
+    writer.write_records(fsevents)?;
+    writer.flush()
+}
+
+/// Write records as LEEF events with the default severity rules
+pub fn write_leef<W: Write>(fsevents: &[FsEvents], writer: W) -> Result<(), FsEventsError> {
+    let mut writer = SiemWriter::new(writer, SiemFormat::Leef);
+    writer.write_records(fsevents)?;
+    writer.flush()
+}
+
+/// Format a record as a CEF event
+pub fn cef_line(fsevent: &FsEvents, severity: u8) -> String {
+    let flags = fsevent.event_flags();
+    let mut extensions = vec![
+        ("act", event_action(flags).to_string()),
+        ("filePath", fsevent.path.clone()),
+        ("fname", file_name(&fsevent.path).to_string()),
+        ("externalId", fsevent.event_id.to_string()),
+        ("cs1Label", String::from("Flags")),
+        ("cs1", flags.to_string()),
+        ("cn1Label", String::from("Raw Flags")),
+        ("cn1", fsevent.raw_flags.to_string()),
+        ("cs2Label", String::from("Source File")),
+        ("cs2", fsevent.source_file.clone()),
+    ];
+    if fsevent.node != 0 {
+        extensions.push(("fileId", fsevent.node.to_string()));
+    }
+    let latest = latest_time(fsevent);
+    if latest != 0 {
+        // CEF receipt times are in milliseconds
+        extensions.push(("rt", (latest * 1000).to_string()));
+    }
+    if !fsevent.volume_uuid.is_empty() {
+        extensions.push(("cs3Label", String::from("Volume UUID")));
+        extensions.push(("cs3", fsevent.volume_uuid.clone()));
+    }
+    if !fsevent.source_tag.is_empty() {
+        extensions.push(("cs4Label", String::from("Source Tag")));
+        extensions.push(("cs4", fsevent.source_tag.clone()));
+    }
+
+    let extensions: Vec<String> = extensions
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, escape_cef_value(value)))
+        .collect();
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|{}|{}",
+        escape_header(VENDOR),
+        escape_header(PRODUCT),
+        escape_header(VERSION),
+        event_action(flags),
+        escape_header(&event_name(flags)),
+        severity,
+        extensions.join(" ")
+    )
+}
+
+/// Format a record as a tab delimited LEEF 1.0 event
+pub fn leef_line(fsevent: &FsEvents, severity: u8) -> String {
+    let flags = fsevent.event_flags();
+    let mut attributes = vec![
+        ("cat", event_name(flags)),
+        ("sev", severity.max(1).to_string()),
+        ("filePath", fsevent.path.clone()),
+        ("fileName", file_name(&fsevent.path).to_string()),
+        ("eventId", fsevent.event_id.to_string()),
+        ("flags", flags.to_string()),
+        ("rawFlags", fsevent.raw_flags.to_string()),
+        ("node", fsevent.node.to_string()),
+        ("sourceFile", fsevent.source_file.clone()),
+    ];
+    let latest = latest_time(fsevent);
+    if latest != 0 {
+        attributes.push(("devTime", iso8601(latest)));
+        attributes.push(("devTimeFormat", String::from("yyyy-MM-dd'T'HH:mm:ssX")));
+    }
+    if !fsevent.volume_uuid.is_empty() {
+        attributes.push(("volumeUuid", fsevent.volume_uuid.clone()));
+    }
+    if !fsevent.source_tag.is_empty() {
+        attributes.push(("sourceTag", fsevent.source_tag.clone()));
+    }
+
+    let attributes: Vec<String> = attributes
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, escape_leef_value(value)))
+        .collect();
+    format!(
+        "LEEF:1.0|{}|{}|{}|{}|{}",
+        escape_header(VENDOR),
+        escape_header(PRODUCT),
+        escape_header(VERSION),
+        event_action(flags),
+        attributes.join("\t")
+    )
+}
+
+/// Get the severity (0-10) of each record
+/// Deletions and renames that are part of a burst get a higher severity than the action alone
+pub fn severities(fsevents: &[FsEvents], burst_size: usize, burst_window: u64) -> Vec<u8> {
+    let mut severities: Vec<u8> = fsevents
+        .iter()
+        .map(|fsevent| action_severity(fsevent.event_flags()))
+        .collect();
+
+    let deletion = FsEventFlags::REMOVED | FsEventFlags::LAST_HARD_LINK_REMOVED;
+    for (mask, burst_severity) in [(deletion, 8), (FsEventFlags::RENAMED, 7)] {
+        let mut indexes: Vec<usize> = (0..fsevents.len())
+            .filter(|index| fsevents[*index].event_flags().intersects(mask))
+            .collect();
+        indexes.sort_by_key(|index| fsevents[*index].event_id);
+        for index in burst_indexes(fsevents, &indexes, burst_size, burst_window) {
+            severities[index] = severities[index].max(burst_severity);
+        }
+    }
+    severities
+}
+
+/// Get the records (sorted by event ID) that have at least burst_size records within burst_window
+/// event IDs of each other
+fn burst_indexes(
+    fsevents: &[FsEvents],
+    indexes: &[usize],
+    burst_size: usize,
+    burst_window: u64,
+) -> Vec<usize> {
+    let mut bursts = Vec::new();
+    if burst_size == 0 {
+        return bursts;
+    }
+    let mut start = 0;
+    // Records before this position have already been added to a burst
+    let mut marked = 0;
+    for end in 0..indexes.len() {
+        let end_id = fsevents[indexes[end]].event_id;
+        while end_id - fsevents[indexes[start]].event_id > burst_window {
+            start += 1;
+        }
+        if end - start + 1 >= burst_size {
+            bursts.extend_from_slice(&indexes[start.max(marked)..=end]);
+            marked = end + 1;
+        }
+    }
+    bursts
+}
+
+/// Severity of a single record from its most significant action
+fn action_severity(flags: FsEventFlags) -> u8 {
+    match event_action(flags) {
+        "deleted" => 5,
+        "renamed" | "permissions-changed" | "exchanged" | "truncated" => 4,
+        "created" | "modified" | "extended-attributes-changed" => 3,
+        _ => 2,
+    }
+}
+
+/// Event name from the record flags (ex: `FsEvent Created, IsFile`)
+fn event_name(flags: FsEventFlags) -> String {
+    let names: Vec<&str> = flags.names().collect();
+    if names.is_empty() {
+        return String::from("FsEvent");
+    }
+    format!("FsEvent {}", names.join(", "))
+}
+
+/// Get the file name from a path
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or_default()
+}
+
+/// Escape a CEF or LEEF header value
+fn escape_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Escape a CEF extension value
+fn escape_cef_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Escape a LEEF attribute value, tabs would split the attribute
+fn escape_leef_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cef_line, leef_line, severities, write_cef, SiemFormat, SiemWriter};
+    use crate::{fsevents::FsEvents, parser::parse_bytes};
+    use std::{fs, path::PathBuf};
+
+    fn test_records() -> Vec<FsEvents> {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        parse_bytes(&fs::read(test_location).unwrap()).unwrap()
+    }
+
+    fn test_record(path: &str, event_id: u64, raw_flags: u32) -> FsEvents {
+        FsEvents {
+            path: path.to_string(),
+            event_id,
+            raw_flags,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cef_line() {
+        let records = test_records();
+        let line = cef_line(&records[0], 3);
+        assert!(line.starts_with(&format!(
+            "CEF:0|Apple|macOS FsEvents|{}|created|FsEvent Created, IsFile|3|act=created ",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(line.contains(" filePath=/Users/bob/Downloads/invoice.pdf fname=invoice.pdf "));
+        assert!(line.contains(" externalId=196608 "));
+        assert!(!line.contains(" rt="));
+    }
+
+    #[test]
+    fn test_cef_escape() {
+        let mut record = test_record("/tmp/a=b\\c", 1, 0x02);
+        record.est_time_end = 1700000000;
+        let line = cef_line(&record, 5);
+        assert!(line.contains(" filePath=/tmp/a\\=b\\\\c "));
+        assert!(line.contains(" rt=1700000000000"));
+    }
+
+    #[test]
+    fn test_leef_line() {
+        let records = test_records();
+        let line = leef_line(&records[4], 4);
+        assert!(line.starts_with(&format!(
+            "LEEF:1.0|Apple|macOS FsEvents|{}|renamed|cat=FsEvent Renamed, IsFile\tsev=4\t",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(line.contains("\tfilePath=/Users/bob/.ssh/authorized_keys\t"));
+        assert!(line.contains("\teventId=196625\t"));
+    }
+
+    #[test]
+    fn test_severities() {
+        let mut records = vec![
+            test_record("/tmp/created", 1, 0x01),
+            test_record("/tmp/renamed", 2, 0x08),
+        ];
+        for event_id in 0..5 {
+            records.push(test_record("/tmp/deleted", 100 + event_id, 0x02));
+        }
+        // Too far away from the other deletions to be part of the burst
+        records.push(test_record("/tmp/deleted", 5000, 0x02));
+
+        let results = severities(&records, 5, 10);
+        assert!(results == vec![3, 4, 8, 8, 8, 8, 8, 5]);
+
+        let results = severities(&records, 6, 10);
+        assert!(results == vec![3, 4, 5, 5, 5, 5, 5, 5]);
+    }
+
+    #[test]
+    fn test_write_siem() {
+        let mut output = Vec::new();
+        write_cef(&test_records(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.lines().count() == 5);
+        assert!(output.lines().all(|line| line.starts_with("CEF:0|")));
+
+        let mut writer = SiemWriter::new(Vec::new(), SiemFormat::Leef)
+            .burst_size(2)
+            .burst_window(100);
+        writer.write_records(&test_records()).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(output.lines().count() == 5);
+        assert!(output.lines().all(|line| line.starts_with("LEEF:1.0|")));
+    }
+}
@@ -0,0 +1,114 @@
+//! Split output into numbered parts
+//!
+//! Excel and many SIEM uploaders cannot open multi-gigabyte files, so CSV and JSON Lines output
+//! can be rotated into parts (ex: `output_0001.csv`, `output_0002.csv`) once a part reaches a
+//! record count or size. `CountingWriter` tracks how many bytes have been written to a part, even
+//! when it is wrapped by a format writer or compression.
+
+use std::{
+    cell::Cell,
+    io::{Result, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// Parse a size in bytes, with an optional unit: `B`, `KB`, `MB`, `GB`, or `TB` (ex: `500MB`)
+/// Units are powers of 1024, and `K`, `KiB`, and the other short and binary names are accepted
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let digits = text
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(digits);
+    let number = number.parse::<u64>().ok()?;
+    let power = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 1,
+        "m" | "mb" | "mib" => 2,
+        "g" | "gb" | "gib" => 3,
+        "t" | "tb" | "tib" => 4,
+        _ => return None,
+    };
+    number.checked_mul(1024_u64.pow(power))
+}
+
+/// Get the path of a numbered part, added before the file extensions
+/// (ex: `output.csv.gz` part 2 is `output_0002.csv.gz`)
+pub fn part_path(path: &Path, part: usize) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (stem, extension) = match name.find('.') {
+        Some(index) => name.split_at(index),
+        None => (name.as_str(), ""),
+    };
+    path.with_file_name(format!("{}_{:04}{}", stem, part, extension))
+}
+
+/// Writer that counts the bytes written through it
+pub struct CountingWriter<W: Write> {
+    writer: W,              // Output the bytes are written to
+    written: Rc<Cell<u64>>, // Bytes written, shared with the counter returned by `counter`
+}
+
+impl<W: Write> CountingWriter<W> {
+    /// Count the bytes written to a writer
+    pub fn new(writer: W) -> CountingWriter<W> {
+        CountingWriter {
+            writer,
+            written: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Get the count of bytes written, which can be read after the writer is wrapped
+    pub fn counter(&self) -> Rc<Cell<u64>> {
+        self.written.clone()
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let size = self.writer.write(buf)?;
+        self.written.set(self.written.get() + size as u64);
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_size, part_path, CountingWriter};
+    use std::{io::Write, path::Path};
+
+    #[test]
+    fn test_parse_size() {
+        assert!(parse_size("1024") == Some(1024));
+        assert!(parse_size("500MB") == Some(500 * 1024 * 1024));
+        assert!(parse_size("2 GiB") == Some(2 * 1024 * 1024 * 1024));
+        assert!(parse_size("64k") == Some(64 * 1024));
+        assert!(parse_size("1.5GB").is_none());
+        assert!(parse_size("MB").is_none());
+        assert!(parse_size("10PB").is_none());
+        assert!(parse_size("99999999999TB").is_none());
+    }
+
+    #[test]
+    fn test_part_path() {
+        let path = Path::new("/tmp/out/output.csv.gz");
+        assert!(part_path(path, 2) == Path::new("/tmp/out/output_0002.csv.gz"));
+        assert!(part_path(Path::new("output"), 12345) == Path::new("output_12345"));
+    }
+
+    #[test]
+    fn test_counting_writer() {
+        let mut writer = CountingWriter::new(Vec::new());
+        let counter = writer.counter();
+        writer.write_all(b"Path,Flags\n").unwrap();
+        writer.write_all(b"/tmp/a.txt,Created\n").unwrap();
+        assert!(counter.get() == 30);
+    }
+}
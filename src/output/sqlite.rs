@@ -0,0 +1,247 @@
+//! Write records to SQLite databases
+//!
+//! Records are written to a `records` table indexed on path, event ID, and flags. Each FsEvent file
+//! the records came from is listed once in a `source_files` table with its record count, event ID
+//! range, and its size and modified time if the file can still be read. Records link to their file
+//! with `source_file_id`.
+
+use crate::{error::FsEventsError, fsevents::FsEvents};
+use rusqlite::{params, Connection};
+use std::{collections::HashMap, fs::metadata, time::UNIX_EPOCH};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS source_files (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    records INTEGER NOT NULL,
+    min_event_id INTEGER NOT NULL,
+    max_event_id INTEGER NOT NULL,
+    volume_uuid TEXT NOT NULL,
+    source_tag TEXT NOT NULL,
+    size INTEGER,
+    modified INTEGER
+);
+CREATE TABLE IF NOT EXISTS records (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL,
+    flags TEXT NOT NULL,
+    raw_flags INTEGER NOT NULL,
+    node INTEGER NOT NULL,
+    event_id INTEGER NOT NULL,
+    extra_id INTEGER NOT NULL,
+    source_file_id INTEGER NOT NULL REFERENCES source_files(id),
+    offset INTEGER NOT NULL,
+    page INTEGER NOT NULL,
+    approx_earliest_time INTEGER NOT NULL,
+    approx_latest_time INTEGER NOT NULL,
+    est_time_start INTEGER NOT NULL,
+    est_time_end INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS records_path ON records(path);
+CREATE INDEX IF NOT EXISTS records_event_id ON records(event_id);
+CREATE INDEX IF NOT EXISTS records_raw_flags ON records(raw_flags);
+CREATE INDEX IF NOT EXISTS records_flags ON records(flags);
+";
+
+/// Summary of the records from one FsEvent file
+struct SourceFile<'a> {
+    records: usize,       // Number of records from the file
+    min_event_id: u64,    // Lowest event ID in the file
+    max_event_id: u64,    // Highest event ID in the file
+    volume_uuid: &'a str, // Volume UUID attached to the records
+    source_tag: &'a str,  // Where the FsEvent directory came from
+}
+
+/// Write records to a SQLite database file, creating the tables if they do not exist
+/// Records are added to any records already in the database
+pub fn write_sqlite(fsevents: &[FsEvents], path: &str) -> Result<(), FsEventsError> {
+    let mut connection = Connection::open(path).map_err(output_error)?;
+    write_sqlite_connection(fsevents, &mut connection)
+}
+
+/// Write records to an open SQLite connection, all records are written in one transaction
+pub fn write_sqlite_connection(
+    fsevents: &[FsEvents],
+    connection: &mut Connection,
+) -> Result<(), FsEventsError> {
+    let transaction = connection.transaction().map_err(output_error)?;
+    transaction.execute_batch(SCHEMA).map_err(output_error)?;
+    {
+        let mut insert_file = transaction
+            .prepare(
+                "INSERT INTO source_files (path, records, min_event_id, max_event_id, volume_uuid,
+                source_tag, size, modified) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                ON CONFLICT(path) DO UPDATE SET records = records + excluded.records,
+                min_event_id = MIN(min_event_id, excluded.min_event_id),
+                max_event_id = MAX(max_event_id, excluded.max_event_id)
+                RETURNING id",
+            )
+            .map_err(output_error)?;
+        let mut file_ids: HashMap<&str, i64> = HashMap::new();
+        for (path, file) in source_files(fsevents) {
+            let (size, modified) = file_metadata(path);
+            let id = insert_file
+                .query_row(
+                    params![
+                        path,
+                        file.records as i64,
+                        file.min_event_id as i64,
+                        file.max_event_id as i64,
+                        file.volume_uuid,
+                        file.source_tag,
+                        size,
+                        modified
+                    ],
+                    |row| row.get(0),
+                )
+                .map_err(output_error)?;
+            file_ids.insert(path, id);
+        }
+
+        let mut insert_record = transaction
+            .prepare(
+                "INSERT INTO records (path, flags, raw_flags, node, event_id, extra_id,
+                source_file_id, offset, page, approx_earliest_time, approx_latest_time,
+                est_time_start, est_time_end)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            )
+            .map_err(output_error)?;
+        for fsevent in fsevents {
+            insert_record
+                .execute(params![
+                    fsevent.path,
+                    fsevent.flags,
+                    fsevent.raw_flags,
+                    fsevent.node as i64,
+                    fsevent.event_id as i64,
+                    fsevent.extra_id,
+                    file_ids.get(fsevent.source_file.as_str()),
+                    fsevent.offset as i64,
+                    fsevent.page,
+                    fsevent.approx_earliest_time,
+                    fsevent.approx_latest_time,
+                    fsevent.est_time_start,
+                    fsevent.est_time_end
+                ])
+                .map_err(output_error)?;
+        }
+    }
+    transaction.commit().map_err(output_error)
+}
+
+/// Summarize the records from each source file, in the order the files first appear
+fn source_files(fsevents: &[FsEvents]) -> Vec<(&str, SourceFile<'_>)> {
+    let mut files: Vec<(&str, SourceFile)> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for fsevent in fsevents {
+        let position = *index.entry(&fsevent.source_file).or_insert_with(|| {
+            files.push((
+                &fsevent.source_file,
+                SourceFile {
+                    records: 0,
+                    min_event_id: u64::MAX,
+                    max_event_id: 0,
+                    volume_uuid: &fsevent.volume_uuid,
+                    source_tag: &fsevent.source_tag,
+                },
+            ));
+            files.len() - 1
+        });
+        let file = &mut files[position].1;
+        file.records += 1;
+        file.min_event_id = file.min_event_id.min(fsevent.event_id);
+        file.max_event_id = file.max_event_id.max(fsevent.event_id);
+    }
+    files
+}
+
+/// Get the size and modified time (Unix epoch) of a source file, if it can still be read
+fn file_metadata(path: &str) -> (Option<i64>, Option<i64>) {
+    let meta = match metadata(path) {
+        Ok(result) => result,
+        Err(_) => return (None, None),
+    };
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs() as i64);
+    (Some(meta.len() as i64), modified)
+}
+
+/// Convert a SQLite error to an output error
+fn output_error(err: rusqlite::Error) -> FsEventsError {
+    FsEventsError::Output(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_sqlite, write_sqlite_connection};
+    use crate::{options::ParserOptions, parser::parse_directory_with_options};
+    use rusqlite::Connection;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_sqlite_connection() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let fsevents = parse_directory_with_options(
+            &test_location.display().to_string(),
+            &ParserOptions::new(),
+        )
+        .unwrap();
+
+        let mut connection = Connection::open_in_memory().unwrap();
+        write_sqlite_connection(&fsevents, &mut connection).unwrap();
+
+        let records: i64 = connection
+            .query_row("SELECT COUNT(*) FROM records", [], |row| row.get(0))
+            .unwrap();
+        assert!(records == 5736);
+
+        let (file_records, max_event_id, size): (i64, i64, i64) = connection
+            .query_row(
+                "SELECT records, max_event_id, size FROM source_files WHERE path LIKE '%27d79'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert!(file_records == 736);
+        assert!(max_event_id == 0x27d78);
+        assert!(size > 0);
+
+        let joined: i64 = connection
+            .query_row(
+                "SELECT COUNT(*) FROM records JOIN source_files
+                ON records.source_file_id = source_files.id WHERE source_files.path LIKE '%2469c'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(joined == 5000);
+
+        let index: String = connection
+            .query_row(
+                "EXPLAIN QUERY PLAN SELECT * FROM records WHERE event_id = 1",
+                [],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(index.contains("records_event_id"));
+    }
+
+    #[test]
+    fn test_write_sqlite() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("output.db");
+        let path = test_location.display().to_string();
+
+        write_sqlite(&[], &path).unwrap();
+        let connection = Connection::open(&path).unwrap();
+        let files: i64 = connection
+            .query_row("SELECT COUNT(*) FROM source_files", [], |row| row.get(0))
+            .unwrap();
+        assert!(files == 0);
+    }
+}
@@ -0,0 +1,249 @@
+//! Format the times added to records (approximate, estimated, and live file times)
+//!
+//! Times are Unix epoch seconds by default. `TimeFormat` writes them as ISO 8601 or with a
+//! strftime pattern, in UTC, the system time zone, a fixed offset, or an IANA time zone
+//! (ex: `America/New_York`), so reports match the time zone a case requires. Times that were not
+//! added (0) are left empty.
+
+use crate::{error::FsEventsError, fsevents::FsEvents};
+use jiff::{
+    fmt::strtime,
+    tz::{Offset, TimeZone},
+    Timestamp,
+};
+use serde_json::Value;
+
+/// strftime pattern for ISO 8601 times with the time zone offset
+const ISO8601_PATTERN: &str = "%Y-%m-%dT%H:%M:%S%:z";
+/// Record fields that hold times
+const TIME_FIELDS: [&str; 4] = [
+    "approx_earliest_time",
+    "approx_latest_time",
+    "est_time_start",
+    "est_time_end",
+];
+/// Live status fields that hold times
+const LIVE_TIME_FIELDS: [&str; 3] = ["modified", "accessed", "created"];
+
+/// How times are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeStyle {
+    Unix,            // Unix epoch seconds
+    Iso8601,         // ISO 8601 with the time zone offset (ex: 2023-11-14T17:13:20-05:00)
+    Pattern(String), // strftime pattern (ex: %Y-%m-%d %H:%M:%S %Z)
+}
+
+impl TimeStyle {
+    /// Get a style from its name: `unix`, `iso8601`, or a strftime pattern containing `%`
+    pub fn from_name(name: &str) -> Option<TimeStyle> {
+        match name.trim().to_lowercase().as_str() {
+            "unix" | "epoch" => Some(TimeStyle::Unix),
+            "iso8601" | "iso" => Some(TimeStyle::Iso8601),
+            _ if name.contains('%') => Some(TimeStyle::Pattern(name.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// Style and time zone for record times
+#[derive(Debug, Clone)]
+pub struct TimeFormat {
+    style: TimeStyle,   // How times are written
+    timezone: TimeZone, // Time zone times are converted to
+}
+
+impl TimeFormat {
+    /// Write times as Unix epoch seconds, in UTC
+    pub fn new() -> TimeFormat {
+        TimeFormat {
+            style: TimeStyle::Unix,
+            timezone: TimeZone::UTC,
+        }
+    }
+
+    /// Set how times are written. Patterns are checked when they are set
+    pub fn style(mut self, style: TimeStyle) -> Result<TimeFormat, FsEventsError> {
+        if let TimeStyle::Pattern(pattern) = &style {
+            let zoned = Timestamp::UNIX_EPOCH.to_zoned(self.timezone.clone());
+            strtime::format(pattern, &zoned).map_err(time_error)?;
+        }
+        self.style = style;
+        Ok(self)
+    }
+
+    /// Set the time zone: `UTC`, `local` (the system time zone), a fixed offset (ex: `+05:30`
+    /// or `-0800`), or an IANA time zone name (ex: `America/New_York`)
+    pub fn timezone(mut self, name: &str) -> Result<TimeFormat, FsEventsError> {
+        let name = name.trim();
+        self.timezone = if name.eq_ignore_ascii_case("utc") || name == "Z" {
+            TimeZone::UTC
+        } else if name.eq_ignore_ascii_case("local") {
+            TimeZone::system()
+        } else if let Some(offset) = parse_offset(name) {
+            TimeZone::fixed(offset)
+        } else {
+            TimeZone::get(name).map_err(time_error)?
+        };
+        Ok(self)
+    }
+
+    /// Check if times are written as Unix epoch seconds
+    pub fn is_unix(&self) -> bool {
+        self.style == TimeStyle::Unix
+    }
+
+    /// Format a Unix epoch time in seconds
+    pub fn format(&self, seconds: i64) -> String {
+        let pattern = match &self.style {
+            TimeStyle::Unix => return seconds.to_string(),
+            _ if seconds == 0 => return String::new(),
+            TimeStyle::Iso8601 => ISO8601_PATTERN,
+            TimeStyle::Pattern(pattern) => pattern.as_str(),
+        };
+        // Times outside jiff's range (year -9999 to 9999) are written as seconds
+        match Timestamp::from_second(seconds) {
+            Ok(timestamp) => {
+                let zoned = timestamp.to_zoned(self.timezone.clone());
+                strtime::format(pattern, &zoned).unwrap_or_else(|_| seconds.to_string())
+            }
+            Err(_) => seconds.to_string(),
+        }
+    }
+
+    /// Convert a record to JSON with its times formatted. Times that were not added are null
+    pub fn json_record(&self, fsevent: &FsEvents) -> Result<Value, FsEventsError> {
+        let mut record =
+            serde_json::to_value(fsevent).map_err(|err| FsEventsError::Output(err.to_string()))?;
+        if self.is_unix() {
+            return Ok(record);
+        }
+        for field in TIME_FIELDS {
+            self.format_json(&mut record[field]);
+        }
+        if let Some(status) = record.get_mut("live_status") {
+            for field in LIVE_TIME_FIELDS {
+                self.format_json(&mut status[field]);
+            }
+        }
+        Ok(record)
+    }
+
+    /// Replace a JSON time in seconds with the formatted time
+    fn format_json(&self, value: &mut Value) {
+        if let Some(seconds) = value.as_i64() {
+            let time = self.format(seconds);
+            *value = if time.is_empty() {
+                Value::Null
+            } else {
+                Value::String(time)
+            };
+        }
+    }
+}
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        TimeFormat::new()
+    }
+}
+
+/// Parse a fixed offset: +HH, +HH:MM, or +HHMM (or with -)
+fn parse_offset(name: &str) -> Option<Offset> {
+    let (sign, value) = match name.split_at_checked(1)? {
+        ("+", value) => (1, value),
+        ("-", value) => (-1, value),
+        _ => return None,
+    };
+    let digits = value.replace(':', "");
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    if hours > 23 || minutes >= 60 {
+        return None;
+    }
+    Offset::from_seconds(sign * (hours * 3600 + minutes * 60)).ok()
+}
+
+/// Convert a jiff error to a time zone or format error
+fn time_error(err: jiff::Error) -> FsEventsError {
+    FsEventsError::InvalidTimeFormat(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeFormat, TimeStyle};
+    use crate::{error::FsEventsError, fsevents::FsEvents};
+
+    #[test]
+    fn test_time_format_unix() {
+        let format = TimeFormat::new().timezone("America/New_York").unwrap();
+        assert!(format.format(1700000000) == "1700000000");
+        assert!(format.format(0) == "0");
+    }
+
+    #[test]
+    fn test_time_format_iso8601() {
+        let format = TimeFormat::new().style(TimeStyle::Iso8601).unwrap();
+        assert!(format.format(1700000000) == "2023-11-14T22:13:20+00:00");
+        assert!(format.format(0).is_empty());
+
+        let format = format.timezone("America/New_York").unwrap();
+        assert!(format.format(1700000000) == "2023-11-14T17:13:20-05:00");
+        // Daylight saving time
+        assert!(format.format(1690000000) == "2023-07-22T00:26:40-04:00");
+
+        let format = format.timezone("+05:30").unwrap();
+        assert!(format.format(1700000000) == "2023-11-15T03:43:20+05:30");
+        let format = format.timezone("-0800").unwrap();
+        assert!(format.format(1700000000) == "2023-11-14T14:13:20-08:00");
+    }
+
+    #[test]
+    fn test_time_format_pattern() {
+        let style = TimeStyle::from_name("%m/%d/%Y %H:%M:%S %Z").unwrap();
+        let format = TimeFormat::new()
+            .timezone("Europe/Berlin")
+            .unwrap()
+            .style(style)
+            .unwrap();
+        assert!(format.format(1700000000) == "11/14/2023 23:13:20 CET");
+    }
+
+    #[test]
+    fn test_time_format_invalid() {
+        assert!(matches!(
+            TimeFormat::new().timezone("Mars/Olympus_Mons"),
+            Err(FsEventsError::InvalidTimeFormat(_))
+        ));
+        assert!(TimeFormat::new().timezone("+25:00").is_err());
+        assert!(TimeFormat::new()
+            .style(TimeStyle::Pattern(String::from("%Y %Q %!")))
+            .is_err());
+        assert!(TimeStyle::from_name("rfc").is_none());
+    }
+
+    #[test]
+    fn test_json_record() {
+        let record = FsEvents {
+            path: String::from("/tmp/a.txt"),
+            approx_earliest_time: 1700000000,
+            ..Default::default()
+        };
+        let format = TimeFormat::new().style(TimeStyle::Iso8601).unwrap();
+        let json = format.json_record(&record).unwrap();
+        assert!(json["approx_earliest_time"] == "2023-11-14T22:13:20+00:00");
+        assert!(json["est_time_end"].is_null());
+        assert!(json["path"] == "/tmp/a.txt");
+
+        let json = TimeFormat::new().json_record(&record).unwrap();
+        assert!(json["approx_earliest_time"] == 1700000000);
+    }
+}
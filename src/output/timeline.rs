@@ -0,0 +1,238 @@
+//! Export records as a super-timeline for plaso and Timesketch
+//!
+//! Two layouts are supported: plaso's l2tcsv CSV format and Timesketch JSON Lines (one event per
+//! line with `message`, `datetime`, `timestamp`, and `timestamp_desc`). FsEvent records do not
+//! store a time, so each event uses the latest time the record could have happened and says how
+//! that time was found in `timestamp_desc`. Records without a time use the Unix epoch with
+//! `Not a time`, the same as plaso.
+
+use super::{iso8601, latest_time, macb_flags};
+use crate::{error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents};
+use serde::Serialize;
+use std::io::{BufWriter, Write};
+
+/// Source and data type of every event
+const SOURCE: &str = "FSEVENTS";
+const SOURCE_LONG: &str = "macOS FsEvents";
+const DATA_TYPE: &str = "macos:fseventsd:record";
+
+/// l2tcsv column names
+const L2TCSV_HEADER: [&str; 17] = [
+    "date",
+    "time",
+    "timezone",
+    "MACB",
+    "source",
+    "sourcetype",
+    "type",
+    "user",
+    "host",
+    "short",
+    "desc",
+    "version",
+    "filename",
+    "inode",
+    "notes",
+    "format",
+    "extra",
+];
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TimelineEvent {
+    pub message: String,              // Path and flags of the record
+    pub datetime: String,             // ISO 8601 time of the event
+    pub timestamp: i64,               // Time of the event in microseconds (Unix epoch)
+    pub timestamp_desc: &'static str, // How the time was found
+    pub source: &'static str,         // Always "FSEVENTS"
+    pub source_long: &'static str,    // Always "macOS FsEvents"
+    pub data_type: &'static str,      // Always "macos:fseventsd:record"
+    pub path: String,                 // Full path of the file
+    pub flags: String,                // Flag names
+    pub event_id: u64,                // FsEvent event ID
+    pub node: u64,                    // Node ID
+    pub source_file: String,          // FsEvent file that contained the record
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub volume_uuid: String, // Volume UUID from the fseventsd-uuid file
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub source_tag: String, // Where the FsEvent directory came from
+}
+
+/// Convert a record to a timeline event
+pub fn timeline_event(fsevent: &FsEvents) -> TimelineEvent {
+    let latest = latest_time(fsevent);
+    let timestamp_desc = if fsevent.est_time_end != 0 {
+        "Estimated Latest Time"
+    } else if fsevent.approx_latest_time != 0 {
+        "Approximate Latest Time"
+    } else {
+        "Not a time"
+    };
+    let flags = fsevent.event_flags().to_string();
+
+    TimelineEvent {
+        message: format!("{} Flags: {}", fsevent.path, flags),
+        datetime: iso8601(latest),
+        timestamp: latest.saturating_mul(1000000),
+        timestamp_desc,
+        source: SOURCE,
+        source_long: SOURCE_LONG,
+        data_type: DATA_TYPE,
+        path: fsevent.path.clone(),
+        flags,
+        event_id: fsevent.event_id,
+        node: fsevent.node,
+        source_file: fsevent.source_file.clone(),
+        volume_uuid: fsevent.volume_uuid.clone(),
+        source_tag: fsevent.source_tag.clone(),
+    }
+}
+
+/// Write records as Timesketch JSON Lines, one event per line
+pub fn write_timesketch<W: Write>(fsevents: &[FsEvents], writer: W) -> Result<(), FsEventsError> {
+    let mut writer = BufWriter::new(writer);
+    for fsevent in fsevents {
+        serde_json::to_writer(&mut writer, &timeline_event(fsevent))
+            .map_err(|err| FsEventsError::Output(err.to_string()))?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write records as plaso l2tcsv rows, with a header. Dates and times are UTC
+pub fn write_l2tcsv<W: Write>(fsevents: &[FsEvents], writer: W) -> Result<(), FsEventsError> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(L2TCSV_HEADER).map_err(output_error)?;
+    for fsevent in fsevents {
+        let event = timeline_event(fsevent);
+        // ISO 8601 times are YYYY-MM-DDTHH:MM:SSZ, l2tcsv dates are MM/DD/YYYY
+        let (date, time) = event.datetime.split_once('T').unwrap_or_default();
+        let mut date_parts = date.rsplitn(3, '-');
+        let (day, month, year) = (
+            date_parts.next().unwrap_or_default(),
+            date_parts.next().unwrap_or_default(),
+            date_parts.next().unwrap_or_default(),
+        );
+        let extra = format!(
+            "event_id: {}; raw_flags: {:#x}; source_tag: {}; volume_uuid: {}",
+            fsevent.event_id, fsevent.raw_flags, fsevent.source_tag, fsevent.volume_uuid
+        );
+        writer
+            .write_record([
+                format!("{}/{}/{}", month, day, year).as_str(),
+                time.trim_end_matches('Z'),
+                "UTC",
+                macb(fsevent.event_flags()),
+                SOURCE,
+                SOURCE_LONG,
+                event.timestamp_desc,
+                "-",
+                "-",
+                &event.message,
+                &format!(
+                    "{} Flags: {} Event ID: {} Node: {}",
+                    event.path, event.flags, event.event_id, event.node
+                ),
+                "2",
+                &event.source_file,
+                &event.node.to_string(),
+                "-",
+                DATA_TYPE,
+                &extra,
+            ])
+            .map_err(output_error)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Get the l2tcsv MACB value (Modified, Accessed, Changed, Born) from the flags
+fn macb(flags: FsEventFlags) -> &'static str {
+    match macb_flags(flags) {
+        (true, true, true) => "M.CB",
+        (true, true, false) => "M.C.",
+        (true, false, true) => "M..B",
+        (true, false, false) => "M...",
+        (false, true, true) => "..CB",
+        (false, true, false) => "..C.",
+        (false, false, true) => "...B",
+        (false, false, false) => "....",
+    }
+}
+
+/// Convert a CSV error to an output error
+fn output_error(err: csv::Error) -> FsEventsError {
+    FsEventsError::Output(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{macb, timeline_event, write_l2tcsv, write_timesketch};
+    use crate::{flags::FsEventFlags, fsevents::FsEvents, parser::parse_bytes};
+    use std::{fs, path::PathBuf};
+
+    fn test_records() -> Vec<FsEvents> {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        parse_bytes(&fs::read(test_location).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_timeline_event() {
+        let mut record = FsEvents {
+            path: String::from("/tmp/a.txt"),
+            raw_flags: 0x01,
+            ..Default::default()
+        };
+        let event = timeline_event(&record);
+        assert!(event.datetime == "1970-01-01T00:00:00Z");
+        assert!(event.timestamp_desc == "Not a time");
+        assert!(event.message == "/tmp/a.txt Flags: Created");
+
+        record.approx_latest_time = 1700000000;
+        let event = timeline_event(&record);
+        assert!(event.timestamp == 1700000000000000);
+        assert!(event.timestamp_desc == "Approximate Latest Time");
+
+        record.est_time_end = 1700000060;
+        let event = timeline_event(&record);
+        assert!(event.datetime == "2023-11-14T22:14:20Z");
+        assert!(event.timestamp_desc == "Estimated Latest Time");
+    }
+
+    #[test]
+    fn test_macb() {
+        assert!(macb(FsEventFlags::CREATED | FsEventFlags::IS_FILE) == "...B");
+        assert!(macb(FsEventFlags::MODIFIED | FsEventFlags::RENAMED) == "M.C.");
+        assert!(macb(FsEventFlags::REMOVED) == "....");
+    }
+
+    #[test]
+    fn test_write_timesketch() {
+        let mut output = Vec::new();
+        write_timesketch(&test_records(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.len() == 5);
+
+        let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert!(event["message"] == "/Users/bob/Downloads/invoice.pdf Flags: Created,IsFile");
+        assert!(event["timestamp_desc"] == "Not a time");
+        assert!(event["data_type"] == "macos:fseventsd:record");
+    }
+
+    #[test]
+    fn test_write_l2tcsv() {
+        let mut records = test_records();
+        records[0].est_time_end = 1700000000;
+        let mut output = Vec::new();
+        write_l2tcsv(&records, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.len() == 6);
+        assert!(lines[0].starts_with("date,time,timezone,MACB,source,sourcetype,type,"));
+        assert!(lines[1].starts_with(
+            "11/14/2023,22:13:20,UTC,...B,FSEVENTS,macOS FsEvents,Estimated Latest Time,-,-,"
+        ));
+    }
+}
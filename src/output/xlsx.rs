@@ -0,0 +1,287 @@
+//! Write records to XLSX workbooks
+//!
+//! The workbook has three sheets:
+//! - `Records`: every record with the CSV columns. Excel sheets hold at most 1,048,576 rows, so
+//!   larger collections continue on `Records 2`, `Records 3`, etc
+//! - `Flags`: the number of records with each flag
+//! - `Directories`: for each parent directory, the number of records and unique paths, the event ID
+//!   range, and how many records created, modified, removed, or renamed files in it
+
+use super::csv::Column;
+use crate::{error::FsEventsError, flags::FsEventFlags, fsevents::FsEvents, stats::get_stats};
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{Seek, Write},
+    path::Path,
+};
+
+/// Rows per Records sheet, the Excel row limit minus the header
+const MAX_SHEET_RECORDS: usize = 1048575;
+/// Largest integer an Excel number (f64) holds exactly
+const MAX_EXACT_NUMBER: u64 = 1 << 53;
+
+/// Columns in the Directories sheet
+const DIRECTORY_HEADERS: [&str; 9] = [
+    "Directory",
+    "Records",
+    "Unique Paths",
+    "First Event ID",
+    "Last Event ID",
+    "Created",
+    "Modified",
+    "Removed",
+    "Renamed",
+];
+
+/// Summary of the records in a directory
+#[derive(Default)]
+struct DirectorySummary<'a> {
+    records: u64,
+    paths: HashSet<&'a str>,
+    first_event_id: u64,
+    last_event_id: u64,
+    created: u64,
+    modified: u64,
+    removed: u64,
+    renamed: u64,
+}
+
+/// Write records as an XLSX workbook to any seekable writer
+pub fn write_xlsx<W: Write + Seek + Send>(
+    fsevents: &[FsEvents],
+    writer: W,
+) -> Result<(), FsEventsError> {
+    let mut workbook = build_workbook(fsevents).map_err(output_error)?;
+    workbook.save_to_writer(writer).map_err(output_error)
+}
+
+#[cfg(feature = "filesystem")]
+/// Write records as an XLSX workbook to a file
+pub fn write_xlsx_file(fsevents: &[FsEvents], path: &str) -> Result<(), FsEventsError> {
+    write_xlsx(fsevents, std::fs::File::create(path)?)
+}
+
+/// Build the workbook with the records and summary sheets
+fn build_workbook(fsevents: &[FsEvents]) -> Result<Workbook, XlsxError> {
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+
+    let chunks: Vec<&[FsEvents]> = if fsevents.is_empty() {
+        vec![fsevents]
+    } else {
+        fsevents.chunks(MAX_SHEET_RECORDS).collect()
+    };
+    for (index, chunk) in chunks.iter().enumerate() {
+        let sheet = workbook.add_worksheet();
+        if index == 0 {
+            sheet.set_name("Records")?;
+        } else {
+            sheet.set_name(format!("Records {}", index + 1))?;
+        }
+        write_records_sheet(sheet, chunk, &bold)?;
+    }
+
+    let sheet = workbook.add_worksheet().set_name("Flags")?;
+    write_header(sheet, &["Flag", "Records"], &bold)?;
+    let stats = get_stats(fsevents);
+    for (row, (flag, count)) in stats.flags.iter().enumerate() {
+        let row = row as u32 + 1;
+        sheet.write_string(row, 0, flag)?;
+        sheet.write_number(row, 1, *count as f64)?;
+    }
+    sheet.set_column_width(0, 30)?;
+
+    let sheet = workbook.add_worksheet().set_name("Directories")?;
+    write_header(sheet, &DIRECTORY_HEADERS, &bold)?;
+    for (row, (directory, summary)) in directory_summaries(fsevents).iter().enumerate() {
+        let row = row as u32 + 1;
+        sheet.write_string(row, 0, *directory)?;
+        let values = [
+            summary.records,
+            summary.paths.len() as u64,
+            summary.first_event_id,
+            summary.last_event_id,
+            summary.created,
+            summary.modified,
+            summary.removed,
+            summary.renamed,
+        ];
+        for (column, value) in values.into_iter().enumerate() {
+            write_integer(sheet, row, column as u16 + 1, value)?;
+        }
+    }
+    sheet.set_column_width(0, 60)?;
+    Ok(workbook)
+}
+
+/// Write records to a sheet with a header row, filter buttons, and a frozen header
+fn write_records_sheet(
+    sheet: &mut Worksheet,
+    fsevents: &[FsEvents],
+    bold: &Format,
+) -> Result<(), XlsxError> {
+    let headers: Vec<&str> = Column::ALL.iter().map(Column::header).collect();
+    write_header(sheet, &headers, bold)?;
+    for (row, fsevent) in fsevents.iter().enumerate() {
+        let row = row as u32 + 1;
+        for (column, field) in Column::ALL.iter().enumerate() {
+            let column = column as u16;
+            let value = field.value(fsevent);
+            if is_numeric(field) {
+                if let Ok(number) = value.parse::<i64>() {
+                    if number.unsigned_abs() <= MAX_EXACT_NUMBER {
+                        sheet.write_number(row, column, number as f64)?;
+                        continue;
+                    }
+                }
+            }
+            if !value.is_empty() {
+                sheet.write_string(row, column, value)?;
+            }
+        }
+    }
+    sheet.autofilter(0, 0, fsevents.len() as u32, Column::ALL.len() as u16 - 1)?;
+    sheet.set_column_width(0, 60)?;
+    Ok(())
+}
+
+/// Write a bold header row and freeze it
+fn write_header(sheet: &mut Worksheet, headers: &[&str], bold: &Format) -> Result<(), XlsxError> {
+    for (column, header) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, column as u16, *header, bold)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+/// Write an integer as a number, or as text if Excel cannot hold it exactly
+fn write_integer(
+    sheet: &mut Worksheet,
+    row: u32,
+    column: u16,
+    value: u64,
+) -> Result<(), XlsxError> {
+    if value <= MAX_EXACT_NUMBER {
+        sheet.write_number(row, column, value as f64)?;
+    } else {
+        sheet.write_string(row, column, value.to_string())?;
+    }
+    Ok(())
+}
+
+/// Check if a column holds integers
+fn is_numeric(column: &Column) -> bool {
+    !matches!(
+        column,
+        Column::Path
+            | Column::Flags
+            | Column::SourceFile
+            | Column::VolumeUuid
+            | Column::SourceTag
+            | Column::LiveExists
+            | Column::CurrentPath
+            | Column::TriageRule
+    )
+}
+
+/// Summarize the records in each parent directory
+fn directory_summaries(fsevents: &[FsEvents]) -> BTreeMap<&str, DirectorySummary<'_>> {
+    let mut directories: BTreeMap<&str, DirectorySummary> = BTreeMap::new();
+    for fsevent in fsevents {
+        let directory = Path::new(&fsevent.path)
+            .parent()
+            .and_then(|parent| parent.to_str())
+            .unwrap_or_default();
+        let summary = directories.entry(directory).or_default();
+        if summary.records == 0 {
+            summary.first_event_id = fsevent.event_id;
+        }
+        summary.records += 1;
+        summary.paths.insert(&fsevent.path);
+        summary.first_event_id = summary.first_event_id.min(fsevent.event_id);
+        summary.last_event_id = summary.last_event_id.max(fsevent.event_id);
+
+        let flags = fsevent.event_flags();
+        let counts = [
+            (FsEventFlags::CREATED, &mut summary.created),
+            (FsEventFlags::MODIFIED, &mut summary.modified),
+            (FsEventFlags::REMOVED, &mut summary.removed),
+            (FsEventFlags::RENAMED, &mut summary.renamed),
+        ];
+        for (flag, count) in counts {
+            if flags.contains(flag) {
+                *count += 1;
+            }
+        }
+    }
+    directories
+}
+
+/// Convert an XLSX error to an output error
+fn output_error(err: XlsxError) -> FsEventsError {
+    FsEventsError::Output(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{directory_summaries, write_xlsx};
+    use crate::{fsevents::FsEvents, parser::parse_bytes};
+    use std::{fs, io::Cursor, io::Read, path::PathBuf};
+
+    fn test_records() -> Vec<FsEvents> {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        parse_bytes(&fs::read(test_location).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_directory_summaries() {
+        let records = test_records();
+        let directories = directory_summaries(&records);
+        let downloads = &directories["/Users/bob/Downloads"];
+        assert!(downloads.records >= 1);
+        assert!(downloads.created >= 1);
+        assert!(downloads.first_event_id == 196608);
+
+        let ssh = &directories["/Users/bob/.ssh"];
+        assert!(ssh.renamed == 1);
+        assert!(ssh.last_event_id == 196625);
+    }
+
+    #[test]
+    fn test_write_xlsx() {
+        let mut output = Cursor::new(Vec::new());
+        write_xlsx(&test_records(), &mut output).unwrap();
+        let data = output.into_inner();
+        // XLSX files are zip archives
+        assert!(data.starts_with(b"PK\x03\x04"));
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        let mut workbook = String::new();
+        archive
+            .by_name("xl/workbook.xml")
+            .unwrap()
+            .read_to_string(&mut workbook)
+            .unwrap();
+        assert!(workbook.contains(r#"name="Records""#));
+        assert!(workbook.contains(r#"name="Flags""#));
+        assert!(workbook.contains(r#"name="Directories""#));
+
+        let mut strings = String::new();
+        archive
+            .by_name("xl/sharedStrings.xml")
+            .unwrap()
+            .read_to_string(&mut strings)
+            .unwrap();
+        assert!(strings.contains("/Users/bob/Downloads/invoice.pdf"));
+        assert!(strings.contains("/Users/bob/.ssh"));
+    }
+
+    #[test]
+    fn test_write_xlsx_empty() {
+        let mut output = Cursor::new(Vec::new());
+        write_xlsx(&[], &mut output).unwrap();
+        assert!(!output.into_inner().is_empty());
+    }
+}
@@ -2,56 +2,154 @@
 //!
 //! Provides a library to decompress and parse FsEvent files.
 
-use crate::{fsevents::FsEvents, size::get_file_size};
-use flate2::read::MultiGzDecoder;
-use log::error;
+use crate::{
+    compression::Decoder,
+    error::FsEventsError,
+    fsevents::{FsEventRef, FsEvents, FsEventsPage},
+    stream::PageReader,
+};
+#[cfg(feature = "filesystem")]
+use crate::{
+    dedup::{dedup_fsevents, DedupKey},
+    enrich::{add_approx_times, add_estimated_times, add_live_status},
+    node::add_current_paths,
+    options::ParserOptions,
+    report::{ParseReport, ResyncedPage, SkippedPage},
+    size::open_file,
+    sort::sort_fsevents,
+};
+use log::warn;
+#[cfg(feature = "filesystem")]
+use log::{error, info};
+use std::io::Read;
+#[cfg(feature = "filesystem")]
 use std::{
     fs::{self, metadata, read_dir},
-    io::{Error, ErrorKind, Read},
+    io::BufReader,
     path::Path,
 };
 
-/// Decompress gzip compressed files
-pub fn decompress(path: &str) -> Result<Vec<u8>, std::io::Error> {
-    if !Path::new(path).is_file() && get_file_size(path) {
-        return Err(Error::new(
-            ErrorKind::InvalidInput,
-            format!("Not a file: {}", path),
-        ));
-    }
-    let buffer = fs::read(path)?;
-    let mut data = MultiGzDecoder::new(&buffer[..]);
+#[cfg(feature = "filesystem")]
+/// Decompress gzip compressed files, files that are not gzip compressed are returned as is
+/// The full decompressed data is returned, use `stream::PageReader` to read one page at a time
+pub fn decompress(path: &str) -> Result<Vec<u8>, FsEventsError> {
+    let file = open_file(path, ParserOptions::default().max_file_size)?;
+    decompress_reader(BufReader::new(file))
+}
+
+/// Decompress gzip compressed data from any reader (stdin, sockets, archives, memory buffers)
+/// Data that is not gzip compressed is returned as is
+pub fn decompress_reader<R: Read>(reader: R) -> Result<Vec<u8>, FsEventsError> {
+    let mut data = Decoder::detect(reader).map_err(FsEventsError::Decompress)?;
 
     let mut decompress_data = Vec::new();
-    data.read_to_end(&mut decompress_data)?;
+    data.read_to_end(&mut decompress_data)
+        .map_err(FsEventsError::Decompress)?;
 
     Ok(decompress_data)
 }
 
+/// Decompressed data and whether the compressed data ended early
+#[derive(Debug, Default, Clone)]
+pub struct Decompressed {
+    pub data: Vec<u8>, // Data decompressed before the compressed data ended or an error occurred
+    pub truncated: bool, // Compressed data was truncated or corrupt, the data is incomplete
+}
+
+#[cfg(feature = "filesystem")]
+/// Decompress a file, keeping the data decompressed before an error instead of failing
+/// The newest FsEvent file on a live system is often truncated while it is being written
+pub fn decompress_recover(path: &str) -> Result<Decompressed, FsEventsError> {
+    let file = open_file(path, ParserOptions::default().max_file_size)?;
+    decompress_reader_recover(BufReader::new(file))
+}
+
+/// Decompress data from any reader, keeping the data decompressed before an error
+pub fn decompress_reader_recover<R: Read>(reader: R) -> Result<Decompressed, FsEventsError> {
+    let mut decoder = Decoder::detect(reader).map_err(FsEventsError::Decompress)?;
+
+    let mut decompressed = Decompressed::default();
+    if let Err(err) = decoder.read_to_end(&mut decompressed.data) {
+        warn!(
+            "FsEvent data ended early after {} bytes, err: {}",
+            decompressed.data.len(),
+            err
+        );
+        decompressed.truncated = true;
+    }
+    Ok(decompressed)
+}
+
 /// Get FsEvents data from decompressed file
-pub fn parse_fsevents(data: &[u8]) -> nom::IResult<&[u8], Vec<FsEvents>> {
+pub fn parse_fsevents(data: &[u8]) -> Result<Vec<FsEvents>, FsEventsError> {
     FsEvents::fsevents_data(data)
 }
 
+/// Get FsEvents data from decompressed file without copying the paths. Each record borrows its
+/// path from the data, so only the list of records is allocated
+pub fn parse_fsevents_ref(data: &[u8]) -> Result<Vec<FsEventRef<'_>>, FsEventsError> {
+    FsEvents::fsevents_data_ref(data)
+}
+
+/// Decompress and get FsEvents data from any reader
+/// Data is decompressed one page at a time, so the full decompressed data is never held in memory
+pub fn parse_fsevents_reader<R: Read>(reader: R) -> Result<Vec<FsEvents>, FsEventsError> {
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    for records in PageReader::new(reader) {
+        fsevents_data.append(&mut records?);
+    }
+    Ok(fsevents_data)
+}
+
+/// Decompress and parse the bytes of a FsEvent file, compressed or already decompressed
+/// Nothing is read from the filesystem, so this works where there is none (ex: WebAssembly)
+pub fn parse_bytes(data: &[u8]) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_fsevents_reader(data)
+}
+
+/// Get FsEvents pages, with their headers and records, from decompressed data
+pub fn parse_fsevents_pages(data: &[u8]) -> Result<Vec<FsEventsPage>, FsEventsError> {
+    FsEvents::fsevents_pages(data)
+}
+
+/// Decompress and get FsEvents pages, with their headers and records, from any reader
+pub fn parse_fsevents_pages_reader<R: Read>(reader: R) -> Result<Vec<FsEventsPage>, FsEventsError> {
+    let mut reader = PageReader::new(reader);
+    let mut pages: Vec<FsEventsPage> = Vec::new();
+    while let Some(page) = reader.next_page_with_header()? {
+        pages.push(page);
+    }
+    Ok(pages)
+}
+
+#[cfg(feature = "filesystem")]
+pub(crate) const CURRENT_PATH: &str = "/System/Volumes/Data/.fseventsd/";
+#[cfg(feature = "filesystem")]
+pub(crate) const OLD_PATH: &str = "/.fseventsd";
+
+#[cfg(feature = "filesystem")]
 /// Get FsEvents files at default path
-pub fn get_fseventsd() -> Result<Vec<String>, std::io::Error> {
-    const CURRENT_PATH: &str = "/System/Volumes/Data/.fseventsd/";
+pub fn get_fseventsd() -> Result<Vec<String>, FsEventsError> {
     fseventsd(CURRENT_PATH)
 }
 
+#[cfg(feature = "filesystem")]
 /// Get FsEvents files at old path
-pub fn get_fseventsd_legacy() -> Result<Vec<String>, std::io::Error> {
-    const OLD_PATH: &str = "/.fseventsd";
+pub fn get_fseventsd_legacy() -> Result<Vec<String>, FsEventsError> {
     fseventsd(OLD_PATH)
 }
 
+#[cfg(feature = "filesystem")]
 /// Get list of files in a directory
-pub fn fseventsd(directory: &str) -> Result<Vec<String>, std::io::Error> {
+pub fn fseventsd(directory: &str) -> Result<Vec<String>, FsEventsError> {
+    list_files(directory, true)
+}
+
+#[cfg(feature = "filesystem")]
+/// Get list of files in a directory, optionally skipping the fseventsd-uuid file
+pub(crate) fn list_files(directory: &str, skip_uuid: bool) -> Result<Vec<String>, FsEventsError> {
     if metadata(directory).is_err() {
-        return Err(Error::new(
-            ErrorKind::InvalidInput,
-            format!("Not a directory: {}", directory),
-        ));
+        return Err(FsEventsError::NotADirectory(directory.to_string()));
     }
     let dir = read_dir(directory)?;
     let mut files: Vec<String> = Vec::new();
@@ -60,7 +158,7 @@ pub fn fseventsd(directory: &str) -> Result<Vec<String>, std::io::Error> {
     // Skip fseventsd-uuid because it is not a fsevents file
     for file_path in dir {
         let data = file_path?;
-        if data.file_name() == "fseventsd-uuid" {
+        if skip_uuid && data.file_name() == "fseventsd-uuid" {
             continue;
         }
         files.push(data.path().display().to_string())
@@ -68,45 +166,500 @@ pub fn fseventsd(directory: &str) -> Result<Vec<String>, std::io::Error> {
     Ok(files)
 }
 
-pub fn parse_fseventsd_data(legacy: bool) -> Result<Vec<FsEvents>, std::io::Error> {
-    let fsevents_files = if !legacy {
-        get_fseventsd()?
-    } else {
-        get_fseventsd_legacy()?
+#[cfg(feature = "filesystem")]
+/// Parse FsEvents files at the default path (and legacy path if enabled in the options)
+pub fn parse_fseventsd_data(options: &ParserOptions) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_fseventsd_data_with_report(options).map(|(data, _)| data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse FsEvents files at the default path, and report any files or directories that were skipped
+pub fn parse_fseventsd_data_with_report(
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let mut directories = vec![CURRENT_PATH];
+    if options.include_legacy {
+        directories.push(OLD_PATH);
+    }
+
+    let mut report = ParseReport::default();
+    let fsevents_data = parse_directory_list(&directories, options, &mut report)?;
+    Ok((fsevents_data, report))
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse a single FsEvents file, keeping only records that match the options filters
+pub fn parse_file_with_options(
+    file: &str,
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_file_with_report(file, options).map(|(data, _)| data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse a single FsEvents file, and report if it was truncated or had pages skipped or resynced
+pub fn parse_file_with_report(
+    file: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let parsed = parse_file(file, options)?;
+    let mut report = ParseReport {
+        files_parsed: 1,
+        ..Default::default()
+    };
+    let fsevents_data = parsed.add_to_report(file, &mut report);
+    Ok((fsevents_data, report))
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse FsEvent data from any reader (ex: stdin), keeping only records that match the options
+/// filters. Concatenated gzip files (ex: `cat 0000*`) are parsed as one stream, and `name` is used
+/// as the source file of each record
+pub fn parse_reader_with_report<R: Read>(
+    reader: R,
+    name: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let mut report = ParseReport {
+        files_parsed: 1,
+        ..Default::default()
     };
+    let fsevents_data = parse_reader_pages(reader, name, options, &mut report)?;
+    Ok((fsevents_data, report))
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse each page from a reader, keeping only records that match the options filters
+/// Truncated data and skipped or resynced pages are added to the report
+pub(crate) fn parse_reader_pages<R: Read>(
+    reader: R,
+    file: &str,
+    options: &ParserOptions,
+    report: &mut ParseReport,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_pages(PageReader::new(reader).path(file), file, options)
+        .map(|parsed| parsed.add_to_report(file, report))
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse all FsEvents files in a directory using the provided options
+pub fn parse_directory_with_options(
+    directory: &str,
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_directory_with_report(directory, options).map(|(data, _)| data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse all FsEvents files in a directory, and report any files that were skipped
+pub fn parse_directory_with_report(
+    directory: &str,
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let mut report = ParseReport::default();
+    let fsevents_data = parse_directory(directory, options, &mut report)?;
+    Ok((fsevents_data, report))
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse all FsEvents files in a directory, adding skipped files to the report
+pub(crate) fn parse_directory(
+    directory: &str,
+    options: &ParserOptions,
+    report: &mut ParseReport,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    let fsevents_files = list_files(directory, options.skip_uuid)?;
+    parse_file_list(directory, &fsevents_files, options, report)
+}
 
+#[cfg(feature = "filesystem")]
+/// Parse FsEvents files that are all in one directory, adding skipped files to the report
+pub(crate) fn parse_file_list(
+    directory: &str,
+    files: &[String],
+    options: &ParserOptions,
+    report: &mut ParseReport,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    options.notify(|observer| observer.files_discovered(directory, files.len()));
+    let mut fsevents_data = parse_files(files, options, report)?;
+    enrich_directory(directory, &mut fsevents_data, options)?;
+    Ok(fsevents_data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse all FsEvents files in each directory, adding skipped files and directories to the report
+fn parse_directory_list<T: AsRef<str>>(
+    directories: &[T],
+    options: &ParserOptions,
+    report: &mut ParseReport,
+) -> Result<Vec<FsEvents>, FsEventsError> {
     let mut fsevents_data: Vec<FsEvents> = Vec::new();
-    for file in fsevents_files {
-        let decompress_data = decompress(&file)?;
-        let results = parse_fsevents(&decompress_data);
+    for directory in directories {
+        if options.is_cancelled() {
+            break;
+        }
+        let directory = directory.as_ref();
+        let results = parse_directory(directory, options, report);
         match results {
-            Ok((_, mut data)) => fsevents_data.append(&mut data),
-            Err(err) => error!("Failed to parse FsEvent file {}, err: {:?}", file, err),
+            Ok(mut data) => fsevents_data.append(&mut data),
+            Err(err) if options.lenient => {
+                error!(
+                    "Failed to parse FsEvent directory {}, err: {}",
+                    directory, err
+                );
+                report.skip_directory(directory, err.to_string());
+            }
+            Err(err) => return Err(err),
         }
     }
     Ok(fsevents_data)
 }
 
+#[cfg(feature = "filesystem")]
+/// Attach the volume UUID, approximate times, estimated times, and live status to records parsed
+/// from a directory, if enabled
+pub(crate) fn enrich_directory(
+    directory: &str,
+    fsevents_data: &mut [FsEvents],
+    options: &ParserOptions,
+) -> Result<(), FsEventsError> {
+    if options.attach_uuid {
+        let volume_uuid = match get_fseventsd_uuid(directory) {
+            Ok(result) => result,
+            Err(err) if options.lenient => {
+                warn!(
+                    "Failed to get fseventsd UUID for {}, err: {}",
+                    directory, err
+                );
+                String::new()
+            }
+            Err(err) => return Err(err),
+        };
+        for fsevent in fsevents_data.iter_mut() {
+            fsevent.volume_uuid = volume_uuid.clone();
+        }
+    }
+
+    if options.approx_times {
+        add_approx_times(fsevents_data);
+    }
+    if options.estimate_times {
+        add_estimated_times(fsevents_data);
+    }
+    // The FsEvents directory is in the root of the volume its records are for
+    let root = Path::new(directory).parent().unwrap_or(Path::new("/"));
+    if options.live_status {
+        add_live_status(fsevents_data, &root.display().to_string());
+    }
+    if options.resolve_nodes {
+        add_current_paths(fsevents_data, &root.display().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse FsEvents files from multiple directories (system, legacy, external volumes, collected copies)
+/// Records are merged, deduplicated, and sorted by event ID. Each record keeps its source file
+pub fn parse_directories(
+    directories: &[String],
+    options: &ParserOptions,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    parse_directories_with_report(directories, options).map(|(data, _)| data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse FsEvents files from multiple directories, and report any files or directories that were skipped
+pub fn parse_directories_with_report(
+    directories: &[String],
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let mut report = ParseReport::default();
+    let mut fsevents_data = parse_directory_list(directories, options, &mut report)?;
+
+    let duplicates = dedup_fsevents(&mut fsevents_data, DedupKey::Record);
+    if duplicates != 0 {
+        info!("Removed {} duplicate FsEvent records", duplicates);
+    }
+    sort_fsevents(&mut fsevents_data);
+    Ok((fsevents_data, report))
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse any mix of FsEvents files and directories, and report any files or directories that were
+/// skipped. Records are merged and sorted by event ID, duplicates are kept so callers can choose how
+/// to remove them (see `dedup::dedup_fsevents`). Each record keeps its source file
+pub fn parse_paths_with_report(
+    paths: &[String],
+    options: &ParserOptions,
+) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+    let mut report = ParseReport::default();
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    for path in paths {
+        if options.is_cancelled() {
+            break;
+        }
+        let file = Path::new(path);
+        let mut data = if file.is_file() {
+            let directory = file
+                .parent()
+                .unwrap_or(Path::new("/"))
+                .display()
+                .to_string();
+            parse_file_list(&directory, std::slice::from_ref(path), options, &mut report)?
+        } else {
+            parse_directory_list(&[path], options, &mut report)?
+        };
+        fsevents_data.append(&mut data);
+    }
+    sort_fsevents(&mut fsevents_data);
+    Ok((fsevents_data, report))
+}
+
+#[cfg(feature = "filesystem")]
+/// Get the volume UUID from the fseventsd-uuid file in a FsEvents directory
+pub fn get_fseventsd_uuid(directory: &str) -> Result<String, FsEventsError> {
+    let uuid_path = Path::new(directory).join("fseventsd-uuid");
+    let data = fs::read(uuid_path)?;
+    parse_fseventsd_uuid(&data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Get the volume UUID from the contents of a fseventsd-uuid file
+pub(crate) fn parse_fseventsd_uuid(data: &[u8]) -> Result<String, FsEventsError> {
+    let uuid = String::from_utf8_lossy(data)
+        .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+        .to_string();
+
+    if !is_uuid(&uuid) {
+        return Err(FsEventsError::InvalidUuid(uuid));
+    }
+    Ok(uuid)
+}
+
+#[cfg(feature = "filesystem")]
+/// Check if value is formatted as a UUID (ex: 5B3A1C2E-8F4D-4E6A-9C1B-2D3E4F5A6B7C)
+fn is_uuid(value: &str) -> bool {
+    let dashes = [8, 13, 18, 23];
+    value.len() == 36
+        && value.char_indices().all(|(index, c)| {
+            if dashes.contains(&index) {
+                c == '-'
+            } else {
+                c.is_ascii_hexdigit()
+            }
+        })
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse a list of FsEvents files, keeping only records that match the options filters
+/// Files are parsed concurrently if the `parallel` feature is enabled, records keep the file order
+fn parse_files(
+    files: &[String],
+    options: &ParserOptions,
+    report: &mut ParseReport,
+) -> Result<Vec<FsEvents>, FsEventsError> {
+    #[cfg(feature = "parallel")]
+    let results = {
+        use rayon::prelude::*;
+        files
+            .par_iter()
+            .map(|file| parse_file(file, options))
+            .collect::<Vec<_>>()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results = files.iter().map(|file| parse_file(file, options));
+
+    let mut fsevents_data: Vec<FsEvents> = Vec::new();
+    for (file, result) in files.iter().zip(results) {
+        match result {
+            Ok(parsed) => {
+                report.files_parsed += 1;
+                fsevents_data.append(&mut parsed.add_to_report(file, report));
+            }
+            // Large files are always skipped, even if not lenient
+            Err(err @ FsEventsError::FileTooLarge(..)) => {
+                warn!("Skipping FsEvent file {}, err: {}", file, err);
+                report.skip_file(file, err.to_string());
+            }
+            Err(err) if options.lenient => {
+                error!("Failed to parse FsEvent file {}, err: {}", file, err);
+                report.skip_file(file, err.to_string());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if options.is_cancelled() {
+        info!("FsEvent parsing cancelled, returning partial results");
+    }
+    Ok(fsevents_data)
+}
+
+#[cfg(feature = "filesystem")]
+/// Records parsed from a FsEvents file and what could not be parsed
+#[derive(Default)]
+struct ParsedFile {
+    records: Vec<FsEvents>,            // Records that match the options filters
+    truncated: bool,                   // Compressed data ended early
+    skipped_pages: Vec<SkippedPage>,   // Corrupt pages that were skipped
+    resynced_pages: Vec<ResyncedPage>, // Pages where bad records were skipped
+}
+
+#[cfg(feature = "filesystem")]
+impl ParsedFile {
+    /// Add what could not be parsed to the report and return the records
+    fn add_to_report(mut self, file: &str, report: &mut ParseReport) -> Vec<FsEvents> {
+        if self.truncated {
+            report.truncated_files.push(file.to_string());
+        }
+        report.skipped_pages.append(&mut self.skipped_pages);
+        report.resynced_pages.append(&mut self.resynced_pages);
+        self.records
+    }
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse a FsEvents file, keeping only records that match the options filters
+fn parse_file(file: &str, options: &ParserOptions) -> Result<ParsedFile, FsEventsError> {
+    if options.is_cancelled() {
+        return Ok(ParsedFile::default());
+    }
+    options.notify(|observer| observer.file_started(file));
+    let results = parse_file_pages(file, options);
+    let records = results
+        .as_ref()
+        .map(|parsed| parsed.records.len())
+        .unwrap_or_default();
+    options.notify(|observer| observer.file_finished(file, records));
+    results
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse each page in a FsEvents file, keeping only records that match the options filters
+fn parse_file_pages(file: &str, options: &ParserOptions) -> Result<ParsedFile, FsEventsError> {
+    // Files in the live directories can change while they are read, which kills the process if
+    // they are memory mapped
+    #[cfg(feature = "mmap")]
+    if !is_live_path(file) {
+        let reader = PageReader::from_mmap_with_options(file, options)?;
+        return parse_pages(reader, file, options);
+    }
+    let reader = PageReader::from_path_with_options(file, options)?;
+    parse_pages(reader, file, options)
+}
+
+#[cfg(feature = "mmap")]
+/// Check if a file is in the live system's FsEvents directories
+fn is_live_path(file: &str) -> bool {
+    let path = Path::new(file);
+    path.starts_with(CURRENT_PATH) || path.starts_with(OLD_PATH)
+}
+
+#[cfg(feature = "filesystem")]
+/// Parse each page from a page reader, keeping only records that match the options filters
+fn parse_pages<R: Read>(
+    reader: PageReader<R>,
+    file: &str,
+    options: &ParserOptions,
+) -> Result<ParsedFile, FsEventsError> {
+    let mut reader = reader.page_options(options);
+
+    // Filter each page as it is parsed, so only matching records are kept in memory
+    let mut data: Vec<FsEvents> = Vec::new();
+    let mut invalid_paths = 0;
+    for records in reader.by_ref() {
+        if options.is_cancelled() {
+            break;
+        }
+        let mut records = records?;
+        options.notify(|observer| observer.records_parsed(file, records.len()));
+        invalid_paths += records
+            .iter()
+            .filter(|fsevent| fsevent.has_invalid_path())
+            .count();
+        records.retain(|fsevent| options.matches(fsevent));
+        data.append(&mut records);
+    }
+    Ok(ParsedFile {
+        records: source_records(file, data, invalid_paths),
+        truncated: reader.is_truncated(),
+        skipped_pages: reader.skipped_pages().to_vec(),
+        resynced_pages: reader.resynced_pages().to_vec(),
+    })
+}
+
+#[cfg(feature = "filesystem")]
+/// Set the source file of the matching records, and log how many parsed paths were not valid UTF-8
+fn source_records(file: &str, mut data: Vec<FsEvents>, invalid_paths: usize) -> Vec<FsEvents> {
+    if invalid_paths != 0 {
+        warn!(
+            "FsEvent file {} has {} paths that are not valid UTF-8",
+            file, invalid_paths
+        );
+    }
+    for fsevent in data.iter_mut() {
+        fsevent.source_file = file.to_string();
+    }
+    data
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::parser::{decompress, fseventsd, get_fseventsd, parse_fsevents};
-    use std::path::PathBuf;
+    use crate::parser::{decompress_reader, parse_fsevents_reader};
+    use std::{
+        fs::{self, File},
+        io::Cursor,
+        path::PathBuf,
+    };
 
-    use super::parse_fseventsd_data;
+    #[cfg(feature = "filesystem")]
+    use super::{
+        get_fseventsd_uuid, is_uuid, parse_directories, parse_directories_with_report,
+        parse_directory_with_options, parse_directory_with_report, parse_file_with_options,
+        parse_file_with_report, parse_files, parse_fseventsd_data, parse_paths_with_report,
+        parse_reader_with_report,
+    };
+    #[cfg(feature = "filesystem")]
+    use crate::test_util::test_data;
+    #[cfg(feature = "filesystem")]
+    use crate::{
+        dedup::{dedup_fsevents, DedupKey},
+        error::FsEventsError,
+        flags::FsEventFlags,
+        options::ParserOptions,
+        parser::{
+            decompress, decompress_reader_recover, decompress_recover, fseventsd, get_fseventsd,
+            parse_bytes, parse_fsevents, parse_fsevents_pages, parse_fsevents_pages_reader,
+            parse_fsevents_ref,
+        },
+        progress::ProgressObserver,
+        report::ParseReport,
+    };
+    #[cfg(feature = "filesystem")]
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+    #[cfg(feature = "filesystem")]
+    use tempfile::tempdir;
 
     #[test]
+    #[cfg(feature = "filesystem")]
+    #[cfg_attr(not(target_os = "macos"), ignore = "macOS system files required")]
     fn test_get_fseventsd() {
         let files = get_fseventsd().unwrap();
         assert!(files.len() > 3);
     }
 
     #[test]
+    #[cfg(feature = "filesystem")]
+    #[cfg_attr(not(target_os = "macos"), ignore = "macOS system files required")]
     fn test_parse_fseventsd_data() {
-        let results = parse_fseventsd_data(false).unwrap();
+        let results = parse_fseventsd_data(&ParserOptions::default()).unwrap();
         assert!(results.len() > 100);
     }
 
     #[test]
+    #[cfg(feature = "filesystem")]
     fn test_decompress() {
         let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         test_location.push("tests/test_data/DLS2/0000000000027d79");
@@ -116,6 +669,141 @@ mod tests {
     }
 
     #[test]
+    fn test_decompress_reader() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let file = File::open(test_location).unwrap();
+        let files = decompress_reader(file).unwrap();
+        assert!(files.len() == 78970);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_fsevents_pages() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let test_path = test_location.display().to_string();
+
+        let pages = parse_fsevents_pages(&decompress(&test_path).unwrap()).unwrap();
+        let reader_pages = parse_fsevents_pages_reader(File::open(&test_path).unwrap()).unwrap();
+        assert!(pages.len() == reader_pages.len());
+        assert!(pages[0].header == reader_pages[0].header);
+        assert!(pages[0].header.unknown == 779163104);
+
+        let records: usize = pages.iter().map(|page| page.records.len()).sum();
+        assert!(records == 736);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_decompress_recover() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let test_path = test_location.display().to_string();
+        let results = decompress_recover(&test_path).unwrap();
+        assert!(!results.truncated);
+        assert!(results.data.len() == 78970);
+
+        let data = fs::read(&test_path).unwrap();
+        let results = decompress_reader_recover(&data[..data.len() / 2]).unwrap();
+        assert!(results.truncated);
+        assert!(!results.data.is_empty() && results.data.len() < 78970);
+        assert!(decompress(&test_path).unwrap().starts_with(&results.data));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_truncated() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        let data = fs::read(test_data("DLS2/0000000000027d79")).unwrap();
+        fs::write(
+            test_location.join("0000000000027d79"),
+            &data[..data.len() / 2],
+        )
+        .unwrap();
+
+        let options = ParserOptions::new();
+        let (results, report) =
+            parse_directory_with_report(&test_location.display().to_string(), &options).unwrap();
+        assert!(!results.is_empty() && results.len() < 736);
+        assert!(report.files_parsed == 1);
+        assert!(report.truncated_files.len() == 1);
+        assert!(!report.is_complete());
+    }
+
+    #[test]
+    fn test_decompress_reader_not_gzip() {
+        let results = decompress_reader(Cursor::new(b"not gzip data".to_vec())).unwrap();
+        assert!(results == b"not gzip data");
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_uncompressed() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        let source = test_data("DLS3/0000000000030012");
+        let data = decompress(&source.display().to_string()).unwrap();
+        fs::write(test_location.join("0000000000030012"), data).unwrap();
+
+        let results = parse_directory_with_options(
+            &test_location.display().to_string(),
+            &ParserOptions::new(),
+        )
+        .unwrap();
+        assert!(results.len() == 5);
+    }
+
+    #[test]
+    fn test_parse_fsevents_reader() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let buffer = fs::read(test_location).unwrap();
+        let results = parse_fsevents_reader(Cursor::new(buffer)).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[4].path == "/Users/bob/.ssh/authorized_keys");
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_reader_with_report() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let mut concatenated = Vec::new();
+        let mut expected = 0;
+        for file in ["000000000002469c", "0000000000027d79"] {
+            let data = fs::read(test_location.join(file)).unwrap();
+            expected += parse_bytes(&data).unwrap().len();
+            concatenated.extend(data);
+        }
+
+        let options = ParserOptions::default();
+        let (results, report) =
+            parse_reader_with_report(Cursor::new(concatenated), "stdin", &options).unwrap();
+        assert!(results.len() == expected);
+        assert!(results.iter().all(|fsevent| fsevent.source_file == "stdin"));
+        assert!(report.files_parsed == 1);
+        assert!(report.truncated_files.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_bytes() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let compressed = fs::read(&test_location).unwrap();
+        let results = parse_bytes(&compressed).unwrap();
+        assert!(results.len() == 5);
+
+        let decompressed = decompress(&test_location.display().to_string()).unwrap();
+        let results = parse_bytes(&decompressed).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[4].path == "/Users/bob/.ssh/authorized_keys");
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
     fn test_fseventsd() {
         let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         test_location.push("tests/test_data/DLS2/");
@@ -124,32 +812,410 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_with_options() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let options = ParserOptions::new().path_prefix("/Users/bob");
+        let results =
+            parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.len() == 3);
+
+        let options = ParserOptions::new().include_flags(FsEventFlags::RENAMED);
+        let results =
+            parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.len() == 1);
+        assert!(results[0].path == "/Users/bob/.ssh/authorized_keys");
+        assert!(results[0].source_file.ends_with("DLS3/0000000000030012"));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_with_options_glob() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let options = ParserOptions::new()
+            .include_glob("/Users/*/Downloads/**")
+            .unwrap();
+        let results =
+            parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.len() == 2);
+        assert!(results
+            .iter()
+            .all(|fsevent| fsevent.path == "/Users/bob/Downloads/invoice.pdf"));
+
+        // Pages from a reader are filtered the same way
+        let data = fs::read(test_location.join("0000000000030012")).unwrap();
+        let (results, report) =
+            parse_reader_with_report(Cursor::new(data), "stdin", &options).unwrap();
+        assert!(results.len() == 2);
+        assert!(results.iter().all(|fsevent| fsevent.source_file == "stdin"));
+        assert!(report.is_complete());
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_with_options_exclude_flags() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let options = ParserOptions::new().exclude_flags(FsEventFlags::IS_FILE);
+        let results =
+            parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.len() == 1);
+        assert!(results[0].path == "/private/var/folders/tmp");
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directories() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data");
+        let dls2 = test_location.join("DLS2").display().to_string();
+        let dls3 = test_location.join("DLS3").display().to_string();
+
+        let mut single = parse_directory_with_options(&dls2, &ParserOptions::new()).unwrap();
+        dedup_fsevents(&mut single, DedupKey::Record);
+        let results = parse_directories(
+            &[dls2.clone(), dls3.clone(), dls2.clone()],
+            &ParserOptions::new(),
+        )
+        .unwrap();
+        // Records from the repeated directory are removed as duplicates
+        assert!(results.len() == single.len() + 5);
+        assert!(results
+            .windows(2)
+            .all(|pair| pair[0].event_id <= pair[1].event_id));
+        assert!(results
+            .iter()
+            .any(|fsevent| fsevent.source_file.contains("DLS3")));
+
+        let missing = test_location.join("Missing").display().to_string();
+        let results = parse_directories(&[dls3.clone(), missing.clone()], &ParserOptions::new());
+        assert!(results.unwrap().len() == 5);
+
+        let options = ParserOptions::new().lenient(false);
+        let results = parse_directories(&[dls3, missing], &options);
+        assert!(matches!(results, Err(FsEventsError::NotADirectory(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_files_order() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data");
+        let files = vec![
+            test_location
+                .join("DLS3/0000000000030012")
+                .display()
+                .to_string(),
+            test_location
+                .join("DLS2/0000000000027d79")
+                .display()
+                .to_string(),
+            test_location
+                .join("DLS1/0000000000027d7a")
+                .display()
+                .to_string(),
+        ];
+        let results =
+            parse_files(&files, &ParserOptions::new(), &mut ParseReport::default()).unwrap();
+        assert!(results.len() == 743);
+        assert!(results[0].source_file == files[0]);
+        assert!(results[5].source_file == files[1]);
+        assert!(results[742].source_file == files[2]);
+    }
+
+    #[cfg(feature = "filesystem")]
+    struct CancelAfterPage {
+        cancel: Arc<AtomicBool>,
+    }
+
+    #[cfg(feature = "filesystem")]
+    impl ProgressObserver for CancelAfterPage {
+        fn records_parsed(&self, _file: &str, _count: usize) {
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_with_options_cancel() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = ParserOptions::new()
+            .cancel_flag(cancel.clone())
+            .progress_observer(Arc::new(CancelAfterPage {
+                cancel: cancel.clone(),
+            }));
+
+        // Cancelled after the first page, only the first page records are returned
+        let results =
+            parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.len() == 3);
+
+        let results =
+            parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_get_fseventsd_uuid() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let uuid = get_fseventsd_uuid(&test_location.display().to_string()).unwrap();
+        assert!(uuid == "5B3A1C2E-8F4D-4E6A-9C1B-2D3E4F5A6B7C");
+
+        test_location.pop();
+        test_location.push("DLS2");
+        let results = get_fseventsd_uuid(&test_location.display().to_string());
+        assert!(matches!(results, Err(FsEventsError::Io(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_is_uuid() {
+        assert!(is_uuid("5B3A1C2E-8F4D-4E6A-9C1B-2D3E4F5A6B7C"));
+        assert!(!is_uuid("5B3A1C2E8F4D4E6A9C1B2D3E4F5A6B7C"));
+        assert!(!is_uuid("ZB3A1C2E-8F4D-4E6A-9C1B-2D3E4F5A6B7C"));
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_is_live_path() {
+        use super::is_live_path;
+
+        assert!(is_live_path(
+            "/System/Volumes/Data/.fseventsd/0000000000030012"
+        ));
+        assert!(is_live_path("/.fseventsd/0000000000030012"));
+        assert!(!is_live_path("/cases/mac/.fseventsd/0000000000030012"));
+        assert!(!is_live_path("/.fseventsd-copy/0000000000030012"));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_with_options_uuid() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let options = ParserOptions::new().attach_uuid(true);
+        let results =
+            parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.len() == 5);
+        assert!(results
+            .iter()
+            .all(|fsevent| fsevent.volume_uuid == "5B3A1C2E-8F4D-4E6A-9C1B-2D3E4F5A6B7C"));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_skip_corrupt_pages() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path();
+        let malformed = test_data("Malformed/malformed");
+        let mut data = decompress(&malformed.display().to_string()).unwrap();
+        let dls3 = test_data("DLS3/0000000000030012");
+        data.append(&mut decompress(&dls3.display().to_string()).unwrap());
+        fs::write(test_location.join("0000000000030012"), &data).unwrap();
+        let test_path = test_location.display().to_string();
+
+        let (results, report) =
+            parse_directory_with_report(&test_path, &ParserOptions::new()).unwrap();
+        assert!(results.is_empty());
+        assert!(report.skipped_files.len() == 1);
+
+        let options = ParserOptions::new().skip_corrupt_pages(true);
+        let (results, report) = parse_directory_with_report(&test_path, &options).unwrap();
+        assert!(results.len() == 5);
+        assert!(report.files_parsed == 1);
+        assert!(report.skipped_pages.len() == 1);
+        assert!(report.skipped_pages[0].path.ends_with("0000000000030012"));
+        assert!(!report.is_complete());
+
+        let file = test_location.join("0000000000030012").display().to_string();
+        let (results, report) = parse_file_with_report(&file, &options).unwrap();
+        assert!(results.len() == 5);
+        assert!(report.files_parsed == 1);
+        assert!(report.skipped_pages.len() == 1);
+        assert!(parse_file_with_report(&file, &ParserOptions::new()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_with_options_strict() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/Malformed");
+        let test_path = test_location.display().to_string();
+
+        let results = parse_directory_with_options(&test_path, &ParserOptions::new()).unwrap();
+        assert!(results.is_empty());
+
+        let options = ParserOptions::new().lenient(false);
+        let results = parse_directory_with_options(&test_path, &options);
+        assert!(matches!(results, Err(FsEventsError::TruncatedRecord)));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_with_options_max_size() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let options = ParserOptions::new().max_file_size(10);
+        let results =
+            parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_file_with_options_max_size() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let file = test_location.display().to_string();
+        let options = ParserOptions::new().max_file_size(10);
+        let results = parse_file_with_options(&file, &options);
+        assert!(matches!(results, Err(FsEventsError::FileTooLarge(_, 10))));
+
+        // Large files in a list of paths are skipped even if not lenient
+        let options = options.lenient(false);
+        let (results, report) = parse_paths_with_report(&[file], &options).unwrap();
+        assert!(results.is_empty());
+        assert!(report.files_parsed == 0);
+        assert!(report.skipped_files.len() == 1);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directory_with_report() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let options = ParserOptions::new().max_file_size(10);
+        let (results, report) =
+            parse_directory_with_report(&test_location.display().to_string(), &options).unwrap();
+        assert!(results.is_empty());
+        assert!(report.files_parsed == 0);
+        assert!(report.skipped_files.len() == 2);
+        assert!(report.skipped_files[0].reason.contains("10 bytes"));
+
+        test_location.pop();
+        test_location.push("Malformed");
+        let (_, report) = parse_directory_with_report(
+            &test_location.display().to_string(),
+            &ParserOptions::new(),
+        )
+        .unwrap();
+        assert!(report.skipped_files.len() == 1);
+        assert!(!report.is_complete());
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_directories_with_report() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data");
+        let directories = vec![
+            test_location.join("DLS3").display().to_string(),
+            test_location.join("Missing").display().to_string(),
+        ];
+        let (results, report) =
+            parse_directories_with_report(&directories, &ParserOptions::new()).unwrap();
+        assert!(results.len() == 5);
+        assert!(report.files_parsed == 1);
+        assert!(report.skipped_directories.len() == 1);
+        assert!(report.skipped_directories[0].path.ends_with("Missing"));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_paths_with_report() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data");
+        let paths = vec![
+            test_location.join("DLS3").display().to_string(),
+            test_location
+                .join("DLS2/0000000000027d79")
+                .display()
+                .to_string(),
+            test_location.join("Missing").display().to_string(),
+        ];
+        let (results, report) = parse_paths_with_report(&paths, &ParserOptions::new()).unwrap();
+        assert!(results.len() == 736 + 5);
+        assert!(report.files_parsed == 2);
+        assert!(report.skipped_directories.len() == 1);
+        assert!(results
+            .iter()
+            .any(|fsevent| fsevent.source_file.ends_with("DLS2/0000000000027d79")));
+        assert!(results
+            .windows(2)
+            .all(|pair| pair[0].event_id <= pair[1].event_id));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
     fn test_parse_fsevents() {
         let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         test_location.push("tests/test_data/DLS2/0000000000027d79");
         let test_path: &str = &test_location.display().to_string();
         let files = decompress(test_path).unwrap();
-        let (_, results) = parse_fsevents(&files).unwrap();
+        let results = parse_fsevents(&files).unwrap();
         assert!(results.len() == 736)
     }
 
     #[test]
-    #[should_panic(expected = "Eof")]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_fsevents_ref() {
+        for file in [
+            "DLS1/0000000000027d7a",
+            "DLS2/0000000000027d79",
+            "DLS3/0000000000030012",
+        ] {
+            let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            test_location.push("tests/test_data");
+            test_location.push(file);
+            let data = decompress(&test_location.display().to_string()).unwrap();
+            let owned = parse_fsevents(&data).unwrap();
+            let results = parse_fsevents_ref(&data).unwrap();
+            assert!(results.len() == owned.len());
+
+            let converted: Vec<String> = results
+                .into_iter()
+                .map(|fsevent| serde_json::to_string(&fsevent.into_owned()).unwrap())
+                .collect();
+            let owned: Vec<String> = owned
+                .iter()
+                .map(|fsevent| serde_json::to_string(fsevent).unwrap())
+                .collect();
+            assert!(converted == owned);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
     fn test_malformed() {
         let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         test_location.push("tests/test_data/Malformed/malformed");
         let test_path: &str = &test_location.display().to_string();
         let files = decompress(test_path).unwrap();
-        let _results = parse_fsevents(&files).unwrap();
+        let results = parse_fsevents(&files);
+        assert!(matches!(results, Err(FsEventsError::TruncatedRecord)));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_fseventsd_not_directory() {
+        let results = fseventsd("/this/path/does/not/exist");
+        assert!(matches!(results, Err(FsEventsError::NotADirectory(_))));
     }
 
     #[test]
+    #[cfg(feature = "filesystem")]
     fn test_parse_fsevents_version1() {
         let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         test_location.push("tests/test_data/DLS1/0000000000027d7a");
         let test_path: &str = &test_location.display().to_string();
         let files = decompress(test_path).unwrap();
-        let (_, results) = parse_fsevents(&files).unwrap();
+        let results = parse_fsevents(&files).unwrap();
 
         assert!(results.len() == 2);
         assert!(results[0].path == "/.fseventsd/sl-compat");
@@ -157,4 +1223,21 @@ mod tests {
         assert!(results[0].flags == "IsDirectory");
         assert!(results[0].node == 0);
     }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_parse_fsevents_version3() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let test_path: &str = &test_location.display().to_string();
+        let files = decompress(test_path).unwrap();
+        let results = parse_fsevents(&files).unwrap();
+
+        assert!(results.len() == 5);
+        assert!(results[3].path == "/Library/LaunchAgents/com.evil.plist");
+        assert!(results[3].event_id == 196624);
+        assert!(results[3].flags == "Created,Modified,IsFile");
+        assert!(results[3].node == 99);
+        assert!(results[3].extra_id == 0);
+    }
 }
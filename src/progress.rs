@@ -0,0 +1,87 @@
+//! Report parsing progress to the caller
+//!
+//! Large collections can contain thousands of FsEvent files. Implement `ProgressObserver` and add it
+//! to `ParserOptions` to be notified as files are discovered and parsed.
+
+use std::fmt;
+
+/// Receives progress events while FsEvent files are parsed
+/// All methods do nothing by default. Files may be parsed on multiple threads
+pub trait ProgressObserver: Send + Sync {
+    /// Files were found in a FsEvent directory
+    fn files_discovered(&self, _directory: &str, _count: usize) {}
+    /// Started parsing a FsEvent file
+    fn file_started(&self, _file: &str) {}
+    /// Parsed a page of records from a FsEvent file
+    fn records_parsed(&self, _file: &str, _count: usize) {}
+    /// Finished a FsEvent file, records is the number of records kept after filtering
+    fn file_finished(&self, _file: &str, _records: usize) {}
+}
+
+impl fmt::Debug for dyn ProgressObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ProgressObserver")
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::ProgressObserver;
+    use crate::{options::ParserOptions, parser::parse_directory_with_options};
+    use std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Default)]
+    struct TestObserver {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl ProgressObserver for TestObserver {
+        fn files_discovered(&self, _directory: &str, count: usize) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("discovered {}", count));
+        }
+        fn file_started(&self, _file: &str) {
+            self.events.lock().unwrap().push(String::from("started"));
+        }
+        fn records_parsed(&self, _file: &str, count: usize) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("parsed {}", count));
+        }
+        fn file_finished(&self, _file: &str, records: usize) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("finished {}", records));
+        }
+    }
+
+    #[test]
+    fn test_progress_observer() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let observer = Arc::new(TestObserver::default());
+        let options = ParserOptions::new()
+            .path_prefix("/Users")
+            .progress_observer(observer.clone());
+        parse_directory_with_options(&test_location.display().to_string(), &options).unwrap();
+
+        let events = observer.events.lock().unwrap();
+        assert!(
+            *events
+                == vec![
+                    "discovered 1",
+                    "started",
+                    "parsed 3",
+                    "parsed 2",
+                    "finished 3"
+                ]
+        );
+    }
+}
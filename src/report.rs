@@ -0,0 +1,110 @@
+//! Report what was skipped while parsing FsEvent data
+//!
+//! In lenient mode files and directories that cannot be parsed are logged and skipped.
+//! The report keeps a list of them so callers can tell a complete parse from a partial one.
+//! Files that were truncated (ex: still being written) are parsed up to where the data ends and listed.
+//! Corrupt pages skipped with `ParserOptions::skip_corrupt_pages` are listed with their offset.
+//! Pages where bad records were skipped with `ParserOptions::resync_records` are listed with the
+//! number of bytes skipped.
+
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct ParseReport {
+    pub files_parsed: usize,               // Number of FsEvent files parsed
+    pub skipped_files: Vec<Skipped>,       // FsEvent files that were not parsed
+    pub skipped_directories: Vec<Skipped>, // FsEvent directories that could not be listed
+    pub truncated_files: Vec<String>, // FsEvent files that ended early, records before the end were kept
+    pub skipped_pages: Vec<SkippedPage>, // Corrupt pages that were skipped, the rest of the file was parsed
+    pub resynced_pages: Vec<ResyncedPage>, // Pages where bad records were skipped, the rest of the page was parsed
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Skipped {
+    pub path: String,   // Path to the skipped file or directory
+    pub reason: String, // Why it was skipped
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SkippedPage {
+    pub path: String,   // Path to the FsEvent file
+    pub page: u32,      // Index of the skipped page
+    pub offset: u64,    // Offset to the page in the decompressed data
+    pub reason: String, // Why the page could not be parsed
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ResyncedPage {
+    pub path: String,       // Path to the FsEvent file
+    pub page: u32,          // Index of the page
+    pub offset: u64,        // Offset to the page in the decompressed data
+    pub skipped_bytes: u64, // Bytes in the page that were not part of a record
+}
+
+impl ParseReport {
+    /// Check if every FsEvent file and directory was fully parsed
+    pub fn is_complete(&self) -> bool {
+        self.skipped_files.is_empty()
+            && self.skipped_directories.is_empty()
+            && self.truncated_files.is_empty()
+            && self.skipped_pages.is_empty()
+            && self.resynced_pages.is_empty()
+    }
+
+    #[cfg(feature = "filesystem")]
+    /// Add a skipped file to the report
+    pub(crate) fn skip_file(&mut self, path: &str, reason: String) {
+        self.skipped_files.push(Skipped {
+            path: path.to_string(),
+            reason,
+        });
+    }
+
+    #[cfg(feature = "filesystem")]
+    /// Add a skipped directory to the report
+    pub(crate) fn skip_directory(&mut self, path: &str, reason: String) {
+        self.skipped_directories.push(Skipped {
+            path: path.to_string(),
+            reason,
+        });
+    }
+
+    #[cfg(feature = "async")]
+    /// Add the parsed and skipped files and pages of another report to the report
+    pub(crate) fn append(&mut self, other: &mut ParseReport) {
+        self.files_parsed += other.files_parsed;
+        self.skipped_files.append(&mut other.skipped_files);
+        self.skipped_directories
+            .append(&mut other.skipped_directories);
+        self.truncated_files.append(&mut other.truncated_files);
+        self.skipped_pages.append(&mut other.skipped_pages);
+        self.resynced_pages.append(&mut other.resynced_pages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseReport, Skipped};
+
+    #[test]
+    fn test_parse_report() {
+        let mut report = ParseReport::default();
+        assert!(report.is_complete());
+
+        report.skipped_files.push(Skipped {
+            path: String::from("/.fseventsd/0000000000027d79"),
+            reason: String::from("too large"),
+        });
+        assert!(!report.is_complete());
+        assert!(report.skipped_files[0].reason == "too large");
+
+        let mut report = ParseReport::default();
+        report
+            .truncated_files
+            .push(String::from("/.fseventsd/0000000000027d79"));
+        assert!(!report.is_complete());
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"truncated_files\""));
+    }
+}
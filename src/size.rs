@@ -1,36 +1,65 @@
-use std::path::Path;
+use crate::error::FsEventsError;
+use std::{
+    fs::{metadata, File, Metadata},
+    io::ErrorKind,
+};
 
-use log::warn;
+// Check that the metadata is for a file smaller than the max file size
+pub(crate) fn check_file_size(
+    path: &str,
+    metadata: &Metadata,
+    max_size: u64,
+) -> Result<(), FsEventsError> {
+    if !metadata.is_file() {
+        return Err(FsEventsError::NotAFile(path.to_string()));
+    }
+    if metadata.len() >= max_size {
+        return Err(FsEventsError::FileTooLarge(path.to_string(), max_size));
+    }
+    Ok(())
+}
 
-// Check if provided file path is larger the max file size
-pub(crate) fn get_file_size(path: &str) -> bool {
-    let size_results = Path::new(&path).metadata();
-    let file_size = match size_results {
-        Ok(results) => results.len(),
-        Err(err) => {
-            warn!(
-                "[macos-fsevents] Can not determine file size for fsevents file {}, error: {:?}",
-                path, err
-            );
-            return false;
+// Open a fsevents file if it is smaller than the max file size
+pub(crate) fn open_file(path: &str, max_size: u64) -> Result<File, FsEventsError> {
+    let metadata = match metadata(path) {
+        Ok(result) => result,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            return Err(FsEventsError::NotAFile(path.to_string()))
         }
+        Err(err) => return Err(FsEventsError::Io(err)),
     };
-
-    let max_size = 2147483648; // 2GB
-    if file_size < max_size {
-        return true;
-    }
-    false
+    check_file_size(path, &metadata, max_size)?;
+    Ok(File::open(path)?)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::get_file_size;
+    use super::open_file;
+    use crate::error::FsEventsError;
+
+    #[test]
+    fn test_open_file() {
+        let path = "/bin/ls";
+        let result = open_file(path, 2147483648);
+        assert!(result.is_ok())
+    }
 
     #[test]
-    fn test_get_file_size() {
+    fn test_open_file_too_large() {
         let path = "/bin/ls";
-        let result = get_file_size(path);
-        assert_eq!(result, true)
+        let result = open_file(path, 10);
+        assert!(matches!(result, Err(FsEventsError::FileTooLarge(_, 10))))
+    }
+
+    #[test]
+    fn test_open_file_not_a_file() {
+        assert!(matches!(
+            open_file("/bin", 2147483648),
+            Err(FsEventsError::NotAFile(_))
+        ));
+        assert!(matches!(
+            open_file("/this/path/does/not/exist", 2147483648),
+            Err(FsEventsError::NotAFile(_))
+        ));
     }
 }
@@ -0,0 +1,68 @@
+//! Sort FsEvent records so timelines read chronologically
+
+use crate::fsevents::FsEvents;
+
+/// Sort records by event ID, records with the same event ID are sorted by path
+pub fn sort_fsevents(fsevents: &mut [FsEvents]) {
+    fsevents.sort_by(|first, second| {
+        first
+            .event_id
+            .cmp(&second.event_id)
+            .then_with(|| first.path.cmp(&second.path))
+    });
+}
+
+/// Sort records by path, records with the same path are sorted by event ID
+pub fn sort_fsevents_by_path(fsevents: &mut [FsEvents]) {
+    fsevents.sort_by(|first, second| {
+        first
+            .path
+            .cmp(&second.path)
+            .then_with(|| first.event_id.cmp(&second.event_id))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_fsevents, sort_fsevents_by_path};
+    use crate::fsevents::FsEvents;
+
+    fn test_record(path: &str, event_id: u64, node: u64) -> FsEvents {
+        FsEvents {
+            path: path.to_string(),
+            event_id,
+            node,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sort_fsevents() {
+        let mut fsevents = vec![
+            test_record("/b", 30, 0),
+            test_record("/b", 10, 1),
+            test_record("/a", 10, 2),
+            test_record("/a", 20, 3),
+            test_record("/b", 10, 4),
+        ];
+        sort_fsevents(&mut fsevents);
+
+        let order: Vec<u64> = fsevents.iter().map(|fsevent| fsevent.node).collect();
+        // Records with the same event ID and path keep their original order
+        assert!(order == vec![2, 1, 4, 3, 0]);
+    }
+
+    #[test]
+    fn test_sort_fsevents_by_path() {
+        let mut fsevents = vec![
+            test_record("/b", 30, 0),
+            test_record("/b", 10, 1),
+            test_record("/a", 20, 2),
+            test_record("/a", 10, 3),
+        ];
+        sort_fsevents_by_path(&mut fsevents);
+
+        let order: Vec<u64> = fsevents.iter().map(|fsevent| fsevent.node).collect();
+        assert!(order == vec![3, 2, 1, 0]);
+    }
+}
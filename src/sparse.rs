@@ -0,0 +1,375 @@
+//! Read macOS sparse disk images
+//!
+//! Time Machine network backups and many other macOS images are stored as a sparse bundle, a directory
+//! of band files (`bands/0`, `bands/1`, ...) that together make up the disk image. Older sparse images
+//! (`.sparseimage`) store the bands in one file. Both are reassembled into a seekable stream, bands that
+//! were never written are read as zeros. Encrypted images are not supported.
+
+use crate::error::FsEventsError;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fs::{read_to_string, File},
+    io::{Error, ErrorKind, Read, Result as IoResult, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+// Signature at the start of a sparse image
+pub(crate) const SPARSE_IMAGE_SIGNATURE: &[u8; 4] = b"sprs";
+const SPARSE_IMAGE_HEADER_SIZE: u64 = 4096;
+// Offset to the band table in the sparse image header
+const BAND_TABLE_OFFSET: usize = 64;
+const SECTOR_SIZE: u64 = 512;
+
+/// Seekable reader over the disk image stored in a sparse bundle directory
+#[derive(Debug)]
+pub struct SparseBundleReader {
+    bands: PathBuf,                    // Directory containing the band files
+    band_size: u64,                    // Size of each band
+    size: u64,                         // Size of the disk image
+    position: u64,                     // Current position in the disk image
+    band: Option<(u64, Option<File>)>, // Last band that was opened, None if it does not exist
+}
+
+impl SparseBundleReader {
+    /// Open a sparse bundle directory (ex: backup.sparsebundle)
+    pub fn open(path: &str) -> Result<SparseBundleReader, FsEventsError> {
+        let bundle = Path::new(path);
+        if !bundle.is_dir() {
+            return Err(FsEventsError::NotADirectory(path.to_string()));
+        }
+        let info = read_to_string(bundle.join("Info.plist"))?;
+        let band_size = plist_integer(&info, "band-size").unwrap_or_default();
+        let size = plist_integer(&info, "size").unwrap_or_default();
+        if band_size == 0 || size == 0 {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} has an invalid Info.plist",
+                path
+            )));
+        }
+        if bundle.join("token").is_file() {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} is encrypted",
+                path
+            )));
+        }
+
+        Ok(SparseBundleReader {
+            bands: bundle.join("bands"),
+            band_size,
+            size,
+            position: 0,
+            band: None,
+        })
+    }
+
+    /// Size of the disk image
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Read for SparseBundleReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let (band, band_offset, size) =
+            match next_read(self.position, self.size, self.band_size, buf.len()) {
+                Some(result) => result,
+                None => return Ok(0),
+            };
+
+        if !matches!(&self.band, Some((cached, _)) if *cached == band) {
+            // Band files are named by their index in hex
+            let file = match File::open(self.bands.join(format!("{:x}", band))) {
+                Ok(result) => Some(result),
+                Err(err) if err.kind() == ErrorKind::NotFound => None,
+                Err(err) => return Err(err),
+            };
+            self.band = Some((band, file));
+        }
+
+        let count = match &mut self.band {
+            Some((_, Some(file))) => {
+                file.seek(SeekFrom::Start(band_offset))?;
+                file.read(&mut buf[..size])?
+            }
+            _ => 0,
+        };
+        // Bands that were never written, or were not written to the end, are zeros
+        let count = if count == 0 {
+            buf[..size].fill(0);
+            size
+        } else {
+            count
+        };
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for SparseBundleReader {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        self.position = seek_position(pos, self.position, self.size)?;
+        Ok(self.position)
+    }
+}
+
+/// Seekable reader over the disk image stored in a sparse image file
+#[derive(Debug)]
+pub struct SparseImageReader {
+    file: File,               // Sparse image file
+    band_size: u64,           // Size of each band
+    size: u64,                // Size of the disk image
+    position: u64,            // Current position in the disk image
+    bands: HashMap<u64, u64>, // Index of each band in the disk image to its index in the file
+}
+
+impl SparseImageReader {
+    /// Open a sparse image file (ex: backup.sparseimage)
+    pub fn open(path: &str) -> Result<SparseImageReader, FsEventsError> {
+        let mut file = File::open(path)?;
+        let mut header = vec![0; SPARSE_IMAGE_HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if &header[..4] != SPARSE_IMAGE_SIGNATURE {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} is not a sparse image",
+                path
+            )));
+        }
+
+        // Header values are big endian
+        let band_size = read_u32_be(&header, 8) as u64 * SECTOR_SIZE;
+        let size = read_u32_be(&header, 16) as u64 * SECTOR_SIZE;
+        if band_size == 0 {
+            return Err(FsEventsError::InvalidImage(format!(
+                "{} has an invalid band size",
+                path
+            )));
+        }
+
+        // The band table lists the bands in the order they are stored in the file,
+        // each entry is the band's index in the disk image plus one. Zero is an unused entry
+        let mut bands = HashMap::new();
+        for (index, entry) in header[BAND_TABLE_OFFSET..].chunks_exact(4).enumerate() {
+            let band = read_u32_be(entry, 0) as u64;
+            if band != 0 {
+                bands.insert(band - 1, index as u64);
+            }
+        }
+
+        Ok(SparseImageReader {
+            file,
+            band_size,
+            size,
+            position: 0,
+            bands,
+        })
+    }
+
+    /// Size of the disk image
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Read for SparseImageReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let (band, band_offset, size) =
+            match next_read(self.position, self.size, self.band_size, buf.len()) {
+                Some(result) => result,
+                None => return Ok(0),
+            };
+
+        let count = match self.bands.get(&band) {
+            Some(index) => {
+                let offset = SPARSE_IMAGE_HEADER_SIZE + index * self.band_size + band_offset;
+                self.file.seek(SeekFrom::Start(offset))?;
+                self.file.read(&mut buf[..size])?
+            }
+            None => 0,
+        };
+        let count = if count == 0 {
+            buf[..size].fill(0);
+            size
+        } else {
+            count
+        };
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for SparseImageReader {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        self.position = seek_position(pos, self.position, self.size)?;
+        Ok(self.position)
+    }
+}
+
+/// Get the band, offset in the band, and size of the next read. None at the end of the image
+fn next_read(
+    position: u64,
+    size: u64,
+    band_size: u64,
+    buf_size: usize,
+) -> Option<(u64, u64, usize)> {
+    if buf_size == 0 || position >= size {
+        return None;
+    }
+    let band = position / band_size;
+    let band_offset = position % band_size;
+    let read_size = (buf_size as u64)
+        .min(band_size - band_offset)
+        .min(size - position);
+    Some((band, band_offset, read_size as usize))
+}
+
+fn seek_position(pos: SeekFrom, position: u64, size: u64) -> IoResult<u64> {
+    let new_position = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::End(offset) => size.checked_add_signed(offset),
+        SeekFrom::Current(offset) => position.checked_add_signed(offset),
+    };
+    new_position.ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "Seek to a negative or overflowing position",
+        )
+    })
+}
+
+/// Get an integer value from an XML property list
+fn plist_integer(plist: &str, key: &str) -> Option<u64> {
+    let pattern = format!(
+        r"<key>{}</key>\s*<integer>(\d+)</integer>",
+        regex::escape(key)
+    );
+    let value = Regex::new(&pattern).ok()?.captures(plist)?.get(1)?;
+    value.as_str().parse().ok()
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plist_integer, SparseBundleReader, SparseImageReader};
+    use crate::{carve::carve_image, parser::decompress, test_util::test_data};
+    use std::{
+        fs::{create_dir_all, write},
+        io::{Read, Seek, SeekFrom},
+        path::{Path, PathBuf},
+    };
+    use tempfile::tempdir;
+
+    fn info_plist(band_size: u64, size: u64) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\">\n<dict>\n\
+             \t<key>band-size</key>\n\t<integer>{}</integer>\n\
+             \t<key>diskimage-bundle-type</key>\n\t<string>com.apple.diskimage.sparsebundle</string>\n\
+             \t<key>size</key>\n\t<integer>{}</integer>\n</dict>\n</plist>\n",
+            band_size, size
+        )
+    }
+
+    /// Write a sparse bundle with 1024 byte bands, band 1 is never written and band 2 is short
+    fn write_bundle(directory: &Path, data: &[u8]) -> PathBuf {
+        let test_location = directory.join("test.sparsebundle");
+        create_dir_all(test_location.join("bands")).unwrap();
+        write(
+            test_location.join("Info.plist"),
+            info_plist(1024, data.len() as u64),
+        )
+        .unwrap();
+
+        for (band, chunk) in data.chunks(1024).enumerate() {
+            let band_data = match band {
+                1 => continue,
+                2 => &chunk[..chunk.len() / 2],
+                _ => chunk,
+            };
+            write(
+                test_location.join("bands").join(format!("{:x}", band)),
+                band_data,
+            )
+            .unwrap();
+        }
+        test_location
+    }
+
+    fn band_data() -> Vec<u8> {
+        let mut data: Vec<u8> = (0..12288).map(|value| (value % 251) as u8 + 1).collect();
+        data[1024..2048].fill(0);
+        data[2048 + 512..3072].fill(0);
+        data
+    }
+
+    #[test]
+    fn test_sparse_bundle_reader() {
+        let data = band_data();
+        let temp = tempdir().unwrap();
+        let test_location = write_bundle(temp.path(), &data);
+
+        let mut reader = SparseBundleReader::open(&test_location.display().to_string()).unwrap();
+        assert!(reader.size() == 12288);
+        let mut results = Vec::new();
+        reader.read_to_end(&mut results).unwrap();
+        assert!(results == data);
+
+        // Read across band 10 (named "a") and band 11
+        reader.seek(SeekFrom::Start(11000)).unwrap();
+        let mut buffer = vec![0; 500];
+        reader.read_exact(&mut buffer).unwrap();
+        assert!(buffer == data[11000..11500]);
+    }
+
+    #[test]
+    fn test_sparse_image_reader() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("sparse_image.sparseimage");
+
+        // Two bands of 2 sectors, band 1 is stored before band 0. 6 sectors in the image
+        let mut image = vec![0; 4096];
+        image[..4].copy_from_slice(b"sprs");
+        image[4..8].copy_from_slice(&3u32.to_be_bytes());
+        image[8..12].copy_from_slice(&2u32.to_be_bytes());
+        image[16..20].copy_from_slice(&6u32.to_be_bytes());
+        image[64..68].copy_from_slice(&2u32.to_be_bytes());
+        image[68..72].copy_from_slice(&1u32.to_be_bytes());
+        image.extend_from_slice(&[2; 1024]);
+        image.extend_from_slice(&[1; 1024]);
+        write(&test_location, image).unwrap();
+
+        let mut reader = SparseImageReader::open(&test_location.display().to_string()).unwrap();
+        assert!(reader.size() == 3072);
+        let mut results = Vec::new();
+        reader.read_to_end(&mut results).unwrap();
+        assert!(results[..1024] == [1; 1024]);
+        assert!(results[1024..2048] == [2; 1024]);
+        assert!(results[2048..] == [0; 1024]);
+    }
+
+    #[test]
+    fn test_carve_sparse_bundle() {
+        let fsevents_location = test_data("DLS3/0000000000030012");
+        let mut data = vec![0; 4096];
+        data.extend_from_slice(&decompress(&fsevents_location.display().to_string()).unwrap());
+        data.resize(8192, 0);
+
+        let temp = tempdir().unwrap();
+        let test_location = write_bundle(temp.path(), &data);
+        let results = carve_image(&test_location.display().to_string()).unwrap();
+        assert!(results.len() == 5);
+        assert!(results[0].offset == 4096 + 12);
+    }
+
+    #[test]
+    fn test_plist_integer() {
+        let plist = info_plist(8388608, 1099511627776);
+        assert!(plist_integer(&plist, "band-size") == Some(8388608));
+        assert!(plist_integer(&plist, "size") == Some(1099511627776));
+        assert!(plist_integer(&plist, "missing").is_none());
+    }
+}
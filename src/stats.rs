@@ -0,0 +1,289 @@
+//! Summarize parsed FsEvent records for reports
+
+use crate::{flags::FsEventFlags, fsevents::FsEvents};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Fewest removed records a FsEvent file needs to be a deletion spike
+const SPIKE_MIN_DELETIONS: usize = 50;
+/// How many times the median removed records per FsEvent file a deletion spike needs
+const SPIKE_FACTOR: usize = 3;
+
+#[derive(Debug, Serialize, Default)]
+pub struct FsEventsStats {
+    pub total_records: usize,                     // Total number of records
+    pub unique_paths: usize,                      // Number of unique paths
+    pub invalid_paths: usize,                     // Number of paths that were not valid UTF-8
+    pub min_event_id: u64,                        // Lowest event ID
+    pub max_event_id: u64,                        // Highest event ID
+    pub flags: BTreeMap<String, usize>,           // Number of records with each flag
+    pub top_directories: BTreeMap<String, usize>, // Number of records per top-level directory
+    pub source_files: BTreeMap<String, usize>,    // Number of records from each FsEvent file
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DirectoryCount {
+    pub directory: String, // Parent directory of the records
+    pub records: usize,    // Number of records in the directory
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SourceFileSummary {
+    pub source_file: String,       // FsEvent file that contained the records
+    pub records: usize,            // Number of records from the file
+    pub deletions: usize,          // Number of records with the Removed flag
+    pub first_event_id: u64,       // Lowest event ID in the file
+    pub last_event_id: u64,        // Highest event ID in the file
+    pub approx_earliest_time: i64, // Earliest approximate time (Unix epoch), 0 if not added
+    pub approx_latest_time: i64,   // Latest approximate time (Unix epoch), 0 if not added
+}
+
+/// Get summary statistics for the records
+pub fn get_stats(fsevents: &[FsEvents]) -> FsEventsStats {
+    let mut stats = FsEventsStats {
+        total_records: fsevents.len(),
+        ..Default::default()
+    };
+
+    let mut paths: HashSet<&str> = HashSet::new();
+    for fsevent in fsevents {
+        paths.insert(&fsevent.path);
+        if fsevent.has_invalid_path() {
+            stats.invalid_paths += 1;
+        }
+
+        let flags = fsevent.event_flags();
+        for flag in flags.names() {
+            *stats.flags.entry(flag.to_string()).or_default() += 1;
+        }
+        let is_directory = flags.contains(FsEventFlags::IS_DIRECTORY);
+        *stats
+            .top_directories
+            .entry(top_directory(&fsevent.path, is_directory))
+            .or_default() += 1;
+        if !fsevent.source_file.is_empty() {
+            *stats
+                .source_files
+                .entry(fsevent.source_file.clone())
+                .or_default() += 1;
+        }
+    }
+    stats.unique_paths = paths.len();
+    let event_ids = fsevents.iter().map(|fsevent| fsevent.event_id);
+    stats.min_event_id = event_ids.clone().min().unwrap_or_default();
+    stats.max_event_id = event_ids.max().unwrap_or_default();
+    stats
+}
+
+/// Get the parent directories with the most records, busiest first
+/// Directories with the same number of records are sorted by name
+pub fn busiest_directories(fsevents: &[FsEvents], limit: usize) -> Vec<DirectoryCount> {
+    let mut directories: BTreeMap<&str, usize> = BTreeMap::new();
+    for fsevent in fsevents {
+        let directory = match fsevent.path.rfind('/') {
+            Some(0) => "/",
+            Some(index) => &fsevent.path[..index],
+            None => "",
+        };
+        *directories.entry(directory).or_default() += 1;
+    }
+
+    let mut counts: Vec<DirectoryCount> = directories
+        .into_iter()
+        .map(|(directory, records)| DirectoryCount {
+            directory: directory.to_string(),
+            records,
+        })
+        .collect();
+    // Stable sort keeps directories with the same count in name order
+    counts.sort_by_key(|count| std::cmp::Reverse(count.records));
+    counts.truncate(limit);
+    counts
+}
+
+/// Summarize the records from each FsEvent file, in event ID order
+/// FsEvent files cover consecutive periods of time, so each summary is a slice of the timeline
+pub fn source_file_summaries(fsevents: &[FsEvents]) -> Vec<SourceFileSummary> {
+    let mut summaries: Vec<SourceFileSummary> = Vec::new();
+    let mut index = HashMap::new();
+    for fsevent in fsevents {
+        let summary = *index
+            .entry(fsevent.source_file.as_str())
+            .or_insert_with(|| {
+                summaries.push(SourceFileSummary {
+                    source_file: fsevent.source_file.clone(),
+                    first_event_id: fsevent.event_id,
+                    last_event_id: fsevent.event_id,
+                    ..Default::default()
+                });
+                summaries.len() - 1
+            });
+        let summary = &mut summaries[summary];
+        summary.records += 1;
+        if fsevent.event_flags().contains(FsEventFlags::REMOVED) {
+            summary.deletions += 1;
+        }
+        summary.first_event_id = summary.first_event_id.min(fsevent.event_id);
+        summary.last_event_id = summary.last_event_id.max(fsevent.event_id);
+        if fsevent.approx_earliest_time != 0
+            && (summary.approx_earliest_time == 0
+                || fsevent.approx_earliest_time < summary.approx_earliest_time)
+        {
+            summary.approx_earliest_time = fsevent.approx_earliest_time;
+        }
+        summary.approx_latest_time = summary.approx_latest_time.max(fsevent.approx_latest_time);
+    }
+    summaries.sort_by_key(|summary| summary.first_event_id);
+    summaries
+}
+
+/// Get the FsEvent files with far more removed records than the others (at least 50, and three
+/// times the median), most deletions first. Mass deletions can be cleanup after an intrusion or
+/// the start of ransomware
+pub fn deletion_spikes(summaries: &[SourceFileSummary]) -> Vec<&SourceFileSummary> {
+    let mut deletions: Vec<usize> = summaries.iter().map(|summary| summary.deletions).collect();
+    deletions.sort_unstable();
+    // Lower median, so one busy file out of two still stands out
+    let median = deletions
+        .get(deletions.len().saturating_sub(1) / 2)
+        .copied()
+        .unwrap_or_default();
+    let threshold = SPIKE_MIN_DELETIONS.max(median * SPIKE_FACTOR);
+
+    let mut spikes: Vec<&SourceFileSummary> = summaries
+        .iter()
+        .filter(|summary| summary.deletions >= threshold)
+        .collect();
+    spikes.sort_by_key(|summary| std::cmp::Reverse(summary.deletions));
+    spikes
+}
+
+/// Get the top-level directory for a path (ex: `/Users/bob/file.txt` is `/Users`)
+/// Files directly under the root are in `/`
+fn top_directory(path: &str, is_directory: bool) -> String {
+    match path.trim_start_matches('/').split_once('/') {
+        Some((component, _)) => format!("/{}", component),
+        None if is_directory => format!("/{}", path.trim_start_matches('/')),
+        None => String::from("/"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        busiest_directories, deletion_spikes, get_stats, source_file_summaries, top_directory,
+    };
+    use crate::fsevents::FsEvents;
+    #[cfg(feature = "filesystem")]
+    use crate::{options::ParserOptions, parser::parse_directory_with_options};
+    #[cfg(feature = "filesystem")]
+    use std::path::PathBuf;
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_get_stats() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let results = parse_directory_with_options(
+            &test_location.display().to_string(),
+            &ParserOptions::new(),
+        )
+        .unwrap();
+        let stats = get_stats(&results);
+
+        assert!(stats.total_records == 5);
+        assert!(stats.unique_paths == 4);
+        assert!(stats.invalid_paths == 0);
+        assert!(stats.min_event_id == 196608);
+        assert!(stats.max_event_id == 196625);
+        assert!(stats.flags["IsFile"] == 4);
+        assert!(stats.flags["Created"] == 2);
+        assert!(stats.top_directories["/Users"] == 3);
+        assert!(stats.top_directories["/private"] == 1);
+        assert!(stats.source_files.len() == 1);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert!(json.contains("\"total_records\":5"));
+    }
+
+    #[test]
+    fn test_get_stats_empty() {
+        let stats = get_stats(&[]);
+        assert!(stats.total_records == 0);
+        assert!(stats.min_event_id == 0);
+    }
+
+    #[test]
+    fn test_busiest_directories() {
+        let records: Vec<FsEvents> = ["/Users/bob/a", "/tmp/b", "/Users/bob/c", "/d", "/tmp/e"]
+            .iter()
+            .map(|path| FsEvents {
+                path: path.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let directories = busiest_directories(&records, 2);
+        assert!(directories.len() == 2);
+        assert!(directories[0].directory == "/Users/bob");
+        assert!(directories[0].records == 2);
+        assert!(directories[1].directory == "/tmp");
+
+        let directories = busiest_directories(&records, 10);
+        assert!(directories[2].directory == "/");
+    }
+
+    #[test]
+    fn test_source_file_summaries() {
+        let records: Vec<FsEvents> = [("b", 9, 0x02), ("a", 2, 0x01), ("b", 5, 0x02), ("a", 3, 0)]
+            .iter()
+            .map(|(source, event_id, flags)| FsEvents {
+                source_file: source.to_string(),
+                event_id: *event_id,
+                raw_flags: *flags,
+                approx_latest_time: *event_id as i64,
+                ..Default::default()
+            })
+            .collect();
+        let summaries = source_file_summaries(&records);
+        assert!(summaries.len() == 2);
+        assert!(summaries[0].source_file == "a");
+        assert!(summaries[0].deletions == 0);
+        assert!(summaries[0].approx_earliest_time == 0);
+        assert!(summaries[1].records == 2);
+        assert!(summaries[1].deletions == 2);
+        assert!(summaries[1].first_event_id == 5);
+        assert!(summaries[1].last_event_id == 9);
+        assert!(summaries[1].approx_latest_time == 9);
+    }
+
+    #[test]
+    fn test_deletion_spikes() {
+        let mut records = Vec::new();
+        for (source, deletions) in [("a", 2), ("b", 60), ("c", 3), ("d", 10)] {
+            for event_id in 0..deletions {
+                records.push(FsEvents {
+                    source_file: source.to_string(),
+                    event_id,
+                    raw_flags: 0x02,
+                    ..Default::default()
+                });
+            }
+        }
+        let summaries = source_file_summaries(&records);
+        let spikes = deletion_spikes(&summaries);
+        assert!(spikes.len() == 1);
+        assert!(spikes[0].source_file == "b");
+
+        // Every file has many deletions, so none stand out
+        assert!(deletion_spikes(&summaries[1..2]).is_empty());
+    }
+
+    #[test]
+    fn test_top_directory() {
+        assert!(top_directory("/Users/bob/file.txt", false) == "/Users");
+        assert!(top_directory("/Users/bob", true) == "/Users");
+        assert!(top_directory("/Applications", true) == "/Applications");
+        assert!(top_directory("/file.txt", false) == "/");
+        assert!(top_directory("/", true) == "/");
+    }
+}
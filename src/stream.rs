@@ -0,0 +1,675 @@
+//! Stream FsEvent pages from compressed data
+//!
+//! Decompressed FsEvent data can be hundreds of MBs. Instead of decompressing the whole file into
+//! memory, the gzip data is decoded one page at a time so only a single page is buffered.
+//! Data that is already decompressed is read as is.
+//! If the compressed data ends early (ex: the newest file is still being written) the records that
+//! were decompressed before the end are kept and the reader is marked as truncated.
+//! With `skip_corrupt_pages` a page that cannot be parsed is skipped instead of stopping the reader.
+//! The next page is found by searching for its DLS signature.
+//! With `resync_records` a page with a bad record is parsed again, skipping data until the next record
+//! that looks valid, so one bad record does not discard the rest of the page.
+
+#[cfg(feature = "filesystem")]
+use crate::size::open_file;
+use crate::{
+    carve::{find_page_signatures, resync_page},
+    compression::Decoder,
+    error::FsEventsError,
+    fsevents::{FsEvents, FsEventsPage, FsEventsPageHeader},
+    options::ParserOptions,
+    report::{ParseReport, ResyncedPage, SkippedPage},
+};
+use log::warn;
+use std::io::{ErrorKind, Read};
+#[cfg(feature = "filesystem")]
+use std::{fs::File, io::BufReader};
+
+const HEADER_SIZE: usize = 12;
+const RESYNC_CHUNK_SIZE: usize = 65536;
+
+pub struct PageReader<R: Read> {
+    reader: Option<R>, // FsEvent data before the compression has been detected
+    decoder: Option<Decoder<R>>, // Decoder for the FsEvent data
+    offset: u64,       // Offset to the next page in the decompressed data
+    page: u32,         // Index of the next page
+    done: bool,        // No more pages can be read
+    truncated: bool,   // Compressed data ended before the end of the last page
+    skip_corrupt: bool, // Skip pages that cannot be parsed instead of returning an error
+    skipped_pages: Vec<SkippedPage>, // Pages that were skipped
+    resync: bool,      // Skip bad records in a page instead of failing the page
+    resynced_pages: Vec<ResyncedPage>, // Pages where bad records were skipped
+    pending: Vec<u8>,  // Decompressed data read ahead while searching for the next page
+    path: String,      // Path to the FsEvent file, if read from a file
+    header: FsEventsPageHeader, // Header of the last page read
+}
+
+#[cfg(feature = "filesystem")]
+impl PageReader<BufReader<File>> {
+    /// Create a page reader for a compressed FsEvent file
+    /// Files that are not smaller than the default max file size are not read
+    pub fn from_path(path: &str) -> Result<PageReader<BufReader<File>>, FsEventsError> {
+        PageReader::from_path_with_options(path, &ParserOptions::default())
+    }
+
+    /// Create a page reader for a compressed FsEvent file, using the max file size and the page
+    /// options from the parser options
+    pub fn from_path_with_options(
+        path: &str,
+        options: &ParserOptions,
+    ) -> Result<PageReader<BufReader<File>>, FsEventsError> {
+        let file = open_file(path, options.max_file_size)?;
+        let reader = PageReader::new(BufReader::new(file))
+            .path(path)
+            .page_options(options);
+        Ok(reader)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl PageReader<std::io::Cursor<memmap2::Mmap>> {
+    /// Create a page reader for a memory mapped FsEvent file
+    /// The compressed data is read directly from the mapped file instead of being copied
+    /// Only use this for copied or collected FsEvent files. If a mapped file is truncated or
+    /// replaced while it is read the process is killed (SIGBUS), so files in a live fseventsd
+    /// directory should be read with `PageReader::from_path`
+    pub fn from_mmap(
+        path: &str,
+    ) -> Result<PageReader<std::io::Cursor<memmap2::Mmap>>, FsEventsError> {
+        PageReader::from_mmap_with_options(path, &ParserOptions::default())
+    }
+
+    /// Create a page reader for a memory mapped FsEvent file, using the max file size and the
+    /// page options from the parser options
+    pub fn from_mmap_with_options(
+        path: &str,
+        options: &ParserOptions,
+    ) -> Result<PageReader<std::io::Cursor<memmap2::Mmap>>, FsEventsError> {
+        let file = open_file(path, options.max_file_size)?;
+        // Safety: the file is only read. If another process truncates the file while it is mapped,
+        // reading the unmapped pages raises SIGBUS and kills the process instead of returning an
+        // error. fseventsd rewrites and deletes files in a live directory, callers must only map
+        // files that are not changing
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        let reader = PageReader::new(std::io::Cursor::new(mmap))
+            .path(path)
+            .page_options(options);
+        Ok(reader)
+    }
+}
+
+impl<R: Read> PageReader<R> {
+    /// Create a page reader for compressed (or already decompressed) FsEvent data from any reader
+    pub fn new(reader: R) -> PageReader<R> {
+        PageReader {
+            reader: Some(reader),
+            decoder: None,
+            offset: 0,
+            page: 0,
+            done: false,
+            truncated: false,
+            skip_corrupt: false,
+            skipped_pages: Vec::new(),
+            resync: false,
+            resynced_pages: Vec::new(),
+            pending: Vec::new(),
+            path: String::new(),
+            header: FsEventsPageHeader::default(),
+        }
+    }
+
+    /// Skip pages that cannot be parsed and continue with the next page, instead of stopping
+    /// Skipped pages are returned as an empty list of records and listed in `skipped_pages`
+    pub fn skip_corrupt_pages(mut self, skip: bool) -> PageReader<R> {
+        self.skip_corrupt = skip;
+        self
+    }
+
+    /// Set the path (or a name, ex: `stdin`) listed in the skipped and resynced pages
+    pub fn path(mut self, path: &str) -> PageReader<R> {
+        self.path = path.to_string();
+        self
+    }
+
+    /// Get the pages that were skipped because they could not be parsed
+    pub fn skipped_pages(&self) -> &[SkippedPage] {
+        &self.skipped_pages
+    }
+
+    /// Skip data that is not a record in a page that fails to parse, and keep the records after it
+    /// Records are found by a null terminated path followed by valid flags
+    pub fn resync_records(mut self, resync: bool) -> PageReader<R> {
+        self.resync = resync;
+        self
+    }
+
+    /// Get the pages where data was skipped to find the next record
+    pub fn resynced_pages(&self) -> &[ResyncedPage] {
+        &self.resynced_pages
+    }
+
+    /// Use the skip corrupt pages and resync records options from the parser options
+    pub fn page_options(self, options: &ParserOptions) -> PageReader<R> {
+        self.skip_corrupt_pages(options.skip_corrupt_pages)
+            .resync_records(options.resync_records)
+    }
+
+    /// Add the path (if truncated) and the skipped and resynced pages to a report
+    pub fn add_to_report(&self, report: &mut ParseReport) {
+        if self.truncated {
+            report.truncated_files.push(self.path.clone());
+        }
+        report.skipped_pages.extend_from_slice(&self.skipped_pages);
+        report
+            .resynced_pages
+            .extend_from_slice(&self.resynced_pages);
+    }
+
+    /// Check if the compressed data ended early. Records before the end were still returned
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Decompress and parse the next page, returns None when all pages have been read
+    /// After an error no more pages are returned, because the next page cannot be located
+    /// (unless corrupt pages are skipped)
+    pub fn next_page(&mut self) -> Result<Option<Vec<FsEvents>>, FsEventsError> {
+        if self.done {
+            return Ok(None);
+        }
+        let results = self.read_page();
+        if !matches!(results, Ok(Some(_))) {
+            self.done = true;
+        }
+        results
+    }
+
+    /// Decompress and parse the next page, also returning the page header and where the page starts
+    pub fn next_page_with_header(&mut self) -> Result<Option<FsEventsPage>, FsEventsError> {
+        let index = self.page;
+        let offset = self.offset;
+        self.header = FsEventsPageHeader::default();
+        let records = match self.next_page()? {
+            Some(result) => result,
+            None => return Ok(None),
+        };
+        Ok(Some(FsEventsPage {
+            index,
+            offset,
+            header: self.header,
+            records,
+        }))
+    }
+
+    /// Read the next page into memory and parse it
+    fn read_page(&mut self) -> Result<Option<Vec<FsEvents>>, FsEventsError> {
+        if let Some(reader) = self.reader.take() {
+            self.decoder = Some(Decoder::detect(reader).map_err(FsEventsError::Decompress)?);
+        }
+        let compressed = match self.decoder.as_ref() {
+            Some(decoder) => decoder.is_compressed(),
+            None => return Ok(None),
+        };
+        let is_truncated =
+            |err: &std::io::Error| compressed && err.kind() == ErrorKind::UnexpectedEof;
+
+        let mut page_data = Vec::with_capacity(HEADER_SIZE);
+        let header_read = match self.read_data(HEADER_SIZE, &mut page_data) {
+            Ok(result) => result,
+            Err(err) if is_truncated(&err) => {
+                return Ok(Some(self.truncated_page(&page_data, err)))
+            }
+            Err(err) => return Err(read_error(err, compressed)),
+        };
+        if header_read == 0 {
+            return Ok(None);
+        }
+
+        if header_read == HEADER_SIZE {
+            self.header = FsEventsPageHeader::parse(&page_data)?;
+            let signature =
+                u32::from_le_bytes([page_data[0], page_data[1], page_data[2], page_data[3]]);
+            if let Err(err) = FsEvents::check_signature(signature) {
+                // The next page may start anywhere after the bad signature
+                return self.corrupt_page(page_data, 1, err, compressed);
+            }
+
+            // Do not allocate the full stream size up front, the header may be corrupt
+            let stream_size =
+                u32::from_le_bytes([page_data[8], page_data[9], page_data[10], page_data[11]]);
+            let stream_size = (stream_size as usize).saturating_sub(HEADER_SIZE);
+            match self.read_data(stream_size, &mut page_data) {
+                Ok(_) => {}
+                Err(err) if is_truncated(&err) => {
+                    return Ok(Some(self.truncated_page(&page_data, err)))
+                }
+                Err(err) => return Err(read_error(err, compressed)),
+            }
+        }
+
+        // Short headers and pages are reported the same way as when parsing from memory
+        let records = match FsEvents::fsevents_page(&page_data, self.page, self.offset) {
+            Ok((_, records)) => records,
+            Err(err) => match self.resync_records_page(&mut page_data) {
+                Some(records) => records,
+                // If the stream size is too large the page data may contain the next page
+                None => return self.corrupt_page(page_data, HEADER_SIZE, err, compressed),
+            },
+        };
+        self.offset += page_data.len() as u64;
+        self.page += 1;
+        Ok(Some(records))
+    }
+
+    /// Parse a page again, skipping data until the next record. Returns None if no records are found
+    /// If the stream size is too large, data after the next page signature is kept for the next read
+    fn resync_records_page(&mut self, page_data: &mut Vec<u8>) -> Option<Vec<FsEvents>> {
+        if !self.resync || page_data.len() < HEADER_SIZE {
+            return None;
+        }
+        if let Some((offset, _)) = find_page_signatures(&page_data[HEADER_SIZE..]).first() {
+            let mut next_page = page_data.split_off(HEADER_SIZE + offset);
+            next_page.append(&mut self.pending);
+            self.pending = next_page;
+        }
+
+        let (records, skipped_bytes) = resync_page(page_data, self.page, self.offset)?;
+        if records.is_empty() {
+            return None;
+        }
+        warn!(
+            "Skipped {} bytes of bad records in FsEvent page {} at offset {}",
+            skipped_bytes, self.page, self.offset
+        );
+        self.resynced_pages.push(ResyncedPage {
+            path: self.path.clone(),
+            page: self.page,
+            offset: self.offset,
+            skipped_bytes,
+        });
+        Some(records)
+    }
+
+    /// Read up to size bytes of decompressed data, data that was read ahead is used first
+    fn read_data(&mut self, size: usize, buffer: &mut Vec<u8>) -> std::io::Result<usize> {
+        let pending = size.min(self.pending.len());
+        buffer.extend(self.pending.drain(..pending));
+        let decoder = match self.decoder.as_mut() {
+            Some(result) => result,
+            None => return Ok(pending),
+        };
+        let read = decoder.take((size - pending) as u64).read_to_end(buffer)?;
+        Ok(pending + read)
+    }
+
+    /// Skip a page that cannot be parsed and find the start of the next page
+    /// The search for the next page signature begins at `search_start` in the page data
+    fn corrupt_page(
+        &mut self,
+        page_data: Vec<u8>,
+        search_start: usize,
+        err: FsEventsError,
+        compressed: bool,
+    ) -> Result<Option<Vec<FsEvents>>, FsEventsError> {
+        if !self.skip_corrupt {
+            return Err(err);
+        }
+        warn!(
+            "Skipping corrupt FsEvent page {} at offset {}. Err: {}",
+            self.page, self.offset, err
+        );
+        self.skipped_pages.push(SkippedPage {
+            path: self.path.clone(),
+            page: self.page,
+            offset: self.offset,
+            reason: err.to_string(),
+        });
+        self.page += 1;
+        self.offset += self.resync(page_data, search_start, compressed)?;
+        Ok(Some(Vec::new()))
+    }
+
+    /// Search the data, and the decompressed data after it, for the next page signature
+    /// Data after the signature is kept for the next read. Returns the number of bytes skipped
+    fn resync(
+        &mut self,
+        mut data: Vec<u8>,
+        search_start: usize,
+        compressed: bool,
+    ) -> Result<u64, FsEventsError> {
+        data.append(&mut self.pending);
+        let mut search_start = search_start.min(data.len());
+        let mut skipped = 0;
+        loop {
+            if let Some((offset, _)) = find_page_signatures(&data[search_start..]).first() {
+                let offset = search_start + offset;
+                self.pending = data.split_off(offset);
+                return Ok(skipped + offset as u64);
+            }
+
+            // Keep the last bytes in case a signature is split between reads
+            let keep_size = 3;
+            let drop_size = data.len().saturating_sub(keep_size).max(search_start);
+            data.drain(..drop_size);
+            skipped += drop_size as u64;
+            search_start = 0;
+
+            match self.read_data(RESYNC_CHUNK_SIZE, &mut data) {
+                Ok(0) => return Ok(skipped + data.len() as u64),
+                Ok(_) => {}
+                Err(err) if compressed && err.kind() == ErrorKind::UnexpectedEof => {
+                    warn!("FsEvent data ended early while searching for the next page");
+                    self.truncated = true;
+                    self.done = true;
+                    return Ok(skipped + data.len() as u64);
+                }
+                Err(err) => return Err(read_error(err, compressed)),
+            }
+        }
+    }
+
+    /// Keep the records in a page that ended because the compressed data is truncated
+    fn truncated_page(&mut self, page_data: &[u8], err: std::io::Error) -> Vec<FsEvents> {
+        warn!(
+            "FsEvent data ended early in page {}, keeping records before the end. Err: {}",
+            self.page, err
+        );
+        self.truncated = true;
+        self.done = true;
+        FsEvents::fsevents_partial_page(page_data, self.page, self.offset)
+    }
+}
+
+/// Convert a read error to the error returned when parsing from memory
+fn read_error(err: std::io::Error, compressed: bool) -> FsEventsError {
+    match err.kind() {
+        ErrorKind::UnexpectedEof => FsEventsError::TruncatedRecord,
+        _ if compressed => FsEventsError::Decompress(err),
+        _ => FsEventsError::Io(err),
+    }
+}
+
+impl<R: Read> Iterator for PageReader<R> {
+    type Item = Result<Vec<FsEvents>, FsEventsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_page().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PageReader;
+    use crate::error::FsEventsError;
+    #[cfg(feature = "filesystem")]
+    use crate::{fsevents::DlsVersion, options::ParserOptions, parser::decompress};
+    use flate2::{write::GzEncoder, Compression};
+    use std::{io::Write, path::PathBuf};
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let mut reader = PageReader::from_path(&test_location.display().to_string()).unwrap();
+
+        let records = reader.next_page().unwrap().unwrap();
+        assert!(records.len() == 3);
+        assert!(records[2].offset == 124);
+
+        let records = reader.next_page().unwrap().unwrap();
+        assert!(records.len() == 2);
+        assert!(records[0].page == 1);
+        assert!(records[0].offset == 184);
+        assert!(reader.next_page().unwrap().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_with_header() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let mut reader = PageReader::from_path(&test_location.display().to_string()).unwrap();
+
+        let page = reader.next_page_with_header().unwrap().unwrap();
+        assert!(page.index == 0);
+        assert!(page.header.version() == Some(DlsVersion::Dls3));
+        assert!(page.header.stream_size == 172);
+        assert!(page.records.len() == 3);
+
+        let page = reader.next_page_with_header().unwrap().unwrap();
+        assert!(page.index == 1);
+        assert!(page.offset == 172);
+        assert!(page.header.stream_size == 127);
+        assert!(page.records.len() == 2);
+        assert!(reader.next_page_with_header().unwrap().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_dls2() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let reader = PageReader::from_path(&test_location.display().to_string()).unwrap();
+        let count: usize = reader.map(|records| records.unwrap().len()).sum();
+        assert!(count == 736);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_malformed() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/Malformed/malformed");
+        let mut reader = PageReader::from_path(&test_location.display().to_string()).unwrap();
+        let results = reader.next_page();
+        assert!(matches!(results, Err(FsEventsError::TruncatedRecord)));
+        assert!(reader.next_page().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_page_reader_truncated_page() {
+        // DLS2 header with a stream size larger than the data
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x444c5332u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0x1000u32.to_le_bytes());
+        data.extend_from_slice(b"/a\0");
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut reader = PageReader::new(&compressed[..]);
+        assert!(matches!(
+            reader.next_page(),
+            Err(FsEventsError::TruncatedRecord)
+        ));
+    }
+
+    #[test]
+    fn test_page_reader_truncated_gzip() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let data = std::fs::read(test_location).unwrap();
+
+        let mut reader = PageReader::new(&data[..data.len() / 2]);
+        let records: Vec<_> = reader
+            .by_ref()
+            .flat_map(|records| records.unwrap())
+            .collect();
+        assert!(reader.is_truncated());
+        assert!(!records.is_empty() && records.len() < 736);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_skip_corrupt_pages() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data");
+        let mut data = decompress(
+            &test_location
+                .join("Malformed/malformed")
+                .display()
+                .to_string(),
+        )
+        .unwrap();
+        let dls3 = decompress(
+            &test_location
+                .join("DLS3/0000000000030012")
+                .display()
+                .to_string(),
+        )
+        .unwrap();
+        data.extend_from_slice(&dls3);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut reader = PageReader::new(&compressed[..]);
+        assert!(matches!(
+            reader.next_page(),
+            Err(FsEventsError::TruncatedRecord)
+        ));
+
+        let mut reader = PageReader::new(&compressed[..]).skip_corrupt_pages(true);
+        let records: Vec<_> = reader
+            .by_ref()
+            .flat_map(|records| records.unwrap())
+            .collect();
+        assert!(records.len() == 5);
+        assert!(records[0].page == 1);
+        assert!(records[0].offset == 45 + 12);
+        assert!(reader.skipped_pages().len() == 1);
+        assert!(reader.skipped_pages()[0].offset == 0);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_skip_bad_signatures() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let dls3 = decompress(&test_location.display().to_string()).unwrap();
+
+        let mut data = vec![0xff; 37];
+        data.extend_from_slice(&dls3);
+        data.extend_from_slice(&[0xff; 20]);
+        data.extend_from_slice(&dls3);
+        data.extend_from_slice(&[0xff; 5]);
+
+        let mut reader = PageReader::new(&data[..]).skip_corrupt_pages(true);
+        let records: Vec<_> = reader
+            .by_ref()
+            .flat_map(|records| records.unwrap())
+            .collect();
+        assert!(records.len() == 10);
+        assert!(records[0].offset == 37 + 12);
+        assert!(records[5].offset == 37 + 299 + 20 + 12);
+
+        let skipped = reader.skipped_pages();
+        assert!(skipped.len() == 3);
+        assert!(skipped[1].offset == 37 + 299);
+        assert!(skipped[2].offset == 37 + 299 + 20 + 299);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_resync_records() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let mut data = decompress(&test_location.display().to_string()).unwrap();
+
+        // Insert a bad record and zeroed data before the third record in the first page
+        let mut garbage = vec![0x41, 0x00];
+        garbage.extend_from_slice(&[0; 25]);
+        data.splice(124..124, garbage.iter().copied());
+        let stream_size = 172 + garbage.len() as u32;
+        data[8..12].copy_from_slice(&stream_size.to_le_bytes());
+
+        let mut reader = PageReader::new(&data[..]);
+        assert!(matches!(
+            reader.next_page(),
+            Err(FsEventsError::TruncatedRecord)
+        ));
+
+        let mut reader = PageReader::new(&data[..]).resync_records(true);
+        let records: Vec<_> = reader
+            .by_ref()
+            .flat_map(|records| records.unwrap())
+            .collect();
+        assert!(records.len() == 5);
+        assert!(records[2].offset == 124 + garbage.len() as u64);
+        assert!(records[3].page == 1);
+
+        let resynced = reader.resynced_pages();
+        assert!(resynced.len() == 1);
+        assert!(resynced[0].skipped_bytes == garbage.len() as u64);
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_uncompressed() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let data = decompress(&test_location.display().to_string()).unwrap();
+
+        let reader = PageReader::new(&data[..]);
+        let records: Vec<_> = reader.flat_map(|records| records.unwrap()).collect();
+        assert!(records.len() == 5);
+        assert!(records[3].offset == 184);
+    }
+
+    #[test]
+    fn test_page_reader_not_fsevents() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"not fsevents data").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut reader = PageReader::new(&compressed[..]);
+        assert!(matches!(
+            reader.next_page(),
+            Err(FsEventsError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_page_reader_mmap() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let reader = PageReader::from_mmap(&test_location.display().to_string()).unwrap();
+        let count: usize = reader.map(|records| records.unwrap().len()).sum();
+        assert!(count == 736);
+
+        let results = PageReader::from_mmap("/this/path/does/not/exist");
+        assert!(matches!(results, Err(FsEventsError::NotAFile(_))));
+
+        let options = ParserOptions::new().max_file_size(10);
+        let results =
+            PageReader::from_mmap_with_options(&test_location.display().to_string(), &options);
+        assert!(matches!(results, Err(FsEventsError::FileTooLarge(_, 10))));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_not_file() {
+        let results = PageReader::from_path("/this/path/does/not/exist");
+        assert!(matches!(results, Err(FsEventsError::NotAFile(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "filesystem")]
+    fn test_page_reader_max_size() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2/0000000000027d79");
+        let path = test_location.display().to_string();
+        let options = ParserOptions::new().max_file_size(10);
+        let results = PageReader::from_path_with_options(&path, &options);
+        assert!(matches!(results, Err(FsEventsError::FileTooLarge(_, 10))));
+
+        let options = ParserOptions::new().max_file_size(100000);
+        let reader = PageReader::from_path_with_options(&path, &options).unwrap();
+        let count: usize = reader.map(|records| records.unwrap().len()).sum();
+        assert!(count == 736);
+    }
+}
@@ -0,0 +1,160 @@
+//! Follow an fseventsd directory and parse each FsEvent file once fseventsd finishes it
+//!
+//! fseventsd adds records to the newest FsEvent file in its directory, and starts a new file (named
+//! after the next event ID) when the current one is full or the volume is unmounted. A file's gzip
+//! data is incomplete until it is closed, so `DirectoryTail` polls the directory and returns the
+//! records of each new file once its data is complete or a newer file has started. This logs the
+//! records in near real time without the FSEvents API or kernel hooks.
+
+use crate::{
+    error::FsEventsError,
+    fsevents::FsEvents,
+    options::ParserOptions,
+    parser::{fseventsd, parse_file_list},
+    report::ParseReport,
+};
+use std::{collections::HashSet, path::Path};
+
+/// Follows an fseventsd directory for finished FsEvent files
+#[derive(Debug)]
+pub struct DirectoryTail {
+    directory: String,         // fseventsd directory being followed
+    options: ParserOptions,    // Options used to parse each file
+    finished: HashSet<String>, // FsEvent files that have been returned or skipped
+}
+
+impl DirectoryTail {
+    /// Follow a directory. Files that are already finished are skipped, so only the records of
+    /// files finished after this are returned
+    pub fn new(directory: &str, options: ParserOptions) -> Result<DirectoryTail, FsEventsError> {
+        let mut tail = DirectoryTail::from_start(directory, options)?;
+        tail.poll()?;
+        Ok(tail)
+    }
+
+    /// Follow a directory, starting with the files that are already finished
+    pub fn from_start(
+        directory: &str,
+        options: ParserOptions,
+    ) -> Result<DirectoryTail, FsEventsError> {
+        // Fail early if the directory cannot be listed
+        fseventsd(directory)?;
+        Ok(DirectoryTail {
+            directory: directory.to_string(),
+            options,
+            finished: HashSet::new(),
+        })
+    }
+
+    /// Check for FsEvent files that have finished since the last poll, and parse them in event ID
+    /// order. The newest file is left until its data is complete (or a newer file starts), so it
+    /// is not returned while fseventsd is still writing it
+    pub fn poll(&mut self) -> Result<(Vec<FsEvents>, ParseReport), FsEventsError> {
+        let mut files = fseventsd(&self.directory)?;
+        // File names are the event IDs in fixed width hex, so name order is event ID order
+        files.sort_by_key(|file| file_name(file));
+        let newest = files.last().cloned();
+
+        let mut records = Vec::new();
+        let mut report = ParseReport::default();
+        for file in files {
+            if self.finished.contains(&file) {
+                continue;
+            }
+            let mut file_report = ParseReport::default();
+            let results = parse_file_list(
+                &self.directory,
+                std::slice::from_ref(&file),
+                &self.options,
+                &mut file_report,
+            );
+            let is_newest = newest.as_ref() == Some(&file);
+            let is_complete = results.is_ok()
+                && file_report.truncated_files.is_empty()
+                && file_report.skipped_files.is_empty();
+            if is_newest && !is_complete {
+                continue;
+            }
+            // Older files will not change, so they are returned even if they are incomplete
+            self.finished.insert(file);
+            records.append(&mut results?);
+            report.files_parsed += file_report.files_parsed;
+            report.skipped_files.append(&mut file_report.skipped_files);
+            report
+                .truncated_files
+                .append(&mut file_report.truncated_files);
+            report.skipped_pages.append(&mut file_report.skipped_pages);
+            report
+                .resynced_pages
+                .append(&mut file_report.resynced_pages);
+        }
+        Ok((records, report))
+    }
+}
+
+/// Get the file name of an FsEvent file path
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DirectoryTail;
+    use crate::{options::ParserOptions, test_util::test_data};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn dls3_data() -> Vec<u8> {
+        fs::read(test_data("DLS3/0000000000030012")).unwrap()
+    }
+
+    #[test]
+    fn test_directory_tail() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        let data = dls3_data();
+        fs::write(root.join("0000000000030012"), &data).unwrap();
+        // fseventsd is still writing the newest file
+        fs::write(root.join("0000000000030030"), &data[..data.len() / 2]).unwrap();
+
+        let directory = root.display().to_string();
+        let mut tail = DirectoryTail::new(&directory, ParserOptions::new()).unwrap();
+        let (records, _) = tail.poll().unwrap();
+        assert!(records.is_empty());
+
+        fs::write(root.join("0000000000030030"), &data).unwrap();
+        let (records, report) = tail.poll().unwrap();
+        assert!(records.len() == 5);
+        assert!(records[0].source_file.ends_with("0000000000030030"));
+        assert!(report.files_parsed == 1);
+        assert!(tail.poll().unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_directory_tail_from_start() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        let data = dls3_data();
+        fs::write(root.join("0000000000030012"), &data[..data.len() / 2]).unwrap();
+        fs::write(root.join("fseventsd-uuid"), "5B3A1C2E").unwrap();
+
+        let directory = root.display().to_string();
+        let mut tail = DirectoryTail::from_start(&directory, ParserOptions::new()).unwrap();
+        assert!(tail.poll().unwrap().0.is_empty());
+
+        // A newer file started, so the truncated file will not change
+        fs::write(root.join("0000000000030030"), &data[..data.len() / 2]).unwrap();
+        let (records, report) = tail.poll().unwrap();
+        assert!(records
+            .iter()
+            .all(|record| record.source_file.ends_with("0000000000030012")));
+        assert!(report.truncated_files.len() == 1);
+
+        // The directory no longer exists
+        temp.close().unwrap();
+        assert!(DirectoryTail::new(&directory, ParserOptions::new()).is_err());
+    }
+}
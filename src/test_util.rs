@@ -0,0 +1,23 @@
+//! Fixtures shared by the unit tests
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Get the path to a file or directory in tests/test_data
+pub(crate) fn test_data(name: &str) -> PathBuf {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data");
+    test_location.push(name);
+    test_location
+}
+
+/// Copy a file from tests/test_data into a directory, creating the directory if needed
+/// Returns the path to the copy, which keeps the file name
+pub(crate) fn copy_test_data(name: &str, directory: &Path) -> PathBuf {
+    fs::create_dir_all(directory).unwrap();
+    let target = directory.join(Path::new(name).file_name().unwrap());
+    fs::copy(test_data(name), &target).unwrap();
+    target
+}
@@ -0,0 +1,160 @@
+//! Built-in triage rules for a fast first look at FsEvent records
+//!
+//! Each rule is a case insensitive regex (macOS volumes are usually case insensitive) matched
+//! against the record path. Rules cover common persistence, staging, and credential locations.
+//! Records that match are tagged with the rule names in `triage_rule`.
+
+use crate::fsevents::FsEvents;
+use regex::Regex;
+
+/// A named path rule
+#[derive(Debug, Clone, Copy)]
+pub struct TriageRule {
+    pub name: &'static str,    // Name written to the record's triage_rule
+    pub pattern: &'static str, // Regex matched against the record path
+}
+
+/// Built-in rules, in the order their names are written
+pub const TRIAGE_RULES: [TriageRule; 6] = [
+    TriageRule {
+        name: "LaunchAgent",
+        pattern: r"/Library/LaunchAgents/",
+    },
+    TriageRule {
+        name: "LaunchDaemon",
+        pattern: r"/Library/LaunchDaemons/",
+    },
+    // Scripts, binaries, and app bundles in /tmp, /var/tmp, or /Users/Shared
+    TriageRule {
+        name: "TmpExecutable",
+        pattern: r"^/(private/)?((var/)?tmp|Users/Shared)/.*\.(sh|command|py|pl|rb|js|scpt|dylib|so|bin|app|pkg|dmg)(/|$)",
+    },
+    // Downloads folders and in progress Safari and Chrome downloads
+    TriageRule {
+        name: "BrowserDownload",
+        pattern: r"^/Users/[^/]+/Downloads/|\.(download|crdownload)(/|$)",
+    },
+    TriageRule {
+        name: "SshKey",
+        pattern: r"/\.ssh(/|$)|^/(private/)?etc/ssh/",
+    },
+    // Login items, startup items, login hooks, cron, periodic, and emond
+    TriageRule {
+        name: "Persistence",
+        pattern: r"/Library/StartupItems/|com\.apple\.(loginitems|loginwindow)\.plist$|/com\.apple\.backgroundtaskmanagementagent/|/Library/Security/SecurityAgentPlugins/|^/(private/)?(var/at/tabs|etc/(periodic|emond\.d))/",
+    },
+];
+
+/// Compiled triage rules
+#[derive(Debug, Clone)]
+pub struct Triage {
+    rules: Vec<(&'static str, Regex)>, // Rule name and compiled pattern
+}
+
+impl Triage {
+    /// Compile the built-in rules
+    pub fn new() -> Triage {
+        let rules = TRIAGE_RULES
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&format!("(?i){}", rule.pattern))
+                    .expect("built-in triage rules are valid regexes");
+                (rule.name, pattern)
+            })
+            .collect();
+        Triage { rules }
+    }
+
+    /// Get the names of the rules that match a path
+    pub fn matching_rules(&self, path: &str) -> Vec<&'static str> {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| pattern.is_match(path))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// Set the record's triage_rule to the comma separated names of the matching rules
+    /// Returns false if no rule matched
+    pub fn tag(&self, fsevent: &mut FsEvents) -> bool {
+        let rules = self.matching_rules(&fsevent.path);
+        fsevent.triage_rule = rules.join(",");
+        !rules.is_empty()
+    }
+}
+
+impl Default for Triage {
+    fn default() -> Self {
+        Triage::new()
+    }
+}
+
+/// Keep only the records that match a built-in rule, tagged with the rule names
+/// Returns the number of records removed
+pub fn triage_fsevents(fsevents: &mut Vec<FsEvents>) -> usize {
+    let original_count = fsevents.len();
+    let triage = Triage::new();
+    fsevents.retain_mut(|fsevent| triage.tag(fsevent));
+    original_count - fsevents.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{triage_fsevents, Triage};
+    use crate::parser::parse_bytes;
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn test_matching_rules() {
+        let triage = Triage::new();
+        let tests = [
+            ("/Library/LaunchAgents/com.evil.plist", vec!["LaunchAgent"]),
+            (
+                "/Users/bob/Library/LaunchAgents/com.evil.plist",
+                vec!["LaunchAgent"],
+            ),
+            (
+                "/Library/LaunchDaemons/com.evil.plist",
+                vec!["LaunchDaemon"],
+            ),
+            ("/private/tmp/stage/payload.sh", vec!["TmpExecutable"]),
+            (
+                "/tmp/Installer.app/Contents/MacOS/run",
+                vec!["TmpExecutable"],
+            ),
+            ("/private/var/tmp/notes.txt", vec![]),
+            (
+                "/Users/bob/Downloads/invoice.pdf.download/invoice.pdf",
+                vec!["BrowserDownload"],
+            ),
+            ("/Users/bob/Downloads/Setup.DMG", vec!["BrowserDownload"]),
+            ("/Users/bob/.ssh/authorized_keys", vec!["SshKey"]),
+            ("/private/etc/ssh/sshd_config", vec!["SshKey"]),
+            (
+                "/Users/bob/Library/Preferences/com.apple.loginitems.plist",
+                vec!["Persistence"],
+            ),
+            ("/private/var/at/tabs/root", vec!["Persistence"]),
+            ("/Users/bob/Documents/report.docx", vec![]),
+        ];
+        for (path, rules) in tests {
+            assert!(triage.matching_rules(path) == rules, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_triage_fsevents() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let mut fsevents = parse_bytes(&fs::read(test_location).unwrap()).unwrap();
+
+        assert!(triage_fsevents(&mut fsevents) == 1);
+        assert!(fsevents.len() == 4);
+        assert!(fsevents[0].triage_rule == "BrowserDownload");
+        let agent = fsevents
+            .iter()
+            .find(|fsevent| fsevent.path == "/Library/LaunchAgents/com.evil.plist")
+            .unwrap();
+        assert!(agent.triage_rule == "LaunchAgent");
+    }
+}
@@ -0,0 +1,311 @@
+//! Check FsEvent files for damage without fully parsing them
+//!
+//! Validation decompresses each file in a directory and walks its pages, checking the gzip data,
+//! page signatures, page sizes, and that every record can be read. Records in a page are sorted by
+//! path and the pages of a file can cover overlapping event IDs, so event IDs are only expected to
+//! increase from one file to the next. The report lists every problem found so a collection can be
+//! triaged before it is parsed.
+
+use crate::{compression::Decoder, error::FsEventsError, fsevents::FsEvents, parser::fseventsd};
+use log::warn;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+};
+
+const HEADER_SIZE: usize = 12;
+
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct ValidationReport {
+    pub directory: String,          // Directory that was validated
+    pub files: Vec<FileValidation>, // Validation results for each FsEvent file
+}
+
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct FileValidation {
+    pub path: String,                 // Path to the FsEvent file
+    pub compressed: bool,             // File data is compressed
+    pub decompressed_size: u64,       // Size of the data that could be decompressed
+    pub pages: u32,                   // Number of pages found
+    pub records: usize,               // Number of records that could be read
+    pub min_event_id: u64,            // Lowest event ID in the file
+    pub max_event_id: u64,            // Highest event ID in the file
+    pub issues: Vec<ValidationIssue>, // Problems found in the file
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// File could not be opened or read
+    Unreadable { reason: String },
+    /// Compressed data is corrupt or ended early, only the data before the error was checked
+    Decompress { reason: String },
+    /// Page does not start with a DLS1/DLS2/DLS3 signature, the rest of the file cannot be checked
+    InvalidSignature { offset: u64, signature: u32 },
+    /// Page header stream size is smaller than the header or larger than the remaining data
+    PageSize {
+        offset: u64,
+        stream_size: u32,
+        available: u64,
+    },
+    /// Records in the page could not be read
+    InvalidRecords { page: u32, offset: u64 },
+    /// File has event IDs lower than the highest event ID of the file before it
+    EventIdDecreased { event_id: u64, previous: u64 },
+}
+
+impl ValidationReport {
+    /// Check if no problems were found in any file
+    pub fn is_valid(&self) -> bool {
+        self.files.iter().all(FileValidation::is_valid)
+    }
+}
+
+impl FileValidation {
+    /// Check if no problems were found in the file
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Validate every FsEvent file in a directory
+pub fn validate_fseventsd(directory: &str) -> Result<ValidationReport, FsEventsError> {
+    let mut files = fseventsd(directory)?;
+    files.sort();
+
+    let mut report = ValidationReport {
+        directory: directory.to_string(),
+        files: Vec::new(),
+    };
+    // File names are the event IDs in fixed width hex, so files are in event ID order
+    let mut previous: Option<u64> = None;
+    for file in files {
+        let mut validation = validate_file(&file);
+        if validation.records != 0 {
+            if let Some(previous) = previous.filter(|id| validation.min_event_id < *id) {
+                validation.issues.push(ValidationIssue::EventIdDecreased {
+                    event_id: validation.min_event_id,
+                    previous,
+                });
+            }
+            previous = Some(validation.max_event_id);
+        }
+        report.files.push(validation);
+    }
+    Ok(report)
+}
+
+/// Validate a single FsEvent file
+pub fn validate_file(path: &str) -> FileValidation {
+    let mut validation = FileValidation {
+        path: path.to_string(),
+        ..Default::default()
+    };
+    let file = match File::open(path) {
+        Ok(result) => result,
+        Err(err) => {
+            warn!(
+                "Could not open FsEvent file {} for validation: {}",
+                path, err
+            );
+            validation.issues.push(ValidationIssue::Unreadable {
+                reason: err.to_string(),
+            });
+            return validation;
+        }
+    };
+    let mut decoder = match Decoder::detect(BufReader::new(file)) {
+        Ok(result) => result,
+        Err(err) => {
+            validation.issues.push(ValidationIssue::Unreadable {
+                reason: err.to_string(),
+            });
+            return validation;
+        }
+    };
+    validation.compressed = decoder.is_compressed();
+
+    let mut data = Vec::new();
+    if let Err(err) = decoder.read_to_end(&mut data) {
+        validation.issues.push(ValidationIssue::Decompress {
+            reason: err.to_string(),
+        });
+    }
+    validation.decompressed_size = data.len() as u64;
+    validate_pages(&data, &mut validation);
+    validation
+}
+
+/// Walk the pages in decompressed data and check each header and record
+fn validate_pages(data: &[u8], validation: &mut FileValidation) {
+    let mut offset = 0;
+    while offset < data.len() {
+        let page_offset = offset as u64;
+        let available = data.len() - offset;
+        if available < HEADER_SIZE {
+            validation.issues.push(ValidationIssue::PageSize {
+                offset: page_offset,
+                stream_size: 0,
+                available: available as u64,
+            });
+            return;
+        }
+
+        let header = &data[offset..offset + HEADER_SIZE];
+        let signature = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        if FsEvents::check_signature(signature).is_err() {
+            validation.issues.push(ValidationIssue::InvalidSignature {
+                offset: page_offset,
+                signature,
+            });
+            return;
+        }
+
+        let stream_size = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        let page_size = stream_size as usize;
+        let size_valid = (HEADER_SIZE..=available).contains(&page_size);
+        if !size_valid {
+            validation.issues.push(ValidationIssue::PageSize {
+                offset: page_offset,
+                stream_size,
+                available: available as u64,
+            });
+        }
+        // If the size is wrong the next page cannot be found, check the records in the data left
+        let page_end = if size_valid {
+            offset + page_size
+        } else {
+            data.len()
+        };
+
+        let page = validation.pages;
+        validation.pages += 1;
+        let page_data = &data[offset..page_end];
+        let records = match FsEvents::fsevents_page(page_data, page, page_offset) {
+            Ok((_, records)) => records,
+            Err(_) => {
+                if size_valid {
+                    validation.issues.push(ValidationIssue::InvalidRecords {
+                        page,
+                        offset: page_offset,
+                    });
+                }
+                FsEvents::fsevents_partial_page(page_data, page, page_offset)
+            }
+        };
+
+        for fsevent in &records {
+            if validation.records == 0 {
+                validation.min_event_id = fsevent.event_id;
+                validation.max_event_id = fsevent.event_id;
+            }
+            validation.min_event_id = validation.min_event_id.min(fsevent.event_id);
+            validation.max_event_id = validation.max_event_id.max(fsevent.event_id);
+            validation.records += 1;
+        }
+        offset = page_end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_file, validate_fseventsd, ValidationIssue};
+    use crate::{parser::decompress, test_util::test_data};
+    use std::{fs, path::PathBuf};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_validate_fseventsd() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3");
+        let report = validate_fseventsd(&test_location.display().to_string()).unwrap();
+        assert!(report.is_valid());
+        assert!(report.files.len() == 1);
+
+        let file = &report.files[0];
+        assert!(file.compressed);
+        assert!(file.decompressed_size == 299);
+        assert!(file.pages == 2);
+        assert!(file.records == 5);
+        assert!(file.min_event_id == 0x30000);
+        assert!(file.max_event_id == 0x30011);
+    }
+
+    #[test]
+    fn test_validate_dls2() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS2");
+        let report = validate_fseventsd(&test_location.display().to_string()).unwrap();
+        assert!(report.is_valid());
+        assert!(report.files.len() == 2);
+        // Pages overlap and records are sorted by path, so the lowest ID is not the first record
+        assert!(report.files[0].min_event_id == 0x18354);
+        assert!(report.files[0].max_event_id == 0x2469b);
+
+        let file = validate_file(&test_location.join("0000000000027d79").display().to_string());
+        assert!(file.is_valid());
+        assert!(file.records == 736);
+        assert!(file.min_event_id == 0x246a1);
+        assert!(file.max_event_id == 0x27d78);
+    }
+
+    #[test]
+    fn test_validate_malformed() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/Malformed");
+        let report = validate_fseventsd(&test_location.display().to_string()).unwrap();
+        assert!(!report.is_valid());
+        assert!(matches!(
+            report.files[0].issues[0],
+            ValidationIssue::PageSize {
+                offset: 0,
+                stream_size: 78970,
+                available: 45
+            }
+        ));
+    }
+
+    #[test]
+    fn test_validate_damaged_files() {
+        let temp = tempdir().unwrap();
+        let test_location = temp.path().join("validate");
+        fs::create_dir_all(&test_location).unwrap();
+
+        let compressed = fs::read(test_data("DLS2/0000000000027d79")).unwrap();
+        fs::write(
+            test_location.join("0000000000027d79"),
+            &compressed[..compressed.len() / 2],
+        )
+        .unwrap();
+
+        let mut data =
+            decompress(&test_data("DLS3/0000000000030012").display().to_string()).unwrap();
+        // Lower the event ID of the second record below the file before it, and corrupt the
+        // second page signature
+        data[68 + 32..68 + 40].copy_from_slice(&0u64.to_le_bytes());
+        data[172] = b'9';
+        fs::write(test_location.join("0000000000030012"), &data).unwrap();
+
+        let report = validate_fseventsd(&test_location.display().to_string()).unwrap();
+        assert!(report.files.len() == 2);
+
+        let truncated = &report.files[0];
+        assert!(matches!(
+            truncated.issues[0],
+            ValidationIssue::Decompress { .. }
+        ));
+
+        let damaged = &report.files[1];
+        assert!(!damaged.compressed);
+        assert!(damaged.pages == 1);
+        assert!(damaged.records == 3);
+        assert!(matches!(
+            damaged.issues[0],
+            ValidationIssue::InvalidSignature { offset: 172, .. }
+        ));
+        assert!(matches!(
+            damaged.issues[1],
+            ValidationIssue::EventIdDecreased { event_id: 0, .. }
+        ));
+    }
+}
@@ -0,0 +1,34 @@
+//! JavaScript bindings for WebAssembly builds
+//!
+//! Build for `wasm32-unknown-unknown` with `--no-default-features --features wasm` and generate the
+//! JavaScript glue with `wasm-bindgen`. The page reads a FsEvent file (ex: from a file input) and
+//! passes its bytes to `parseBytes`, so the evidence is parsed in the browser and never uploaded.
+
+use crate::parser::parse_bytes;
+use wasm_bindgen::prelude::*;
+
+/// Parse the bytes of a FsEvent file, compressed or already decompressed, and return the records
+/// as a JSON array
+#[wasm_bindgen(js_name = parseBytes)]
+pub fn parse_bytes_json(data: &[u8]) -> Result<String, JsError> {
+    let fsevents_data = parse_bytes(data)?;
+    Ok(serde_json::to_string(&fsevents_data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_bytes_json;
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn test_parse_bytes_json() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let data = fs::read(test_location).unwrap();
+
+        let json = parse_bytes_json(&data).unwrap();
+        let records: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        assert!(records.len() == 5);
+        assert!(records[0]["path"] == "/Users/bob/Downloads/invoice.pdf");
+    }
+}
@@ -0,0 +1,384 @@
+//! Record live file system events with the macOS FSEvents API
+//!
+//! Events from an FSEventStream are converted to the same records as the fseventsd files, so
+//! live and historical activity can be output and compared together. The FSEvents API uses its own
+//! flag values, `api_flags` maps them to the flags stored on disk. Event IDs come from the same
+//! counter as the fseventsd files, so `since` can replay events that are still in the logs.
+//!
+//! `watch_paths` blocks and passes each record to a callback. `LiveEvents` runs the stream on its
+//! own thread and returns the records as an iterator, so an embedder can parse the fseventsd files
+//! and then keep monitoring without giving up its thread.
+
+use crate::{flags::FsEventFlags, fsevents::FsEvents};
+
+/// Source file of records from the FSEvents API
+pub const LIVE_SOURCE: &str = "live";
+
+// FSEventStreamEventFlags values from FSEvents.h
+const API_MOUNT: u32 = 0x40;
+const API_UNMOUNT: u32 = 0x80;
+const API_ITEM_CREATED: u32 = 0x100;
+const API_ITEM_REMOVED: u32 = 0x200;
+const API_ITEM_INODE_META_MOD: u32 = 0x400;
+const API_ITEM_RENAMED: u32 = 0x800;
+const API_ITEM_MODIFIED: u32 = 0x1000;
+const API_ITEM_FINDER_INFO_MOD: u32 = 0x2000;
+const API_ITEM_CHANGE_OWNER: u32 = 0x4000;
+const API_ITEM_XATTR_MOD: u32 = 0x8000;
+const API_ITEM_IS_FILE: u32 = 0x10000;
+const API_ITEM_IS_DIR: u32 = 0x20000;
+const API_ITEM_IS_SYMLINK: u32 = 0x40000;
+const API_ITEM_IS_HARDLINK: u32 = 0x100000;
+const API_ITEM_IS_LAST_HARDLINK: u32 = 0x200000;
+const API_ITEM_CLONED: u32 = 0x400000;
+/// Marks the end of replayed events, not a file system change
+pub const API_HISTORY_DONE: u32 = 0x10;
+
+/// FSEvents API flags and the record flags they map to
+const API_FLAGS: [(u32, FsEventFlags); 15] = [
+    (API_MOUNT, FsEventFlags::MOUNT),
+    (API_UNMOUNT, FsEventFlags::UNMOUNT),
+    (API_ITEM_REMOVED, FsEventFlags::REMOVED),
+    (
+        API_ITEM_INODE_META_MOD,
+        FsEventFlags::INODE_METADATA_MODIFIED,
+    ),
+    (API_ITEM_RENAMED, FsEventFlags::RENAMED),
+    (API_ITEM_MODIFIED, FsEventFlags::MODIFIED),
+    (API_ITEM_FINDER_INFO_MOD, FsEventFlags::FINDER_INFO_MODIFIED),
+    (API_ITEM_CHANGE_OWNER, FsEventFlags::PERMISSION_CHANGED),
+    (
+        API_ITEM_XATTR_MOD,
+        FsEventFlags::EXTENDED_ATTRIBUTE_MODIFIED,
+    ),
+    (API_ITEM_IS_FILE, FsEventFlags::IS_FILE),
+    (API_ITEM_IS_DIR, FsEventFlags::IS_DIRECTORY),
+    (API_ITEM_IS_SYMLINK, FsEventFlags::IS_SYMBOLIC_LINK),
+    (API_ITEM_IS_HARDLINK, FsEventFlags::IS_HARD_LINK),
+    (
+        API_ITEM_IS_LAST_HARDLINK,
+        FsEventFlags::LAST_HARD_LINK_REMOVED,
+    ),
+    (API_ITEM_CLONED, FsEventFlags::ITEM_CLONED),
+];
+
+/// Map FSEvents API flags to the flags stored in fseventsd records
+/// Created directories use `DirectoryCreated`, like the fseventsd files
+pub fn api_flags(flags: u32) -> FsEventFlags {
+    let mut record_flags = FsEventFlags::empty();
+    for (api_flag, record_flag) in API_FLAGS {
+        if flags & api_flag != 0 {
+            record_flags |= record_flag;
+        }
+    }
+    if flags & API_ITEM_CREATED != 0 {
+        if flags & API_ITEM_IS_DIR != 0 {
+            record_flags |= FsEventFlags::DIRECTORY_CREATED;
+        } else {
+            record_flags |= FsEventFlags::CREATED;
+        }
+    }
+    record_flags
+}
+
+/// Convert an FSEvents API event to a record
+pub fn live_record(path: &str, flags: u32, event_id: u64) -> FsEvents {
+    let record_flags = api_flags(flags);
+    FsEvents {
+        flags: record_flags.to_string(),
+        raw_flags: record_flags.bits(),
+        path: path.to_string(),
+        event_id,
+        source_file: String::from(LIVE_SOURCE),
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "macos")]
+/// Watch paths with an FSEventStream and pass each event to the callback as a record
+/// Replays events after `since` (an event ID) before live events, if provided. `latency` is the
+/// number of seconds the API waits to group events. Blocks until the callback returns false
+pub fn watch_paths<F: FnMut(FsEvents) -> bool>(
+    paths: &[String],
+    since: Option<u64>,
+    latency: f64,
+    callback: F,
+) -> Result<(), crate::error::FsEventsError> {
+    run_stream(paths, since, latency, callback, |_| {})
+}
+
+#[cfg(target_os = "macos")]
+/// Run an FSEventStream on the current thread until the callback returns false or the run loop is
+/// stopped. `started` is called with the thread's run loop just before events are delivered
+fn run_stream<F: FnMut(FsEvents) -> bool, S: FnOnce(fsevent_sys::core_foundation::CFRunLoopRef)>(
+    paths: &[String],
+    since: Option<u64>,
+    latency: f64,
+    mut callback: F,
+    started: S,
+) -> Result<(), crate::error::FsEventsError> {
+    use crate::error::FsEventsError;
+    use fsevent_sys::{self as fsevent, core_foundation as cf};
+    use std::{ffi::CString, os::raw::c_void};
+
+    let mut watch_callback: &mut dyn FnMut(FsEvents) -> bool = &mut callback;
+    unsafe {
+        let watched =
+            cf::CFArrayCreateMutable(cf::kCFAllocatorDefault, 0, &cf::kCFTypeArrayCallBacks);
+        for path in paths {
+            let Ok(c_path) = CString::new(path.as_str()) else {
+                cf::CFRelease(watched);
+                return Err(FsEventsError::Watch(format!("invalid path {}", path)));
+            };
+            let cf_path = cf::CFStringCreateWithCString(
+                cf::kCFAllocatorDefault,
+                c_path.as_ptr(),
+                cf::kCFStringEncodingUTF8,
+            );
+            cf::CFArrayAppendValue(watched, cf_path);
+            cf::CFRelease(cf_path);
+        }
+
+        let context = fsevent::FSEventStreamContext {
+            version: 0,
+            info: &mut watch_callback as *mut &mut dyn FnMut(FsEvents) -> bool as *mut c_void,
+            retain: None,
+            release: None,
+            copy_description: None,
+        };
+        let stream = fsevent::FSEventStreamCreate(
+            cf::kCFAllocatorDefault,
+            stream_callback,
+            &context,
+            watched,
+            since.unwrap_or(fsevent::kFSEventStreamEventIdSinceNow),
+            latency,
+            fsevent::kFSEventStreamCreateFlagFileEvents | fsevent::kFSEventStreamCreateFlagNoDefer,
+        );
+        cf::CFRelease(watched);
+        if stream.is_null() {
+            return Err(FsEventsError::Watch(String::from(
+                "could not create an FSEventStream",
+            )));
+        }
+
+        fsevent::FSEventStreamScheduleWithRunLoop(
+            stream,
+            cf::CFRunLoopGetCurrent(),
+            cf::kCFRunLoopDefaultMode,
+        );
+        let is_started = fsevent::FSEventStreamStart(stream) != 0;
+        if is_started {
+            started(cf::CFRunLoopGetCurrent());
+            cf::CFRunLoopRun();
+            fsevent::FSEventStreamStop(stream);
+        }
+        fsevent::FSEventStreamInvalidate(stream);
+        fsevent::FSEventStreamRelease(stream);
+        if !is_started {
+            return Err(FsEventsError::Watch(String::from(
+                "could not start the FSEventStream",
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+/// Run loop of a stream thread, which can be stopped from another thread
+struct RunLoop(fsevent_sys::core_foundation::CFRunLoopRef);
+
+#[cfg(target_os = "macos")]
+// CFRunLoopStop and CFRunLoopIsWaiting are thread safe
+unsafe impl Send for RunLoop {}
+
+#[cfg(target_os = "macos")]
+/// Thread running an FSEventStream, returns the stream's result
+type StreamThread = std::thread::JoinHandle<Result<(), crate::error::FsEventsError>>;
+
+#[cfg(target_os = "macos")]
+/// Records from an FSEventStream running on its own thread
+/// Iterating blocks until the next event, use `try_next` to poll. The stream is stopped when
+/// `stop` is called or the `LiveEvents` is dropped
+pub struct LiveEvents {
+    receiver: std::sync::mpsc::Receiver<FsEvents>, // Records sent by the stream thread
+    run_loop: RunLoop,                             // Run loop of the stream thread
+    thread: Option<StreamThread>,                  // Stream thread, None once it is stopped
+}
+
+#[cfg(target_os = "macos")]
+impl LiveEvents {
+    /// Start watching paths on a new thread. Replays events after `since` (an event ID) before
+    /// live events, if provided. `latency` is the number of seconds the API waits to group events
+    pub fn start(
+        paths: &[String],
+        since: Option<u64>,
+        latency: f64,
+    ) -> Result<LiveEvents, crate::error::FsEventsError> {
+        use crate::error::FsEventsError;
+        use std::{sync::mpsc::channel, thread};
+
+        let (sender, receiver) = channel();
+        let (run_loop_sender, run_loop_receiver) = channel();
+        let paths = paths.to_vec();
+        let thread = thread::spawn(move || {
+            run_stream(
+                &paths,
+                since,
+                latency,
+                |record| sender.send(record).is_ok(),
+                |run_loop| {
+                    let _ = run_loop_sender.send(RunLoop(run_loop));
+                },
+            )
+        });
+        match run_loop_receiver.recv() {
+            Ok(run_loop) => Ok(LiveEvents {
+                receiver,
+                run_loop,
+                thread: Some(thread),
+            }),
+            // The stream did not start, so the thread returned an error
+            Err(_) => match thread.join() {
+                Ok(Err(err)) => Err(err),
+                _ => Err(FsEventsError::Watch(String::from(
+                    "could not start the FSEventStream",
+                ))),
+            },
+        }
+    }
+
+    /// Get the next record if one is waiting, without blocking
+    pub fn try_next(&mut self) -> Option<FsEvents> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Stop the stream and wait for its thread to finish
+    pub fn stop(mut self) -> Result<(), crate::error::FsEventsError> {
+        self.stop_thread()
+    }
+
+    /// Stop the run loop of the stream thread and join it
+    fn stop_thread(&mut self) -> Result<(), crate::error::FsEventsError> {
+        use crate::error::FsEventsError;
+        use fsevent_sys::core_foundation as cf;
+
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        // A stop before the run loop is waiting for events would be lost
+        while !thread.is_finished() && unsafe { cf::CFRunLoopIsWaiting(self.run_loop.0) } == 0 {
+            std::thread::yield_now();
+        }
+        unsafe { cf::CFRunLoopStop(self.run_loop.0) };
+        thread
+            .join()
+            .map_err(|_| FsEventsError::Watch(String::from("FSEventStream thread panicked")))?
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Iterator for LiveEvents {
+    type Item = FsEvents;
+
+    /// Wait for the next record. Returns None once the stream has stopped
+    fn next(&mut self) -> Option<FsEvents> {
+        self.receiver.recv().ok()
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for LiveEvents {
+    fn drop(&mut self) {
+        let _ = self.stop_thread();
+    }
+}
+
+#[cfg(target_os = "macos")]
+/// Called by the run loop with each batch of events
+extern "C" fn stream_callback(
+    _stream: fsevent_sys::FSEventStreamRef,
+    info: *mut std::os::raw::c_void,
+    count: usize,
+    paths: *mut std::os::raw::c_void,
+    flags: *const u32,
+    ids: *const u64,
+) {
+    use fsevent_sys::core_foundation as cf;
+    use std::{ffi::CStr, os::raw::c_char};
+
+    let callback = unsafe { &mut *(info as *mut &mut dyn FnMut(FsEvents) -> bool) };
+    let paths = paths as *const *const c_char;
+    for index in 0..count {
+        let (path, event_flags, event_id) = unsafe {
+            (
+                CStr::from_ptr(*paths.add(index)).to_string_lossy(),
+                *flags.add(index),
+                *ids.add(index),
+            )
+        };
+        if event_flags & API_HISTORY_DONE != 0 {
+            continue;
+        }
+        if !callback(live_record(&path, event_flags, event_id)) {
+            unsafe { cf::CFRunLoopStop(cf::CFRunLoopGetCurrent()) };
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{api_flags, live_record, LIVE_SOURCE};
+    use crate::flags::FsEventFlags;
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_live_events() {
+        use super::LiveEvents;
+        use std::fs;
+        use tempfile::tempdir;
+
+        let temp = tempdir().unwrap();
+        // The temp directory is a symlink under /private, which is the path events are reported for
+        let root = temp.path().canonicalize().unwrap();
+        let mut events = LiveEvents::start(&[root.display().to_string()], None, 0.1).unwrap();
+        let path = root.join("created.txt");
+        fs::write(&path, "live").unwrap();
+
+        let record = events
+            .find(|record| record.path == path.display().to_string())
+            .unwrap();
+        assert!(record.event_flags().contains(FsEventFlags::CREATED));
+        assert!(record.source_file == LIVE_SOURCE);
+        events.stop().unwrap();
+    }
+
+    #[test]
+    fn test_api_flags() {
+        // ItemCreated | ItemIsFile
+        let flags = api_flags(0x10100);
+        assert!(flags == FsEventFlags::CREATED | FsEventFlags::IS_FILE);
+
+        // ItemCreated | ItemIsDir
+        let flags = api_flags(0x20100);
+        assert!(flags == FsEventFlags::DIRECTORY_CREATED | FsEventFlags::IS_DIRECTORY);
+
+        // ItemRenamed | ItemModified | ItemXattrMod | ItemIsFile
+        let flags = api_flags(0x19800);
+        assert!(flags.to_string() == "Renamed,Modified,ExtendedAttributeModified,IsFile");
+
+        assert!(api_flags(0).is_empty());
+    }
+
+    #[test]
+    fn test_live_record() {
+        let record = live_record("/Users/bob/Downloads/invoice.pdf", 0x10200, 196700);
+        assert!(record.path == "/Users/bob/Downloads/invoice.pdf");
+        assert!(record.flags == "Removed,IsFile");
+        assert!(record.event_flags() == FsEventFlags::REMOVED | FsEventFlags::IS_FILE);
+        assert!(record.event_id == 196700);
+        assert!(record.source_file == LIVE_SOURCE);
+        assert!(record.node == 0);
+    }
+}
@@ -0,0 +1,221 @@
+//! Match records against a watchlist of IOCs (ex: known malware paths and file names)
+//!
+//! A watchlist has one entry per line, blank lines and lines starting with `#` are skipped:
+//! - Entries with a `/` match the whole record path (ex: `/Users/*/Library/LaunchAgents/com.evil.plist`),
+//!   `*` does not match `/` and `**` matches any number of directories
+//! - Other entries match the file name of the record path (ex: `evil.dylib` or `*.command`)
+//!
+//! Matching is case insensitive, like most macOS volumes.
+
+use crate::{error::FsEventsError, fsevents::FsEvents};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Serialize;
+use std::path::Path;
+
+/// Compiled watchlist entries
+#[derive(Debug, Clone)]
+pub struct Watchlist {
+    entries: Vec<String>,     // Every entry, in the order listed
+    paths: GlobSet,           // Entries with a `/`, matched against the whole path
+    path_entries: Vec<usize>, // Index in entries of each path glob
+    names: GlobSet,           // Other entries, matched against the file name
+    name_entries: Vec<usize>, // Index in entries of each name glob
+}
+
+/// A record that matched the watchlist
+#[derive(Debug, Serialize)]
+pub struct WatchlistHit<'a> {
+    pub iocs: Vec<&'a str>, // Watchlist entries the record matched
+    #[serde(flatten)]
+    pub record: &'a FsEvents, // Matching record
+}
+
+/// Every record that matched the watchlist
+#[derive(Debug, Serialize)]
+pub struct WatchlistReport<'a> {
+    pub entries: usize,              // Number of watchlist entries
+    pub records_checked: usize,      // Number of records compared to the watchlist
+    pub hits: Vec<WatchlistHit<'a>>, // Matching records, in the order checked
+    pub unmatched: Vec<&'a str>,     // Entries that did not match any record
+}
+
+impl Watchlist {
+    /// Compile the entries of a newline delimited watchlist
+    pub fn parse(text: &str) -> Result<Watchlist, FsEventsError> {
+        let mut entries = Vec::new();
+        let mut paths = GlobSetBuilder::new();
+        let mut path_entries = Vec::new();
+        let mut names = GlobSetBuilder::new();
+        let mut name_entries = Vec::new();
+        for line in text.lines() {
+            let entry = line.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            if entry.contains('/') {
+                paths.add(compile_entry(entry)?);
+                path_entries.push(entries.len());
+            } else {
+                names.add(compile_entry(entry)?);
+                name_entries.push(entries.len());
+            }
+            entries.push(entry.to_string());
+        }
+
+        let build = |builder: GlobSetBuilder| {
+            builder
+                .build()
+                .map_err(|err| FsEventsError::InvalidPattern(err.to_string()))
+        };
+        Ok(Watchlist {
+            entries,
+            paths: build(paths)?,
+            path_entries,
+            names: build(names)?,
+            name_entries,
+        })
+    }
+
+    #[cfg(feature = "filesystem")]
+    /// Read and compile a watchlist file
+    pub fn from_file(path: &str) -> Result<Watchlist, FsEventsError> {
+        Watchlist::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Get the watchlist entries
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Get the entries that match a path, in the order they are listed
+    pub fn matches(&self, path: &str) -> Vec<&str> {
+        self.matching_entries(path)
+            .into_iter()
+            .map(|index| self.entries[index].as_str())
+            .collect()
+    }
+
+    /// Get the indexes of the entries that match a path
+    fn matching_entries(&self, path: &str) -> Vec<usize> {
+        let mut indexes: Vec<usize> = self
+            .paths
+            .matches(path)
+            .into_iter()
+            .map(|index| self.path_entries[index])
+            .collect();
+        if let Some(name) = Path::new(path).file_name() {
+            indexes.extend(
+                self.names
+                    .matches(name)
+                    .into_iter()
+                    .map(|index| self.name_entries[index]),
+            );
+        }
+        indexes.sort_unstable();
+        indexes
+    }
+
+    /// Compare every record to the watchlist
+    pub fn report<'a>(&'a self, fsevents: &'a [FsEvents]) -> WatchlistReport<'a> {
+        let mut matched = vec![false; self.entries.len()];
+        let mut hits = Vec::new();
+        for fsevent in fsevents {
+            let indexes = self.matching_entries(&fsevent.path);
+            if indexes.is_empty() {
+                continue;
+            }
+            for index in &indexes {
+                matched[*index] = true;
+            }
+            hits.push(WatchlistHit {
+                iocs: indexes
+                    .into_iter()
+                    .map(|index| self.entries[index].as_str())
+                    .collect(),
+                record: fsevent,
+            });
+        }
+
+        let unmatched = self
+            .entries
+            .iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(entry, _)| entry.as_str())
+            .collect();
+        WatchlistReport {
+            entries: self.entries.len(),
+            records_checked: fsevents.len(),
+            hits,
+            unmatched,
+        }
+    }
+}
+
+/// Compile a case insensitive watchlist glob
+fn compile_entry(entry: &str) -> Result<Glob, FsEventsError> {
+    GlobBuilder::new(entry)
+        .literal_separator(true)
+        .case_insensitive(true)
+        .build()
+        .map_err(|err| FsEventsError::InvalidPattern(format!("{}: {}", entry, err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Watchlist;
+    use crate::{error::FsEventsError, parser::parse_bytes};
+    use std::{fs, path::PathBuf};
+
+    const WATCHLIST: &str = "# Known bad\n\
+        /Library/LaunchAgents/com.evil.plist\n\
+        \n\
+        INVOICE.pdf\n\
+        /Users/*/.ssh/**\n\
+        *.command\n\
+        /Users/*/Documents/*.docx\n";
+
+    #[test]
+    fn test_watchlist_matches() {
+        let watchlist = Watchlist::parse(WATCHLIST).unwrap();
+        assert!(watchlist.entries().len() == 5);
+        assert!(
+            watchlist.matches("/Library/LaunchAgents/com.evil.plist")
+                == vec!["/Library/LaunchAgents/com.evil.plist"]
+        );
+        assert!(watchlist.matches("/Users/bob/Downloads/invoice.pdf") == vec!["INVOICE.pdf"]);
+        assert!(watchlist.matches("/Users/bob/.ssh/authorized_keys") == vec!["/Users/*/.ssh/**"]);
+        assert!(watchlist.matches("/private/tmp/run.command") == vec!["*.command"]);
+        // * does not match directories in path entries
+        assert!(watchlist
+            .matches("/Users/bob/Documents/old/report.docx")
+            .is_empty());
+        assert!(watchlist.matches("/Users/bob/Documents/report.docx").len() == 1);
+    }
+
+    #[test]
+    fn test_watchlist_report() {
+        let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_location.push("tests/test_data/DLS3/0000000000030012");
+        let fsevents = parse_bytes(&fs::read(test_location).unwrap()).unwrap();
+
+        let watchlist = Watchlist::parse(WATCHLIST).unwrap();
+        let report = watchlist.report(&fsevents);
+        assert!(report.entries == 5);
+        assert!(report.records_checked == 5);
+        assert!(report.hits.len() == 4);
+        assert!(report.hits[0].iocs == vec!["INVOICE.pdf"]);
+        assert!(report.hits[0].record.path == "/Users/bob/Downloads/invoice.pdf");
+        assert!(report.unmatched == vec!["*.command", "/Users/*/Documents/*.docx"]);
+
+        let json = serde_json::to_value(&report.hits[0]).unwrap();
+        assert!(json["iocs"][0] == "INVOICE.pdf");
+        assert!(json["event_id"] == 196608);
+    }
+
+    #[test]
+    fn test_watchlist_invalid() {
+        let results = Watchlist::parse("/Users/[bob/file");
+        assert!(matches!(results, Err(FsEventsError::InvalidPattern(_))));
+    }
+}
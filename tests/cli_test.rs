@@ -0,0 +1,1055 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use tempfile::tempdir;
+
+#[test]
+fn cli_version_test() {
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("--version")
+        .output()
+        .unwrap();
+    let version = String::from_utf8(output.stdout).unwrap();
+    assert!(version.trim() == format!("fsevents_parser {}", env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn cli_input_output_dir_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .arg("-o")
+        .arg(output_dir)
+        .args(["--sort", "--columns", "path,flags,event_id"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let csv = fs::read_to_string(output_dir.join("output.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert!(lines.len() == 6);
+    assert!(lines[1].starts_with("/Users/bob/Downloads/invoice.pdf,"));
+    assert!(!output_dir.join("output.json").exists());
+}
+
+#[test]
+fn cli_format_json_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .arg("-o")
+        .arg(output_dir)
+        .args(["--format", "json"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let json = fs::read_to_string(output_dir.join("output.json")).unwrap();
+    let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(records.as_array().unwrap().len() == 5);
+    assert!(!output_dir.join("output.csv").exists());
+}
+
+#[test]
+fn cli_invalid_argument_test() {
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .args(["--columns", "not_a_column"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cli_creates_output_dir_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let output_dir = root.join("case").join("fsevents");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("--input")
+        .arg(&test_location)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .args(["--format", "jsonl"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let jsonl = fs::read_to_string(output_dir.join("output.jsonl")).unwrap();
+    assert!(jsonl.lines().count() == 5);
+}
+
+#[test]
+fn cli_split_per_file_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS2");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .arg("-o")
+        .arg(output_dir)
+        .args(["--split-per-file", "--format", "bodyfile"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert!(output_dir.join("000000000002469c.body").exists());
+    assert!(output_dir.join("0000000000027d79.body").exists());
+    assert!(!output_dir.join("output.body").exists());
+}
+
+#[test]
+fn cli_flag_filters_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .arg("-o")
+        .arg(output_dir)
+        .args(["--include-flags", "Removed,Renamed", "--columns", "path"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let csv = fs::read_to_string(output_dir.join("output.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert!(
+        lines
+            == [
+                "Path",
+                "/Users/bob/Downloads/invoice.pdf",
+                "/Users/bob/.ssh/authorized_keys"
+            ]
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .args(["--exclude-flags", "NotAFlag"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cli_path_match_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .arg("-o")
+        .arg(output_dir)
+        .args([
+            "--path-match",
+            "~/**",
+            "--path-match",
+            "/Library/LaunchAgents/*",
+        ])
+        .args(["--path-exclude", "regex:/Downloads/", "--columns", "path"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let csv = fs::read_to_string(output_dir.join("output.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert!(
+        lines
+            == [
+                "Path",
+                "/Library/LaunchAgents/com.evil.plist",
+                "/Users/bob/.ssh/authorized_keys"
+            ]
+    );
+}
+
+#[test]
+fn cli_stdin_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3/0000000000030012");
+    let data = fs::read(test_location).unwrap();
+
+    for format in ["csv", "jsonl"] {
+        let temp = tempdir().unwrap();
+        let output_dir = temp.path();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .args(["-i", "-", "--format", format])
+            .arg("-o")
+            .arg(output_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(&data).unwrap();
+        assert!(child.wait().unwrap().success());
+
+        let output = fs::read_to_string(output_dir.join(format!("output.{}", format))).unwrap();
+        assert!(output.contains("/Users/bob/.ssh/authorized_keys"));
+        assert!(output.contains("stdin"));
+    }
+}
+
+#[test]
+fn cli_stdout_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args(["--stdout", "--format", "jsonl", "--no-progress"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines.len() == 5);
+    let record: serde_json::Value = serde_json::from_str(lines[4]).unwrap();
+    assert!(record["path"] == "/Users/bob/.ssh/authorized_keys");
+    // Status messages are not mixed with the records
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Finished parsing FsEvents data"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args(["--stdout", "--columns", "path,event_id"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Path,Event ID\n/Users/bob/Downloads/invoice.pdf,196608\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .args(["--stdout", "--format", "xlsx"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cli_log_level_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3/0000000000030012");
+    // A directory without a fseventsd-uuid file logs a warning
+    let temp = tempdir().unwrap();
+    let input_dir = temp.path();
+    fs::copy(&test_location, input_dir.join("0000000000030012")).unwrap();
+
+    for (arg, shows_warnings) in [("-v", true), ("-q", false)] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("-i")
+            .arg(input_dir)
+            .args(["--stdout", "--format", "json", arg])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("[WARN]") == shows_warnings);
+    }
+}
+
+#[test]
+fn cli_recursive_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data");
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let fseventsd = root.join("mnt/image/.fseventsd");
+    fs::create_dir_all(&fseventsd).unwrap();
+    fs::copy(
+        test_location.join("DLS3/0000000000030012"),
+        fseventsd.join("0000000000030012"),
+    )
+    .unwrap();
+    fs::copy(
+        test_location.join("DLS2/000000000002469c"),
+        root.join("000000000002469c"),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(root)
+        .args(["--recursive", "--stdout", "--columns", "path,source_tag"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("/Users/bob/.ssh/authorized_keys,/mnt/image\n"));
+    assert!(stdout.lines().any(|line| line.ends_with(",/")));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("--recursive")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cli_file_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS2/0000000000027d79");
+
+    for format in ["csv", "jsonl"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("--file")
+            .arg(&test_location)
+            .args(["--stdout", "--format", format])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("0000000000027d79"));
+        assert!(!stdout.contains("000000000002469c"));
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("--file")
+        .arg(&test_location)
+        .args(["--input", "/tmp"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cli_multiple_paths_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data");
+
+    for format in ["csv", "jsonl"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg(test_location.join("DLS3"))
+            .arg(test_location.join("DLS2/0000000000027d79"))
+            .args(["--stdout", "--format", format])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("DLS3/0000000000030012"));
+        assert!(stdout.contains("DLS2/0000000000027d79"));
+        assert!(!stdout.contains("DLS2/000000000002469c"));
+    }
+}
+
+#[test]
+fn cli_dedupe_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    for (dedupe, records) in [("--dedupe", 5), ("--dedupe=path", 4)] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg(&test_location)
+            .arg(test_location.join("0000000000030012"))
+            .args([dedupe, "--stdout", "--format", "jsonl"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout).unwrap().lines().count() == records);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(&format!("Removed {} duplicate records", 10 - records)));
+    }
+}
+
+#[test]
+fn cli_triage_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    for format in ["csv", "jsonl"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("-i")
+            .arg(&test_location)
+            .args(["--triage", "--stdout", "--format", format])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(!stdout.contains("/private/var/folders/tmp"));
+        assert!(stdout.contains("BrowserDownload"));
+        assert!(stdout.contains("LaunchAgent"));
+        assert!(stdout.contains("SshKey"));
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args(["--triage", "--stdout", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(records.as_array().unwrap().len() == 4);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Kept 4 records matching a triage rule"));
+}
+
+#[test]
+fn cli_watchlist_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+    let watchlist = output_dir.join("iocs.txt");
+    fs::write(
+        &watchlist,
+        "# IOCs\ncom.evil.plist\n/Users/*/.ssh/**\nmissing.dylib\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .arg("--watchlist")
+        .arg(&watchlist)
+        .arg("-o")
+        .arg(output_dir)
+        .args(["--format", "jsonl"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Watchlist matched 2 records (2 of 3 entries)"));
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&fs::read(output_dir.join("watchlist_hits.json")).unwrap()).unwrap();
+    assert!(report["hits"].as_array().unwrap().len() == 2);
+    assert!(report["hits"][0]["iocs"][0] == "com.evil.plist");
+    assert!(report["hits"][0]["path"] == "/Library/LaunchAgents/com.evil.plist");
+    assert!(report["unmatched"][0] == "missing.dylib");
+    let records = fs::read_to_string(output_dir.join("output.jsonl")).unwrap();
+    assert!(records.lines().count() == 5);
+}
+
+#[test]
+fn cli_baseline_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+    let baseline = output_dir.join("baseline.txt");
+
+    let parse = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("-i")
+            .arg(&test_location)
+            .arg("-o")
+            .arg(output_dir)
+            .args(["--format", "jsonl"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+    let baseline_path = baseline.display().to_string();
+    let stderr = parse(&["--save-baseline", &baseline_path]);
+    assert!(stderr.contains("Saved a baseline of 5 paths and flags"));
+    let text = fs::read_to_string(&baseline).unwrap();
+    assert!(text.contains(" /users/*/downloads/invoice.pdf\n"));
+
+    // The same system has no anomalous activity
+    let stderr = parse(&["--baseline", &baseline_path]);
+    assert!(stderr.contains("Suppressed 5 records matching the baseline"));
+    let records = fs::read_to_string(output_dir.join("output.jsonl")).unwrap();
+    assert!(records.is_empty());
+
+    fs::write(&baseline, "/Users/*/**\n").unwrap();
+    let stderr = parse(&["--baseline", &baseline_path]);
+    assert!(stderr.contains("Suppressed 3 records matching the baseline"));
+    let records = fs::read_to_string(output_dir.join("output.jsonl")).unwrap();
+    assert!(records.lines().count() == 2);
+    assert!(!records.contains("/Users/"));
+}
+
+#[test]
+fn cli_compress_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+
+    // JSON Lines is streamed, CSV is sorted before it is saved
+    for (format, file_name, lines) in [("jsonl", "output.jsonl.gz", 5), ("csv", "output.csv.gz", 6)]
+    {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("-i")
+            .arg(&test_location)
+            .arg("-o")
+            .arg(output_dir)
+            .args(["--format", format, "--compress", "gzip"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let data = fs::read(output_dir.join(file_name)).unwrap();
+        let mut records = String::new();
+        flate2::read::GzDecoder::new(data.as_slice())
+            .read_to_string(&mut records)
+            .unwrap();
+        assert!(records.lines().count() == lines);
+        assert!(records.contains("/Users/bob/.ssh/authorized_keys"));
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args(["--format", "xlsx", "--compress", "gzip"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cli_completions_test() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .args(["completions", shell])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let script = String::from_utf8(output.stdout).unwrap();
+        assert!(script.contains("fsevents_parser"));
+        assert!(script.contains("include-flags"));
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .args(["completions", "tcsh"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn cli_config_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let config = root.join("profile.toml");
+    fs::write(
+        &config,
+        format!(
+            "input = {:?}\noutput_dir = {:?}\nformat = \"jsonl\"\ninclude-flags = [\"Removed\", \"Renamed\"]\nquiet = true\n",
+            test_location.display().to_string(),
+            root.join("output").display().to_string()
+        ),
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("--config")
+        .arg(&config)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let jsonl = fs::read_to_string(root.join("output").join("output.jsonl")).unwrap();
+    assert!(jsonl.lines().count() == 2);
+
+    // Command line options take precedence over the config
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("--config")
+        .arg(&config)
+        .args(["--stdout", "--format", "csv", "--columns", "path"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().collect::<Vec<_>>()
+            == [
+                "Path",
+                "/Users/bob/Downloads/invoice.pdf",
+                "/Users/bob/.ssh/authorized_keys"
+            ]
+    );
+
+    fs::write(&config, "not_an_option = true\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("--config")
+        .arg(&config)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("unknown option not_an_option"));
+}
+
+#[test]
+fn cli_exit_code_test() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let mixed = root.join("mixed");
+    let empty = root.join("empty");
+    let corrupt = root.join("corrupt");
+    fs::create_dir_all(&mixed).unwrap();
+    fs::create_dir_all(&empty).unwrap();
+    fs::create_dir_all(&corrupt).unwrap();
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3/0000000000030012");
+    fs::copy(&test_location, mixed.join("0000000000030012")).unwrap();
+    fs::write(mixed.join("0000000000099999"), b"not an FsEvent file").unwrap();
+    let malformed =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_data/Malformed/malformed");
+    fs::copy(&malformed, corrupt.join("0000000000099999")).unwrap();
+
+    let exit_code = |args: &[&str], input: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("-i")
+            .arg(input)
+            .arg("-o")
+            .arg(root.join("output"))
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .code()
+            .unwrap()
+    };
+    assert!(exit_code(&[], test_location.parent().unwrap()) == 0);
+    assert!(exit_code(&[], &mixed) == 1);
+    assert!(exit_code(&["--format", "jsonl"], &mixed) == 1);
+    assert!(exit_code(&[], &empty) == 2);
+    assert!(exit_code(&[], &root.join("missing")) == 2);
+    // Files were found, but none could be parsed
+    assert!(exit_code(&[], &corrupt) == 4);
+    assert!(exit_code(&["--format", "jsonl"], &corrupt) == 4);
+    // Streamed JSON Lines uses the same file and page options
+    for format in ["csv", "jsonl"] {
+        assert!(exit_code(&["--format", format, "--skip-corrupt-pages"], &corrupt) == 1);
+        let args = ["--format", format, "--max-file-size", "10"];
+        assert!(exit_code(&args, test_location.parent().unwrap()) == 4);
+    }
+    assert!(exit_code(&["--fail-fast"], &mixed) == 4);
+    assert!(exit_code(&["--format", "jsonl", "--fail-fast"], &mixed) == 4);
+    assert!(exit_code(&["--columns", "not_a_column"], &mixed) == 4);
+
+    // A single file reports its skipped pages
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("--file")
+        .arg(&malformed)
+        .arg("-o")
+        .arg(root.join("output"))
+        .args(["--format", "json", "--skip-corrupt-pages"])
+        .output()
+        .unwrap();
+    assert!(output.status.code() == Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Skipped page 0 at offset 0"));
+}
+
+#[test]
+fn cli_json_style_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args(["--stdout", "--format", "json", "--json-style", "pretty"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("[\n  {\n"));
+    let records: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert!(records.as_array().unwrap().len() == 5);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args(["--stdout", "--format", "json", "--json-style", "ndjson"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().count() == 5);
+    for line in stdout.lines() {
+        let record: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert!(record["event_id"].is_u64());
+    }
+}
+
+#[test]
+fn cli_tsv_columns_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args([
+            "--stdout",
+            "--format",
+            "tsv",
+            "--columns",
+            "event_id,path,flags",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines.len() == 6);
+    assert!(lines[0] == "Event ID\tPath\tFlags");
+    assert!(lines[1] == "196608\t/Users/bob/Downloads/invoice.pdf\tCreated,IsFile");
+}
+
+#[test]
+fn cli_time_format_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args(["--stdout", "--format", "jsonl", "--sort", "event-id"])
+        .args(["--time-format", "iso8601", "--timezone", "+05:30"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let record: serde_json::Value = serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+    let time = record["approx_latest_time"].as_str().unwrap();
+    assert!(time.ends_with("+05:30"));
+    assert!(record["est_time_end"].is_null());
+    assert!(record["event_id"] == 196608);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .args(["--stdout", "--timezone", "Mars/Olympus_Mons"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Invalid time format"));
+}
+
+#[test]
+fn cli_sort_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    let sorted = [
+        ("event-id", "/Users/bob/Downloads/invoice.pdf"),
+        ("path", "/Library/LaunchAgents/com.evil.plist"),
+        ("none", "/Users/bob/Downloads/invoice.pdf"),
+    ];
+    for (order, first) in sorted {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("-i")
+            .arg(&test_location)
+            .args(["--stdout", "--format", "json", "--sort", order])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(records[0]["path"] == first);
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .args(["--sort", "size"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+#[cfg(not(target_os = "macos"))]
+fn cli_watch_test() {
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .args(["watch", "/tmp"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("requires macOS"));
+}
+
+#[test]
+fn cli_stats_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("stats")
+        .arg(test_location.join("DLS3"))
+        .arg(test_location.join("Missing"))
+        .args(["--top", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Records: 5\n"));
+    assert!(stdout.contains("Event IDs: 196608 - 196625"));
+    assert!(stdout.contains("/Users/bob/Downloads"));
+    assert!(!stdout.contains("/Users/bob/.ssh "));
+    assert!(stdout.contains("Unparseable files: 1\n"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("stats")
+        .arg(test_location.join("DLS3"))
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(summary["total_records"] == 5);
+    assert!(summary["flags"]["Created"] == 2);
+    assert!(summary["busiest_directories"][0]["directory"] == "/Users/bob/Downloads");
+    assert!(summary["unparseable_files"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn cli_carve_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3/0000000000030012");
+    let temp = tempdir().unwrap();
+    let output_dir = temp.path();
+
+    // Deleted FsEvent file in unallocated space
+    let mut image = vec![0; 4096];
+    image.extend(fs::read(&test_location).unwrap());
+    image.resize(8192, 0);
+    let image_path = output_dir.join("image.dd");
+    fs::write(&image_path, image).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("carve")
+        .arg(&image_path)
+        .arg("-o")
+        .arg(output_dir)
+        .args(["--format", "jsonl"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let jsonl = fs::read_to_string(output_dir.join("output.jsonl")).unwrap();
+    assert!(jsonl.lines().count() == 5);
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(output_dir.join("carve_report.json")).unwrap())
+            .unwrap();
+    let pages = report["pages"].as_array().unwrap();
+    assert!(pages.len() == 2);
+    assert!(pages[0]["source"]
+        .as_str()
+        .unwrap()
+        .ends_with("image.dd@0x1000"));
+    assert!(pages[0]["confidence"] == 1.0);
+    assert!(pages[0]["truncated"] == false);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("carve")
+        .arg(&test_location)
+        .args(["--kind", "blob"])
+        .arg("-o")
+        .arg(output_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let csv = fs::read_to_string(output_dir.join("output.csv")).unwrap();
+    assert!(csv.lines().count() == 6);
+}
+
+#[test]
+fn cli_checkpoint_test() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let input = root.join("input");
+    fs::create_dir_all(&input).unwrap();
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3/0000000000030012");
+    fs::copy(&test_location, input.join("0000000000030012")).unwrap();
+    fs::copy(&test_location, input.join("0000000000030013")).unwrap();
+    let checkpoint = root.join("checkpoint.jsonl");
+
+    let run = |format: &str| {
+        let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("-i")
+            .arg(&input)
+            .arg("-o")
+            .arg(root.join("output"))
+            .arg("--checkpoint")
+            .arg(&checkpoint)
+            .args(["--format", format])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    run("csv");
+    let output = root.join("output/output.csv");
+    let complete = fs::read_to_string(&output).unwrap();
+    assert!(complete.lines().count() == 11);
+    let entries = fs::read_to_string(&checkpoint).unwrap();
+    assert!(entries.lines().count() == 2);
+
+    // Interrupted while the second file was written
+    let first = entries.lines().next().unwrap();
+    fs::write(&checkpoint, format!("{first}\n")).unwrap();
+    let mut partial = fs::OpenOptions::new().append(true).open(&output).unwrap();
+    partial.write_all(b"/Users/bob/partial,").unwrap();
+    run("csv");
+    assert!(fs::read_to_string(&output).unwrap() == complete);
+
+    // Nothing is written again once every file is completed
+    run("csv");
+    assert!(fs::read_to_string(&output).unwrap() == complete);
+    assert!(fs::read_to_string(&checkpoint).unwrap().lines().count() == 2);
+
+    // The checkpoint is for a different output
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(root.join("output"))
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .args(["--format", "jsonl"])
+        .status()
+        .unwrap();
+    assert!(status.code() == Some(4));
+}
+
+#[test]
+fn cli_split_output_test() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    let run = |input: &Path, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("-i")
+            .arg(input)
+            .arg("-o")
+            .arg(root)
+            .args(args)
+            .status()
+            .unwrap()
+    };
+    assert!(run(&test_location, &["--split-records", "2"]).success());
+    for (part, records) in [(1, 2), (2, 2), (3, 1)] {
+        let data = fs::read_to_string(root.join(format!("output_000{part}.csv"))).unwrap();
+        assert!(data.starts_with("Path,Flags"));
+        assert!(data.lines().count() == records + 1);
+    }
+    assert!(!root.join("output_0004.csv").exists());
+
+    let args = ["--format", "jsonl", "--split-records", "4"];
+    assert!(run(&test_location, &args).success());
+    let data = fs::read_to_string(root.join("output_0002.jsonl")).unwrap();
+    assert!(data.lines().count() == 1);
+
+    // Each part is closed once it is over 1 MiB
+    test_location.set_file_name("DLS2");
+    let args = ["--format", "jsonl", "--split-size", "1MB"];
+    assert!(run(&test_location, &args).success());
+    let sizes: Vec<u64> = (1..=3)
+        .map(|part| {
+            let path = root.join(format!("output_000{part}.jsonl"));
+            fs::metadata(path).unwrap().len()
+        })
+        .collect();
+    assert!(sizes[0] >= 1024 * 1024 && sizes[0] < 1024 * 1024 + 16384);
+    assert!(sizes[2] < 1024 * 1024);
+    let args = [
+        "--format",
+        "jsonl",
+        "--split-size",
+        "1MB",
+        "--compress",
+        "gzip",
+    ];
+    assert!(run(&test_location, &args).success());
+    assert!(root.join("output_0001.jsonl.gz").exists());
+
+    let status = run(&test_location, &["--format", "json", "--split-size", "1MB"]);
+    assert!(status.code() == Some(4));
+    let status = run(&test_location, &["--split-size", "lots"]);
+    assert!(status.code() == Some(4));
+}
+
+#[test]
+fn cli_html_report_test() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+        .arg("-i")
+        .arg(&test_location)
+        .arg("-o")
+        .arg(root)
+        .args(["--format", "jsonl", "--report"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let html = fs::read_to_string(root.join("report.html")).unwrap();
+    assert!(html.contains("<a href=\"output.jsonl\">output.jsonl</a>"));
+    assert!(html.contains("<tr><th>Records</th><td>5</td></tr>"));
+    assert!(html.contains("0000000000030012</td>"));
+    // The export is saved after the records are sorted and times are added
+    let export = fs::read_to_string(root.join("output.jsonl")).unwrap();
+    assert!(export.lines().count() == 5);
+    assert!(html.contains("<tr><th>Approximate times</th>"));
+}
+
+#[test]
+fn cli_tail_test() {
+    let temp = tempdir().unwrap();
+    let root = temp.path();
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS3/0000000000030012");
+    let data = fs::read(&test_location).unwrap();
+    fs::write(root.join("0000000000030012"), &data).unwrap();
+    // fseventsd is still writing the newest file
+    fs::write(root.join("0000000000030030"), &data[..data.len() / 2]).unwrap();
+
+    let tail = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_fsevents_parser"))
+            .arg("tail")
+            .arg(root)
+            .args(["--once"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let stdout = tail(&["--from-start"]);
+    assert!(stdout.lines().count() == 5);
+    assert!(stdout
+        .lines()
+        .all(|line| line.contains("0000000000030012\"")));
+    assert!(tail(&[]).is_empty());
+    let stdout = tail(&["--from-start", "--format", "csv"]);
+    assert!(stdout.starts_with("Path,Flags"));
+    assert!(stdout.lines().count() == 6);
+}
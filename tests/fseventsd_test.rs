@@ -1,7 +1,5 @@
 use std::{fs::read_dir, path::PathBuf};
 
-use macos_fseventsd;
-
 #[test]
 fn fseventd_local_test() {
     let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -17,12 +15,13 @@ fn fseventd_local_test() {
         let data = macos_fseventsd::parser::decompress(&fsevent).unwrap();
         assert!(data.len() > 10);
 
-        let (_, results) = macos_fseventsd::parser::parse_fsevents(&data).unwrap();
+        let results = macos_fseventsd::parser::parse_fsevents(&data).unwrap();
         assert!(results.len() > 100);
     }
 }
 
 #[test]
+#[cfg_attr(not(target_os = "macos"), ignore = "macOS system files required")]
 fn fseventd_system_filelist_test() {
     let files = macos_fseventsd::parser::get_fseventsd().unwrap();
     assert!(files.len() > 3);
@@ -38,7 +37,23 @@ fn fseventd_system_files_test() {
         let data = macos_fseventsd::parser::decompress(&fsevent).unwrap();
         assert!(data.len() > 10);
 
-        let (_, results) = macos_fseventsd::parser::parse_fsevents(&data).unwrap();
+        let results = macos_fseventsd::parser::parse_fsevents(&data).unwrap();
         assert!(results.len() > 100);
     }
 }
+
+#[test]
+fn fseventd_iter_local_test() {
+    let mut test_location = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    test_location.push("tests/test_data/DLS2");
+    let iter =
+        macos_fseventsd::iter::FsEventsIter::from_directory(&test_location.display().to_string())
+            .unwrap();
+
+    let mut count = 0;
+    for record in iter {
+        assert!(record.unwrap().event_id > 0);
+        count += 1;
+    }
+    assert!(count > 200);
+}